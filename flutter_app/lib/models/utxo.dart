@@ -2,17 +2,30 @@ class Utxo {
   final String txid;
   final int vout;
   final int amount; // in Satoshis/Satoshi-equivalent
+  final int? height;
+  final int confirmations;
+  final bool isCoinbase;
+  final bool isCoinstake;
 
   Utxo({
     required this.txid,
     required this.vout,
     required this.amount,
+    this.height,
+    this.confirmations = 0,
+    this.isCoinbase = false,
+    this.isCoinstake = false,
   });
 
+  // Field names match rust_core's `Utxo` so the list can be handed to the signer as-is.
   Map<String, dynamic> toJson() => {
         'txid': txid,
         'vout': vout,
         'amount': amount,
+        if (height != null) 'height': height,
+        'confirmations': confirmations,
+        'is_coinbase': isCoinbase,
+        'is_coinstake': isCoinstake,
       };
 
   factory Utxo.fromJson(Map<String, dynamic> json) {
@@ -20,6 +33,10 @@ class Utxo {
       txid: json['txid'] ?? '',
       vout: json['vout'] ?? 0,
       amount: int.tryParse(json['value'] ?? '0') ?? (json['amount'] ?? 0),
+      height: json['height'] as int?,
+      confirmations: json['confirmations'] as int? ?? 0,
+      isCoinbase: json['coinbase'] == true || json['is_coinbase'] == true,
+      isCoinstake: json['coinstake'] == true || json['is_coinstake'] == true,
     );
   }
 }
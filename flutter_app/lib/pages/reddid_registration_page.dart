@@ -122,7 +122,7 @@ class _ReddIDRegistrationPageState extends State<ReddIDRegistrationPage> {
         privateKeyHex: mnemonic,
         utxos: selectedUtxos,
         destination: myAddress,
-        amount: 0.0,
+        amount: '0',
         changeAddress: myAddress,
         opReturnData: "RDD:ID:$handle:$cid",
       );
@@ -24,7 +24,7 @@ class VaultCryptoService {
     dynamic utxos,
     String? toAddress,
     String? destination,
-    double? amount,
+    String? amount,
     String? mnemonic,
     String? privateKeyHex,
     String? changeAddress,
@@ -0,0 +1,84 @@
+//! Signing pipeline benchmarks.
+//!
+//! Run with `cargo bench --bench signing`. Each case signs (and separately verifies) a P2PKH
+//! transaction with 1, 100 and 1000 inputs, serially and with automatic threading, and prints the
+//! mean time per transaction. 1000 inputs is above the standard size limit; it is measured to
+//! show how the pipeline scales, not because the wallet would broadcast it.
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use bitcoin::absolute::LockTime;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::SecretKey;
+use bitcoin::{
+    Amount, OutPoint, PublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+};
+use rust_core::script_interpreter::verify_inputs;
+use rust_core::secp_context::secp;
+use rust_core::transaction_signer::sign_p2pkh_inputs;
+
+const INPUT_COUNTS: [usize; 3] = [1, 100, 1000];
+/// Keeps each case to roughly this long so slow devices finish in reasonable time.
+const TARGET_DURATION: Duration = Duration::from_secs(2);
+
+fn unsigned_transaction(inputs: usize, pubkey: &PublicKey) -> Transaction {
+    Transaction {
+        version: bitcoin::transaction::Version(2),
+        lock_time: LockTime::ZERO,
+        input: (0..inputs)
+            .map(|index| TxIn {
+                previous_output: OutPoint {
+                    txid: Txid::from_byte_array([(index % 251) as u8; 32]),
+                    vout: index as u32,
+                },
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            })
+            .collect(),
+        output: vec![TxOut {
+            value: Amount::from_sat(100_000_000),
+            script_pubkey: ScriptBuf::new_p2pkh(&pubkey.pubkey_hash()),
+        }],
+    }
+}
+
+/// Runs `f` repeatedly for about [`TARGET_DURATION`] and prints the mean time per run.
+fn bench(name: &str, mut f: impl FnMut()) {
+    f();
+    let start = Instant::now();
+    let mut runs = 0u32;
+    while start.elapsed() < TARGET_DURATION || runs == 0 {
+        f();
+        runs += 1;
+    }
+    let mean = start.elapsed() / runs;
+    println!("{name:<40} {mean:>12.2?}  ({runs} runs)");
+}
+
+fn main() {
+    let secret_key = SecretKey::from_slice(&[0x42; 32]).expect("valid key");
+    let pubkey = PublicKey::new(secret_key.public_key(secp()));
+    let spent_script = ScriptBuf::new_p2pkh(&pubkey.pubkey_hash());
+
+    for inputs in INPUT_COUNTS {
+        let unsigned = unsigned_transaction(inputs, &pubkey);
+        for (mode, threads) in [("serial", Some(1)), ("auto", None)] {
+            bench(&format!("sign {inputs} inputs ({mode})"), || {
+                let mut tx = unsigned.clone();
+                sign_p2pkh_inputs(&mut tx, &secret_key, &pubkey, threads).expect("signing");
+                black_box(tx);
+            });
+        }
+
+        let mut signed = unsigned.clone();
+        sign_p2pkh_inputs(&mut signed, &secret_key, &pubkey, None).expect("signing");
+        let prevout_scripts = vec![spent_script.clone(); inputs];
+        for (mode, threads) in [("serial", Some(1)), ("auto", None)] {
+            bench(&format!("verify {inputs} inputs ({mode})"), || {
+                verify_inputs(&signed, &prevout_scripts, threads).expect("verification");
+            });
+        }
+    }
+}
@@ -1,6 +1,7 @@
 #ifndef REDD_CRYPTO_H
 #define REDD_CRYPTO_H
 
+#include <stdbool.h>
 #include <stddef.h>
 #include <stdint.h>
 
@@ -89,6 +90,475 @@ char *sign_opreturn_transaction_ffi(
     const char *change_address,
     uint64_t network_fee);
 
+/**
+ * Decodes a Reddcoin WIF private key (paper wallet or Reddcoin Core `dumpprivkey` output).
+ *
+ * Both compressed and uncompressed keys are accepted. Keys tagged with another network's
+ * secret-key prefix are rejected.
+ *
+ * Return format:
+ *   - On success: "OK:{\"private_key_hex\":..., \"compressed\":..., \"address\":...}"
+ *   - On error:   "ERR:<error_message>"
+ *
+ * @return
+ *   Heap-allocated C string owned by Rust. Release it with `rust_cstr_free`.
+ */
+char *import_wif_ffi(const char *wif);
+
+/**
+ * Encodes a 64-char hex private key as Reddcoin WIF.
+ *
+ * @param compressed
+ *   Whether the key's address uses the compressed public key encoding.
+ *
+ * Return format:
+ *   - On success: "OK:<wif>"
+ *   - On error:   "ERR:<error_message>"
+ */
+char *export_wif_ffi(const char *private_key_hex, bool compressed);
+
+/**
+ * Sweeps every UTXO controlled by a WIF key into a single output paying `destination_address`
+ * and returns the signed transaction.
+ *
+ * @param utxos_json
+ *   JSON array of `{"txid", "vout", "amount"}` objects owned by the WIF key.
+ *
+ * @param fee_per_kb
+ *   Fee rate in base units per 1000 bytes.
+ *
+ * @param options_json
+ *   Build options as for `sign_standard_transfer_ffi`; the policy runs before signing.
+ *
+ * Return format:
+ *   - On success: "OK:{\"hex\":...,\"txid\":...,\"fee\":<base_units>,\"change_index\":null}"
+ *   - On error:   "ERR:<error_message>"
+ */
+char *sweep_wif_ffi(
+    const char *utxos_json,
+    const char *wif,
+    const char *destination_address,
+    uint64_t fee_per_kb,
+    const char *options_json);
+
+/**
+ * Parses the text written by Reddcoin Core's `dumpwallet` RPC.
+ *
+ * Every WIF key is returned with its address, decoded label, role (receive, reserve, change,
+ * hd_seed, inactive_hd_seed), creation time and HD key path. Redeem scripts and the extended
+ * master key header are returned alongside.
+ *
+ * Return format:
+ *   - On success: "OK:<wallet_import_json>"
+ *   - On error:   "ERR:<error_message>"
+ */
+char *parse_dumpwallet_ffi(const char *dump);
+
+/**
+ * Recreates a ReddWallet (Electrum 1.x) deterministic wallet from its 32-character hex seed or
+ * its 12-word mnemonic, deriving `receive_count` receive keys and `change_count` change keys.
+ *
+ * Return format:
+ *   - On success: "OK:<wallet_import_json>" (keys carry uncompressed WIFs)
+ *   - On error:   "ERR:<error_message>"
+ */
+char *import_reddwallet_seed_ffi(const char *seed, uint32_t receive_count, uint32_t change_count);
+
+/**
+ * Derives ReddWallet addresses from its 128-character hex master public key for watch-only
+ * tracking. Returned keys have a null `wif` and `watch_only` is true.
+ */
+char *import_reddwallet_mpk_ffi(const char *mpk_hex, uint32_t receive_count, uint32_t change_count);
+
+/**
+ * Splits a JSON UTXO array into spendable and excluded outputs.
+ *
+ * Coinbase and coinstake outputs need Reddcoin's coinbase maturity before they can be spent;
+ * ordinary outputs need `min_confirmations`. Each excluded output is reported with a reason
+ * (`unconfirmed`, `immature_coinbase`, `immature_coinstake`).
+ *
+ * @param tip_height
+ *   Current chain height, used to derive confirmations from `height`. Pass 0 if unknown.
+ *
+ * Return format:
+ *   - On success: "OK:{\"spendable\":[...],\"excluded\":[...],...}"
+ *   - On error:   "ERR:<error_message>"
+ */
+char *filter_spendable_utxos_ffi(const char *utxos_json, uint32_t min_confirmations, uint32_t tip_height);
+
+/**
+ * Parses an exact decimal amount into base units (1 RDD = 100000000 base units).
+ *
+ * @param denomination
+ *   "rdd" (8 decimals), "mrdd" (5 decimals) or "base" (integer base units). Inputs with more
+ *   decimal places than the unit allows are rejected rather than rounded.
+ *
+ * Return format:
+ *   - On success: "OK:<base_units>" (decimal integer string)
+ *   - On error:   "ERR:<error_message>"
+ */
+char *parse_amount_ffi(const char *amount, const char *denomination);
+
+/**
+ * Formats a base-unit amount in `denomination` with all of the unit's decimal places,
+ * e.g. 1250000000 in "rdd" becomes "OK:12.50000000".
+ */
+char *format_amount_ffi(uint64_t base_units, const char *denomination);
+
+/**
+ * Parses a `reddcoin:` payment URI (BIP21), or a bare address / `@handle`, as scanned from a QR.
+ *
+ * The target is validated as a Reddcoin address or ReddID handle, `amount` is parsed exactly as
+ * whole RDD, `label` and `message` are percent-decoded, and any `req-` parameter makes the parse
+ * fail as BIP21 requires.
+ *
+ * Return format:
+ *   - On success: "OK:{\"target\":{\"address\"|\"handle\":...},\"amount\":<base_units|null>,...}"
+ *   - On error:   "ERR:<error_message>"
+ */
+char *parse_payment_uri_ffi(const char *uri);
+
+/**
+ * Builds a `reddcoin:` payment URI for a receive QR code. `target` is an address or `@handle`;
+ * empty `amount`, `label` or `message` strings omit that parameter.
+ *
+ * Return format:
+ *   - On success: "OK:<uri>"
+ *   - On error:   "ERR:<error_message>"
+ */
+char *build_payment_uri_ffi(
+    const char *target,
+    const char *amount,
+    const char *label,
+    const char *message);
+
+/**
+ * Builds and signs a P2PKH transfer paying `recipient_address`, returning change to
+ * `change_address`.
+ *
+ * @param amount_base_units
+ *   Amount to send in base units (1 RDD = 100000000).
+ *
+ * @param options_json
+ *   Empty string for defaults, or `{"input_ordering": ..., "output_ordering": ...}` where each
+ *   policy is "random" (default), "bip69" or "preserve". Randomized or BIP69 ordering keeps the
+ *   change output from always sitting at a fixed index. An optional
+ *   `"lock_time": {"height": n}` or `{"time": unix}` post-dates the transaction. An optional
+ *   `"policy": {"rules": {...}, "context": {...}}` runs guardrails before signing (see
+ *   `evaluate_policy_ffi`); a deny verdict fails with ERR. An optional `"signing_threads": n`
+ *   fixes how many threads sign and verify inputs; by default large transactions use every core.
+ *   `"require_verified_inputs": true` refuses any UTXO whose funding transaction is not
+ *   Merkle-proven against the wallet's header chain. A `"verified"` field in `utxos_json` is
+ *   ignored; pass `"input_proofs": {"header_chain": "<hex of the stored chain>",
+ *   "checkpoints": [...], "proofs": {"<txid>": {"kind": "branch" | "merkle_block",
+ *   "proof": ...}}}` and the proofs are checked before signing. Each UTXO needs its `height`.
+ *
+ * Return format:
+ *   - On success: "OK:{\"hex\":...,\"txid\":...,\"fee\":<base_units>,\"change_index\":<n|null>}"
+ *     plus `"policy_warnings":[...]` when a policy rule warned
+ *   - On error:   "ERR:<error_message>"
+ */
+char *sign_standard_transfer_ffi(
+    const char *utxos_json,
+    const char *private_key_hex,
+    const char *recipient_address,
+    const char *change_address,
+    uint64_t amount_base_units,
+    uint64_t fee_per_kb,
+    const char *options_json);
+
+/**
+ * Builds a timelocked P2SH output: `<lock> OP_CHECKLOCKTIMEVERIFY|OP_CHECKSEQUENCEVERIFY OP_DROP
+ * <pubkey> OP_CHECKSIG`.
+ *
+ * @param lock_json
+ *   `{"absolute":{"height":n}}`, `{"absolute":{"time":unix}}`, `{"relative":{"blocks":n}}` or
+ *   `{"relative":{"seconds":n}}`.
+ *
+ * @param pubkey_hex
+ *   Hex-encoded compressed or uncompressed public key that can spend once the lock expires.
+ *
+ * Return format:
+ *   - On success: "OK:{\"redeem_script\":\"<hex>\",\"address\":\"<P2SH address>\"}"
+ *   - On error:   "ERR:<error_message>"
+ *
+ * Store the redeem script: it is required to spend the output. Free with `rust_cstr_free`.
+ */
+char *create_timelock_output_ffi(const char *lock_json, const char *pubkey_hex);
+
+/**
+ * Spends outputs of a timelocked P2SH address into a single destination output.
+ *
+ * Fails without signing if the lock is not satisfied at the given tip (absolute locks) or if any
+ * input has too few confirmations (relative locks).
+ * Every UTXO must carry the `address` or `script_pubkey` of the redeem script's P2SH output;
+ * UTXOs locked to anything else are rejected. `options_json` takes the build options of
+ * `sign_standard_transfer_ffi` except `lock_time`, which the redeem script decides.
+ *
+ * Return format:
+ *   - On success: "OK:{\"hex\":...,\"txid\":...,\"fee\":<base_units>,\"change_index\":null}"
+ *   - On error:   "ERR:<error_message>"
+ *
+ * Free with `rust_cstr_free`.
+ */
+char *sign_timelock_spend_ffi(
+    const char *utxos_json,
+    const char *redeem_script_hex,
+    const char *private_key_hex,
+    const char *destination_address,
+    uint64_t fee_per_kb,
+    uint32_t tip_height,
+    uint32_t tip_median_time_past,
+    const char *options_json);
+
+/**
+ * Pre-signs an inheritance recovery transaction sweeping every UTXO to `heir_address`, with an
+ * nLockTime that nodes enforce before it can be broadcast.
+ *
+ * @param lock_json
+ *   `{"height":n}` or `{"time":unix}`. Must not already be satisfied at the given tip.
+ *
+ * @param options_json
+ *   Build options as for `sign_standard_transfer_ffi`, without `lock_time`. A policy is
+ *   evaluated on the recovery transaction now, at signing time.
+ *
+ * Return format:
+ *   - On success: "OK:{\"heir_address\":...,\"lock_time\":...,\"inputs\":[\"txid:vout\",...],\"recovery\":<signed tx>}"
+ *   - On error:   "ERR:<error_message>"
+ *
+ * Encrypt the bundle before storing it for the heir. Free with `rust_cstr_free`.
+ */
+char *create_inheritance_bundle_ffi(
+    const char *utxos_json,
+    const char *private_key_hex,
+    const char *heir_address,
+    const char *lock_json,
+    uint64_t fee_per_kb,
+    uint32_t tip_height,
+    uint32_t tip_median_time_past,
+    const char *options_json);
+
+/**
+ * Liveness check-in: spends one input of the bundle's recovery transaction back to the owner
+ * (invalidating it) and pre-signs a replacement locked until `new_lock_json`, which must be later
+ * than the current lock. `options_json` is as for `create_inheritance_bundle_ffi` and applies
+ * to both transactions; the liveness output counts as change, not as a payment.
+ *
+ * Return format:
+ *   - On success: "OK:{\"liveness\":<signed tx>,\"bundle\":<new bundle>}"
+ *   - On error:   "ERR:<error_message>"
+ *
+ * Broadcast `liveness` before discarding the old bundle. Free with `rust_cstr_free`.
+ */
+char *refresh_inheritance_bundle_ffi(
+    const char *bundle_json,
+    const char *utxos_json,
+    const char *private_key_hex,
+    const char *new_lock_json,
+    uint64_t fee_per_kb,
+    uint32_t tip_height,
+    uint32_t tip_median_time_past,
+    const char *options_json);
+
+/**
+ * Signs a child-pays-for-parent transaction that spends one of a stuck parent's outputs (usually
+ * its unconfirmed change) so the parent+child package reaches `target_fee_per_kb`.
+ *
+ * @param parent_vout
+ *   Index of the parent output paid to `private_key_hex`'s address.
+ *
+ * @param parent_fee_base_units
+ *   Fee the parent already pays (the `fee` reported when it was signed).
+ *
+ * @param options_json
+ *   Build options as for `sign_standard_transfer_ffi`. A policy sees the child alone.
+ *
+ * Return format:
+ *   - On success: "OK:{\"child\":<signed tx>,\"parent_txid\":...,\"parent_size\":n,\"parent_fee\":n,\"package_size\":n,\"package_fee\":n,\"package_fee_per_kb\":n}"
+ *   - On error:   "ERR:<error_message>"
+ *
+ * Free with `rust_cstr_free`.
+ */
+char *sign_cpfp_child_ffi(
+    const char *parent_tx_hex,
+    uint32_t parent_vout,
+    uint64_t parent_fee_base_units,
+    const char *private_key_hex,
+    const char *destination_address,
+    uint64_t target_fee_per_kb,
+    const char *options_json);
+
+/**
+ * Validates a batch payment list without signing anything.
+ *
+ * @param entries
+ *   CSV (`recipient,amount[,memo]` per line, optional header, quoted fields allowed) or a JSON
+ *   array of `{"recipient": ..., "amount": "<RDD decimal>", "memo": ...}`.
+ *
+ * @param format
+ *   "csv" or "json".
+ *
+ * @param handles_json
+ *   Empty string, or an object mapping ReddID handles to their resolved addresses.
+ *
+ * Return format:
+ *   - On success: "OK:{\"entries\":[{\"row\":n,...,\"issues\":[...]}],\"total\":n,\"signable\":bool}"
+ *   - On error:   "ERR:<error_message>" (only for unparseable input; row problems are reported)
+ *
+ * Issue kinds: invalid_recipient, unresolved_handle, invalid_amount, dust (all blocking) and
+ * duplicate_recipient (warning). Free with `rust_cstr_free`.
+ */
+char *validate_batch_payment_ffi(const char *entries, const char *format, const char *handles_json);
+
+/**
+ * Signs a batch payment. Produces several transactions when one would exceed the 100000-byte
+ * standard size limit.
+ *
+ * Return format:
+ *   - On success: "OK:{\"transactions\":[<signed tx>...],\"recipients\":[{\"row\":n,\"address\":...,\"amount\":n,\"memo\":...,\"transaction_index\":n,\"output_index\":n}],\"total_sent\":n,\"total_fee\":n}"
+ *   - On error:   "ERR:<error_message>"
+ *
+ * Free with `rust_cstr_free`.
+ */
+char *sign_batch_payment_ffi(
+    const char *utxos_json,
+    const char *private_key_hex,
+    const char *entries,
+    const char *format,
+    const char *handles_json,
+    const char *change_address,
+    uint64_t fee_per_kb,
+    const char *options_json);
+
+/**
+ * Plans a UTXO consolidation: groups the wallet's UTXOs into standard-size transactions that
+ * either merge them (default) or re-split them into staking-sized outputs.
+ *
+ * @param options_json
+ *   Empty string for defaults, or any of:
+ *   `{"goal": {"mode": "merge"} | {"mode": "split", "target_output": <base_units>},
+ *     "max_fee_per_kb": n, "min_confirmations": n, "tip_height": n, "max_inputs_per_tx": n,
+ *     "input_ordering": ..., "output_ordering": ...}`, plus any other build option
+ *   (`policy`, `require_verified_inputs`, ...), which `sign_consolidation_ffi` applies to every
+ *   transaction. With `max_fee_per_kb` set, planning fails while `fee_per_kb` is above it.
+ *
+ * Return format:
+ *   - On success: "OK:{\"transactions\":[{\"inputs\":[...],\"input_amount\":n,\"outputs\":[n...],\"fee\":n,\"size\":n}],\"excluded\":[...],\"total_fee\":n,\"utxo_count_before\":n,\"utxo_count_after\":n}"
+ *   - On error:   "ERR:<error_message>"
+ *
+ * Free with `rust_cstr_free`.
+ */
+char *plan_consolidation_ffi(const char *utxos_json, uint64_t fee_per_kb, const char *options_json);
+
+/**
+ * Plans and signs a UTXO consolidation, paying every output to `destination_address`.
+ *
+ * Return format:
+ *   - On success: "OK:{\"plan\":<plan>,\"transactions\":[<signed tx>...]}"
+ *   - On error:   "ERR:<error_message>"
+ *
+ * Free with `rust_cstr_free`.
+ */
+char *sign_consolidation_ffi(
+    const char *utxos_json,
+    const char *private_key_hex,
+    const char *destination_address,
+    uint64_t fee_per_kb,
+    const char *options_json);
+
+/**
+ * Estimates PoSV v2 staking for the wallet's UTXOs: per-output coin-age weight, expected time to
+ * stake and projected annual reward.
+ *
+ * @param network_json
+ *   `{"network_weight": <coin-days from getstakinginfo netstakeweight>, "tip_height": n}`.
+ *
+ * Return format:
+ *   - On success: "OK:{\"utxos\":[{\"txid\":...,\"weight\":f,\"expected_secs_to_stake\":n|null,\"projected_annual_reward\":n}],\"excluded\":[...],\"staking_amount\":n,\"total_weight\":f,\"expected_secs_to_stake\":n|null,\"projected_annual_reward\":n}"
+ *   - On error:   "ERR:<error_message>"
+ *
+ * Figures are estimates for display. Free with `rust_cstr_free`.
+ */
+char *estimate_staking_ffi(const char *utxos_json, const char *network_json);
+
+/**
+ * What-if comparison of steady-state staking rewards for different output layouts.
+ *
+ * @param strategies_json
+ *   Array of `{"strategy":"current"}`, `{"strategy":"consolidate"}` or
+ *   `{"strategy":"split","target_output":<base_units>}`.
+ *
+ * Return format:
+ *   - On success: "OK:[{\"strategy\":...,\"output_count\":n,\"mean_secs_between_stakes\":n|null,\"projected_annual_reward\":n,\"annual_yield_bps\":n}]"
+ *   - On error:   "ERR:<error_message>"
+ *
+ * Free with `rust_cstr_free`.
+ */
+char *compare_staking_strategies_ffi(
+    const char *utxos_json,
+    const char *network_json,
+    const char *strategies_json);
+
+/**
+ * Evaluates transaction policy guardrails against a built (signed or unsigned) transaction.
+ *
+ * @param change_index
+ *   Output returning to the wallet, or -1 for none. Every other non-OP_RETURN output counts as a
+ *   payment.
+ *
+ * @param policy_json
+ *   `{"rules": {...}, "context": {...}}`. Rules (all optional): `max_fee`, `max_fee_ratio_bps`,
+ *   `daily_spend_limit`, `max_op_return_bytes` as `{"limit": n, "action": "warn"|"deny"}`
+ *   (action defaults to deny); `allowlist` and `blocklist` as address arrays;
+ *   `first_time_recipient` as "warn" or "deny". Context: `spent_today` (base units) and
+ *   `known_recipients` (address array).
+ *
+ * Return format:
+ *   - On success: "OK:{\"verdict\":\"allow\"|\"warn\"|\"deny\",\"findings\":[{\"rule\":...,\"verdict\":...,\"reason\":...}]}"
+ *   - On error:   "ERR:<error_message>"
+ *
+ * Free with `rust_cstr_free`.
+ */
+char *evaluate_policy_ffi(
+    const char *tx_hex,
+    uint64_t fee_base_units,
+    int64_t change_index,
+    const char *policy_json);
+
+/**
+ * Checks a built transaction against Reddcoin relay standardness rules: version, size, scriptSig
+ * size and push-only, output types, dust per output type, OP_RETURN count and size, sigops and
+ * the minimum relay fee. Every signing function already runs these checks and fails with ERR.
+ *
+ * Return format:
+ *   - On success: "OK:{\"size\":<bytes>,\"sigops\":<n>,\"min_relay_fee\":<base_units>,\"violations\":[...]}"
+ *     The transaction is standard when `violations` is empty.
+ *   - On error:   "ERR:<error_message>"
+ *
+ * Free with `rust_cstr_free`.
+ */
+char *check_standardness_ffi(
+    const char *tx_hex,
+    uint64_t fee_base_units);
+
+/**
+ * Runs every input's scriptSig against the scriptPubKey it spends (P2PKH, P2PK, P2SH, multisig and
+ * CLTV/CSV timelocks). Every signing function already does this before returning.
+ *
+ * @param prevouts_json
+ *   The UTXOs the inputs spend, in the same shape the signing functions take. Inputs are matched
+ *   by txid and vout; each UTXO needs `script_pubkey` (hex) or `address`.
+ *
+ * Return format:
+ *   - On success: "OK:<txid>"
+ *   - On error:   "ERR:<error_message>" naming the failing input
+ *
+ * Free with `rust_cstr_free`.
+ */
+char *verify_transaction_ffi(
+    const char *tx_hex,
+    const char *prevouts_json);
+
 /**
  * Frees C strings allocated and returned by Rust FFI functions in this library.
  */
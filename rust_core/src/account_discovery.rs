@@ -0,0 +1,448 @@
+//! HD account discovery.
+//!
+//! Restoring an HD wallet from its seed means finding which of its unbounded address space was
+//! ever used. This follows the BIP44 account discovery procedure on Reddcoin's path
+//! `m/44'/4'/account'/change/index` (coin type 4 per SLIP-44):
+//!
+//! 1. Derive account 0's receive chain and query addresses in order until `gap_limit`
+//!    consecutive ones have no history.
+//! 2. If the receive chain had no history at all, stop: that account is the next one to create.
+//! 3. Otherwise scan its change chain the same way and move on to the next account.
+//!
+//! Only public derivation is used past the account level, and every query goes through a
+//! [`ChainBackend`], so the engine runs against any backend or a mock.
+
+use bitcoin::bip32::{ChildNumber, DerivationPath, Xpriv, Xpub};
+use bitcoin::{NetworkKind, PublicKey};
+use serde::{Deserialize, Serialize};
+
+use crate::address::ReddcoinAddress;
+use crate::amount::Amount;
+use crate::chain_backend::{ChainBackend, Utxo};
+use crate::secp_context::secp;
+
+/// BIP44 purpose level.
+pub const BIP44_PURPOSE: u32 = 44;
+/// Reddcoin's registered SLIP-44 coin type.
+pub const REDDCOIN_COIN_TYPE: u32 = 4;
+/// Consecutive unused addresses after which a chain is considered exhausted, per BIP44.
+pub const DEFAULT_GAP_LIMIT: u32 = 20;
+/// Accounts scanned at most, so a backend reporting history for everything cannot loop forever.
+pub const DEFAULT_MAX_ACCOUNTS: u32 = 100;
+/// Upper bound on addresses queried per chain, for the same reason.
+const MAX_ADDRESSES_PER_CHAIN: u32 = 100_000;
+/// BIP32 allows seeds of 128 to 512 bits.
+const MIN_SEED_BYTES: usize = 16;
+const MAX_SEED_BYTES: usize = 64;
+
+/// The two chains of an account.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressChain {
+    /// Addresses handed out for receiving (`change` = 0).
+    Receive,
+    /// Change addresses (`change` = 1).
+    Change,
+}
+
+impl AddressChain {
+    fn index(self) -> u32 {
+        match self {
+            Self::Receive => 0,
+            Self::Change => 1,
+        }
+    }
+}
+
+/// Settings for [`discover_accounts`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct DiscoveryOptions {
+    /// Consecutive unused addresses that end a chain's scan. Wallets that hand out addresses
+    /// without waiting for payment need a larger gap to be restored completely.
+    pub gap_limit: u32,
+    /// Stop after this many accounts even if the last one was used.
+    pub max_accounts: u32,
+    /// Also fetch the UTXOs of every used address.
+    pub fetch_utxos: bool,
+}
+
+impl Default for DiscoveryOptions {
+    fn default() -> Self {
+        Self {
+            gap_limit: DEFAULT_GAP_LIMIT,
+            max_accounts: DEFAULT_MAX_ACCOUNTS,
+            fetch_utxos: true,
+        }
+    }
+}
+
+/// An address with on-chain history.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct UsedAddress {
+    pub address: String,
+    pub chain: AddressChain,
+    pub index: u32,
+    /// Full derivation path, e.g. `m/44'/4'/0'/0/3`.
+    pub path: String,
+    pub tx_count: usize,
+}
+
+/// One account found by discovery.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DiscoveredAccount {
+    pub account: u32,
+    /// Account path, e.g. `m/44'/4'/0'`.
+    pub path: String,
+    pub used_addresses: Vec<UsedAddress>,
+    /// UTXOs of the used addresses; empty unless [`DiscoveryOptions::fetch_utxos`] is set.
+    pub utxos: Vec<Utxo>,
+    /// Confirmed and unconfirmed value of `utxos`.
+    pub balance: Amount,
+    /// First receive index after the last used one: the next address to hand out.
+    pub next_receive_index: u32,
+    /// First change index after the last used one.
+    pub next_change_index: u32,
+}
+
+/// Result of [`discover_accounts`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AccountDiscovery {
+    /// Used accounts, in order. Empty for a wallet that was never used.
+    pub accounts: Vec<DiscoveredAccount>,
+    /// The first unused account, the one to create next.
+    pub next_account: u32,
+    /// Whether discovery stopped at [`DiscoveryOptions::max_accounts`] rather than at an unused
+    /// account; later accounts may still hold funds.
+    pub truncated: bool,
+    /// Addresses queried in total.
+    pub addresses_checked: u32,
+}
+
+/// BIP44 path of `account`: `m/44'/4'/account'`.
+pub fn account_path(account: u32) -> Result<DerivationPath, String> {
+    [BIP44_PURPOSE, REDDCOIN_COIN_TYPE, account]
+        .into_iter()
+        .map(|index| {
+            ChildNumber::from_hardened_idx(index)
+                .map_err(|e| format!("invalid account index {index}: {e}"))
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map(DerivationPath::from)
+}
+
+/// Derives the extended public key of `account` from a BIP32 `seed` (for a BIP39 mnemonic, the
+/// 64-byte PBKDF2 output).
+pub fn account_xpub(seed: &[u8], account: u32) -> Result<Xpub, String> {
+    if !(MIN_SEED_BYTES..=MAX_SEED_BYTES).contains(&seed.len()) {
+        return Err(format!(
+            "seed must be {MIN_SEED_BYTES} to {MAX_SEED_BYTES} bytes, got {}",
+            seed.len()
+        ));
+    }
+    let secp = secp();
+    let master =
+        Xpriv::new_master(NetworkKind::Main, seed).map_err(|e| format!("invalid seed: {e}"))?;
+    let account_key = master
+        .derive_priv(secp, &account_path(account)?)
+        .map_err(|e| format!("failed to derive account {account}: {e}"))?;
+    Ok(Xpub::from_priv(secp, &account_key))
+}
+
+/// The P2PKH address at `chain`/`index` under an account key.
+pub fn derive_address(
+    account_xpub: &Xpub,
+    chain: AddressChain,
+    index: u32,
+) -> Result<ReddcoinAddress, String> {
+    let path = [chain.index(), index]
+        .into_iter()
+        .map(|i| ChildNumber::from_normal_idx(i).map_err(|e| format!("invalid index {i}: {e}")))
+        .collect::<Result<Vec<_>, _>>()?;
+    let child = account_xpub
+        .derive_pub(secp(), &path)
+        .map_err(|e| format!("failed to derive {}/{index}: {e}", chain.index()))?;
+    Ok(ReddcoinAddress::p2pkh(&PublicKey::new(child.public_key)))
+}
+
+/// Finds the used accounts of the wallet with BIP32 `seed` by querying `backend`.
+pub fn discover_accounts(
+    seed: &[u8],
+    backend: &dyn ChainBackend,
+    options: &DiscoveryOptions,
+) -> Result<AccountDiscovery, String> {
+    if options.gap_limit == 0 {
+        return Err("gap limit must be at least 1".to_string());
+    }
+    let mut discovery = AccountDiscovery {
+        accounts: Vec::new(),
+        next_account: 0,
+        truncated: false,
+        addresses_checked: 0,
+    };
+    loop {
+        if discovery.next_account >= options.max_accounts {
+            discovery.truncated = true;
+            return Ok(discovery);
+        }
+        let account = discovery.next_account;
+        let xpub = account_xpub(seed, account)?;
+        let base_path = format!("m/{}", account_path(account)?);
+
+        let mut scanner = ChainScanner {
+            backend,
+            xpub: &xpub,
+            base_path: &base_path,
+            options,
+            checked: 0,
+        };
+        let (receive, next_receive_index) = scanner.scan(AddressChain::Receive)?;
+        if receive.is_empty() {
+            discovery.addresses_checked += scanner.checked;
+            return Ok(discovery);
+        }
+        let (change, next_change_index) = scanner.scan(AddressChain::Change)?;
+        discovery.addresses_checked += scanner.checked;
+
+        let used_addresses: Vec<UsedAddress> = receive.into_iter().chain(change).collect();
+        let mut utxos = Vec::new();
+        if options.fetch_utxos {
+            for used in &used_addresses {
+                utxos.extend(backend.utxos(&used.address)?);
+            }
+        }
+        let balance = Amount::checked_sum(utxos.iter().map(|utxo| utxo.amount))
+            .ok_or_else(|| format!("balance of account {account} overflows"))?;
+        discovery.accounts.push(DiscoveredAccount {
+            account,
+            path: base_path,
+            used_addresses,
+            utxos,
+            balance,
+            next_receive_index,
+            next_change_index,
+        });
+        discovery.next_account += 1;
+    }
+}
+
+/// Walks one chain of one account until the gap limit.
+struct ChainScanner<'a> {
+    backend: &'a dyn ChainBackend,
+    xpub: &'a Xpub,
+    base_path: &'a str,
+    options: &'a DiscoveryOptions,
+    checked: u32,
+}
+
+impl ChainScanner<'_> {
+    /// Returns the used addresses and the index after the last one.
+    fn scan(&mut self, chain: AddressChain) -> Result<(Vec<UsedAddress>, u32), String> {
+        let mut used = Vec::new();
+        let mut next_unused = 0u32;
+        let mut index = 0u32;
+        while index - next_unused < self.options.gap_limit {
+            if index >= MAX_ADDRESSES_PER_CHAIN {
+                return Err(format!(
+                    "{}/{} has more than {MAX_ADDRESSES_PER_CHAIN} used addresses",
+                    self.base_path,
+                    chain.index()
+                ));
+            }
+            let address = derive_address(self.xpub, chain, index)?.to_string();
+            let history = self.backend.history(&address)?;
+            self.checked += 1;
+            if !history.is_empty() {
+                used.push(UsedAddress {
+                    address,
+                    chain,
+                    index,
+                    path: format!("{}/{}/{index}", self.base_path, chain.index()),
+                    tx_count: history.len(),
+                });
+                next_unused = index + 1;
+            }
+            index += 1;
+        }
+        Ok((used, next_unused))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::chain_backend::{AddressBalance, ChainStatus, HistoryEntry};
+
+    const SEED: [u8; 32] = [7; 32];
+
+    /// Reports one transaction and one 1000-unit UTXO for each used address.
+    struct MockBackend {
+        used: HashSet<String>,
+    }
+
+    impl MockBackend {
+        fn new(used: &[(u32, AddressChain, u32)]) -> Self {
+            let used = used
+                .iter()
+                .map(|&(account, chain, index)| {
+                    let xpub = account_xpub(&SEED, account).unwrap();
+                    derive_address(&xpub, chain, index).unwrap().to_string()
+                })
+                .collect();
+            Self { used }
+        }
+    }
+
+    impl ChainBackend for MockBackend {
+        fn name(&self) -> String {
+            "mock".to_string()
+        }
+
+        fn chain_status(&self) -> Result<ChainStatus, String> {
+            Err("unused".to_string())
+        }
+
+        fn utxos(&self, address: &str) -> Result<Vec<Utxo>, String> {
+            Ok(serde_json::from_value(serde_json::json!([{
+                "txid": "ab".repeat(32),
+                "vout": 0,
+                "amount": 1000,
+                "address": address,
+            }]))
+            .unwrap())
+        }
+
+        fn history(&self, address: &str) -> Result<Vec<HistoryEntry>, String> {
+            Ok(if self.used.contains(address) {
+                vec![HistoryEntry {
+                    txid: "ab".repeat(32),
+                    height: Some(1),
+                    verified: false,
+                }]
+            } else {
+                Vec::new()
+            })
+        }
+
+        fn balance(&self, _: &str) -> Result<AddressBalance, String> {
+            Err("unused".to_string())
+        }
+
+        fn estimate_fee_per_kb(&self, _: u16) -> Result<u64, String> {
+            Err("unused".to_string())
+        }
+
+        fn raw_transaction(&self, _: &str) -> Result<String, String> {
+            Err("unused".to_string())
+        }
+
+        fn broadcast(&self, _: &str) -> Result<String, String> {
+            Err("unused".to_string())
+        }
+    }
+
+    fn discover(used: &[(u32, AddressChain, u32)], options: DiscoveryOptions) -> AccountDiscovery {
+        discover_accounts(&SEED, &MockBackend::new(used), &options).unwrap()
+    }
+
+    #[test]
+    fn empty_wallet_checks_one_gap() {
+        let discovery = discover(&[], DiscoveryOptions::default());
+        assert!(discovery.accounts.is_empty());
+        assert_eq!(discovery.next_account, 0);
+        assert!(!discovery.truncated);
+        assert_eq!(discovery.addresses_checked, DEFAULT_GAP_LIMIT);
+
+        let options = DiscoveryOptions {
+            gap_limit: 0,
+            ..DiscoveryOptions::default()
+        };
+        assert!(discover_accounts(&SEED, &MockBackend::new(&[]), &options).is_err());
+    }
+
+    #[test]
+    fn finds_history_at_chosen_indices() {
+        let discovery = discover(
+            &[
+                (0, AddressChain::Receive, 0),
+                (0, AddressChain::Receive, 5),
+                (0, AddressChain::Change, 2),
+            ],
+            DiscoveryOptions::default(),
+        );
+        assert_eq!(discovery.next_account, 1);
+        let account = &discovery.accounts[0];
+        let paths: Vec<_> = account
+            .used_addresses
+            .iter()
+            .map(|used| used.path.as_str())
+            .collect();
+        assert_eq!(
+            paths,
+            ["m/44'/4'/0'/0/0", "m/44'/4'/0'/0/5", "m/44'/4'/0'/1/2"]
+        );
+        assert_eq!(account.next_receive_index, 6);
+        assert_eq!(account.next_change_index, 3);
+        assert_eq!(account.balance, Amount::from_base(3000));
+        // Each chain runs a full gap past its last used address, then account 1's receive chain.
+        assert_eq!(discovery.addresses_checked, (6 + 20) + (3 + 20) + 20);
+    }
+
+    #[test]
+    fn stops_after_gap_limit_unused_addresses() {
+        let options = DiscoveryOptions {
+            gap_limit: 5,
+            fetch_utxos: false,
+            ..DiscoveryOptions::default()
+        };
+        // Four unused addresses between two used ones stay within the gap...
+        let within = discover(
+            &[(0, AddressChain::Receive, 0), (0, AddressChain::Receive, 5)],
+            options.clone(),
+        );
+        assert_eq!(within.accounts[0].next_receive_index, 6);
+        assert!(within.accounts[0].utxos.is_empty());
+        // ...five end the chain before the next one.
+        let beyond = discover(
+            &[(0, AddressChain::Receive, 0), (0, AddressChain::Receive, 6)],
+            options,
+        );
+        assert_eq!(beyond.accounts[0].used_addresses.len(), 1);
+        assert_eq!(beyond.accounts[0].next_receive_index, 1);
+    }
+
+    #[test]
+    fn walks_accounts_until_an_unused_one() {
+        let used = [
+            (0, AddressChain::Receive, 0),
+            (1, AddressChain::Receive, 3),
+            (1, AddressChain::Change, 0),
+        ];
+        let discovery = discover(&used, DiscoveryOptions::default());
+        assert_eq!(discovery.next_account, 2);
+        assert!(!discovery.truncated);
+        let account = &discovery.accounts[1];
+        assert_eq!(account.path, "m/44'/4'/1'");
+        assert_eq!(
+            (account.next_receive_index, account.next_change_index),
+            (4, 1)
+        );
+        assert_eq!(account.utxos.len(), 2);
+
+        // An account with change but no receive history is unused.
+        let change_only = discover(&[(0, AddressChain::Change, 0)], DiscoveryOptions::default());
+        assert!(change_only.accounts.is_empty());
+
+        let capped = discover(
+            &used,
+            DiscoveryOptions {
+                max_accounts: 1,
+                ..DiscoveryOptions::default()
+            },
+        );
+        assert_eq!(capped.accounts.len(), 1);
+        assert!(capped.truncated);
+    }
+}
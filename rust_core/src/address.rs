@@ -0,0 +1,113 @@
+//! Reddcoin Base58Check address handling.
+//!
+//! The bitcoin crate only knows Bitcoin-family version bytes, so `Address::from_str` rejects
+//! every `R...` address. This module owns the Reddcoin-specific encoding and converts addresses
+//! to and from the `ScriptBuf` values the transaction code works with.
+
+use std::fmt;
+use std::str::FromStr;
+
+use bitcoin::base58;
+use bitcoin::hashes::Hash;
+use bitcoin::{PubkeyHash, PublicKey, Script, ScriptBuf, ScriptHash};
+
+use crate::transaction_signer::REDDCOIN_VERSION_BYTE;
+
+/// Reddcoin's Base58 P2SH version byte.
+///
+/// Reddcoin kept Bitcoin's `0x05` for script-hash addresses, so P2SH addresses start with `3`
+/// exactly like Bitcoin ones. Only the P2PKH prefix diverges.
+pub const REDDCOIN_P2SH_VERSION_BYTE: u8 = 0x05;
+
+/// A validated Reddcoin address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReddcoinAddress {
+    /// Legacy pay-to-pubkey-hash (`R...`).
+    P2pkh(PubkeyHash),
+    /// Pay-to-script-hash (`3...`).
+    P2sh(ScriptHash),
+}
+
+impl ReddcoinAddress {
+    /// Derives the P2PKH address for a public key.
+    ///
+    /// The compression flag on `pubkey` matters: compressed and uncompressed encodings of the
+    /// same key hash to different addresses, and legacy paper wallets use the uncompressed form.
+    pub fn p2pkh(pubkey: &PublicKey) -> Self {
+        Self::P2pkh(pubkey.pubkey_hash())
+    }
+
+    /// Derives the P2SH address committing to `redeem_script`.
+    pub fn p2sh(redeem_script: &Script) -> Self {
+        Self::P2sh(redeem_script.script_hash())
+    }
+
+    /// Recognizes standard P2PKH/P2SH output scripts.
+    ///
+    /// Returns `None` for any other script shape (OP_RETURN, bare multisig, ...), since those
+    /// have no address representation.
+    pub fn from_script(script: &Script) -> Option<Self> {
+        let bytes = script.as_bytes();
+        if script.is_p2pkh() {
+            let hash = <[u8; 20]>::try_from(&bytes[3..23]).ok()?;
+            return Some(Self::P2pkh(PubkeyHash::from_byte_array(hash)));
+        }
+        if script.is_p2sh() {
+            let hash = <[u8; 20]>::try_from(&bytes[2..22]).ok()?;
+            return Some(Self::P2sh(ScriptHash::from_byte_array(hash)));
+        }
+        None
+    }
+
+    /// Returns the output script that pays to this address.
+    pub fn script_pubkey(&self) -> ScriptBuf {
+        match self {
+            Self::P2pkh(hash) => ScriptBuf::new_p2pkh(hash),
+            Self::P2sh(hash) => ScriptBuf::new_p2sh(hash),
+        }
+    }
+}
+
+impl fmt::Display for ReddcoinAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut payload = [0u8; 21];
+        match self {
+            Self::P2pkh(hash) => {
+                payload[0] = REDDCOIN_VERSION_BYTE;
+                payload[1..].copy_from_slice(hash.as_byte_array());
+            }
+            Self::P2sh(hash) => {
+                payload[0] = REDDCOIN_P2SH_VERSION_BYTE;
+                payload[1..].copy_from_slice(hash.as_byte_array());
+            }
+        }
+        f.write_str(&base58::encode_check(&payload))
+    }
+}
+
+impl FromStr for ReddcoinAddress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let decoded = base58::decode_check(s.trim())
+            .map_err(|e| format!("address is not valid Base58Check: {e}"))?;
+
+        if decoded.len() != 21 {
+            return Err(format!(
+                "address payload must be 21 bytes, got {}",
+                decoded.len()
+            ));
+        }
+
+        let mut hash = [0u8; 20];
+        hash.copy_from_slice(&decoded[1..]);
+
+        match decoded[0] {
+            REDDCOIN_VERSION_BYTE => Ok(Self::P2pkh(PubkeyHash::from_byte_array(hash))),
+            REDDCOIN_P2SH_VERSION_BYTE => Ok(Self::P2sh(ScriptHash::from_byte_array(hash))),
+            other => Err(format!(
+                "address version byte 0x{other:02x} is not a Reddcoin mainnet address"
+            )),
+        }
+    }
+}
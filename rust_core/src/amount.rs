@@ -0,0 +1,382 @@
+//! Exact Reddcoin amounts.
+//!
+//! Amounts cross the FFI boundary as decimal strings or integer base units, never floats: an
+//! `f64` cannot represent most 8-decimal values exactly, so Dart and Rust used to round the same
+//! user input to different base-unit values. Everything here is integer arithmetic.
+
+use std::fmt;
+use std::iter::Sum;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Base units per whole RDD.
+pub const BASE_UNITS_PER_RDD: u64 = 100_000_000;
+
+/// Units amounts can be parsed from and formatted in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Denomination {
+    /// Whole RDD, 8 decimal places.
+    Rdd,
+    /// Thousandths of an RDD, 5 decimal places.
+    MilliRdd,
+    /// Indivisible base units (the Reddcoin equivalent of satoshis).
+    Base,
+}
+
+impl Denomination {
+    /// Number of decimal places between this unit and base units.
+    pub fn decimals(self) -> u32 {
+        match self {
+            Self::Rdd => 8,
+            Self::MilliRdd => 5,
+            Self::Base => 0,
+        }
+    }
+}
+
+impl FromStr for Denomination {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "rdd" => Ok(Self::Rdd),
+            "mrdd" => Ok(Self::MilliRdd),
+            "base" | "sat" | "sats" => Ok(Self::Base),
+            other => Err(format!(
+                "unknown denomination `{other}` (expected rdd, mrdd or base)"
+            )),
+        }
+    }
+}
+
+/// A non-negative Reddcoin amount stored as base units.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Amount(u64);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+    pub const ONE_RDD: Amount = Amount(BASE_UNITS_PER_RDD);
+    pub const MAX: Amount = Amount(u64::MAX);
+
+    /// Wraps a base-unit value.
+    pub const fn from_base(base_units: u64) -> Self {
+        Self(base_units)
+    }
+
+    /// Returns the value in base units.
+    pub const fn to_base(self) -> u64 {
+        self.0
+    }
+
+    /// Parses a decimal string expressed in `denomination`.
+    ///
+    /// Rejects signs, exponents, thousands separators and more fractional digits than the unit
+    /// allows. Silently truncating `0.123456789` RDD would move a different amount than the user
+    /// typed, so excess precision is an error rather than a rounding decision.
+    pub fn from_str_in(s: &str, denomination: Denomination) -> Result<Self, String> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err("amount cannot be empty".to_string());
+        }
+
+        let (whole, fraction) = match s.split_once('.') {
+            Some((whole, fraction)) => (whole, fraction),
+            None => (s, ""),
+        };
+
+        if whole.is_empty() && fraction.is_empty() {
+            return Err(format!("invalid amount `{s}`"));
+        }
+        if !whole.bytes().all(|b| b.is_ascii_digit())
+            || !fraction.bytes().all(|b| b.is_ascii_digit())
+        {
+            return Err(format!(
+                "invalid amount `{s}`: only digits and one `.` are allowed"
+            ));
+        }
+
+        let decimals = denomination.decimals() as usize;
+        if fraction.len() > decimals {
+            return Err(format!(
+                "amount `{s}` has more than {decimals} decimal places"
+            ));
+        }
+
+        let scale = 10u64.pow(denomination.decimals());
+        let whole_units = if whole.is_empty() {
+            0
+        } else {
+            whole
+                .parse::<u64>()
+                .map_err(|_| format!("amount `{s}` is too large"))?
+        };
+        let fraction_units = if fraction.is_empty() {
+            0
+        } else {
+            // Right-pad so "0.5" RDD becomes 50_000_000 base units.
+            format!("{fraction:0<decimals$}")
+                .parse::<u64>()
+                .map_err(|_| format!("invalid amount `{s}`"))?
+        };
+
+        whole_units
+            .checked_mul(scale)
+            .and_then(|v| v.checked_add(fraction_units))
+            .map(Self)
+            .ok_or_else(|| format!("amount `{s}` is too large"))
+    }
+
+    /// Formats the amount in `denomination` with all of the unit's decimal places.
+    pub fn to_string_in(self, denomination: Denomination) -> String {
+        let decimals = denomination.decimals();
+        if decimals == 0 {
+            return self.0.to_string();
+        }
+        let scale = 10u64.pow(decimals);
+        format!(
+            "{}.{:0width$}",
+            self.0 / scale,
+            self.0 % scale,
+            width = decimals as usize
+        )
+    }
+
+    pub fn checked_add(self, rhs: Amount) -> Option<Amount> {
+        self.0.checked_add(rhs.0).map(Self)
+    }
+
+    pub fn saturating_add(self, rhs: Amount) -> Amount {
+        Self(self.0.saturating_add(rhs.0))
+    }
+
+    pub fn checked_sub(self, rhs: Amount) -> Option<Amount> {
+        self.0.checked_sub(rhs.0).map(Self)
+    }
+
+    pub fn checked_mul(self, rhs: u64) -> Option<Amount> {
+        self.0.checked_mul(rhs).map(Self)
+    }
+
+    pub fn checked_div(self, rhs: u64) -> Option<Amount> {
+        self.0.checked_div(rhs).map(Self)
+    }
+
+    /// Sums amounts, returning `None` on overflow instead of wrapping.
+    pub fn checked_sum<I: IntoIterator<Item = Amount>>(amounts: I) -> Option<Amount> {
+        amounts
+            .into_iter()
+            .try_fold(Amount::ZERO, |acc, amount| acc.checked_add(amount))
+    }
+}
+
+/// Formats as whole RDD with 8 decimal places, e.g. `12.50000000`.
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_string_in(Denomination::Rdd))
+    }
+}
+
+/// Parses whole RDD, e.g. `12.5`.
+impl FromStr for Amount {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::from_str_in(s, Denomination::Rdd)
+    }
+}
+
+/// Saturating sum for display totals; use [`Amount::checked_sum`] where overflow must be caught.
+impl Sum for Amount {
+    fn sum<I: Iterator<Item = Amount>>(iter: I) -> Self {
+        iter.fold(Amount::ZERO, Amount::saturating_add)
+    }
+}
+
+impl From<Amount> for bitcoin::Amount {
+    fn from(amount: Amount) -> Self {
+        bitcoin::Amount::from_sat(amount.0)
+    }
+}
+
+impl From<bitcoin::Amount> for Amount {
+    fn from(amount: bitcoin::Amount) -> Self {
+        Self(amount.to_sat())
+    }
+}
+
+/// Serializes as an integer number of base units, matching the UTXO JSON the app already sends.
+impl Serialize for Amount {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_u64(self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Amount {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        u64::deserialize(deserializer).map(Self)
+    }
+}
+
+/// Serde adapter for fields carried as decimal RDD strings (`"12.50000000"`).
+///
+/// Use with `#[serde(with = "crate::amount::as_rdd_str")]`.
+pub mod as_rdd_str {
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+
+    use super::Amount;
+
+    pub fn serialize<S: Serializer>(amount: &Amount, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&amount.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Amount, D::Error> {
+        let raw = String::deserialize(deserializer)?;
+        raw.parse().map_err(D::Error::custom)
+    }
+}
+
+/// Serde adapter for fields carried as JSON numbers of RDD (`12.5`), as Reddcoin Core emits them.
+///
+/// The number's text is parsed exactly. Going through `f64` would round any amount above about
+/// 90 million RDD. Only works with `serde_json`, which is the only format these fields arrive in.
+///
+/// Use with `#[serde(with = "crate::amount::as_rdd_number")]`.
+pub mod as_rdd_number {
+    use serde::{de::Error, ser::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+    use serde_json::value::RawValue;
+
+    use super::Amount;
+
+    pub fn serialize<S: Serializer>(amount: &Amount, serializer: S) -> Result<S::Ok, S::Error> {
+        RawValue::from_string(amount.to_string())
+            .map_err(S::Error::custom)?
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Amount, D::Error> {
+        let raw = Box::<RawValue>::deserialize(deserializer)?;
+        raw.get().parse().map_err(D::Error::custom)
+    }
+}
+
+/// Parses a decimal amount string in the named unit and returns base units as a string.
+///
+/// This is the conversion Dart calls before handing amounts to any signing function.
+pub fn parse_amount(amount: String, denomination: String) -> Result<String, String> {
+    let denomination = Denomination::from_str(&denomination)?;
+    Amount::from_str_in(&amount, denomination).map(|amount| amount.to_base().to_string())
+}
+
+/// Formats a base-unit amount in the named unit.
+pub fn format_amount(base_units: u64, denomination: String) -> Result<String, String> {
+    let denomination = Denomination::from_str(&denomination)?;
+    Ok(Amount::from_base(base_units).to_string_in(denomination))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[test]
+    fn parses_each_denomination_exactly() {
+        assert_eq!(Amount::from_str("12.5").unwrap().to_base(), 1_250_000_000);
+        assert_eq!(Amount::from_str(".00000001").unwrap().to_base(), 1);
+        assert_eq!(Amount::from_str(" 7. ").unwrap().to_base(), 700_000_000);
+        assert_eq!(
+            Amount::from_str_in("0.00001", Denomination::MilliRdd)
+                .unwrap()
+                .to_base(),
+            1
+        );
+        assert_eq!(
+            Amount::from_str_in("42", Denomination::Base)
+                .unwrap()
+                .to_base(),
+            42
+        );
+        // 0.1 + 0.2 is not 0.3 in f64; it must be here.
+        let sum = Amount::from_str("0.1")
+            .unwrap()
+            .checked_add(Amount::from_str("0.2").unwrap());
+        assert_eq!(sum, Some(Amount::from_str("0.3").unwrap()));
+    }
+
+    #[test]
+    fn rejects_malformed_and_excess_precision() {
+        for input in [
+            "", ".", "-1", "+1", "1e8", "1,000", "1.2.3", "0x10", "1 000",
+        ] {
+            assert!(Amount::from_str(input).is_err(), "{input:?}");
+        }
+        assert!(Amount::from_str("0.123456789")
+            .unwrap_err()
+            .contains("more than 8 decimal places"));
+        assert!(Amount::from_str_in("0.000001", Denomination::MilliRdd).is_err());
+        assert!(Amount::from_str_in("1.5", Denomination::Base).is_err());
+        assert!(Amount::from_str("184467440737.09551616")
+            .unwrap_err()
+            .contains("too large"));
+    }
+
+    #[test]
+    fn formats_with_all_decimals() {
+        let amount = Amount::from_base(1_250_000_001);
+        assert_eq!(amount.to_string(), "12.50000001");
+        assert_eq!(amount.to_string_in(Denomination::MilliRdd), "12500.00001");
+        assert_eq!(amount.to_string_in(Denomination::Base), "1250000001");
+        assert_eq!(Amount::ZERO.to_string(), "0.00000000");
+        assert_eq!(format_amount(1, "mrdd".to_string()).unwrap(), "0.00001");
+        assert_eq!(
+            parse_amount("1.5".to_string(), "RDD".to_string()).unwrap(),
+            "150000000"
+        );
+        assert!(parse_amount("1".to_string(), "btc".to_string()).is_err());
+    }
+
+    #[test]
+    fn checked_arithmetic_catches_overflow() {
+        assert_eq!(Amount::MAX.checked_add(Amount::from_base(1)), None);
+        assert_eq!(Amount::ZERO.checked_sub(Amount::from_base(1)), None);
+        assert_eq!(Amount::MAX.checked_mul(2), None);
+        assert_eq!(Amount::ONE_RDD.checked_div(0), None);
+        assert_eq!(
+            Amount::checked_sum([Amount::MAX, Amount::from_base(1)]),
+            None
+        );
+        assert_eq!(
+            [Amount::MAX, Amount::from_base(1)]
+                .into_iter()
+                .sum::<Amount>(),
+            Amount::MAX
+        );
+    }
+
+    #[test]
+    fn serde_adapters_are_exact() {
+        #[derive(Deserialize, Serialize)]
+        struct Fields {
+            base: Amount,
+            #[serde(with = "as_rdd_str")]
+            text: Amount,
+            #[serde(with = "as_rdd_number")]
+            number: Amount,
+        }
+
+        // 92233720.36854775 RDD does not survive a round trip through f64.
+        let json = r#"{"base":5,"text":"0.1","number":92233720.36854775}"#;
+        let fields: Fields = serde_json::from_str(json).unwrap();
+        assert_eq!(fields.base.to_base(), 5);
+        assert_eq!(fields.text.to_base(), 10_000_000);
+        assert_eq!(fields.number.to_base(), 9_223_372_036_854_775);
+        assert_eq!(
+            serde_json::to_string(&fields).unwrap(),
+            r#"{"base":5,"text":"0.10000000","number":92233720.36854775}"#
+        );
+        assert!(
+            serde_json::from_str::<Fields>(r#"{"base":5,"text":"0.1","number":1e-8}"#).is_err()
+        );
+    }
+}
@@ -0,0 +1,853 @@
+//! Batch payments: one signing pass paying many recipients.
+//!
+//! Community managers pay dozens of tips at once, so entries arrive as CSV or JSON rows of
+//! `recipient, amount, memo`. Every row is validated up front and reported individually, so a
+//! bad line can be fixed in the spreadsheet instead of failing the whole batch with one message.
+//! Batches that would exceed the standard transaction size are split across several
+//! transactions.
+
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+use bitcoin::absolute::LockTime;
+use bitcoin::consensus::encode::deserialize;
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use bitcoin::{PublicKey as BitcoinPublicKey, ScriptBuf, Transaction, TxOut};
+use serde::{Deserialize, Serialize};
+
+use crate::address::ReddcoinAddress;
+use crate::amount::Amount;
+use crate::chain_params::ChainParams;
+use crate::payment_uri::PaymentTarget;
+use crate::secp_context::secp;
+use crate::standardness::dust_threshold;
+use crate::transaction_signer::{
+    ensure_verified, estimate_legacy_tx_fee, estimate_legacy_tx_size, order_and_sign,
+    unsigned_inputs, validate_utxos, BuildOptions, SignedTransaction, Utxo,
+    LEGACY_P2PKH_DUST_LIMIT, LEGACY_P2PKH_INPUT_SIZE, MAX_STANDARD_TX_SIZE,
+};
+
+/// One requested payment, as entered.
+///
+/// The amount stays a string until validation so a malformed value is reported against its row
+/// rather than rejecting the whole JSON document.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct BatchEntry {
+    /// Reddcoin address or ReddID `@handle`.
+    pub recipient: String,
+    /// Decimal RDD, e.g. `"12.5"`.
+    pub amount: String,
+    #[serde(default)]
+    pub memo: Option<String>,
+}
+
+/// A problem with one row. Only [`BatchIssue::DuplicateRecipient`] is a warning; every other
+/// issue blocks signing.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BatchIssue {
+    InvalidRecipient {
+        message: String,
+    },
+    /// A `@handle` with no entry in the caller's resolution map.
+    UnresolvedHandle,
+    InvalidAmount {
+        message: String,
+    },
+    /// Below the dust limit, so the output would be non-standard.
+    Dust {
+        minimum: Amount,
+    },
+    /// Same address as an earlier row, which is usually a copy-paste mistake.
+    DuplicateRecipient {
+        first_row: usize,
+    },
+}
+
+impl BatchIssue {
+    pub fn is_blocking(&self) -> bool {
+        !matches!(self, Self::DuplicateRecipient { .. })
+    }
+}
+
+/// Validation result for one row.
+#[derive(Debug, Clone, Serialize)]
+pub struct EntryReport {
+    /// 1-based CSV line or JSON array position.
+    pub row: usize,
+    pub recipient: String,
+    /// Address the payment goes to, after handle resolution.
+    pub address: Option<String>,
+    pub amount: Option<Amount>,
+    pub memo: Option<String>,
+    pub issues: Vec<BatchIssue>,
+}
+
+/// Validation result for a whole batch.
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchValidation {
+    pub entries: Vec<EntryReport>,
+    /// Sum of every amount that parsed.
+    pub total: Amount,
+    /// `false` if any row has a blocking issue.
+    pub signable: bool,
+}
+
+/// Where one recipient ended up after signing.
+#[derive(Debug, Clone, Serialize)]
+pub struct RecipientPayment {
+    pub row: usize,
+    pub recipient: String,
+    pub address: String,
+    pub amount: Amount,
+    pub memo: Option<String>,
+    /// Index into [`BatchPayment::transactions`].
+    pub transaction_index: usize,
+    /// Output position inside that transaction.
+    pub output_index: u32,
+}
+
+/// Signed transactions for a batch and the per-recipient report.
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchPayment {
+    pub transactions: Vec<SignedTransaction>,
+    pub recipients: Vec<RecipientPayment>,
+    pub total_sent: Amount,
+    pub total_fee: Amount,
+}
+
+/// Parses `recipient,amount[,memo]` rows.
+///
+/// Blank lines and lines starting with `#` are skipped, a leading `recipient`/`address` header
+/// row is ignored, and fields may be double-quoted (with `""` as an escaped quote) so memos can
+/// contain commas. Row numbers in reports are the 1-based line numbers of the input.
+pub fn parse_batch_csv(csv: &str) -> Result<Vec<(usize, BatchEntry)>, String> {
+    let mut entries = Vec::new();
+    for (index, line) in csv.lines().enumerate() {
+        let row = index + 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let fields = split_csv_line(trimmed).map_err(|e| format!("line {row}: {e}"))?;
+        if entries.is_empty()
+            && matches!(
+                fields[0].to_ascii_lowercase().as_str(),
+                "recipient" | "address"
+            )
+        {
+            continue;
+        }
+        if !(2..=3).contains(&fields.len()) {
+            return Err(format!(
+                "line {row}: expected `recipient,amount[,memo]`, found {} fields",
+                fields.len()
+            ));
+        }
+
+        let mut fields = fields.into_iter();
+        entries.push((
+            row,
+            BatchEntry {
+                recipient: fields.next().unwrap_or_default(),
+                amount: fields.next().unwrap_or_default(),
+                memo: fields.next().filter(|memo| !memo.is_empty()),
+            },
+        ));
+    }
+
+    if entries.is_empty() {
+        return Err("batch contains no payments".to_string());
+    }
+    Ok(entries)
+}
+
+/// Parses a JSON array of [`BatchEntry`] objects, numbering rows from 1.
+pub fn parse_batch_json(json: &str) -> Result<Vec<(usize, BatchEntry)>, String> {
+    let entries: Vec<BatchEntry> =
+        serde_json::from_str(json).map_err(|e| format!("invalid batch JSON: {e}"))?;
+    if entries.is_empty() {
+        return Err("batch contains no payments".to_string());
+    }
+    Ok(entries
+        .into_iter()
+        .enumerate()
+        .map(|(index, entry)| (index + 1, entry))
+        .collect())
+}
+
+/// Validates every row. `handles` maps ReddID handles (without `@`) to resolved addresses.
+pub fn validate_batch(
+    entries: &[(usize, BatchEntry)],
+    handles: &HashMap<String, String>,
+) -> BatchValidation {
+    let mut reports = Vec::with_capacity(entries.len());
+    let mut first_row_by_address: HashMap<String, usize> = HashMap::new();
+
+    for (row, entry) in entries {
+        let mut issues = Vec::new();
+
+        let address = match resolve_recipient(&entry.recipient, handles) {
+            Ok(address) => Some(address),
+            Err(issue) => {
+                issues.push(issue);
+                None
+            }
+        };
+        if let Some(address) = &address {
+            match first_row_by_address.get(address) {
+                Some(first_row) => issues.push(BatchIssue::DuplicateRecipient {
+                    first_row: *first_row,
+                }),
+                None => {
+                    first_row_by_address.insert(address.clone(), *row);
+                }
+            }
+        }
+
+        let amount = match Amount::from_str(&entry.amount) {
+            Ok(amount) => {
+                // P2SH outputs are cheaper to spend than P2PKH ones, so their threshold is lower.
+                let minimum = address
+                    .as_deref()
+                    .and_then(|address| ReddcoinAddress::from_str(address).ok())
+                    .map(|address| dust_threshold(&address.script_pubkey(), &ChainParams::MAINNET))
+                    .unwrap_or(LEGACY_P2PKH_DUST_LIMIT);
+                if amount < minimum {
+                    issues.push(BatchIssue::Dust { minimum });
+                }
+                Some(amount)
+            }
+            Err(message) => {
+                issues.push(BatchIssue::InvalidAmount { message });
+                None
+            }
+        };
+
+        reports.push(EntryReport {
+            row: *row,
+            recipient: entry.recipient.trim().to_string(),
+            address,
+            amount,
+            memo: entry.memo.clone(),
+            issues,
+        });
+    }
+
+    BatchValidation {
+        total: reports.iter().filter_map(|report| report.amount).sum(),
+        signable: reports
+            .iter()
+            .all(|report| report.issues.iter().all(|issue| !issue.is_blocking())),
+        entries: reports,
+    }
+}
+
+/// JSON entry point for validation. `format` is `csv` or `json`; `handles_json` is an object of
+/// handle → address (empty string for none).
+pub fn validate_batch_payment(
+    entries: String,
+    format: String,
+    handles_json: String,
+) -> Result<BatchValidation, String> {
+    let entries = parse_batch(&entries, &format)?;
+    let handles = parse_handles(&handles_json)?;
+    Ok(validate_batch(&entries, &handles))
+}
+
+/// Validates the batch and signs as many transactions as the size limit requires.
+///
+/// Recipients keep their input order across transactions. UTXOs are spent largest first, and
+/// each transaction returns its remainder to `change_address`.
+///
+/// `options.policy` is evaluated per transaction, with every earlier transaction of the batch
+/// counted in `spent_today`.
+#[allow(clippy::too_many_arguments)]
+pub fn sign_batch_payment(
+    utxos_json: String,
+    private_key_hex: String,
+    entries: String,
+    format: String,
+    handles_json: String,
+    change_address: String,
+    fee_per_kb: u64,
+    mut options: BuildOptions,
+) -> Result<BatchPayment, String> {
+    if private_key_hex.len() != 64 {
+        return Err("private_key_hex must be exactly 64 hex characters".to_string());
+    }
+
+    let validation = validate_batch_payment(entries, format, handles_json)?;
+    if !validation.signable {
+        let bad_rows: Vec<String> = validation
+            .entries
+            .iter()
+            .filter(|report| report.issues.iter().any(BatchIssue::is_blocking))
+            .map(|report| report.row.to_string())
+            .collect();
+        return Err(format!(
+            "batch has invalid rows ({}); run validation for details",
+            bad_rows.join(", ")
+        ));
+    }
+
+    let mut pool: Vec<Utxo> = serde_json::from_str(&utxos_json)
+        .map_err(|e| format!("utxos_json must be a valid JSON array of UTXOs: {e}"))?;
+    validate_utxos(&pool)?;
+    // Largest first keeps input counts, and so sizes and fees, low.
+    pool.sort_by_key(|utxo| std::cmp::Reverse(utxo.amount));
+
+    let private_key_raw =
+        hex::decode(private_key_hex).map_err(|e| format!("private_key_hex decode failed: {e}"))?;
+    let secret_key = SecretKey::from_slice(&private_key_raw)
+        .map_err(|e| format!("invalid secp256k1 private key: {e}"))?;
+    let bitcoin_pubkey = BitcoinPublicKey::new(PublicKey::from_secret_key(secp(), &secret_key));
+    let change_script = ReddcoinAddress::from_str(&change_address)
+        .map_err(|e| format!("invalid change_address: {e}"))?
+        .script_pubkey();
+
+    let payments: Vec<Payment> = validation
+        .entries
+        .into_iter()
+        .map(|report| -> Result<Payment, String> {
+            let address = report.address.expect("signable rows have an address");
+            Ok(Payment {
+                script: ReddcoinAddress::from_str(&address)?.script_pubkey(),
+                amount: report.amount.expect("signable rows have an amount"),
+                row: report.row,
+                recipient: report.recipient,
+                address,
+                memo: report.memo,
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    let mut result = BatchPayment {
+        transactions: Vec::new(),
+        recipients: Vec::with_capacity(payments.len()),
+        total_sent: Amount::ZERO,
+        total_fee: Amount::ZERO,
+    };
+
+    let mut remaining = payments.as_slice();
+    while !remaining.is_empty() {
+        let (chunk_len, input_count) = plan_chunk(remaining, &pool, fee_per_kb)?;
+        let (chunk, rest) = remaining.split_at(chunk_len);
+        remaining = rest;
+        let inputs: Vec<Utxo> = pool.drain(..input_count).collect();
+
+        let signed = sign_chunk(
+            chunk,
+            &inputs,
+            &change_script,
+            fee_per_kb,
+            &options,
+            &secret_key,
+            &bitcoin_pubkey,
+        )?;
+
+        let tx: Transaction = deserialize(
+            &hex::decode(&signed.hex).map_err(|e| format!("signed hex decode failed: {e}"))?,
+        )
+        .map_err(|e| format!("signed transaction does not decode: {e}"))?;
+        let transaction_index = result.transactions.len();
+        let mut claimed = vec![false; tx.output.len()];
+        for payment in chunk {
+            // Identical (script, value) outputs are interchangeable, so first-unclaimed is exact.
+            let output_index = tx
+                .output
+                .iter()
+                .enumerate()
+                .position(|(index, output)| {
+                    !claimed[index]
+                        && output.script_pubkey == payment.script
+                        && Amount::from(output.value) == payment.amount
+                })
+                .ok_or_else(|| format!("row {} missing from signed transaction", payment.row))?;
+            claimed[output_index] = true;
+
+            result.recipients.push(RecipientPayment {
+                row: payment.row,
+                recipient: payment.recipient.clone(),
+                address: payment.address.clone(),
+                amount: payment.amount,
+                memo: payment.memo.clone(),
+                transaction_index,
+                output_index: output_index as u32,
+            });
+            result.total_sent = result.total_sent.saturating_add(payment.amount);
+        }
+
+        // Later chunks leave the wallet on the same day, so the daily limit must see this one.
+        if let Some(policy) = &mut options.policy {
+            let chunk_total = Amount::checked_sum(chunk.iter().map(|payment| payment.amount))
+                .and_then(|total| total.checked_add(signed.fee))
+                .ok_or_else(|| "batch total overflowed u64".to_string())?;
+            policy.context.spent_today = policy.context.spent_today.saturating_add(chunk_total);
+        }
+
+        result.total_fee = result.total_fee.saturating_add(signed.fee);
+        result.transactions.push(signed);
+    }
+
+    Ok(result)
+}
+
+struct Payment {
+    row: usize,
+    recipient: String,
+    address: String,
+    amount: Amount,
+    memo: Option<String>,
+    script: ScriptBuf,
+}
+
+/// Picks how many of `payments` fit in the next transaction and how many of the (sorted) pool's
+/// leading UTXOs fund them, keeping the estimate under [`MAX_STANDARD_TX_SIZE`].
+fn plan_chunk(
+    payments: &[Payment],
+    pool: &[Utxo],
+    fee_per_kb: u64,
+) -> Result<(usize, usize), String> {
+    let mut best = None;
+    let mut input_count = 0;
+    let mut input_total = Amount::ZERO;
+    let mut payment_total = Amount::ZERO;
+
+    for (index, payment) in payments.iter().enumerate() {
+        let outputs = index + 2; // recipients so far plus change
+        payment_total = payment_total
+            .checked_add(payment.amount)
+            .ok_or_else(|| "batch total overflowed u64".to_string())?;
+
+        loop {
+            let fee = estimate_legacy_tx_fee(input_count, outputs, fee_per_kb)?;
+            let required = payment_total
+                .checked_add(fee)
+                .ok_or_else(|| "batch total overflowed u64".to_string())?;
+            if input_total >= required && input_count > 0 {
+                break;
+            }
+            let Some(utxo) = pool.get(input_count) else {
+                // Funds run out mid-batch: report it rather than silently paying a prefix.
+                return Err(format!(
+                    "insufficient funds for row {}: need {required}, have {input_total} across \
+                     all inputs",
+                    payment.row
+                ));
+            };
+            input_total = input_total.saturating_add(utxo.amount);
+            input_count += 1;
+        }
+
+        if estimate_legacy_tx_size(input_count, LEGACY_P2PKH_INPUT_SIZE, outputs)?
+            > MAX_STANDARD_TX_SIZE
+        {
+            break;
+        }
+        best = Some((index + 1, input_count));
+    }
+
+    best.ok_or_else(|| {
+        format!(
+            "row {} cannot fit in a standard-size transaction with the available inputs",
+            payments[0].row
+        )
+    })
+}
+
+#[allow(clippy::too_many_arguments)]
+fn sign_chunk(
+    chunk: &[Payment],
+    inputs: &[Utxo],
+    change_script: &ScriptBuf,
+    fee_per_kb: u64,
+    options: &BuildOptions,
+    secret_key: &SecretKey,
+    bitcoin_pubkey: &BitcoinPublicKey,
+) -> Result<SignedTransaction, String> {
+    let total_input_amount = validate_utxos(inputs)?;
+    ensure_verified(inputs, options)?;
+    let payment_total = Amount::checked_sum(chunk.iter().map(|payment| payment.amount))
+        .ok_or_else(|| "batch total overflowed u64".to_string())?;
+    let fee = estimate_legacy_tx_fee(inputs.len(), chunk.len() + 1, fee_per_kb)?;
+
+    let mut change_value = total_input_amount
+        .checked_sub(payment_total)
+        .and_then(|value| value.checked_sub(fee))
+        .ok_or_else(|| "inputs do not cover batch payments and fee".to_string())?;
+    // Sub-dust change is folded into the fee; `order_and_sign` drops the zero-value output.
+    if change_value < LEGACY_P2PKH_DUST_LIMIT {
+        change_value = Amount::ZERO;
+    }
+
+    let mut outputs: Vec<TxOut> = chunk
+        .iter()
+        .map(|payment| TxOut {
+            value: payment.amount.into(),
+            script_pubkey: payment.script.clone(),
+        })
+        .collect();
+    outputs.push(TxOut {
+        value: change_value.into(),
+        script_pubkey: change_script.clone(),
+    });
+
+    let tx = Transaction {
+        version: bitcoin::transaction::Version(2),
+        lock_time: LockTime::ZERO,
+        input: unsigned_inputs(inputs)?,
+        output: outputs,
+    };
+
+    order_and_sign(
+        tx,
+        Some(chunk.len()),
+        options,
+        secret_key,
+        bitcoin_pubkey,
+        total_input_amount,
+    )
+}
+
+fn parse_batch(entries: &str, format: &str) -> Result<Vec<(usize, BatchEntry)>, String> {
+    match format.trim().to_ascii_lowercase().as_str() {
+        "csv" => parse_batch_csv(entries),
+        "json" => parse_batch_json(entries),
+        other => Err(format!(
+            "unknown batch format `{other}` (expected csv or json)"
+        )),
+    }
+}
+
+fn parse_handles(handles_json: &str) -> Result<HashMap<String, String>, String> {
+    if handles_json.trim().is_empty() {
+        return Ok(HashMap::new());
+    }
+    let handles: BTreeMap<String, String> = serde_json::from_str(handles_json)
+        .map_err(|e| format!("handles_json must map handles to addresses: {e}"))?;
+    Ok(handles
+        .into_iter()
+        .map(|(handle, address)| (handle.trim_start_matches('@').to_string(), address))
+        .collect())
+}
+
+/// Resolves a recipient cell to a canonical address string.
+fn resolve_recipient(
+    recipient: &str,
+    handles: &HashMap<String, String>,
+) -> Result<String, BatchIssue> {
+    let target = crate::payment_uri::parse_payment_uri(recipient, &Default::default())
+        .map_err(|message| BatchIssue::InvalidRecipient { message })?
+        .target;
+
+    let address = match target {
+        PaymentTarget::Address(address) => address,
+        PaymentTarget::Handle(handle) => handles
+            .get(&handle)
+            .cloned()
+            .ok_or(BatchIssue::UnresolvedHandle)?,
+    };
+
+    ReddcoinAddress::from_str(&address)
+        .map(|address| address.to_string())
+        .map_err(|message| BatchIssue::InvalidRecipient { message })
+}
+
+/// Splits one CSV line, honouring double-quoted fields.
+fn split_csv_line(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, in_quotes) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => in_quotes = false,
+            ('"', false) if field.trim().is_empty() => {
+                field.clear();
+                in_quotes = true;
+            }
+            (',', false) => fields.push(std::mem::take(&mut field).trim().to_string()),
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err("unterminated quoted field".to_string());
+    }
+    fields.push(field.trim().to_string());
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn p2pkh_address(seed: u8) -> String {
+        let secret_key = SecretKey::from_slice(&[seed; 32]).unwrap();
+        ReddcoinAddress::p2pkh(&BitcoinPublicKey::new(secret_key.public_key(secp()))).to_string()
+    }
+
+    fn p2sh_address() -> String {
+        ReddcoinAddress::p2sh(&ScriptBuf::from_bytes(vec![0x51])).to_string()
+    }
+
+    fn entry(recipient: String, amount: &str) -> BatchEntry {
+        BatchEntry {
+            recipient,
+            amount: amount.to_string(),
+            memo: None,
+        }
+    }
+
+    #[test]
+    fn csv_skips_headers_comments_and_honours_quotes() {
+        let csv = format!(
+            "# tips for March\n\
+             Recipient,Amount,Memo\n\
+             \n\
+             {}, 12.5 ,\"thanks, \"\"redd\"\" fan\"\n\
+             \"{}\",1\n\
+             {},2,\n",
+            p2pkh_address(1),
+            p2pkh_address(2),
+            p2pkh_address(3)
+        );
+        let entries = parse_batch_csv(&csv).unwrap();
+        let rows: Vec<usize> = entries.iter().map(|(row, _)| *row).collect();
+        assert_eq!(rows, [4, 5, 6]);
+        assert_eq!(
+            entries[0].1,
+            BatchEntry {
+                recipient: p2pkh_address(1),
+                amount: "12.5".to_string(),
+                memo: Some("thanks, \"redd\" fan".to_string()),
+            }
+        );
+        assert_eq!(entries[1].1, entry(p2pkh_address(2), "1"));
+        // An empty memo column is no memo.
+        assert_eq!(entries[2].1.memo, None);
+    }
+
+    #[test]
+    fn csv_reports_malformed_rows_by_line() {
+        let address = p2pkh_address(1);
+        let err = parse_batch_csv(&format!("{address},1\n{address},1,memo,extra\n")).unwrap_err();
+        assert_eq!(
+            err,
+            "line 2: expected `recipient,amount[,memo]`, found 4 fields"
+        );
+        let err = parse_batch_csv(&format!("# header\n{address}\n")).unwrap_err();
+        assert!(err.starts_with("line 2: expected"), "{err}");
+        let err = parse_batch_csv(&format!("{address},1,\"open memo\n")).unwrap_err();
+        assert_eq!(err, "line 1: unterminated quoted field");
+        let err = parse_batch_csv("address,amount\n\n# nothing yet\n").unwrap_err();
+        assert_eq!(err, "batch contains no payments");
+        // Only a first row can be a header; later it is an (invalid) payment.
+        let entries = parse_batch_csv(&format!("{address},1\naddress,amount\n")).unwrap();
+        assert_eq!(entries.len(), 2);
+    }
+
+    #[test]
+    fn json_rows_are_numbered_from_one() {
+        let json = format!(
+            r#"[{{"recipient":"{}","amount":"1"}},{{"recipient":"{}","amount":"2","memo":"hi"}}]"#,
+            p2pkh_address(1),
+            p2pkh_address(2)
+        );
+        let entries = parse_batch_json(&json).unwrap();
+        assert_eq!(entries[0].0, 1);
+        assert_eq!(entries[1].0, 2);
+        assert_eq!(entries[1].1.memo.as_deref(), Some("hi"));
+        assert_eq!(
+            parse_batch_json("[]").unwrap_err(),
+            "batch contains no payments"
+        );
+        assert!(parse_batch_json(r#"[{"amount":"1"}]"#)
+            .unwrap_err()
+            .starts_with("invalid batch JSON"));
+    }
+
+    #[test]
+    fn validation_reports_every_row() {
+        let handles = parse_handles(&format!(r#"{{"@alice":"{}"}}"#, p2pkh_address(1))).unwrap();
+        let entries = vec![
+            (1, entry(p2pkh_address(1), "1")),
+            (2, entry("@alice".to_string(), "2")),
+            (3, entry("@bob".to_string(), "3")),
+            (4, entry("not-an-address".to_string(), "4")),
+            (5, entry(p2pkh_address(2), "1.2.3")),
+        ];
+        let validation = validate_batch(&entries, &handles);
+        let issues: Vec<_> = validation
+            .entries
+            .iter()
+            .map(|report| report.issues.clone())
+            .collect();
+        // A handle resolving to an address already paid is a duplicate, but only a warning.
+        assert_eq!(issues[0], []);
+        assert_eq!(issues[1], [BatchIssue::DuplicateRecipient { first_row: 1 }]);
+        assert!(!issues[1][0].is_blocking());
+        assert_eq!(issues[2], [BatchIssue::UnresolvedHandle]);
+        assert!(matches!(
+            issues[3][..],
+            [BatchIssue::InvalidRecipient { .. }]
+        ));
+        assert!(matches!(issues[4][..], [BatchIssue::InvalidAmount { .. }]));
+        assert_eq!(validation.entries[1].address, Some(p2pkh_address(1)));
+        assert_eq!(validation.total, Amount::from_base(10 * 100_000_000));
+        assert!(!validation.signable);
+
+        let duplicates_only = validate_batch(&entries[..2], &handles);
+        assert!(duplicates_only.signable);
+    }
+
+    #[test]
+    fn large_batches_split_into_standard_transactions() {
+        let utxos = serde_json::to_string(
+            &[1u8, 2, 3]
+                .map(|index| serde_json::json!({"txid": format!("{index:02x}").repeat(32), "vout": 0, "amount": 50_000 * 100_000_000u64})),
+        )
+        .unwrap();
+        let addresses: Vec<String> = (10..20).map(p2pkh_address).collect();
+        let csv: String = (0..3500)
+            .map(|index| format!("{},{}\n", addresses[index % 10], 1 + index % 7))
+            .collect();
+        let batch = sign_batch_payment(
+            utxos,
+            "05".repeat(32),
+            csv,
+            "csv".to_string(),
+            String::new(),
+            p2pkh_address(5),
+            1_000_000,
+            BuildOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(batch.transactions.len(), 2);
+        assert_eq!(batch.recipients.len(), 3500);
+        let transactions: Vec<Transaction> = batch
+            .transactions
+            .iter()
+            .map(|signed| {
+                assert!(signed.hex.len() as u64 / 2 <= MAX_STANDARD_TX_SIZE);
+                deserialize(&hex::decode(&signed.hex).unwrap()).unwrap()
+            })
+            .collect();
+        let mut seen = vec![Vec::new(); transactions.len()];
+        for (index, payment) in batch.recipients.iter().enumerate() {
+            assert_eq!(payment.row, index + 1);
+            assert_eq!(payment.address, addresses[index % 10]);
+            assert_eq!(
+                payment.amount,
+                Amount::from_base((1 + index as u64 % 7) * 100_000_000)
+            );
+            let output =
+                &transactions[payment.transaction_index].output[payment.output_index as usize];
+            assert_eq!(
+                output.script_pubkey,
+                ReddcoinAddress::from_str(&payment.address)
+                    .unwrap()
+                    .script_pubkey()
+            );
+            assert_eq!(Amount::from(output.value), payment.amount);
+            seen[payment.transaction_index].push(payment.output_index);
+        }
+        // Recipients keep their order: the first transaction holds a prefix of the batch.
+        assert!(batch
+            .recipients
+            .windows(2)
+            .all(|pair| { pair[0].transaction_index <= pair[1].transaction_index }));
+        for (tx, mut outputs) in transactions.iter().zip(seen) {
+            outputs.sort_unstable();
+            outputs.dedup();
+            // Every output but the change is claimed by exactly one recipient.
+            assert_eq!(outputs.len(), tx.output.len() - 1);
+        }
+        let total: u64 = (0..3500u64).map(|index| 1 + index % 7).sum();
+        assert_eq!(batch.total_sent, Amount::from_base(total * 100_000_000));
+        assert_eq!(
+            batch.total_fee,
+            batch.transactions.iter().map(|signed| signed.fee).sum()
+        );
+    }
+
+    #[test]
+    fn dust_threshold_depends_on_the_recipient_script() {
+        let entries = vec![
+            (1, entry(p2pkh_address(1), "0.00000545")),
+            (2, entry(p2pkh_address(2), "0.00000546")),
+            (3, entry(p2sh_address(), "0.00000540")),
+        ];
+        let validation = validate_batch(&entries, &HashMap::new());
+        assert_eq!(
+            validation.entries[0].issues,
+            vec![BatchIssue::Dust {
+                minimum: Amount::from_base(546)
+            }]
+        );
+        assert!(validation.entries[1].issues.is_empty());
+        assert!(validation.entries[2].issues.is_empty());
+        assert!(!validation.signable);
+    }
+
+    #[test]
+    fn daily_limit_counts_earlier_chunks() {
+        let key_hex = "05".repeat(32);
+        // Change is not re-spent within a batch, so each transaction needs its own input.
+        let utxos = serde_json::to_string(
+            &[1u8, 2]
+                .map(|index| serde_json::json!({"txid": format!("{index:02x}").repeat(32), "vout": 0, "amount": 5_000 * 100_000_000u64})),
+        )
+        .unwrap();
+        // Enough outputs to overflow one standard transaction.
+        let entries = serde_json::to_string(
+            &(0..3500)
+                .map(|_| serde_json::json!({"recipient": p2pkh_address(6), "amount": "1"}))
+                .collect::<Vec<_>>(),
+        )
+        .unwrap();
+        let sign = |options: BuildOptions| {
+            sign_batch_payment(
+                utxos.clone(),
+                key_hex.clone(),
+                entries.clone(),
+                "json".to_string(),
+                String::new(),
+                p2pkh_address(5),
+                1_000_000,
+                options,
+            )
+        };
+
+        let unlimited = sign(BuildOptions::default()).unwrap();
+        assert_eq!(unlimited.transactions.len(), 2);
+        // Each transaction alone stays under this limit; together they exceed it.
+        let largest = unlimited
+            .transactions
+            .iter()
+            .enumerate()
+            .map(|(index, tx)| {
+                let sent = unlimited
+                    .recipients
+                    .iter()
+                    .filter(|payment| payment.transaction_index == index)
+                    .count() as u64;
+                tx.fee.to_base() + sent * 100_000_000
+            })
+            .max()
+            .unwrap();
+        let options = BuildOptions::from_json(&format!(
+            r#"{{"policy":{{"rules":{{"daily_spend_limit":{{"limit":{largest}}}}}}}}}"#
+        ))
+        .unwrap();
+        let err = sign(options).unwrap_err();
+        assert!(err.contains("today's spending would reach"), "{err}");
+    }
+}
@@ -0,0 +1,529 @@
+//! Compact block filters (BIP158) for private wallet sync.
+//!
+//! Asking an indexer for the history of every wallet address tells it exactly which addresses
+//! the wallet owns. A basic block filter instead summarises all scripts a block pays to or spends
+//! from in a Golomb-coded set of a few hundred bytes. The wallet downloads the filters, tests its
+//! own scripts against them locally, and fetches in full only the blocks that match; the source
+//! learns which blocks were fetched, which false positives (about 1 in 784,931 per script) blur.
+//!
+//! Filters come from a node with `-blockfilterindex` (`getblockfilter`) or from peers advertising
+//! `NODE_COMPACT_FILTERS` (BIP157). Blocks are checked against the local [`HeaderChain`], so a
+//! source cannot invent transactions. It can hide them by serving a filter that does not match,
+//! which only a trusted node or comparing filters from several peers rules out.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use bitcoin::bip158::BlockFilter;
+use bitcoin::consensus::encode::{deserialize_partial, Decodable, VarInt};
+use bitcoin::{Block, BlockHash, OutPoint, ScriptBuf, Txid};
+use serde::Serialize;
+
+use crate::address::ReddcoinAddress;
+use crate::amount::Amount;
+use crate::chain_backend::{is_coinstake, HistoryEntry, Utxo};
+use crate::header_chain::HeaderChain;
+
+/// Filters requested per round trip, the most one BIP157 `getcfilters` may ask for.
+pub const FILTER_BATCH: u32 = 1000;
+/// BIP158 filter type of the basic filter.
+pub const BASIC_FILTER_TYPE: u8 = 0;
+
+/// A source of basic block filters and full blocks.
+///
+/// Takes `&mut self`, unlike the other sources, because a P2P peer is a single stateful
+/// connection.
+pub trait FilterSource {
+    /// The basic filters of `blocks` (height and hash, consecutive and lowest first), in the
+    /// same order.
+    fn block_filters(&mut self, blocks: &[(u32, BlockHash)]) -> Result<Vec<BlockFilter>, String>;
+
+    fn block(&mut self, hash: &BlockHash) -> Result<Block, String>;
+}
+
+/// Result of [`scan_filters`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct FilterScan {
+    pub start_height: u32,
+    /// Last height scanned, the header chain's tip.
+    pub end_height: u32,
+    pub filters_checked: u32,
+    pub blocks_downloaded: u32,
+    /// Downloaded blocks that held nothing for the wallet.
+    pub false_positives: u32,
+    /// Wallet transactions found, in chain order. Always `verified`: their blocks were checked
+    /// against the header chain.
+    pub history: Vec<HistoryEntry>,
+    /// The wallet's unspent outputs after the scan: the `known_utxos` passed in, minus those
+    /// spent in the scanned range, plus the new ones.
+    pub utxos: Vec<Utxo>,
+}
+
+/// Builds the basic filter of `block`. `prevout_scripts` must hold the script of every output
+/// the block's non-coinbase transactions spend.
+pub fn build_basic_filter(
+    block: &Block,
+    prevout_scripts: &HashMap<OutPoint, ScriptBuf>,
+) -> Result<BlockFilter, String> {
+    BlockFilter::new_script_filter(block, |outpoint| {
+        prevout_scripts
+            .get(outpoint)
+            .cloned()
+            .ok_or(bitcoin::bip158::Error::UtxoMissing(*outpoint))
+    })
+    .map_err(|e| format!("failed to build the filter of {}: {e}", block.block_hash()))
+}
+
+/// Whether `filter` (of block `block_hash`) may contain any of `scripts`. `false` is certain;
+/// `true` may be a false positive.
+pub fn filter_matches(
+    filter: &BlockFilter,
+    block_hash: &BlockHash,
+    scripts: &[ScriptBuf],
+) -> Result<bool, String> {
+    filter
+        .match_any(block_hash, scripts.iter().map(|script| script.as_bytes()))
+        .map_err(|e| format!("invalid filter for block {block_hash}: {e}"))
+}
+
+/// Decodes a serialized block. PoSV blocks end with the staker's block signature, which is not
+/// part of the Bitcoin block layout and is skipped.
+pub fn decode_block(raw: &[u8]) -> Result<Block, String> {
+    let (block, consumed): (Block, usize) =
+        deserialize_partial(raw).map_err(|e| format!("invalid block: {e}"))?;
+    let mut signature = &raw[consumed..];
+    if !signature.is_empty() {
+        let len = VarInt::consensus_decode(&mut signature)
+            .map_err(|e| format!("invalid block signature: {e}"))?;
+        if len.0 != signature.len() as u64 {
+            return Err(format!(
+                "block {} has {} unexpected trailing bytes",
+                block.block_hash(),
+                raw.len() - consumed
+            ));
+        }
+    }
+    Ok(block)
+}
+
+/// Scans the header chain from `start_height` to its tip for transactions involving `scripts`.
+///
+/// Filters are fetched in batches of [`FILTER_BATCH`] and matched locally; only matching blocks
+/// are downloaded. Each block must hash to the header at its height and its transactions must
+/// hash to the header's Merkle root. `known_utxos` are the wallet's outputs from before
+/// `start_height`, so spends of them are noticed; pass the previous scan's `utxos` to continue
+/// it.
+///
+/// `scripts` should cover every address the wallet has handed out plus its look-ahead (gap
+/// limit); rescan when the look-ahead grows.
+pub fn scan_filters(
+    chain: &HeaderChain,
+    source: &mut dyn FilterSource,
+    scripts: &[ScriptBuf],
+    start_height: u32,
+    known_utxos: &[Utxo],
+) -> Result<FilterScan, String> {
+    if start_height < chain.anchor_height() {
+        return Err(format!(
+            "cannot scan from {start_height}, below the header chain's anchor at {}",
+            chain.anchor_height()
+        ));
+    }
+    let wallet_scripts: HashSet<&ScriptBuf> = scripts.iter().collect();
+    let mut utxos = known_utxos
+        .iter()
+        .map(|utxo| {
+            let txid = utxo
+                .txid
+                .parse::<Txid>()
+                .map_err(|e| format!("invalid txid {:?}: {e}", utxo.txid))?;
+            Ok((OutPoint::new(txid, utxo.vout), utxo.clone()))
+        })
+        .collect::<Result<BTreeMap<_, _>, String>>()?;
+    let mut scan = FilterScan {
+        start_height,
+        end_height: chain.tip_height(),
+        ..FilterScan::default()
+    };
+
+    let mut batch_start = start_height;
+    while batch_start <= chain.tip_height() {
+        let batch_end = batch_start
+            .saturating_add(FILTER_BATCH - 1)
+            .min(chain.tip_height());
+        let blocks = (batch_start..=batch_end)
+            .map(|height| {
+                let header = chain.header_at(height).expect("height within the chain");
+                (height, header.block_hash())
+            })
+            .collect::<Vec<_>>();
+        let filters = source.block_filters(&blocks)?;
+        if filters.len() != blocks.len() {
+            return Err(format!(
+                "asked for {} filters from height {batch_start}, got {}",
+                blocks.len(),
+                filters.len()
+            ));
+        }
+
+        for ((height, hash), filter) in blocks.iter().zip(&filters) {
+            scan.filters_checked += 1;
+            if !filter_matches(filter, hash, scripts)? {
+                continue;
+            }
+            let block = source.block(hash)?;
+            if block.block_hash() != *hash {
+                return Err(format!(
+                    "asked for block {hash} at height {height}, got {}",
+                    block.block_hash()
+                ));
+            }
+            if !block.check_merkle_root() {
+                return Err(format!(
+                    "block {hash} at height {height} fails its Merkle root"
+                ));
+            }
+            scan.blocks_downloaded += 1;
+            let found = apply_block(&block, *height, chain, &wallet_scripts, &mut utxos);
+            if found.is_empty() {
+                scan.false_positives += 1;
+            }
+            scan.history
+                .extend(found.into_iter().map(|txid| HistoryEntry {
+                    txid: txid.to_string(),
+                    height: Some(*height),
+                    verified: true,
+                }));
+        }
+        batch_start = batch_end + 1;
+    }
+
+    scan.utxos = utxos
+        .into_values()
+        .map(|mut utxo| {
+            if let Some(height) = utxo.height {
+                utxo.confirmations = chain.confirmations(height);
+            }
+            utxo
+        })
+        .collect();
+    Ok(scan)
+}
+
+/// Applies `block`'s wallet-relevant effects to `utxos` and returns the txids involved.
+fn apply_block(
+    block: &Block,
+    height: u32,
+    chain: &HeaderChain,
+    wallet_scripts: &HashSet<&ScriptBuf>,
+    utxos: &mut BTreeMap<OutPoint, Utxo>,
+) -> Vec<Txid> {
+    let mut found = Vec::new();
+    for tx in &block.txdata {
+        let mut relevant = false;
+        if !tx.is_coinbase() {
+            for input in &tx.input {
+                relevant |= utxos.remove(&input.previous_output).is_some();
+            }
+        }
+        let txid = tx.compute_txid();
+        for (vout, output) in tx.output.iter().enumerate() {
+            if !wallet_scripts.contains(&output.script_pubkey) {
+                continue;
+            }
+            relevant = true;
+            utxos.insert(
+                OutPoint::new(txid, vout as u32),
+                Utxo {
+                    txid: txid.to_string(),
+                    vout: vout as u32,
+                    amount: Amount::from(output.value),
+                    height: Some(height),
+                    confirmations: chain.confirmations(height),
+                    is_coinbase: tx.is_coinbase(),
+                    is_coinstake: is_coinstake(tx),
+                    script_pubkey: Some(output.script_pubkey.to_hex_string()),
+                    address: ReddcoinAddress::from_script(&output.script_pubkey)
+                        .map(|address| address.to_string()),
+                    sequence: None,
+                    verified: true,
+                },
+            );
+        }
+        if relevant {
+            found.push(txid);
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::absolute::LockTime;
+    use bitcoin::bip158::BlockFilterWriter;
+    use bitcoin::block::{Header, Version};
+    use bitcoin::consensus::encode::serialize;
+    use bitcoin::hashes::Hash;
+    use bitcoin::pow::CompactTarget;
+    use bitcoin::transaction::Version as TxVersion;
+    use bitcoin::{Transaction, TxIn, TxMerkleNode, TxOut};
+
+    use super::*;
+    use crate::chain_params::ChainParams;
+    use crate::header_chain::next_target;
+
+    const ANCHOR_HEIGHT: u32 = 3_000_000;
+
+    fn script(byte: u8) -> ScriptBuf {
+        ScriptBuf::from_bytes(vec![0x76, 0xa9, 0x14, byte, 0x88, 0xac])
+    }
+
+    fn tx(spends: Option<OutPoint>, outputs: &[(u8, u64)]) -> Transaction {
+        Transaction {
+            version: TxVersion(1),
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: spends.unwrap_or_else(OutPoint::null),
+                ..TxIn::default()
+            }],
+            output: outputs
+                .iter()
+                .map(|&(byte, value)| TxOut {
+                    value: bitcoin::Amount::from_sat(value),
+                    script_pubkey: script(byte),
+                })
+                .collect(),
+        }
+    }
+
+    fn anchor() -> Header {
+        Header {
+            version: Version::from_consensus(4),
+            prev_blockhash: BlockHash::all_zeros(),
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: 1_700_000_000,
+            bits: CompactTarget::from_consensus(0x1d00ffff),
+            nonce: 0,
+        }
+    }
+
+    /// Chains blocks of `txdata` onto the anchor with valid headers.
+    fn blocks(txdata: Vec<Vec<Transaction>>) -> (HeaderChain, Vec<Block>) {
+        let mut headers = vec![anchor()];
+        let mut blocks = Vec::new();
+        for (height, txdata) in (ANCHOR_HEIGHT + 1..).zip(txdata) {
+            let parent = *headers.last().unwrap();
+            let bits = match headers.len() {
+                1 => parent.bits,
+                len => next_target(height, &parent, &headers[len - 2], &ChainParams::MAINNET),
+            };
+            let mut block = Block {
+                header: Header {
+                    prev_blockhash: parent.block_hash(),
+                    time: parent.time + 60,
+                    bits,
+                    ..parent
+                },
+                txdata,
+            };
+            block.header.merkle_root = block.compute_merkle_root().unwrap();
+            headers.push(block.header);
+            blocks.push(block);
+        }
+        let mut chain = HeaderChain::new(headers[0], ANCHOR_HEIGHT, &[]).unwrap();
+        chain.connect(&headers[1..], u32::MAX).unwrap();
+        (chain, blocks)
+    }
+
+    /// Serves a block and a filter per hash.
+    struct Source {
+        blocks: HashMap<BlockHash, (Block, BlockFilter)>,
+        downloaded: Vec<BlockHash>,
+    }
+
+    impl FilterSource for Source {
+        fn block_filters(
+            &mut self,
+            blocks: &[(u32, BlockHash)],
+        ) -> Result<Vec<BlockFilter>, String> {
+            Ok(blocks
+                .iter()
+                .map(|(_, hash)| self.blocks[hash].1.clone())
+                .collect())
+        }
+
+        fn block(&mut self, hash: &BlockHash) -> Result<Block, String> {
+            self.downloaded.push(*hash);
+            Ok(self.blocks[hash].0.clone())
+        }
+    }
+
+    #[test]
+    fn decode_block_skips_the_posv_signature() {
+        let (_, blocks) = blocks(vec![vec![tx(None, &[(1, 50)])]]);
+        let mut raw = serialize(&blocks[0]);
+        assert_eq!(decode_block(&raw), Ok(blocks[0].clone()));
+
+        raw.push(3);
+        raw.extend_from_slice(&[0x30, 0x01, 0x02]);
+        assert_eq!(decode_block(&raw), Ok(blocks[0].clone()));
+
+        raw.push(0);
+        assert!(decode_block(&raw)
+            .unwrap_err()
+            .contains("unexpected trailing bytes"));
+    }
+
+    #[test]
+    fn filters_match_output_and_spent_scripts() {
+        let funding = tx(None, &[(1, 50)]);
+        let spend = tx(Some(OutPoint::new(funding.compute_txid(), 0)), &[(2, 40)]);
+        let (_, blocks) = blocks(vec![vec![tx(None, &[(9, 50)]), spend]]);
+        let prevouts = HashMap::from([(OutPoint::new(funding.compute_txid(), 0), script(1))]);
+        let filter = build_basic_filter(&blocks[0], &prevouts).unwrap();
+        let hash = blocks[0].block_hash();
+
+        assert_eq!(filter_matches(&filter, &hash, &[script(1)]), Ok(true));
+        assert_eq!(filter_matches(&filter, &hash, &[script(2)]), Ok(true));
+        assert_eq!(filter_matches(&filter, &hash, &[script(3)]), Ok(false));
+        assert!(build_basic_filter(&blocks[0], &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn scan_tracks_wallet_outputs_across_blocks() {
+        let wallet = script(1);
+        let known = Utxo {
+            txid: "11".repeat(32),
+            vout: 0,
+            amount: Amount::from_base(500),
+            height: Some(ANCHOR_HEIGHT - 10),
+            confirmations: 0,
+            is_coinbase: false,
+            is_coinstake: false,
+            script_pubkey: Some(wallet.to_hex_string()),
+            address: None,
+            sequence: None,
+            verified: false,
+        };
+        let known_outpoint = OutPoint::new(known.txid.parse().unwrap(), 0);
+        let reward = tx(None, &[(1, 50)]);
+        let spend = tx(
+            Some(OutPoint::new(reward.compute_txid(), 0)),
+            &[(2, 30), (1, 19)],
+        );
+        let spend_known = tx(Some(known_outpoint), &[(3, 499)]);
+        let (chain, blocks) = blocks(vec![
+            vec![reward.clone()],
+            vec![tx(None, &[(9, 50)]), spend.clone()],
+            vec![tx(None, &[(9, 50)]), spend_known.clone()],
+            vec![tx(None, &[(9, 50)])],
+            vec![tx(None, &[(8, 50)])],
+        ]);
+
+        let mut prevouts = HashMap::from([
+            (OutPoint::new(reward.compute_txid(), 0), wallet.clone()),
+            (known_outpoint, wallet.clone()),
+        ]);
+        prevouts.extend(spend.output.iter().enumerate().map(|(vout, output)| {
+            (
+                OutPoint::new(spend.compute_txid(), vout as u32),
+                output.script_pubkey.clone(),
+            )
+        }));
+        let mut filters: Vec<BlockFilter> = blocks
+            .iter()
+            .map(|block| build_basic_filter(block, &prevouts).unwrap())
+            .collect();
+        // The fourth block's filter falsely claims the wallet script.
+        let mut content = Vec::new();
+        let mut writer = BlockFilterWriter::new(&mut content, &blocks[3]);
+        writer.add_output_scripts();
+        writer.add_element(wallet.as_bytes());
+        writer.finish().unwrap();
+        filters[3] = BlockFilter::new(&content);
+
+        let mut source = Source {
+            blocks: blocks
+                .iter()
+                .zip(filters)
+                .map(|(block, filter)| (block.block_hash(), (block.clone(), filter)))
+                .collect(),
+            downloaded: Vec::new(),
+        };
+        let scan = scan_filters(
+            &chain,
+            &mut source,
+            std::slice::from_ref(&wallet),
+            ANCHOR_HEIGHT + 1,
+            &[known],
+        )
+        .unwrap();
+
+        assert_eq!(scan.end_height, ANCHOR_HEIGHT + 5);
+        assert_eq!(scan.filters_checked, 5);
+        assert_eq!(scan.blocks_downloaded, 4);
+        assert_eq!(scan.false_positives, 1);
+        assert!(!source.downloaded.contains(&blocks[4].block_hash()));
+        let history: Vec<_> = scan
+            .history
+            .iter()
+            .map(|entry| (entry.txid.clone(), entry.height, entry.verified))
+            .collect();
+        assert_eq!(
+            history,
+            [
+                (
+                    reward.compute_txid().to_string(),
+                    Some(ANCHOR_HEIGHT + 1),
+                    true
+                ),
+                (
+                    spend.compute_txid().to_string(),
+                    Some(ANCHOR_HEIGHT + 2),
+                    true
+                ),
+                (
+                    spend_known.compute_txid().to_string(),
+                    Some(ANCHOR_HEIGHT + 3),
+                    true
+                ),
+            ]
+        );
+        assert_eq!(scan.utxos.len(), 1);
+        let change = &scan.utxos[0];
+        assert_eq!(
+            (change.txid.clone(), change.vout, change.amount),
+            (spend.compute_txid().to_string(), 1, Amount::from_base(19))
+        );
+        assert_eq!(change.confirmations, 4);
+        assert!(change.verified);
+    }
+
+    #[test]
+    fn scan_rejects_blocks_that_do_not_match_the_header() {
+        let (chain, blocks) = blocks(vec![vec![tx(None, &[(1, 50)])], vec![tx(None, &[(2, 50)])]]);
+        let filter = |block: &Block| build_basic_filter(block, &HashMap::new()).unwrap();
+        // The first block's hash is answered with the second block.
+        let mut source = Source {
+            blocks: HashMap::from([
+                (
+                    blocks[0].block_hash(),
+                    (blocks[1].clone(), filter(&blocks[0])),
+                ),
+                (
+                    blocks[1].block_hash(),
+                    (blocks[1].clone(), filter(&blocks[1])),
+                ),
+            ]),
+            downloaded: Vec::new(),
+        };
+        let err =
+            scan_filters(&chain, &mut source, &[script(1)], ANCHOR_HEIGHT + 1, &[]).unwrap_err();
+        assert!(err.starts_with("asked for block"), "{err}");
+
+        assert!(
+            scan_filters(&chain, &mut source, &[script(1)], ANCHOR_HEIGHT - 1, &[])
+                .unwrap_err()
+                .contains("below the header chain's anchor")
+        );
+    }
+}
@@ -0,0 +1,568 @@
+//! Blockbook v2 REST backend.
+//!
+//! Replaces the loosely typed parsing in the Dart `BlockbookService`: every endpoint has a typed
+//! response model, amounts stay exact integers, and responses are converted into the
+//! [`ChainBackend`] models at this edge. Fee estimates are the clearest example: Blockbook
+//! reports them as a decimal coin amount per kB, which the Dart client had to guess at; here they
+//! are parsed exactly and converted to base units.
+//!
+//! The public instance at `https://blockbook.reddcoin.com` is TLS-only, and the built-in
+//! [`crate::http_transport::StdHttpTransport`] speaks plain HTTP. Reaching it needs an
+//! [`HttpTransport`] supplied by the platform layer; `StdHttpTransport` suits a self-hosted
+//! Blockbook on the LAN or behind a local proxy.
+
+use std::str::FromStr;
+
+use bitcoin::Transaction;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::amount::Amount;
+use crate::chain_backend::{
+    is_coinstake, normalize_fee_per_kb, AddressBalance, AttemptError, ChainBackend, ChainStatus,
+    HistoryEntry, RetryPolicy, Utxo,
+};
+use crate::chain_params::ChainParams;
+use crate::http_transport::{HttpRequest, HttpTransport};
+
+/// Largest page Blockbook serves for address history.
+const HISTORY_PAGE_SIZE: u32 = 1000;
+
+/// `GET /api/v2/`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct BlockbookStatus {
+    pub blockbook: BlockbookInfo,
+    pub backend: BlockbookBackendInfo,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockbookInfo {
+    pub best_height: u32,
+    #[serde(default)]
+    pub in_sync: bool,
+}
+
+/// The node behind Blockbook.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockbookBackendInfo {
+    pub blocks: u32,
+    pub best_block_hash: String,
+    #[serde(default)]
+    pub chain: String,
+}
+
+/// One entry of `GET /api/v2/utxo/{address}`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockbookUtxo {
+    pub txid: String,
+    pub vout: u32,
+    /// Base units, as a decimal string.
+    pub value: String,
+    /// Absent or zero while unconfirmed.
+    #[serde(default)]
+    pub height: Option<u32>,
+    #[serde(default)]
+    pub confirmations: u32,
+    #[serde(default)]
+    pub coinbase: bool,
+}
+
+impl BlockbookUtxo {
+    /// Converts to the signer's model. Blockbook does not report the address for a single-address
+    /// query, so the queried one is filled in. Nor does it flag coinstakes, so `is_coinstake` is
+    /// left `false`; [`BlockbookBackend`]'s `utxos` classifies immature outputs itself.
+    pub fn into_utxo(self, address: &str) -> Result<Utxo, String> {
+        let amount = self
+            .value
+            .parse::<u64>()
+            .map(Amount::from_base)
+            .map_err(|_| {
+                format!(
+                    "utxo {}:{} has invalid value {:?}",
+                    self.txid, self.vout, self.value
+                )
+            })?;
+        Ok(Utxo {
+            txid: self.txid,
+            vout: self.vout,
+            amount,
+            height: self.height.filter(|height| *height > 0),
+            confirmations: self.confirmations,
+            is_coinbase: self.coinbase,
+            is_coinstake: false,
+            script_pubkey: None,
+            address: Some(address.to_string()),
+            sequence: None,
+            verified: false,
+        })
+    }
+}
+
+/// `GET /api/v2/address/{address}`, with `details=basic` or `details=txslight`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockbookAddress {
+    pub address: String,
+    /// Confirmed balance in base units.
+    pub balance: String,
+    /// Signed net mempool effect in base units.
+    pub unconfirmed_balance: String,
+    pub txs: u32,
+    #[serde(default)]
+    pub page: u32,
+    #[serde(default)]
+    pub total_pages: u32,
+    #[serde(default)]
+    pub transactions: Vec<BlockbookTxSummary>,
+}
+
+/// A `txslight` history entry.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockbookTxSummary {
+    pub txid: String,
+    /// Zero or negative while in the mempool.
+    pub block_height: i64,
+}
+
+/// `GET /api/v2/tx/{txid}`, reduced to the fields the wallet uses.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BlockbookTransaction {
+    pub txid: String,
+    pub hex: String,
+}
+
+/// `GET /api/v2/estimatefee/{blocks}`: coins per kB as a decimal string.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct BlockbookFeeEstimate {
+    pub result: String,
+}
+
+/// `POST /api/v2/sendtx/` success body.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct BlockbookSendResult {
+    pub result: String,
+}
+
+/// Blockbook error bodies come as `{"error": "..."}` or `{"error": {"message": "..."}}`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+struct BlockbookErrorBody {
+    error: BlockbookError,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+enum BlockbookError {
+    Message(String),
+    Object { message: String },
+}
+
+/// A Blockbook v2 server.
+pub struct BlockbookBackend {
+    base_url: String,
+    transport: Box<dyn HttpTransport>,
+    retry: RetryPolicy,
+    params: ChainParams,
+}
+
+impl BlockbookBackend {
+    /// A backend for `base_url` (without a trailing `/api/v2`) using `transport`.
+    pub fn new(base_url: &str, transport: Box<dyn HttpTransport>) -> Self {
+        Self {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            transport,
+            retry: RetryPolicy::default(),
+            params: ChainParams::MAINNET,
+        }
+    }
+
+    /// Replaces the default retry schedule.
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// `GET /api/v2/`.
+    pub fn status(&self) -> Result<BlockbookStatus, String> {
+        self.get("/api/v2/")
+    }
+
+    /// Fetches and decodes `path`, retrying transient failures.
+    fn get<T: DeserializeOwned>(&self, path: &str) -> Result<T, String> {
+        let request = HttpRequest::get(format!("{}{path}", self.base_url));
+        self.retry.run(|| self.attempt(&request))
+    }
+
+    fn attempt<T: DeserializeOwned>(&self, request: &HttpRequest) -> Result<T, AttemptError> {
+        let response = self
+            .transport
+            .send(request)
+            .map_err(AttemptError::Transient)?;
+
+        if response.status == 429 || response.status >= 500 {
+            return Err(AttemptError::Transient(format!(
+                "{} returned HTTP {}",
+                request.url, response.status
+            )));
+        }
+        if response.status != 200 {
+            let reason = serde_json::from_slice::<BlockbookErrorBody>(&response.body)
+                .map(|body| match body.error {
+                    BlockbookError::Message(message) | BlockbookError::Object { message } => {
+                        message
+                    }
+                })
+                .unwrap_or_else(|_| String::from_utf8_lossy(&response.body).trim().to_string());
+            return Err(AttemptError::Permanent(format!(
+                "{} returned HTTP {}: {reason}",
+                request.url, response.status
+            )));
+        }
+
+        serde_json::from_slice(&response.body).map_err(|e| {
+            AttemptError::Permanent(format!("unexpected response from {}: {e}", request.url))
+        })
+    }
+
+    /// Fetches `txid` and reports whether it is a coinbase and whether it is a coinstake.
+    fn generation_kind(&self, txid: &str) -> Result<(bool, bool), String> {
+        let raw = hex::decode(self.raw_transaction(txid)?)
+            .map_err(|e| format!("server returned invalid hex for {txid}: {e}"))?;
+        let tx: Transaction = bitcoin::consensus::deserialize(&raw)
+            .map_err(|e| format!("server returned an invalid transaction for {txid}: {e}"))?;
+        Ok((tx.is_coinbase(), is_coinstake(&tx)))
+    }
+}
+
+impl ChainBackend for BlockbookBackend {
+    fn name(&self) -> String {
+        format!("blockbook {}", self.base_url)
+    }
+
+    fn chain_status(&self) -> Result<ChainStatus, String> {
+        let status = self.status()?;
+        Ok(ChainStatus {
+            height: status.backend.blocks,
+            best_block_hash: status.backend.best_block_hash,
+        })
+    }
+
+    /// Blockbook flags coinbases but not coinstakes, so as with Electrum the funding transaction
+    /// of every confirmed output short of coinbase maturity is fetched and classified.
+    fn utxos(&self, address: &str) -> Result<Vec<Utxo>, String> {
+        let path = format!("/api/v2/utxo/{}", path_segment(address, "address")?);
+        let utxos: Vec<BlockbookUtxo> = self.get(&path)?;
+        utxos
+            .into_iter()
+            .map(|utxo| {
+                let mut utxo = utxo.into_utxo(address)?;
+                if utxo.height.is_some() && utxo.confirmations < self.params.coinbase_maturity {
+                    let (is_coinbase, is_coinstake) = self.generation_kind(&utxo.txid)?;
+                    utxo.is_coinbase |= is_coinbase;
+                    utxo.is_coinstake = is_coinstake;
+                }
+                Ok(utxo)
+            })
+            .collect()
+    }
+
+    fn history(&self, address: &str) -> Result<Vec<HistoryEntry>, String> {
+        let address_segment = path_segment(address, "address")?;
+        let mut entries = Vec::new();
+        let mut page = 1;
+        loop {
+            let details: BlockbookAddress = self.get(&format!(
+                "/api/v2/address/{address_segment}?details=txslight&page={page}&pageSize={HISTORY_PAGE_SIZE}"
+            ))?;
+            entries.extend(details.transactions.into_iter().map(|tx| {
+                HistoryEntry {
+                    txid: tx.txid,
+                    height: u32::try_from(tx.block_height)
+                        .ok()
+                        .filter(|height| *height > 0),
+                    verified: false,
+                }
+            }));
+            if page >= details.total_pages {
+                break;
+            }
+            page += 1;
+        }
+
+        // Blockbook lists newest first; the trait promises oldest first with mempool last.
+        entries.reverse();
+        entries.sort_by_key(|entry| entry.height.unwrap_or(u32::MAX));
+        Ok(entries)
+    }
+
+    fn balance(&self, address: &str) -> Result<AddressBalance, String> {
+        let path = format!(
+            "/api/v2/address/{}?details=basic",
+            path_segment(address, "address")?
+        );
+        let details: BlockbookAddress = self.get(&path)?;
+        Ok(AddressBalance {
+            confirmed: details
+                .balance
+                .parse::<u64>()
+                .map(Amount::from_base)
+                .map_err(|_| format!("invalid balance {:?}", details.balance))?,
+            unconfirmed: details.unconfirmed_balance.parse::<i64>().map_err(|_| {
+                format!(
+                    "invalid unconfirmed balance {:?}",
+                    details.unconfirmed_balance
+                )
+            })?,
+        })
+    }
+
+    fn estimate_fee_per_kb(&self, target_blocks: u16) -> Result<u64, String> {
+        let estimate: BlockbookFeeEstimate =
+            self.get(&format!("/api/v2/estimatefee/{target_blocks}"))?;
+        let fee = parse_fee_per_kb(&estimate.result)?;
+        Ok(normalize_fee_per_kb(fee, &self.params))
+    }
+
+    fn raw_transaction(&self, txid: &str) -> Result<String, String> {
+        let path = format!("/api/v2/tx/{}", path_segment(txid, "txid")?);
+        let tx: BlockbookTransaction = self.get(&path)?;
+        Ok(tx.hex)
+    }
+
+    /// Rebroadcasting the same transaction is harmless, so transient failures are retried.
+    fn broadcast(&self, tx_hex: &str) -> Result<String, String> {
+        let tx_hex = tx_hex.trim();
+        hex::decode(tx_hex).map_err(|e| format!("tx_hex must be valid hex: {e}"))?;
+        let request = HttpRequest::post(
+            format!("{}/api/v2/sendtx/", self.base_url),
+            "text/plain",
+            tx_hex.as_bytes().to_vec(),
+        );
+        let sent: BlockbookSendResult = self.retry.run(|| self.attempt(&request))?;
+        Ok(sent.result)
+    }
+}
+
+/// Parses Blockbook's coins-per-kB fee string into base units per kB.
+///
+/// A zero or negative value means the node has no estimate yet.
+pub fn parse_fee_per_kb(coins_per_kb: &str) -> Result<u64, String> {
+    let trimmed = coins_per_kb.trim();
+    if trimmed.starts_with('-') {
+        return Err("server has no fee estimate yet".to_string());
+    }
+    let fee = Amount::from_str(trimmed)
+        .map_err(|e| format!("invalid fee estimate {coins_per_kb:?}: {e}"))?;
+    if fee == Amount::ZERO {
+        return Err("server has no fee estimate yet".to_string());
+    }
+    Ok(fee.to_base())
+}
+
+/// Rejects values that would change the request path when interpolated into it.
+fn path_segment<'a>(value: &'a str, field: &str) -> Result<&'a str, String> {
+    if value.is_empty() || !value.bytes().all(|byte| byte.is_ascii_alphanumeric()) {
+        return Err(format!("invalid {field} {value:?}"));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bitcoin::consensus::encode::serialize_hex;
+    use bitcoin::transaction::Version;
+    use bitcoin::{absolute::LockTime, OutPoint, ScriptBuf, TxIn, TxOut};
+
+    use super::*;
+    use crate::http_transport::test_server::{response, serve, TestServer};
+    use crate::http_transport::StdHttpTransport;
+
+    const ADDRESS: &str = "Rxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxxx";
+
+    fn backend(server: &TestServer) -> BlockbookBackend {
+        BlockbookBackend::new(&server.url, Box::new(StdHttpTransport::default())).with_retry(
+            RetryPolicy {
+                max_retries: 2,
+                base_delay: Duration::from_millis(1),
+                max_jitter: Duration::ZERO,
+            },
+        )
+    }
+
+    fn coinstake_hex() -> String {
+        serialize_hex(&Transaction {
+            version: Version(1),
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new("22".repeat(32).parse().unwrap(), 1),
+                ..TxIn::default()
+            }],
+            output: vec![
+                TxOut {
+                    value: bitcoin::Amount::ZERO,
+                    script_pubkey: ScriptBuf::new(),
+                },
+                TxOut {
+                    value: bitcoin::Amount::from_sat(1_000_000_000),
+                    script_pubkey: ScriptBuf::from_bytes(vec![0x51]),
+                },
+            ],
+        })
+    }
+
+    #[test]
+    fn utxos_classify_immature_outputs() {
+        let young = "aa".repeat(32);
+        let server = serve(vec![
+            response(
+                200,
+                &format!(
+                    r#"[{{"txid":"{}","vout":0,"value":"1250000001","height":100,"confirmations":900}},
+                        {{"txid":"{young}","vout":1,"value":"1000000000","height":990,"confirmations":11}},
+                        {{"txid":"{}","vout":2,"value":"5","confirmations":0}}]"#,
+                    "bb".repeat(32),
+                    "cc".repeat(32)
+                ),
+            ),
+            response(
+                200,
+                &format!(r#"{{"txid":"{young}","hex":"{}"}}"#, coinstake_hex()),
+            ),
+        ]);
+        let utxos = backend(&server).utxos(ADDRESS).unwrap();
+        let requests = server.finish();
+
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].path, format!("/api/v2/utxo/{ADDRESS}"));
+        assert_eq!(requests[1].path, format!("/api/v2/tx/{young}"));
+        assert_eq!(utxos[0].amount, Amount::from_base(1_250_000_001));
+        assert!(!utxos[0].is_coinstake);
+        assert!(utxos[1].is_coinstake && !utxos[1].is_coinbase);
+        assert_eq!(utxos[2].height, None);
+        assert!(utxos
+            .iter()
+            .all(|utxo| utxo.address.as_deref() == Some(ADDRESS)));
+    }
+
+    #[test]
+    fn history_follows_pages_oldest_first() {
+        let page = |page: u32, txs: &str| {
+            response(
+                200,
+                &format!(
+                    r#"{{"address":"{ADDRESS}","balance":"0","unconfirmedBalance":"0","txs":3,
+                        "page":{page},"totalPages":2,"transactions":[{txs}]}}"#
+                ),
+            )
+        };
+        let server = serve(vec![
+            page(
+                1,
+                r#"{"txid":"mempool","blockHeight":-1},{"txid":"newer","blockHeight":20}"#,
+            ),
+            page(2, r#"{"txid":"older","blockHeight":10}"#),
+        ]);
+        let history = backend(&server).history(ADDRESS).unwrap();
+        let requests = server.finish();
+
+        assert!(requests[0]
+            .path
+            .ends_with("details=txslight&page=1&pageSize=1000"));
+        assert!(requests[1].path.contains("&page=2&"));
+        let txids: Vec<_> = history.iter().map(|entry| entry.txid.as_str()).collect();
+        assert_eq!(txids, ["older", "newer", "mempool"]);
+        assert_eq!(history[2].height, None);
+    }
+
+    #[test]
+    fn fee_estimates() {
+        let server = serve(vec![
+            response(200, r#"{"result":"-1"}"#),
+            response(200, r#"{"result":"0"}"#),
+            response(200, r#"{"result":"0.00123"}"#),
+            response(200, r#"{"result":"0.000001"}"#),
+        ]);
+        let backend = backend(&server);
+        assert!(backend
+            .estimate_fee_per_kb(6)
+            .unwrap_err()
+            .contains("no fee estimate"));
+        assert!(backend
+            .estimate_fee_per_kb(6)
+            .unwrap_err()
+            .contains("no fee estimate"));
+        assert_eq!(backend.estimate_fee_per_kb(6), Ok(123_000));
+        // Raised to the relay minimum.
+        assert_eq!(
+            backend.estimate_fee_per_kb(6),
+            Ok(ChainParams::MAINNET.min_relay_fee_per_kb)
+        );
+        assert_eq!(server.finish()[0].path, "/api/v2/estimatefee/6");
+    }
+
+    #[test]
+    fn sendtx_errors_are_not_retried() {
+        let server = serve(vec![
+            response(
+                400,
+                r#"{"error":{"message":"bad-txns-inputs-missingorspent"}}"#,
+            ),
+            response(400, r#"{"error":"TX decode failed"}"#),
+            response(200, r#"{"result":"abcd"}"#),
+        ]);
+        let backend = backend(&server);
+        assert!(backend
+            .broadcast("0100")
+            .unwrap_err()
+            .ends_with("HTTP 400: bad-txns-inputs-missingorspent"));
+        assert!(backend
+            .broadcast("0100")
+            .unwrap_err()
+            .ends_with("HTTP 400: TX decode failed"));
+        assert_eq!(backend.broadcast(" 0100\n"), Ok("abcd".to_string()));
+        assert!(backend.broadcast("zz").is_err());
+
+        let requests = server.finish();
+        assert_eq!(requests.len(), 3);
+        assert_eq!(requests[2].method, "POST");
+        assert_eq!(requests[2].body, b"0100");
+    }
+
+    #[test]
+    fn rate_limits_and_server_errors_are_retried() {
+        let status = r#"{"blockbook":{"bestHeight":5,"inSync":true},
+                         "backend":{"blocks":5,"bestBlockHash":"00ff","chain":"main"}}"#;
+        let server = serve(vec![
+            response(429, "slow down"),
+            response(503, "maintenance"),
+            response(200, status),
+        ]);
+        let chain = backend(&server).chain_status().unwrap();
+        assert_eq!(chain.height, 5);
+        assert_eq!(chain.best_block_hash, "00ff");
+        assert_eq!(server.finish().len(), 3);
+
+        let server = serve(vec![
+            response(502, ""),
+            response(502, ""),
+            response(502, "bad gateway"),
+        ]);
+        let err = backend(&server).chain_status().unwrap_err();
+        assert!(err.contains("HTTP 502"), "{err}");
+        assert_eq!(server.finish().len(), 3);
+    }
+
+    #[test]
+    fn rejects_path_injection() {
+        let backend =
+            BlockbookBackend::new("http://127.0.0.1:1", Box::new(StdHttpTransport::default()));
+        assert!(backend.utxos("../admin").is_err());
+        assert!(backend.raw_transaction("ab?x=1").is_err());
+    }
+}
@@ -0,0 +1,165 @@
+//! Chain data source abstraction.
+//!
+//! The wallet needs the same handful of facts from whichever server it talks to: the chain tip,
+//! an address's UTXOs, history and balance, a fee rate, raw transactions, and a way to broadcast.
+//! [`ChainBackend`] names those operations once, with typed results, so signing and sync code do
+//! not care whether the answer came from Blockbook, ElectrumX or the user's own node. Each
+//! implementation converts its wire format into these models at the edge, including the signer's
+//! [`Utxo`], so no caller ever re-parses a server response.
+
+use std::time::Duration;
+
+use bitcoin::Transaction;
+use serde::{Deserialize, Serialize};
+
+use crate::amount::Amount;
+use crate::chain_params::ChainParams;
+pub use crate::utxo::Utxo;
+
+/// The best block a backend knows about.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChainStatus {
+    pub height: u32,
+    pub best_block_hash: String,
+}
+
+/// A transaction touching an address.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub txid: String,
+    /// Confirming block height, or `None` while the transaction is in the mempool.
+    pub height: Option<u32>,
+    /// Whether inclusion at `height` was proven against the local header chain (see
+    /// [`crate::merkle_proof::verify_history`]). Backends always report `false`.
+    #[serde(default)]
+    pub verified: bool,
+}
+
+/// An address's balance in base units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AddressBalance {
+    pub confirmed: Amount,
+    /// Net effect of mempool transactions; negative while an unconfirmed spend is pending.
+    pub unconfirmed: i64,
+}
+
+/// A source of chain data and a way to broadcast transactions.
+///
+/// Every method blocks; the FFI layer is synchronous and callers run it off the UI thread.
+pub trait ChainBackend: Send + Sync {
+    /// Human-readable identity for diagnostics, e.g. the server URL.
+    fn name(&self) -> String;
+
+    fn chain_status(&self) -> Result<ChainStatus, String>;
+
+    /// Unspent outputs paying `address`, including unconfirmed ones.
+    fn utxos(&self, address: &str) -> Result<Vec<Utxo>, String>;
+
+    /// Every transaction paying to or spending from `address`, oldest first.
+    fn history(&self, address: &str) -> Result<Vec<HistoryEntry>, String>;
+
+    fn balance(&self, address: &str) -> Result<AddressBalance, String>;
+
+    /// Fee rate in base units per kB for confirmation within `target_blocks`.
+    ///
+    /// Implementations return the server's estimate through [`normalize_fee_per_kb`], so the
+    /// result is never below the relay minimum.
+    fn estimate_fee_per_kb(&self, target_blocks: u16) -> Result<u64, String>;
+
+    /// Hex-encoded raw transaction.
+    fn raw_transaction(&self, txid: &str) -> Result<String, String>;
+
+    /// Broadcasts a signed transaction and returns its txid.
+    fn broadcast(&self, tx_hex: &str) -> Result<String, String>;
+}
+
+/// Raises a fee estimate to the minimum relay fee.
+///
+/// Servers return the node's `estimatesmartfee` result, which falls below the relay floor on a
+/// quiet chain; a transaction built with it would be rejected.
+pub fn normalize_fee_per_kb(fee_per_kb: u64, params: &ChainParams) -> u64 {
+    fee_per_kb.max(params.min_relay_fee_per_kb)
+}
+
+/// Whether `tx` is a PoSV coinstake: a non-coinbase transaction whose first output is empty.
+///
+/// Backends that only report outpoints use this to recover the maturity rule the signer needs.
+pub fn is_coinstake(tx: &Transaction) -> bool {
+    !tx.is_coinbase()
+        && !tx.input.is_empty()
+        && tx.output.len() >= 2
+        && tx.output[0].value == bitcoin::Amount::ZERO
+        && tx.output[0].script_pubkey.is_empty()
+}
+
+/// Retry schedule for idempotent requests.
+///
+/// The delay before retry `n` (from 0) is `base_delay * 2^n` plus up to `max_jitter` of random
+/// jitter, so many wallets hitting a recovering server do not retry in lockstep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub max_retries: u32,
+    pub base_delay: Duration,
+    pub max_jitter: Duration,
+}
+
+impl Default for RetryPolicy {
+    /// Matches the Dart client: 3 retries starting at 400 ms with up to 250 ms of jitter.
+    fn default() -> Self {
+        Self {
+            max_retries: 3,
+            base_delay: Duration::from_millis(400),
+            max_jitter: Duration::from_millis(250),
+        }
+    }
+}
+
+/// Why a single attempt failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum AttemptError {
+    /// Worth retrying: timeouts, dropped connections, rate limiting, server errors.
+    Transient(String),
+    /// Retrying cannot help: the request or its payload was rejected.
+    Permanent(String),
+}
+
+impl RetryPolicy {
+    /// No retries, for broadcasts and tests.
+    pub const NONE: RetryPolicy = RetryPolicy {
+        max_retries: 0,
+        base_delay: Duration::ZERO,
+        max_jitter: Duration::ZERO,
+    };
+
+    /// Runs `attempt` until it succeeds, fails permanently or retries run out.
+    pub(crate) fn run<T>(
+        &self,
+        mut attempt: impl FnMut() -> Result<T, AttemptError>,
+    ) -> Result<T, String> {
+        let mut retry = 0;
+        loop {
+            match attempt() {
+                Ok(value) => return Ok(value),
+                Err(AttemptError::Permanent(message)) => return Err(message),
+                Err(AttemptError::Transient(message)) if retry >= self.max_retries => {
+                    return Err(message)
+                }
+                Err(AttemptError::Transient(_)) => {
+                    std::thread::sleep(self.delay(retry));
+                    retry += 1;
+                }
+            }
+        }
+    }
+
+    fn delay(&self, retry: u32) -> Duration {
+        let exponential = self.base_delay.saturating_mul(1 << retry.min(16));
+        let jitter_ms = self.max_jitter.as_millis() as u64;
+        let jitter = if jitter_ms == 0 {
+            0
+        } else {
+            rand::random::<u64>() % jitter_ms
+        };
+        exponential.saturating_add(Duration::from_millis(jitter))
+    }
+}
@@ -0,0 +1,59 @@
+//! Reddcoin consensus and policy parameters.
+//!
+//! Values mirror Reddcoin Core's chain params. They are collected here so wallet logic (coin
+//! selection, maturity checks, fee policy) reads them from one place instead of scattering magic
+//! numbers across modules.
+
+/// Parameters the wallet needs to reason about a Reddcoin network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainParams {
+    /// Confirmations a coinbase or coinstake output needs before it can be spent.
+    ///
+    /// PoSV turns every staking reward into a coinstake transaction, so staking wallets hit this
+    /// rule constantly; spending earlier is rejected by every node.
+    pub coinbase_maturity: u32,
+    /// Target block interval in seconds.
+    pub target_spacing_secs: u32,
+    /// Window the PoSV retarget averages block spacing over (`nPowTargetTimespan`).
+    pub target_timespan_secs: u32,
+    /// Height of the last scrypt proof-of-work block; every later block is proof of stake.
+    pub last_pow_height: u32,
+    /// Age an output needs before it earns any PoSV stake weight.
+    pub stake_min_age_secs: u32,
+    /// Cap on the coin-age weight a single output can accumulate.
+    pub stake_max_age_secs: u32,
+    /// PoSV v2 annual interest on coin age, in basis points.
+    pub stake_annual_reward_bps: u32,
+    /// Default `-minrelaytxfee`: the lowest fee rate nodes relay, in base units per kB.
+    pub min_relay_fee_per_kb: u64,
+    /// Default `-dustrelayfee`: the fee rate used to decide whether an output is worth spending,
+    /// in base units per kB. 3000 gives the familiar 546 base-unit P2PKH dust limit.
+    pub dust_relay_fee_per_kb: u64,
+    /// Bytes that open every P2P message, so nodes of different networks ignore each other.
+    pub network_magic: [u8; 4],
+    /// Port nodes listen on for P2P connections.
+    pub default_p2p_port: u16,
+}
+
+impl ChainParams {
+    /// Reddcoin mainnet.
+    pub const MAINNET: ChainParams = ChainParams {
+        coinbase_maturity: 50,
+        target_spacing_secs: 60,
+        target_timespan_secs: 24 * 60 * 60,
+        last_pow_height: 260_799,
+        stake_min_age_secs: 8 * 60 * 60,
+        stake_max_age_secs: 45 * 24 * 60 * 60,
+        stake_annual_reward_bps: 500,
+        min_relay_fee_per_kb: 1000,
+        dust_relay_fee_per_kb: 3000,
+        network_magic: [0xfb, 0xc0, 0xb6, 0xdb],
+        default_p2p_port: 45444,
+    };
+}
+
+impl Default for ChainParams {
+    fn default() -> Self {
+        Self::MAINNET
+    }
+}
@@ -0,0 +1,450 @@
+//! UTXO consolidation for staking wallets.
+//!
+//! Every PoSV stake reward lands as a new output, so staking wallets accumulate hundreds of
+//! small UTXOs. Spending them all at once produces a non-standard transaction, so the planner
+//! groups them into a sequence of standard-size transactions instead. It either merges them into
+//! as few outputs as possible or re-splits them into outputs of a staking-friendly size.
+//!
+//! Consolidating pays a fee per input, so it is meant to run while fee rates are low.
+//! `max_fee_per_kb` lets a scheduled job refuse to plan when they are not.
+
+use std::str::FromStr;
+
+use bitcoin::absolute::LockTime;
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use bitcoin::{PublicKey as BitcoinPublicKey, Transaction, TxOut};
+use serde::{Deserialize, Serialize};
+
+use crate::address::ReddcoinAddress;
+use crate::amount::Amount;
+use crate::chain_params::ChainParams;
+use crate::secp_context::secp;
+use crate::transaction_signer::{
+    ensure_verified, estimate_legacy_tx_fee, estimate_legacy_tx_size, order_and_sign,
+    unsigned_inputs, BuildOptions, SignedTransaction, LEGACY_P2PKH_DUST_LIMIT,
+    LEGACY_P2PKH_INPUT_SIZE, MAX_STANDARD_TX_SIZE,
+};
+use crate::utxo::{filter_spendable, ExcludedUtxo, ExclusionReason, SpendabilityRules, Utxo};
+
+/// What the consolidated outputs should look like.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum ConsolidationGoal {
+    /// One output per transaction.
+    #[default]
+    Merge,
+    /// Outputs of `target_output` each (the last one absorbs the remainder). UTXOs already
+    /// between one and two targets in size are left alone.
+    Split { target_output: Amount },
+}
+
+/// Planner settings. Every field is optional in JSON.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ConsolidationOptions {
+    pub goal: ConsolidationGoal,
+    /// Refuse to plan above this fee rate.
+    pub max_fee_per_kb: Option<u64>,
+    pub min_confirmations: u32,
+    pub tip_height: Option<u32>,
+    /// Cap on inputs per transaction; by default as many as fit in a standard transaction.
+    pub max_inputs_per_tx: Option<usize>,
+    /// How each transaction is built and signed (ordering, policy, proof-verified inputs, ...),
+    /// given as top-level keys like the other signing entry points take them.
+    #[serde(flatten)]
+    pub build: BuildOptions,
+}
+
+impl Default for ConsolidationOptions {
+    fn default() -> Self {
+        Self {
+            goal: ConsolidationGoal::default(),
+            max_fee_per_kb: None,
+            min_confirmations: 1,
+            tip_height: None,
+            max_inputs_per_tx: None,
+            build: BuildOptions::default(),
+        }
+    }
+}
+
+impl ConsolidationOptions {
+    /// Parses options from JSON; an empty string selects the defaults.
+    pub fn from_json(options_json: &str) -> Result<Self, String> {
+        if options_json.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_json::from_str(options_json)
+            .map_err(|e| format!("invalid consolidation options: {e}"))
+    }
+}
+
+/// One transaction in a consolidation plan.
+#[derive(Debug, Clone, Serialize)]
+pub struct PlannedConsolidation {
+    pub inputs: Vec<Utxo>,
+    pub input_amount: Amount,
+    pub outputs: Vec<Amount>,
+    pub fee: Amount,
+    /// Estimated serialized size in bytes.
+    pub size: u64,
+}
+
+/// A full consolidation plan with before/after counts.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConsolidationPlan {
+    pub transactions: Vec<PlannedConsolidation>,
+    /// Outputs left out: immature, unconfirmed, or worth less than the fee to spend them.
+    pub excluded: Vec<ExcludedUtxo>,
+    pub total_fee: Amount,
+    pub utxo_count_before: usize,
+    pub utxo_count_after: usize,
+}
+
+/// A signed consolidation plan.
+#[derive(Debug, Clone, Serialize)]
+pub struct SignedConsolidation {
+    pub plan: ConsolidationPlan,
+    pub transactions: Vec<SignedTransaction>,
+}
+
+/// Groups `utxos` into standard-size consolidation transactions at `fee_per_kb`.
+pub fn plan_consolidation(
+    utxos: Vec<Utxo>,
+    fee_per_kb: u64,
+    options: &ConsolidationOptions,
+) -> Result<ConsolidationPlan, String> {
+    if let Some(max) = options.max_fee_per_kb {
+        if fee_per_kb > max {
+            return Err(format!(
+                "fee rate {fee_per_kb}/kB is above the consolidation ceiling of {max}/kB; try again \
+                 when fees are lower"
+            ));
+        }
+    }
+    if let ConsolidationGoal::Split { target_output } = options.goal {
+        if target_output < LEGACY_P2PKH_DUST_LIMIT {
+            return Err(format!(
+                "split target {target_output} is below the dust limit ({LEGACY_P2PKH_DUST_LIMIT})"
+            ));
+        }
+    }
+
+    let utxo_count_before = utxos.len();
+    let rules = SpendabilityRules {
+        min_confirmations: options.min_confirmations,
+        tip_height: options.tip_height,
+    };
+    let set = filter_spendable(utxos, &ChainParams::MAINNET, &rules);
+    let mut excluded = set.excluded;
+
+    // An input costs its own serialized size in fee; anything worth less only burns money.
+    let spend_fee = estimate_legacy_tx_fee(1, 0, fee_per_kb)?
+        .checked_sub(estimate_legacy_tx_fee(0, 0, fee_per_kb)?)
+        .unwrap_or(Amount::ZERO);
+    let mut candidates = Vec::new();
+    for utxo in set.spendable {
+        let already_sized = match options.goal {
+            ConsolidationGoal::Merge => false,
+            ConsolidationGoal::Split { target_output } => {
+                utxo.amount >= target_output
+                    && utxo.amount < target_output.saturating_add(target_output)
+            }
+        };
+        if already_sized {
+            continue;
+        }
+        if utxo.amount <= spend_fee {
+            excluded.push(ExcludedUtxo {
+                txid: utxo.txid,
+                vout: utxo.vout,
+                amount: utxo.amount,
+                reason: ExclusionReason::Uneconomical { spend_fee },
+            });
+            continue;
+        }
+        candidates.push(utxo);
+    }
+    // Smallest first, so the dust is what gets merged when not everything fits.
+    candidates.sort_by_key(|utxo| utxo.amount);
+
+    let mut transactions = Vec::new();
+    let mut remaining = candidates.as_slice();
+    while !remaining.is_empty() {
+        let (planned, used) = plan_one(remaining, fee_per_kb, options)?;
+        remaining = &remaining[used..];
+        if let Some(planned) = planned {
+            transactions.push(planned);
+        }
+    }
+
+    let consumed: usize = transactions.iter().map(|tx| tx.inputs.len()).sum();
+    let created: usize = transactions.iter().map(|tx| tx.outputs.len()).sum();
+
+    Ok(ConsolidationPlan {
+        total_fee: transactions.iter().map(|tx| tx.fee).sum(),
+        utxo_count_after: utxo_count_before - consumed + created,
+        utxo_count_before,
+        excluded,
+        transactions,
+    })
+}
+
+/// Plans the next transaction from the front of `candidates`.
+///
+/// Returns the planned transaction (or `None` when the inputs are not worth touching) and how
+/// many candidates were consumed.
+fn plan_one(
+    candidates: &[Utxo],
+    fee_per_kb: u64,
+    options: &ConsolidationOptions,
+) -> Result<(Option<PlannedConsolidation>, usize), String> {
+    let input_cap = options.max_inputs_per_tx.unwrap_or(usize::MAX).max(1);
+
+    let mut count = 0;
+    let mut total = Amount::ZERO;
+    for utxo in candidates.iter().take(input_cap) {
+        let next_total = total.saturating_add(utxo.amount);
+        let outputs = output_count(next_total, options.goal);
+        if estimate_legacy_tx_size(count + 1, LEGACY_P2PKH_INPUT_SIZE, outputs)?
+            > MAX_STANDARD_TX_SIZE
+        {
+            break;
+        }
+        count += 1;
+        total = next_total;
+    }
+
+    let inputs = &candidates[..count.max(1)];
+    total = Amount::checked_sum(inputs.iter().map(|utxo| utxo.amount))
+        .ok_or_else(|| "consolidation total overflowed u64".to_string())?;
+
+    // Merging a single output into itself only pays a fee.
+    if inputs.len() < 2 && options.goal == ConsolidationGoal::Merge {
+        return Ok((None, inputs.len()));
+    }
+
+    let mut outputs = output_count(total, options.goal);
+    let (fee, after_fee) = loop {
+        let fee = estimate_legacy_tx_fee(inputs.len(), outputs, fee_per_kb)?;
+        let after_fee = total.checked_sub(fee).unwrap_or(Amount::ZERO);
+        let fits = match options.goal {
+            ConsolidationGoal::Merge => true,
+            ConsolidationGoal::Split { target_output } => {
+                target_output
+                    .checked_mul(outputs as u64)
+                    .unwrap_or(Amount::MAX)
+                    <= after_fee
+            }
+        };
+        if fits || outputs == 1 {
+            break (fee, after_fee);
+        }
+        outputs -= 1;
+    };
+
+    if after_fee < LEGACY_P2PKH_DUST_LIMIT {
+        return Ok((None, inputs.len()));
+    }
+    // A split transaction earns its fee by breaking up an oversized output or by merging small
+    // ones; one that does neither (a lone small output re-sent to itself) only pays the fee.
+    if let ConsolidationGoal::Split { target_output } = options.goal {
+        let splits_any = inputs
+            .iter()
+            .any(|utxo| utxo.amount >= target_output.saturating_add(target_output));
+        if !splits_any && outputs >= inputs.len() {
+            return Ok((None, inputs.len()));
+        }
+    }
+
+    let output_amounts = match options.goal {
+        ConsolidationGoal::Merge => vec![after_fee],
+        ConsolidationGoal::Split { target_output } => {
+            let mut amounts = vec![target_output; outputs - 1];
+            let rest = after_fee
+                .checked_sub(
+                    target_output
+                        .checked_mul(outputs as u64 - 1)
+                        .unwrap_or(Amount::MAX),
+                )
+                .ok_or_else(|| "split outputs exceed consolidated value".to_string())?;
+            amounts.push(rest);
+            amounts
+        }
+    };
+
+    Ok((
+        Some(PlannedConsolidation {
+            size: estimate_legacy_tx_size(inputs.len(), LEGACY_P2PKH_INPUT_SIZE, outputs)?,
+            inputs: inputs.to_vec(),
+            input_amount: total,
+            outputs: output_amounts,
+            fee,
+        }),
+        inputs.len(),
+    ))
+}
+
+/// Outputs a consolidated `total` turns into before fees are taken into account.
+fn output_count(total: Amount, goal: ConsolidationGoal) -> usize {
+    match goal {
+        ConsolidationGoal::Merge => 1,
+        ConsolidationGoal::Split { target_output } => {
+            (total.to_base() / target_output.to_base().max(1)).max(1) as usize
+        }
+    }
+}
+
+/// JSON entry point for [`plan_consolidation`].
+pub fn plan_consolidation_json(
+    utxos_json: String,
+    fee_per_kb: u64,
+    options_json: String,
+) -> Result<ConsolidationPlan, String> {
+    let utxos: Vec<Utxo> = serde_json::from_str(&utxos_json)
+        .map_err(|e| format!("utxos_json must be a valid JSON array of UTXOs: {e}"))?;
+    plan_consolidation(
+        utxos,
+        fee_per_kb,
+        &ConsolidationOptions::from_json(&options_json)?,
+    )
+}
+
+/// Plans and signs a consolidation, paying every output to `destination_address`.
+pub fn sign_consolidation(
+    utxos_json: String,
+    private_key_hex: String,
+    destination_address: String,
+    fee_per_kb: u64,
+    options_json: String,
+) -> Result<SignedConsolidation, String> {
+    if private_key_hex.len() != 64 {
+        return Err("private_key_hex must be exactly 64 hex characters".to_string());
+    }
+
+    let options = ConsolidationOptions::from_json(&options_json)?;
+    let plan = plan_consolidation_json(utxos_json, fee_per_kb, options_json)?;
+    if plan.transactions.is_empty() {
+        return Err("nothing to consolidate".to_string());
+    }
+
+    let private_key_raw =
+        hex::decode(private_key_hex).map_err(|e| format!("private_key_hex decode failed: {e}"))?;
+    let secret_key = SecretKey::from_slice(&private_key_raw)
+        .map_err(|e| format!("invalid secp256k1 private key: {e}"))?;
+    let bitcoin_pubkey = BitcoinPublicKey::new(PublicKey::from_secret_key(secp(), &secret_key));
+    let destination_script = ReddcoinAddress::from_str(&destination_address)
+        .map_err(|e| format!("invalid destination_address: {e}"))?
+        .script_pubkey();
+
+    for planned in &plan.transactions {
+        ensure_verified(&planned.inputs, &options.build)?;
+    }
+
+    let transactions = plan
+        .transactions
+        .iter()
+        .map(|planned| {
+            let tx = Transaction {
+                version: bitcoin::transaction::Version(2),
+                lock_time: LockTime::ZERO,
+                input: unsigned_inputs(&planned.inputs)?,
+                output: planned
+                    .outputs
+                    .iter()
+                    .map(|amount| TxOut {
+                        value: (*amount).into(),
+                        script_pubkey: destination_script.clone(),
+                    })
+                    .collect(),
+            };
+            order_and_sign(
+                tx,
+                None,
+                &options.build,
+                &secret_key,
+                &bitcoin_pubkey,
+                planned.input_amount,
+            )
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(SignedConsolidation { plan, transactions })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY_HEX: &str = "0404040404040404040404040404040404040404040404040404040404040404";
+
+    fn utxo(index: u8, amount: u64) -> Utxo {
+        Utxo {
+            txid: format!("{index:02x}").repeat(32),
+            vout: 0,
+            amount: Amount::from_base(amount),
+            height: Some(100),
+            confirmations: 100,
+            is_coinbase: false,
+            is_coinstake: false,
+            script_pubkey: None,
+            address: None,
+            sequence: None,
+            verified: false,
+        }
+    }
+
+    fn destination() -> String {
+        let secret_key = SecretKey::from_slice(&hex::decode(KEY_HEX).unwrap()).unwrap();
+        ReddcoinAddress::p2pkh(&BitcoinPublicKey::new(secret_key.public_key(secp()))).to_string()
+    }
+
+    fn sign(utxos: &[Utxo], options_json: &str) -> Result<SignedConsolidation, String> {
+        sign_consolidation(
+            serde_json::to_string(utxos).unwrap(),
+            KEY_HEX.to_string(),
+            destination(),
+            1_000_000,
+            options_json.to_string(),
+        )
+    }
+
+    #[test]
+    fn split_skips_transactions_that_change_nothing() {
+        let options = ConsolidationOptions {
+            goal: ConsolidationGoal::Split {
+                target_output: Amount::from_base(100 * 100_000_000),
+            },
+            ..ConsolidationOptions::default()
+        };
+        // A lone small output would only be re-sent to itself.
+        let plan = plan_consolidation(vec![utxo(1, 50 * 100_000_000)], 1_000_000, &options);
+        assert!(plan.unwrap().transactions.is_empty());
+
+        // Small outputs merge, an oversized one splits.
+        let utxos = vec![
+            utxo(1, 30 * 100_000_000),
+            utxo(2, 40 * 100_000_000),
+            utxo(3, 50 * 100_000_000),
+        ];
+        let plan = plan_consolidation(utxos, 1_000_000, &options).unwrap();
+        assert_eq!(plan.transactions.len(), 1);
+        assert_eq!(plan.transactions[0].outputs.len(), 1);
+
+        let plan = plan_consolidation(vec![utxo(1, 350 * 100_000_000)], 1_000_000, &options);
+        assert_eq!(plan.unwrap().transactions[0].outputs.len(), 3);
+    }
+
+    #[test]
+    fn sign_applies_the_callers_build_options() {
+        let utxos = [utxo(1, 100_000_000), utxo(2, 200_000_000)];
+        let signed = sign(&utxos, r#"{"input_ordering":"bip69"}"#).unwrap();
+        assert_eq!(signed.transactions.len(), 1);
+
+        let err = sign(&utxos, r#"{"require_verified_inputs":true}"#).unwrap_err();
+        assert!(err.contains("has not been proof-verified"), "{err}");
+
+        let err = sign(&utxos, r#"{"policy":{"rules":{"max_fee":{"limit":1}}}}"#).unwrap_err();
+        assert!(err.contains("denied by policy"), "{err}");
+    }
+}
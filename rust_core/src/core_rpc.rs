@@ -0,0 +1,646 @@
+//! Reddcoin Core JSON-RPC backend.
+//!
+//! Lets the wallet run against the user's own `reddcoind` instead of a public indexer. Core has
+//! no address index, so the [`ChainBackend`] operations map onto what a stock node offers:
+//! `scantxoutset` for an address's UTXOs and balance, `getrawtransaction` (which needs
+//! `txindex=1` for anything outside the mempool), `estimatesmartfee` (or `estimatefee` on older
+//! nodes), `sendrawtransaction`, and `getblockchaininfo`/`getblockheader` for the tip. Address
+//! history needs an index Core does not have and is reported as unsupported rather than
+//! approximated.
+//!
+//! Core writes amounts as JSON numbers; they are parsed from the number's text so large values
+//! stay exact.
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use bitcoin::bip158::BlockFilter;
+use bitcoin::block::Header;
+use bitcoin::{Block, BlockHash, Transaction};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::value::RawValue;
+use serde_json::{json, Value};
+
+use crate::amount::Amount;
+use crate::block_filter::{decode_block, FilterSource};
+use crate::blockbook::parse_fee_per_kb;
+use crate::chain_backend::{
+    is_coinstake, normalize_fee_per_kb, AddressBalance, AttemptError, ChainBackend, ChainStatus,
+    HistoryEntry, RetryPolicy, Utxo,
+};
+use crate::chain_params::ChainParams;
+use crate::header_chain::{parse_headers_hex, HeaderSource};
+use crate::http_transport::{HttpRequest, HttpTransport};
+use crate::merkle_proof::{InclusionProof, ProofSource};
+
+/// Mainnet RPC port of `reddcoind`.
+pub const DEFAULT_CORE_RPC_PORT: u16 = 45443;
+/// `RPC_IN_WARMUP`: the node is still loading and will answer shortly.
+const RPC_IN_WARMUP: i64 = -28;
+
+/// How to authenticate to the node.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CoreRpcAuth {
+    /// `rpcuser`/`rpcpassword` from `reddcoin.conf`.
+    UserPass { user: String, password: String },
+    /// The `.cookie` file the node writes to its data directory on every start.
+    ///
+    /// Read on every request, so a restarted node's new cookie is picked up without
+    /// reconfiguring the wallet.
+    CookieFile(PathBuf),
+}
+
+impl CoreRpcAuth {
+    fn authorization(&self) -> Result<String, String> {
+        let credentials = match self {
+            CoreRpcAuth::UserPass { user, password } => format!("{user}:{password}"),
+            CoreRpcAuth::CookieFile(path) => std::fs::read_to_string(path)
+                .map_err(|e| format!("cannot read RPC cookie {}: {e}", path.display()))?
+                .trim()
+                .to_string(),
+        };
+        Ok(format!("Basic {}", BASE64.encode(credentials)))
+    }
+}
+
+/// `getblockchaininfo`, reduced to the fields the wallet uses.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CoreBlockchainInfo {
+    pub chain: String,
+    pub blocks: u32,
+    #[serde(default)]
+    pub headers: u32,
+    #[serde(rename = "bestblockhash")]
+    pub best_block_hash: String,
+    #[serde(rename = "initialblockdownload", default)]
+    pub initial_block_download: bool,
+}
+
+/// Verbose `getblockheader`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CoreBlockHeader {
+    pub hash: String,
+    /// -1 once the block has been reorganized out of the active chain.
+    pub confirmations: i64,
+    pub height: u32,
+    pub version: i32,
+    #[serde(rename = "merkleroot")]
+    pub merkle_root: String,
+    pub time: u32,
+    pub bits: String,
+    #[serde(rename = "previousblockhash", default)]
+    pub previous_block_hash: Option<String>,
+    #[serde(rename = "nextblockhash", default)]
+    pub next_block_hash: Option<String>,
+}
+
+/// `scantxoutset start` result.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CoreScanResult {
+    pub success: bool,
+    /// Tip height the scan ran against.
+    pub height: u32,
+    pub unspents: Vec<CoreScanUnspent>,
+    #[serde(with = "crate::amount::as_rdd_number")]
+    pub total_amount: Amount,
+}
+
+/// One output found by `scantxoutset`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CoreScanUnspent {
+    pub txid: String,
+    pub vout: u32,
+    #[serde(rename = "scriptPubKey")]
+    pub script_pubkey: String,
+    #[serde(with = "crate::amount::as_rdd_number")]
+    pub amount: Amount,
+    /// Only reported by newer nodes.
+    #[serde(default)]
+    pub coinbase: Option<bool>,
+    pub height: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcResponse<T> {
+    result: Option<T>,
+    error: Option<RpcError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+/// A Reddcoin Core node's JSON-RPC interface.
+///
+/// `scantxoutset` walks the whole UTXO set and can take minutes on a slow disk; give the
+/// transport a timeout to match.
+pub struct CoreRpcBackend {
+    url: String,
+    auth: CoreRpcAuth,
+    transport: Box<dyn HttpTransport>,
+    retry: RetryPolicy,
+    params: ChainParams,
+    next_id: AtomicU64,
+}
+
+impl CoreRpcBackend {
+    /// A backend for the node at `url`, e.g. `http://127.0.0.1:45443`.
+    pub fn new(url: &str, auth: CoreRpcAuth, transport: Box<dyn HttpTransport>) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_string(),
+            auth,
+            transport,
+            retry: RetryPolicy::default(),
+            params: ChainParams::MAINNET,
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    /// Replaces the default retry schedule.
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    pub fn blockchain_info(&self) -> Result<CoreBlockchainInfo, String> {
+        self.call("getblockchaininfo", json!([]))
+    }
+
+    /// Hash of the best-chain block at `height`.
+    pub fn block_hash(&self, height: u32) -> Result<String, String> {
+        self.call("getblockhash", json!([height]))
+    }
+
+    pub fn block_header(&self, block_hash: &str) -> Result<CoreBlockHeader, String> {
+        self.call(
+            "getblockheader",
+            json!([hex_param(block_hash, "block hash")?, true]),
+        )
+    }
+
+    /// Hex-encoded 80-byte header.
+    pub fn block_header_hex(&self, block_hash: &str) -> Result<String, String> {
+        self.call(
+            "getblockheader",
+            json!([hex_param(block_hash, "block hash")?, false]),
+        )
+    }
+
+    /// Hex-encoded `merkleblock` proving `txid` is in block `block_hash`.
+    pub fn tx_out_proof(&self, txid: &str, block_hash: &str) -> Result<String, String> {
+        self.call(
+            "gettxoutproof",
+            json!([
+                [hex_param(txid, "txid")?],
+                hex_param(block_hash, "block hash")?
+            ]),
+        )
+    }
+
+    /// Hex-encoded basic filter of block `block_hash`. Needs a node started with
+    /// `-blockfilterindex`.
+    pub fn block_filter_hex(&self, block_hash: &str) -> Result<String, String> {
+        #[derive(Deserialize)]
+        struct BlockFilterResult {
+            filter: String,
+        }
+        let result: BlockFilterResult = self.call(
+            "getblockfilter",
+            json!([hex_param(block_hash, "block hash")?, "basic"]),
+        )?;
+        Ok(result.filter)
+    }
+
+    /// Hex-encoded serialized block.
+    pub fn block_hex(&self, block_hash: &str) -> Result<String, String> {
+        self.call("getblock", json!([hex_param(block_hash, "block hash")?, 0]))
+    }
+
+    /// Scans the UTXO set for outputs paying `address`. Confirmed outputs only: the scan does not
+    /// see the mempool.
+    pub fn scan_address(&self, address: &str) -> Result<CoreScanResult, String> {
+        let descriptor = format!("addr({})", address_param(address)?);
+        let scan: CoreScanResult = self.call("scantxoutset", json!(["start", [descriptor]]))?;
+        if !scan.success {
+            return Err(format!("{} could not complete the UTXO scan", self.url));
+        }
+        Ok(scan)
+    }
+
+    /// Calls `method`, retrying transient failures.
+    fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T, String> {
+        self.retry.run(|| self.attempt(method, &params))
+    }
+
+    fn attempt<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: &Value,
+    ) -> Result<T, AttemptError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let body = json!({ "jsonrpc": "1.0", "id": id, "method": method, "params": params });
+        let request = HttpRequest::post(
+            self.url.clone(),
+            "application/json",
+            body.to_string().into_bytes(),
+        )
+        .header(
+            "Authorization",
+            self.auth.authorization().map_err(AttemptError::Permanent)?,
+        );
+        let response = self
+            .transport
+            .send(&request)
+            .map_err(AttemptError::Transient)?;
+
+        // Core answers RPC errors with HTTP 404 or 500 and a JSON body; only other statuses are
+        // transport-level failures.
+        match response.status {
+            200 | 404 | 500 => {}
+            401 | 403 => {
+                return Err(AttemptError::Permanent(format!(
+                    "{} rejected the RPC credentials",
+                    self.url
+                )))
+            }
+            status if status == 429 || status >= 500 => {
+                return Err(AttemptError::Transient(format!(
+                    "{} returned HTTP {status}",
+                    self.url
+                )))
+            }
+            status => {
+                return Err(AttemptError::Permanent(format!(
+                    "{} returned HTTP {status}",
+                    self.url
+                )))
+            }
+        }
+
+        let reply: RpcResponse<T> = serde_json::from_slice(&response.body).map_err(|e| {
+            let message = format!("unexpected {method} response from {}: {e}", self.url);
+            // A bare 500 usually comes from a proxy in front of a node that is restarting.
+            if response.status == 500 {
+                AttemptError::Transient(message)
+            } else {
+                AttemptError::Permanent(message)
+            }
+        })?;
+        if let Some(error) = reply.error {
+            let message = format!(
+                "{} rejected {method}: {} (code {})",
+                self.url, error.message, error.code
+            );
+            return Err(if error.code == RPC_IN_WARMUP {
+                AttemptError::Transient(message)
+            } else {
+                AttemptError::Permanent(message)
+            });
+        }
+        reply.result.ok_or_else(|| {
+            AttemptError::Permanent(format!("{} returned no result for {method}", self.url))
+        })
+    }
+
+    /// Fetches `txid` and reports whether it is a coinbase and whether it is a coinstake.
+    fn generation_kind(&self, txid: &str) -> Result<(bool, bool), String> {
+        let raw = hex::decode(self.raw_transaction(txid)?)
+            .map_err(|e| format!("node returned invalid hex for {txid}: {e}"))?;
+        let tx: Transaction = bitcoin::consensus::deserialize(&raw)
+            .map_err(|e| format!("node returned an invalid transaction for {txid}: {e}"))?;
+        Ok((tx.is_coinbase(), is_coinstake(&tx)))
+    }
+}
+
+impl ChainBackend for CoreRpcBackend {
+    fn name(&self) -> String {
+        format!("reddcoind {}", self.url)
+    }
+
+    fn chain_status(&self) -> Result<ChainStatus, String> {
+        let info = self.blockchain_info()?;
+        Ok(ChainStatus {
+            height: info.blocks,
+            best_block_hash: info.best_block_hash,
+        })
+    }
+
+    /// Confirmed outputs only; see [`CoreRpcBackend::scan_address`]. As with Electrum, the
+    /// funding transaction of each output short of maturity is fetched to classify it.
+    fn utxos(&self, address: &str) -> Result<Vec<Utxo>, String> {
+        let scan = self.scan_address(address)?;
+        scan.unspents
+            .into_iter()
+            .map(|output| {
+                let confirmations = scan.height.saturating_sub(output.height).saturating_add(1);
+                let (is_coinbase, is_coinstake) = if confirmations < self.params.coinbase_maturity {
+                    self.generation_kind(&output.txid)?
+                } else {
+                    (output.coinbase.unwrap_or(false), false)
+                };
+                Ok(Utxo {
+                    txid: output.txid,
+                    vout: output.vout,
+                    amount: output.amount,
+                    height: Some(output.height),
+                    confirmations,
+                    is_coinbase,
+                    is_coinstake,
+                    script_pubkey: Some(output.script_pubkey),
+                    address: Some(address.to_string()),
+                    sequence: None,
+                    verified: false,
+                })
+            })
+            .collect()
+    }
+
+    fn history(&self, address: &str) -> Result<Vec<HistoryEntry>, String> {
+        Err(format!(
+            "{} cannot list history for {address}: Reddcoin Core has no address index",
+            self.url
+        ))
+    }
+
+    /// Confirmed balance from a UTXO scan; the unconfirmed part is always zero.
+    fn balance(&self, address: &str) -> Result<AddressBalance, String> {
+        Ok(AddressBalance {
+            confirmed: self.scan_address(address)?.total_amount,
+            unconfirmed: 0,
+        })
+    }
+
+    /// Asks `estimatesmartfee` first. Nodes predating it, and nodes whose smart estimator has no
+    /// data yet, are asked `estimatefee` instead.
+    fn estimate_fee_per_kb(&self, target_blocks: u16) -> Result<u64, String> {
+        #[derive(Deserialize)]
+        struct SmartFeeEstimate {
+            #[serde(rename = "feerate", default)]
+            fee_rate: Option<Box<RawValue>>,
+        }
+        let smart = self.call::<SmartFeeEstimate>("estimatesmartfee", json!([target_blocks]));
+        let fee = match smart {
+            Ok(SmartFeeEstimate {
+                fee_rate: Some(rate),
+            }) => parse_fee_per_kb(rate.get())?,
+            _ => {
+                let estimate: Box<RawValue> = self.call("estimatefee", json!([target_blocks]))?;
+                parse_fee_per_kb(estimate.get())?
+            }
+        };
+        Ok(normalize_fee_per_kb(fee, &self.params))
+    }
+
+    fn raw_transaction(&self, txid: &str) -> Result<String, String> {
+        self.call(
+            "getrawtransaction",
+            json!([hex_param(txid, "txid")?, false]),
+        )
+    }
+
+    /// Rebroadcasting the same transaction is harmless, so transient failures are retried.
+    fn broadcast(&self, tx_hex: &str) -> Result<String, String> {
+        let tx_hex = tx_hex.trim();
+        hex::decode(tx_hex).map_err(|e| format!("tx_hex must be valid hex: {e}"))?;
+        self.call("sendrawtransaction", json!([tx_hex]))
+    }
+}
+
+/// Core has no batch header call, so each header costs two round trips; fine for a node on the
+/// same machine or LAN.
+impl HeaderSource for CoreRpcBackend {
+    fn header_tip_height(&self) -> Result<u32, String> {
+        self.blockchain_info().map(|info| info.blocks)
+    }
+
+    fn headers(&self, start_height: u32, count: u32) -> Result<Vec<Header>, String> {
+        let tip = self.header_tip_height()?;
+        let end = start_height
+            .saturating_add(count)
+            .min(tip.saturating_add(1));
+        let mut headers = Vec::new();
+        for height in start_height..end {
+            let hash = self.block_hash(height)?;
+            headers.extend(parse_headers_hex(&self.block_header_hex(&hash)?)?);
+        }
+        Ok(headers)
+    }
+}
+
+impl ProofSource for CoreRpcBackend {
+    fn inclusion_proof(&self, txid: &str, height: u32) -> Result<InclusionProof, String> {
+        let block_hash = self.block_hash(height)?;
+        self.tx_out_proof(txid, &block_hash)
+            .map(InclusionProof::MerkleBlock)
+    }
+}
+
+/// One `getblockfilter` per block: a node on the same machine or LAN answers quickly enough.
+impl FilterSource for CoreRpcBackend {
+    fn block_filters(&mut self, blocks: &[(u32, BlockHash)]) -> Result<Vec<BlockFilter>, String> {
+        blocks
+            .iter()
+            .map(|(_, hash)| {
+                let filter = self.block_filter_hex(&hash.to_string())?;
+                hex::decode(&filter)
+                    .map(|content| BlockFilter::new(&content))
+                    .map_err(|e| format!("invalid filter hex for block {hash}: {e}"))
+            })
+            .collect()
+    }
+
+    fn block(&mut self, hash: &BlockHash) -> Result<Block, String> {
+        let raw = hex::decode(self.block_hex(&hash.to_string())?)
+            .map_err(|e| format!("invalid block hex for {hash}: {e}"))?;
+        decode_block(&raw)
+    }
+}
+
+fn hex_param<'a>(value: &'a str, field: &str) -> Result<&'a str, String> {
+    if value.is_empty() || !value.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Err(format!("invalid {field} {value:?}"));
+    }
+    Ok(value)
+}
+
+/// Keeps the address from closing the `addr(...)` descriptor early.
+fn address_param(address: &str) -> Result<&str, String> {
+    if address.is_empty() || !address.bytes().all(|byte| byte.is_ascii_alphanumeric()) {
+        return Err(format!("invalid address {address:?}"));
+    }
+    Ok(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::http_transport::test_server::{response, serve, TestServer};
+    use crate::http_transport::StdHttpTransport;
+
+    fn backend(server: &TestServer, auth: CoreRpcAuth) -> CoreRpcBackend {
+        CoreRpcBackend::new(&server.url, auth, Box::new(StdHttpTransport::default())).with_retry(
+            RetryPolicy {
+                max_retries: 2,
+                base_delay: Duration::from_millis(1),
+                max_jitter: Duration::ZERO,
+            },
+        )
+    }
+
+    fn user_pass() -> CoreRpcAuth {
+        CoreRpcAuth::UserPass {
+            user: "rpc".to_string(),
+            password: "secret".to_string(),
+        }
+    }
+
+    fn result(result: &str) -> String {
+        response(
+            200,
+            &format!(r#"{{"result":{result},"error":null,"id":0}}"#),
+        )
+    }
+
+    fn error(status: u16, code: i64, message: &str) -> String {
+        response(
+            status,
+            &format!(r#"{{"result":null,"error":{{"code":{code},"message":"{message}"}},"id":0}}"#),
+        )
+    }
+
+    fn methods(server: TestServer) -> Vec<String> {
+        server
+            .finish()
+            .iter()
+            .map(|request| {
+                let body: Value = serde_json::from_slice(&request.body).unwrap();
+                body["method"].as_str().unwrap().to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn sends_user_pass_and_cookie_credentials() {
+        let server = serve(vec![result(r#""00""#)]);
+        backend(&server, user_pass()).block_hash(0).unwrap();
+        assert_eq!(
+            server.finish()[0].header("Authorization"),
+            Some(format!("Basic {}", BASE64.encode("rpc:secret")).as_str())
+        );
+
+        // The cookie is re-read per request, so a node restart is picked up.
+        let cookie = std::env::temp_dir().join(format!("core-rpc-{}.cookie", std::process::id()));
+        std::fs::write(&cookie, "__cookie__:first\n").unwrap();
+        let server = serve(vec![result(r#""00""#), result(r#""00""#)]);
+        let backend = backend(&server, CoreRpcAuth::CookieFile(cookie.clone()));
+        backend.block_hash(0).unwrap();
+        std::fs::write(&cookie, "__cookie__:second").unwrap();
+        backend.block_hash(0).unwrap();
+        std::fs::remove_file(&cookie).unwrap();
+        let requests = server.finish();
+        let headers: Vec<_> = requests
+            .iter()
+            .map(|request| request.header("Authorization").unwrap().to_string())
+            .collect();
+        assert_eq!(
+            headers,
+            [
+                format!("Basic {}", BASE64.encode("__cookie__:first")),
+                format!("Basic {}", BASE64.encode("__cookie__:second")),
+            ]
+        );
+        assert!(backend
+            .block_hash(0)
+            .unwrap_err()
+            .starts_with("cannot read RPC cookie"));
+    }
+
+    #[test]
+    fn retries_warmup_but_not_other_errors() {
+        let server = serve(vec![
+            error(500, RPC_IN_WARMUP, "Loading block index..."),
+            result(
+                r#"{"chain":"main","blocks":42,"bestblockhash":"ab","initialblockdownload":false}"#,
+            ),
+        ]);
+        assert_eq!(
+            backend(&server, user_pass()).chain_status(),
+            Ok(ChainStatus {
+                height: 42,
+                best_block_hash: "ab".to_string(),
+            })
+        );
+        assert_eq!(server.finish().len(), 2);
+
+        let server = serve(vec![error(
+            500,
+            -5,
+            "No such mempool or blockchain transaction",
+        )]);
+        assert!(backend(&server, user_pass())
+            .raw_transaction("abcd")
+            .unwrap_err()
+            .ends_with(
+                "rejected getrawtransaction: No such mempool or blockchain transaction (code -5)"
+            ));
+        assert_eq!(server.finish().len(), 1);
+    }
+
+    #[test]
+    fn scan_amounts_are_exact() {
+        let server = serve(vec![result(&format!(
+            r#"{{"success":true,"height":1000,"total_amount":92233720.36854775,
+                "unspents":[{{"txid":"{}","vout":3,"scriptPubKey":"51",
+                "amount":92233720.36854774,"height":100}},
+                {{"txid":"{}","vout":0,"scriptPubKey":"51","amount":0.00000001,"height":200}}]}}"#,
+            "aa".repeat(32),
+            "bb".repeat(32)
+        ))]);
+        let utxos = backend(&server, user_pass()).utxos("Rabc").unwrap();
+        let request: Value = serde_json::from_slice(&server.finish()[0].body).unwrap();
+
+        assert_eq!(request["params"], json!(["start", ["addr(Rabc)"]]));
+        assert_eq!(utxos[0].amount, Amount::from_base(9_223_372_036_854_774));
+        assert_eq!(utxos[0].confirmations, 901);
+        assert_eq!(utxos[1].amount, Amount::from_base(1));
+    }
+
+    #[test]
+    fn fee_prefers_estimatesmartfee() {
+        let server = serve(vec![result(r#"{"feerate":0.00123,"blocks":6}"#)]);
+        assert_eq!(
+            backend(&server, user_pass()).estimate_fee_per_kb(6),
+            Ok(123_000)
+        );
+        assert_eq!(methods(server), ["estimatesmartfee"]);
+
+        // Older nodes do not know the call.
+        let server = serve(vec![
+            error(404, -32601, "Method not found"),
+            result("0.002"),
+        ]);
+        assert_eq!(
+            backend(&server, user_pass()).estimate_fee_per_kb(6),
+            Ok(200_000)
+        );
+        assert_eq!(methods(server), ["estimatesmartfee", "estimatefee"]);
+
+        // Neither estimator has data yet.
+        let server = serve(vec![
+            result(r#"{"errors":["Insufficient data or no feerate found"],"blocks":0}"#),
+            result("-1"),
+        ]);
+        assert!(backend(&server, user_pass())
+            .estimate_fee_per_kb(6)
+            .unwrap_err()
+            .contains("no fee estimate"));
+        assert_eq!(methods(server), ["estimatesmartfee", "estimatefee"]);
+    }
+}
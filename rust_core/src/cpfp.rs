@@ -0,0 +1,309 @@
+//! Child-pays-for-parent fee bumping.
+//!
+//! A broadcast transaction whose fee is too low can sit unconfirmed indefinitely, and ReddMobile
+//! does not signal RBF, so the only recourse is a child spending one of the stuck parent's
+//! outputs. Miners evaluate the parent and child as a package, so the child pays whatever lifts
+//! the combined fee rate to the target.
+
+use std::str::FromStr;
+
+use bitcoin::absolute::LockTime;
+use bitcoin::consensus::encode::{deserialize, serialize};
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use bitcoin::{PublicKey as BitcoinPublicKey, Transaction, TxOut};
+use serde::Serialize;
+
+use crate::address::ReddcoinAddress;
+use crate::amount::Amount;
+use crate::secp_context::secp;
+use crate::transaction_signer::{
+    estimate_legacy_tx_fee, order_and_sign, unsigned_inputs, BuildOptions, SignedTransaction, Utxo,
+    LEGACY_P2PKH_DUST_LIMIT,
+};
+
+/// The signed child plus the package numbers the app shows before broadcasting it.
+#[derive(Debug, Clone, Serialize)]
+pub struct CpfpBump {
+    pub child: SignedTransaction,
+    pub parent_txid: String,
+    pub parent_size: u64,
+    pub parent_fee: Amount,
+    /// Parent plus child, in bytes.
+    pub package_size: u64,
+    /// Parent fee plus child fee.
+    pub package_fee: Amount,
+    /// Effective package fee rate in base units per 1000 bytes.
+    pub package_fee_per_kb: u64,
+}
+
+/// Signs a child spending output `parent_vout` of `parent_tx_hex` into `destination_address`,
+/// paying enough that parent and child together reach `target_fee_per_kb`.
+///
+/// `parent_fee` is the fee the parent already pays (as reported when it was signed); the raw
+/// transaction alone does not carry its input values. The child never pays less than the target
+/// rate for its own size, so it is not itself the bottleneck.
+///
+/// `options.policy` sees the child alone, so a fee limit applies to what the child pays.
+pub fn sign_cpfp_child(
+    parent_tx_hex: String,
+    parent_vout: u32,
+    parent_fee: Amount,
+    private_key_hex: String,
+    destination_address: String,
+    target_fee_per_kb: u64,
+    options: BuildOptions,
+) -> Result<CpfpBump, String> {
+    if private_key_hex.len() != 64 {
+        return Err("private_key_hex must be exactly 64 hex characters".to_string());
+    }
+
+    let parent_raw = hex::decode(parent_tx_hex.trim())
+        .map_err(|e| format!("parent_tx_hex decode failed: {e}"))?;
+    let parent: Transaction =
+        deserialize(&parent_raw).map_err(|e| format!("parent_tx_hex is not a transaction: {e}"))?;
+    let parent_size = serialize(&parent).len() as u64;
+    let parent_txid = parent.compute_txid();
+
+    let parent_output = parent.output.get(parent_vout as usize).ok_or_else(|| {
+        format!(
+            "parent has {} outputs; vout {parent_vout} does not exist",
+            parent.output.len()
+        )
+    })?;
+
+    let private_key_raw =
+        hex::decode(private_key_hex).map_err(|e| format!("private_key_hex decode failed: {e}"))?;
+    let secret_key = SecretKey::from_slice(&private_key_raw)
+        .map_err(|e| format!("invalid secp256k1 private key: {e}"))?;
+    let bitcoin_pubkey = BitcoinPublicKey::new(PublicKey::from_secret_key(secp(), &secret_key));
+
+    if parent_output.script_pubkey != ReddcoinAddress::p2pkh(&bitcoin_pubkey).script_pubkey() {
+        return Err(format!(
+            "parent output {parent_vout} is not paid to this key's address"
+        ));
+    }
+
+    let destination_script = ReddcoinAddress::from_str(&destination_address)
+        .map_err(|e| format!("invalid destination_address: {e}"))?
+        .script_pubkey();
+    let input_value = Amount::from(parent_output.value);
+
+    // Package target = parent bytes at the target rate + child bytes at the target rate. The
+    // child covers whatever part of the parent's share the parent fee does not.
+    let child_own_fee = estimate_legacy_tx_fee(1, 1, target_fee_per_kb)?;
+    let parent_target = parent_size
+        .checked_mul(target_fee_per_kb)
+        .and_then(|v| v.checked_add(999))
+        .map(|v| Amount::from_base(v / 1000))
+        .ok_or_else(|| "package fee calculation overflowed u64".to_string())?;
+    let parent_shortfall = parent_target
+        .checked_sub(parent_fee)
+        .unwrap_or(Amount::ZERO);
+    let child_fee = child_own_fee
+        .checked_add(parent_shortfall)
+        .ok_or_else(|| "child fee overflowed u64".to_string())?;
+
+    let child_value = input_value
+        .checked_sub(child_fee)
+        .filter(|value| *value >= LEGACY_P2PKH_DUST_LIMIT)
+        .ok_or_else(|| {
+            format!(
+                "parent output {input_value} cannot pay a child fee of {child_fee} and leave a \
+                 non-dust output"
+            )
+        })?;
+
+    let parent_utxo = Utxo {
+        txid: parent_txid.to_string(),
+        vout: parent_vout,
+        amount: input_value,
+        height: None,
+        confirmations: 0,
+        is_coinbase: false,
+        is_coinstake: false,
+        script_pubkey: Some(parent_output.script_pubkey.to_hex_string()),
+        address: None,
+        sequence: None,
+        verified: false,
+    };
+
+    let tx = Transaction {
+        version: bitcoin::transaction::Version(2),
+        lock_time: LockTime::ZERO,
+        input: unsigned_inputs(std::slice::from_ref(&parent_utxo))?,
+        output: vec![TxOut {
+            value: child_value.into(),
+            script_pubkey: destination_script,
+        }],
+    };
+    let child = order_and_sign(
+        tx,
+        None,
+        &options,
+        &secret_key,
+        &bitcoin_pubkey,
+        input_value,
+    )?;
+
+    let package_size = parent_size + child.hex.len() as u64 / 2;
+    let package_fee = parent_fee
+        .checked_add(child.fee)
+        .ok_or_else(|| "package fee overflowed u64".to_string())?;
+
+    Ok(CpfpBump {
+        parent_txid: parent_txid.to_string(),
+        parent_size,
+        parent_fee,
+        package_size,
+        package_fee_per_kb: package_fee.to_base().saturating_mul(1000) / package_size,
+        package_fee,
+        child,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::consensus::encode::serialize_hex;
+    use bitcoin::{OutPoint, ScriptBuf, Sequence, TxIn, Witness};
+
+    use super::*;
+
+    const KEY_HEX: &str = "0606060606060606060606060606060606060606060606060606060606060606";
+    const TARGET_FEE_PER_KB: u64 = 1_000_000;
+
+    fn address(key_byte: u8) -> ReddcoinAddress {
+        let secret_key = SecretKey::from_slice(&[key_byte; 32]).unwrap();
+        ReddcoinAddress::p2pkh(&BitcoinPublicKey::new(secret_key.public_key(secp())))
+    }
+
+    /// A stuck parent paying `value` to our key at vout 0 and 1 RDD elsewhere at vout 1.
+    fn parent_hex(value: u64) -> String {
+        let parent = Transaction {
+            version: bitcoin::transaction::Version(2),
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(
+                    bitcoin::Txid::from_str(&"11".repeat(32)).unwrap(),
+                    0,
+                ),
+                // Stands in for a signature and key so the parent has a realistic size.
+                script_sig: ScriptBuf::from_bytes(vec![0x51; 107]),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: vec![
+                TxOut {
+                    value: bitcoin::Amount::from_sat(value),
+                    script_pubkey: address(6).script_pubkey(),
+                },
+                TxOut {
+                    value: bitcoin::Amount::from_sat(100_000_000),
+                    script_pubkey: address(7).script_pubkey(),
+                },
+            ],
+        };
+        serialize_hex(&parent)
+    }
+
+    fn bump(parent_hex: String, vout: u32, parent_fee: u64) -> Result<CpfpBump, String> {
+        sign_cpfp_child(
+            parent_hex,
+            vout,
+            Amount::from_base(parent_fee),
+            KEY_HEX.to_string(),
+            address(8).to_string(),
+            TARGET_FEE_PER_KB,
+            BuildOptions::default(),
+        )
+    }
+
+    #[test]
+    fn child_covers_an_underpaying_parent() {
+        let parent = parent_hex(50_000_000);
+        let parent_size = parent.len() as u64 / 2;
+        let bump = bump(parent, 0, 1_000).unwrap();
+
+        let own_fee = estimate_legacy_tx_fee(1, 1, TARGET_FEE_PER_KB).unwrap();
+        let parent_target = (parent_size * TARGET_FEE_PER_KB).div_ceil(1000);
+        assert_eq!(bump.parent_size, parent_size);
+        assert_eq!(
+            bump.child.fee,
+            Amount::from_base(own_fee.to_base() + parent_target - 1_000)
+        );
+        assert_eq!(
+            bump.package_fee,
+            Amount::from_base(1_000 + bump.child.fee.to_base())
+        );
+        assert_eq!(
+            bump.package_size,
+            parent_size + bump.child.hex.len() as u64 / 2
+        );
+        assert_eq!(
+            bump.package_fee_per_kb,
+            bump.package_fee.to_base() * 1000 / bump.package_size
+        );
+        assert!(bump.package_fee_per_kb >= TARGET_FEE_PER_KB);
+
+        let child: Transaction = deserialize(&hex::decode(&bump.child.hex).unwrap()).unwrap();
+        assert_eq!(
+            child.input[0].previous_output.txid.to_string(),
+            bump.parent_txid
+        );
+        assert_eq!(child.input[0].previous_output.vout, 0);
+        assert_eq!(child.output[0].script_pubkey, address(8).script_pubkey());
+    }
+
+    #[test]
+    fn child_pays_only_its_own_fee_when_the_parent_suffices() {
+        let bump = bump(parent_hex(50_000_000), 0, 10_000_000).unwrap();
+        let own_fee = estimate_legacy_tx_fee(1, 1, TARGET_FEE_PER_KB).unwrap();
+        assert_eq!(bump.child.fee, own_fee);
+        assert_eq!(
+            bump.package_fee,
+            Amount::from_base(10_000_000 + own_fee.to_base())
+        );
+
+        // The policy judges the child's own fee.
+        let options = |limit: Amount| {
+            BuildOptions::from_json(&format!(
+                r#"{{"policy":{{"rules":{{"max_fee":{{"limit":{}}}}}}}}}"#,
+                limit.to_base()
+            ))
+            .unwrap()
+        };
+        let sign = |options| {
+            sign_cpfp_child(
+                parent_hex(50_000_000),
+                0,
+                Amount::from_base(10_000_000),
+                KEY_HEX.to_string(),
+                address(8).to_string(),
+                TARGET_FEE_PER_KB,
+                options,
+            )
+        };
+        assert!(sign(options(own_fee)).is_ok());
+        let limit = own_fee.checked_sub(Amount::from_base(1)).unwrap();
+        assert!(sign(options(limit))
+            .unwrap_err()
+            .starts_with("denied by policy"));
+    }
+
+    #[test]
+    fn rejects_bad_parent_outputs() {
+        let err = bump(parent_hex(50_000_000), 2, 0).unwrap_err();
+        assert_eq!(err, "parent has 2 outputs; vout 2 does not exist");
+
+        let err = bump(parent_hex(50_000_000), 1, 0).unwrap_err();
+        assert!(err.contains("not paid to this key's address"), "{err}");
+
+        // Enough to pay the child's fee but not to leave a spendable output.
+        let own_fee = estimate_legacy_tx_fee(1, 1, TARGET_FEE_PER_KB).unwrap();
+        let err = bump(parent_hex(own_fee.to_base() + 100), 0, 10_000_000).unwrap_err();
+        assert!(err.contains("non-dust output"), "{err}");
+
+        assert!(bump("00".to_string(), 0, 0)
+            .unwrap_err()
+            .contains("is not a transaction"));
+    }
+}
@@ -0,0 +1,1239 @@
+//! Electrum protocol backend.
+//!
+//! Community ElectrumX servers speak newline-delimited JSON-RPC over a long-lived TCP or TLS
+//! connection and index by *scripthash* (the reversed SHA-256 of a scriptPubKey) rather than by
+//! address. [`ElectrumBackend`] keeps one connection open, reconnects transparently when it
+//! drops, and converts the protocol's models into the [`ChainBackend`] ones.
+//!
+//! The connection itself comes from an [`ElectrumConnector`], mirroring the HTTP transport seam:
+//! [`TcpElectrumConnector`] covers plain `tcp://` servers (the usual port 50001, a LAN server or a
+//! Tor proxy), and with the `tls` feature `TlsElectrumConnector` covers the `ssl://` servers
+//! (port 50002) that most public servers require. Without the feature, the platform layer can
+//! still supply its own connector returning a TLS stream.
+
+use std::collections::VecDeque;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+#[cfg(feature = "tls")]
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bitcoin::block::Header;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::{ScriptBuf, Transaction};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::address::ReddcoinAddress;
+use crate::amount::{Amount, BASE_UNITS_PER_RDD};
+use crate::chain_backend::{
+    is_coinstake, normalize_fee_per_kb, AddressBalance, AttemptError, ChainBackend, ChainStatus,
+    HistoryEntry, RetryPolicy, Utxo,
+};
+use crate::chain_params::ChainParams;
+use crate::header_chain::{parse_headers_hex, HeaderSource};
+use crate::merkle_proof::{InclusionProof, MerkleBranch, ProofSource};
+
+/// Protocol version negotiated with `server.version`. 1.4 is the oldest version with
+/// `blockchain.block.header` and the newest that every ElectrumX release still accepts.
+pub const ELECTRUM_PROTOCOL_VERSION: &str = "1.4";
+/// Client name announced in `server.version`.
+const CLIENT_NAME: &str = "redd-wallet";
+/// Default connect, read and write timeout.
+pub const DEFAULT_ELECTRUM_TIMEOUT: Duration = Duration::from_secs(10);
+/// Response lines longer than this are refused rather than buffered.
+const MAX_LINE_BYTES: u64 = 32 * 1024 * 1024;
+
+/// A bidirectional byte stream to an Electrum server.
+pub trait ElectrumStream: Read + Write + Send {}
+
+impl<T: Read + Write + Send> ElectrumStream for T {}
+
+/// Opens connections to one Electrum server.
+///
+/// Called again whenever the previous connection failed, so implementations should not cache
+/// streams.
+pub trait ElectrumConnector: Send + Sync {
+    /// The server's address for diagnostics, e.g. `tcp://host:50001`.
+    fn describe(&self) -> String;
+
+    fn connect(&self) -> Result<Box<dyn ElectrumStream>, String>;
+}
+
+/// Plain-TCP connector over `std::net`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TcpElectrumConnector {
+    pub host: String,
+    pub port: u16,
+    pub timeout: Duration,
+}
+
+impl TcpElectrumConnector {
+    pub fn new(host: &str, port: u16) -> Self {
+        Self {
+            host: host.to_string(),
+            port,
+            timeout: DEFAULT_ELECTRUM_TIMEOUT,
+        }
+    }
+}
+
+impl ElectrumConnector for TcpElectrumConnector {
+    fn describe(&self) -> String {
+        format!("tcp://{}:{}", self.host, self.port)
+    }
+
+    fn connect(&self) -> Result<Box<dyn ElectrumStream>, String> {
+        let stream = open_tcp(&self.host, self.port, self.timeout, &self.describe())?;
+        Ok(Box::new(stream))
+    }
+}
+
+/// TLS connector for `ssl://` servers, built on rustls.
+#[cfg(feature = "tls")]
+#[derive(Debug, Clone)]
+pub struct TlsElectrumConnector {
+    pub host: String,
+    pub port: u16,
+    pub timeout: Duration,
+    config: Arc<rustls::ClientConfig>,
+}
+
+#[cfg(feature = "tls")]
+impl TlsElectrumConnector {
+    /// A connector that accepts certificates chaining to the Mozilla root set.
+    pub fn new(host: &str, port: u16) -> Result<Self, String> {
+        let roots = rustls::RootCertStore {
+            roots: webpki_roots::TLS_SERVER_ROOTS.to_vec(),
+        };
+        Self::with_roots(host, port, roots)
+    }
+
+    /// A connector that accepts only the given DER certificates.
+    ///
+    /// Many community servers use self-signed certificates, which no root set vouches for;
+    /// pinning the server's own certificate is how to reach those.
+    pub fn with_pinned_certificates(
+        host: &str,
+        port: u16,
+        certificates: &[Vec<u8>],
+    ) -> Result<Self, String> {
+        let mut roots = rustls::RootCertStore::empty();
+        for certificate in certificates {
+            roots
+                .add(certificate.clone().into())
+                .map_err(|e| format!("invalid pinned certificate: {e}"))?;
+        }
+        Self::with_roots(host, port, roots)
+    }
+
+    fn with_roots(host: &str, port: u16, roots: rustls::RootCertStore) -> Result<Self, String> {
+        // An explicit provider keeps working if another crate enables a second rustls backend.
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let config = rustls::ClientConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .map_err(|e| format!("cannot configure TLS: {e}"))?
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            timeout: DEFAULT_ELECTRUM_TIMEOUT,
+            config: Arc::new(config),
+        })
+    }
+}
+
+#[cfg(feature = "tls")]
+impl ElectrumConnector for TlsElectrumConnector {
+    fn describe(&self) -> String {
+        format!("ssl://{}:{}", self.host, self.port)
+    }
+
+    fn connect(&self) -> Result<Box<dyn ElectrumStream>, String> {
+        let server = self.describe();
+        let mut socket = open_tcp(&self.host, self.port, self.timeout, &server)?;
+        let name = rustls::pki_types::ServerName::try_from(self.host.clone())
+            .map_err(|e| format!("invalid TLS server name {:?}: {e}", self.host))?;
+        let mut connection = rustls::ClientConnection::new(Arc::clone(&self.config), name)
+            .map_err(|e| format!("cannot start TLS with {server}: {e}"))?;
+        // Handshake now, so certificate errors name the server instead of the first request.
+        while connection.is_handshaking() {
+            connection
+                .complete_io(&mut socket)
+                .map_err(|e| format!("TLS handshake with {server} failed: {e}"))?;
+        }
+        Ok(Box::new(rustls::StreamOwned::new(connection, socket)))
+    }
+}
+
+/// Connects to `host:port` with `timeout` applied to the connect, reads and writes. `server`
+/// names the endpoint in errors.
+fn open_tcp(host: &str, port: u16, timeout: Duration, server: &str) -> Result<TcpStream, String> {
+    let address = (host, port)
+        .to_socket_addrs()
+        .map_err(|e| format!("cannot resolve {host}: {e}"))?
+        .next()
+        .ok_or_else(|| format!("{host} resolved to no addresses"))?;
+    let stream = TcpStream::connect_timeout(&address, timeout)
+        .map_err(|e| format!("cannot connect to {server}: {e}"))?;
+    stream
+        .set_read_timeout(Some(timeout))
+        .and_then(|()| stream.set_write_timeout(Some(timeout)))
+        .map_err(|e| format!("cannot set socket timeout: {e}"))?;
+    Ok(stream)
+}
+
+/// `blockchain.scripthash.get_history` entry.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ElectrumHistoryItem {
+    pub tx_hash: String,
+    /// Confirming height; 0 in the mempool, -1 in the mempool with unconfirmed parents.
+    pub height: i64,
+}
+
+/// `blockchain.scripthash.listunspent` entry.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ElectrumUnspent {
+    pub tx_hash: String,
+    pub tx_pos: u32,
+    /// Confirming height; 0 in the mempool.
+    pub height: i64,
+    /// Base units.
+    pub value: u64,
+}
+
+/// `blockchain.scripthash.get_balance`, in base units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct ElectrumBalance {
+    pub confirmed: u64,
+    pub unconfirmed: i64,
+}
+
+/// `blockchain.headers.subscribe` result and notification payload.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ElectrumHeaderTip {
+    pub height: u32,
+    /// Hex-encoded 80-byte header.
+    pub hex: String,
+}
+
+/// `blockchain.block.headers`: `count` concatenated headers starting at the requested height.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ElectrumHeaders {
+    pub count: u32,
+    pub hex: String,
+    /// Most headers the server returns per request.
+    pub max: u32,
+}
+
+/// A server-initiated message, such as a scripthash status change after a subscription.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ElectrumNotification {
+    pub method: String,
+    pub params: Value,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcMessage {
+    #[serde(default)]
+    id: Option<Value>,
+    #[serde(default)]
+    result: Option<Value>,
+    #[serde(default)]
+    error: Option<RpcError>,
+    #[serde(default)]
+    method: Option<String>,
+    #[serde(default)]
+    params: Option<Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RpcError {
+    Object { message: String },
+    Message(String),
+    Other(Value),
+}
+
+impl RpcError {
+    fn message(self) -> String {
+        match self {
+            RpcError::Object { message } | RpcError::Message(message) => message,
+            RpcError::Other(value) => value.to_string(),
+        }
+    }
+}
+
+/// An open, version-negotiated connection.
+struct Session {
+    stream: BufReader<Box<dyn ElectrumStream>>,
+}
+
+/// An Electrum protocol server.
+pub struct ElectrumBackend {
+    connector: Box<dyn ElectrumConnector>,
+    session: Mutex<Option<Session>>,
+    notifications: Mutex<VecDeque<ElectrumNotification>>,
+    next_id: AtomicU64,
+    retry: RetryPolicy,
+    params: ChainParams,
+}
+
+impl ElectrumBackend {
+    /// A backend connecting through `connector`. Nothing is opened until the first request.
+    pub fn new(connector: Box<dyn ElectrumConnector>) -> Self {
+        Self {
+            connector,
+            session: Mutex::new(None),
+            notifications: Mutex::new(VecDeque::new()),
+            next_id: AtomicU64::new(0),
+            retry: RetryPolicy::default(),
+            params: ChainParams::MAINNET,
+        }
+    }
+
+    /// Replaces the default retry schedule.
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// `server.ping`. Also collects any notifications the server has sent since the last call.
+    pub fn ping(&self) -> Result<(), String> {
+        self.call::<Value>("server.ping", json!([])).map(|_| ())
+    }
+
+    /// Subscribes to `scripthash` and returns its current status hash, `None` if it has no
+    /// history. Later changes arrive as `blockchain.scripthash.subscribe` notifications.
+    ///
+    /// Subscriptions belong to the connection: after a reconnect, subscribe again.
+    pub fn subscribe_scripthash(&self, scripthash: &str) -> Result<Option<String>, String> {
+        self.call(
+            "blockchain.scripthash.subscribe",
+            json!([scripthash_param(scripthash)?]),
+        )
+    }
+
+    pub fn scripthash_history(&self, scripthash: &str) -> Result<Vec<ElectrumHistoryItem>, String> {
+        self.call(
+            "blockchain.scripthash.get_history",
+            json!([scripthash_param(scripthash)?]),
+        )
+    }
+
+    pub fn scripthash_unspent(&self, scripthash: &str) -> Result<Vec<ElectrumUnspent>, String> {
+        self.call(
+            "blockchain.scripthash.listunspent",
+            json!([scripthash_param(scripthash)?]),
+        )
+    }
+
+    pub fn scripthash_balance(&self, scripthash: &str) -> Result<ElectrumBalance, String> {
+        self.call(
+            "blockchain.scripthash.get_balance",
+            json!([scripthash_param(scripthash)?]),
+        )
+    }
+
+    /// Subscribes to new tips and returns the current one. New tips arrive as
+    /// `blockchain.headers.subscribe` notifications.
+    pub fn subscribe_headers(&self) -> Result<ElectrumHeaderTip, String> {
+        self.call("blockchain.headers.subscribe", json!([]))
+    }
+
+    /// Hex-encoded header at `height`.
+    pub fn block_header(&self, height: u32) -> Result<String, String> {
+        self.call("blockchain.block.header", json!([height]))
+    }
+
+    /// Up to `count` consecutive headers starting at `start_height`.
+    pub fn block_headers(&self, start_height: u32, count: u32) -> Result<ElectrumHeaders, String> {
+        self.call("blockchain.block.headers", json!([start_height, count]))
+    }
+
+    /// Merkle branch for `txid` in the block at `height`.
+    pub fn merkle_proof(&self, txid: &str, height: u32) -> Result<MerkleBranch, String> {
+        self.call(
+            "blockchain.transaction.get_merkle",
+            json!([hex_param(txid, "txid")?, height]),
+        )
+    }
+
+    /// Minimum fee rate the server's node relays, in base units per kB.
+    pub fn relay_fee_per_kb(&self) -> Result<u64, String> {
+        let coins: f64 = self.call("blockchain.relayfee", json!([]))?;
+        coins_to_base(coins).ok_or_else(|| format!("invalid relay fee {coins}"))
+    }
+
+    /// Removes and returns every notification received so far, oldest first.
+    ///
+    /// Notifications are read off the connection together with responses; call [`Self::ping`]
+    /// to poll when no other request is due.
+    pub fn take_notifications(&self) -> Vec<ElectrumNotification> {
+        self.notifications
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .drain(..)
+            .collect()
+    }
+
+    /// Calls `method`, retrying transient failures on a fresh connection.
+    fn call<T: DeserializeOwned>(&self, method: &str, params: Value) -> Result<T, String> {
+        self.retry.run(|| self.attempt(method, &params))
+    }
+
+    fn attempt<T: DeserializeOwned>(
+        &self,
+        method: &str,
+        params: &Value,
+    ) -> Result<T, AttemptError> {
+        let mut session = self
+            .session
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if session.is_none() {
+            *session = Some(self.open().map_err(AttemptError::Transient)?);
+        }
+        let open = session.as_mut().expect("session was just opened");
+        let result = self.exchange(open, method, params);
+        if matches!(result, Err(AttemptError::Transient(_))) {
+            // The stream may hold half a response; never reuse it.
+            *session = None;
+        }
+        let value = result?;
+        serde_json::from_value(value).map_err(|e| {
+            AttemptError::Permanent(format!(
+                "unexpected {method} response from {}: {e}",
+                self.connector.describe()
+            ))
+        })
+    }
+
+    /// Connects and negotiates the protocol version, which servers require before anything else.
+    fn open(&self) -> Result<Session, String> {
+        let mut session = Session {
+            stream: BufReader::new(self.connector.connect()?),
+        };
+        self.exchange(
+            &mut session,
+            "server.version",
+            &json!([CLIENT_NAME, ELECTRUM_PROTOCOL_VERSION]),
+        )
+        .map_err(|error| match error {
+            AttemptError::Transient(message) | AttemptError::Permanent(message) => {
+                format!("protocol negotiation failed: {message}")
+            }
+        })?;
+        Ok(session)
+    }
+
+    /// Sends one request and reads lines until its response, queueing notifications on the way.
+    fn exchange(
+        &self,
+        session: &mut Session,
+        method: &str,
+        params: &Value,
+    ) -> Result<Value, AttemptError> {
+        let server = self.connector.describe();
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let mut line =
+            json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }).to_string();
+        line.push('\n');
+        let stream = session.stream.get_mut();
+        stream
+            .write_all(line.as_bytes())
+            .and_then(|()| stream.flush())
+            .map_err(|e| AttemptError::Transient(format!("failed to send to {server}: {e}")))?;
+
+        loop {
+            let mut line = String::new();
+            let read = (&mut session.stream)
+                .take(MAX_LINE_BYTES + 1)
+                .read_line(&mut line)
+                .map_err(|e| {
+                    AttemptError::Transient(format!("failed to read from {server}: {e}"))
+                })?;
+            if read == 0 {
+                return Err(AttemptError::Transient(format!(
+                    "{server} closed the connection"
+                )));
+            }
+            if read as u64 > MAX_LINE_BYTES {
+                return Err(AttemptError::Transient(format!(
+                    "{server} sent an oversized message"
+                )));
+            }
+            let message: RpcMessage = serde_json::from_str(&line).map_err(|e| {
+                AttemptError::Transient(format!("malformed message from {server}: {e}"))
+            })?;
+
+            if let (None, Some(method)) = (&message.id, message.method) {
+                self.notifications
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner())
+                    .push_back(ElectrumNotification {
+                        method,
+                        params: message.params.unwrap_or(Value::Null),
+                    });
+                continue;
+            }
+            if message.id.as_ref().and_then(Value::as_u64) != Some(id) {
+                // A late reply to a request that timed out on an earlier attempt.
+                continue;
+            }
+            if let Some(error) = message.error {
+                return Err(AttemptError::Permanent(format!(
+                    "{server} rejected {method}: {}",
+                    error.message()
+                )));
+            }
+            return Ok(message.result.unwrap_or(Value::Null));
+        }
+    }
+
+    /// Height of the server's tip, for deriving confirmation counts.
+    fn tip_height(&self) -> Result<u32, String> {
+        self.subscribe_headers().map(|tip| tip.height)
+    }
+
+    /// Fetches `txid` and reports whether it is a coinbase and whether it is a coinstake.
+    fn generation_kind(&self, txid: &str) -> Result<(bool, bool), String> {
+        let raw = hex::decode(self.raw_transaction(txid)?)
+            .map_err(|e| format!("server returned invalid hex for {txid}: {e}"))?;
+        let tx: Transaction = bitcoin::consensus::deserialize(&raw)
+            .map_err(|e| format!("server returned an invalid transaction for {txid}: {e}"))?;
+        Ok((tx.is_coinbase(), is_coinstake(&tx)))
+    }
+}
+
+impl ChainBackend for ElectrumBackend {
+    fn name(&self) -> String {
+        format!("electrum {}", self.connector.describe())
+    }
+
+    fn chain_status(&self) -> Result<ChainStatus, String> {
+        let tip = self.subscribe_headers()?;
+        Ok(ChainStatus {
+            height: tip.height,
+            best_block_hash: header_hash(&tip.hex)?,
+        })
+    }
+
+    /// Electrum does not say which outputs are block rewards, so the funding transaction of every
+    /// output still short of coinbase maturity is fetched and classified. Older outputs are
+    /// spendable either way and cost nothing extra.
+    fn utxos(&self, address: &str) -> Result<Vec<Utxo>, String> {
+        let script = address_script(address)?;
+        let unspent = self.scripthash_unspent(&script_hash(&script))?;
+        if unspent.is_empty() {
+            return Ok(Vec::new());
+        }
+        let tip = self.tip_height()?;
+
+        unspent
+            .into_iter()
+            .map(|output| {
+                let height = u32::try_from(output.height)
+                    .ok()
+                    .filter(|height| *height > 0);
+                let confirmations = height
+                    .map(|height| tip.saturating_sub(height).saturating_add(1))
+                    .unwrap_or(0);
+                let (is_coinbase, is_coinstake) =
+                    if height.is_some() && confirmations < self.params.coinbase_maturity {
+                        self.generation_kind(&output.tx_hash)?
+                    } else {
+                        (false, false)
+                    };
+                Ok(Utxo {
+                    txid: output.tx_hash,
+                    vout: output.tx_pos,
+                    amount: Amount::from_base(output.value),
+                    height,
+                    confirmations,
+                    is_coinbase,
+                    is_coinstake,
+                    script_pubkey: Some(script.to_hex_string()),
+                    address: Some(address.to_string()),
+                    sequence: None,
+                    verified: false,
+                })
+            })
+            .collect()
+    }
+
+    fn history(&self, address: &str) -> Result<Vec<HistoryEntry>, String> {
+        let script = address_script(address)?;
+        let mut entries: Vec<HistoryEntry> = self
+            .scripthash_history(&script_hash(&script))?
+            .into_iter()
+            .map(|item| HistoryEntry {
+                txid: item.tx_hash,
+                height: u32::try_from(item.height).ok().filter(|height| *height > 0),
+                verified: false,
+            })
+            .collect();
+        // Servers already order by height with the mempool last; the stable sort only guards
+        // against ones that do not.
+        entries.sort_by_key(|entry| entry.height.unwrap_or(u32::MAX));
+        Ok(entries)
+    }
+
+    fn balance(&self, address: &str) -> Result<AddressBalance, String> {
+        let balance = self.scripthash_balance(&script_hash(&address_script(address)?))?;
+        Ok(AddressBalance {
+            confirmed: Amount::from_base(balance.confirmed),
+            unconfirmed: balance.unconfirmed,
+        })
+    }
+
+    fn estimate_fee_per_kb(&self, target_blocks: u16) -> Result<u64, String> {
+        let coins: f64 = self.call("blockchain.estimatefee", json!([target_blocks]))?;
+        if coins <= 0.0 {
+            return Err("server has no fee estimate yet".to_string());
+        }
+        let fee = coins_to_base(coins).ok_or_else(|| format!("invalid fee estimate {coins}"))?;
+        Ok(normalize_fee_per_kb(fee, &self.params))
+    }
+
+    fn raw_transaction(&self, txid: &str) -> Result<String, String> {
+        self.call(
+            "blockchain.transaction.get",
+            json!([hex_param(txid, "txid")?]),
+        )
+    }
+
+    /// Rebroadcasting the same transaction is harmless, so transient failures are retried.
+    fn broadcast(&self, tx_hex: &str) -> Result<String, String> {
+        let tx_hex = tx_hex.trim();
+        hex::decode(tx_hex).map_err(|e| format!("tx_hex must be valid hex: {e}"))?;
+        self.call("blockchain.transaction.broadcast", json!([tx_hex]))
+    }
+}
+
+impl HeaderSource for ElectrumBackend {
+    fn header_tip_height(&self) -> Result<u32, String> {
+        self.tip_height()
+    }
+
+    fn headers(&self, start_height: u32, count: u32) -> Result<Vec<Header>, String> {
+        parse_headers_hex(&self.block_headers(start_height, count)?.hex)
+    }
+}
+
+impl ProofSource for ElectrumBackend {
+    fn inclusion_proof(&self, txid: &str, height: u32) -> Result<InclusionProof, String> {
+        self.merkle_proof(txid, height).map(InclusionProof::Branch)
+    }
+}
+
+/// The Electrum scripthash of `script`: its SHA-256, byte-reversed, as hex.
+pub fn script_hash(script: &ScriptBuf) -> String {
+    let mut hash = sha256::Hash::hash(script.as_bytes()).to_byte_array();
+    hash.reverse();
+    hex::encode(hash)
+}
+
+/// The Electrum scripthash of a Reddcoin address.
+pub fn address_script_hash(address: &str) -> Result<String, String> {
+    address_script(address).map(|script| script_hash(&script))
+}
+
+fn address_script(address: &str) -> Result<ScriptBuf, String> {
+    ReddcoinAddress::from_str(address)
+        .map(|address| address.script_pubkey())
+        .map_err(|e| format!("invalid address {address:?}: {e}"))
+}
+
+/// Block hash of a hex-encoded 80-byte header.
+fn header_hash(header_hex: &str) -> Result<String, String> {
+    let bytes = hex::decode(header_hex).map_err(|e| format!("invalid header hex: {e}"))?;
+    let header: Header =
+        bitcoin::consensus::deserialize(&bytes).map_err(|e| format!("invalid header: {e}"))?;
+    Ok(header.block_hash().to_string())
+}
+
+/// Electrum reports fee rates as a JSON float of coins per kB. Any rate with at most eight
+/// decimals round-trips exactly through the nearest base unit.
+fn coins_to_base(coins: f64) -> Option<u64> {
+    let base = (coins * BASE_UNITS_PER_RDD as f64).round();
+    (coins.is_finite() && base >= 0.0 && base <= u64::MAX as f64).then_some(base as u64)
+}
+
+fn scripthash_param(scripthash: &str) -> Result<&str, String> {
+    if scripthash.len() != 64 {
+        return Err(format!("invalid scripthash {scripthash:?}"));
+    }
+    hex_param(scripthash, "scripthash")
+}
+
+fn hex_param<'a>(value: &'a str, field: &str) -> Result<&'a str, String> {
+    if value.is_empty() || !value.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+        return Err(format!("invalid {field} {value:?}"));
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::io;
+    use std::sync::Arc;
+
+    use bitcoin::absolute::LockTime;
+    use bitcoin::consensus::encode::serialize_hex;
+    use bitcoin::hashes::hash160;
+    use bitcoin::transaction::Version;
+    use bitcoin::{OutPoint, PubkeyHash, TxIn, TxOut};
+
+    use super::*;
+
+    /// What the fake server writes back for one request.
+    enum Line {
+        Reply(Value),
+        Error(&'static str),
+        /// A reply carrying the previous request's id.
+        Stale(Value),
+        Notify(&'static str, Value),
+    }
+
+    impl Line {
+        /// The JSON-RPC message for this line in reply to request `id`.
+        fn message(self, id: u64) -> Value {
+            match self {
+                Line::Reply(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Line::Error(message) => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": 1, "message": message },
+                }),
+                Line::Stale(result) => json!({ "jsonrpc": "2.0", "id": id - 1, "result": result }),
+                Line::Notify(method, params) => {
+                    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+                }
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct Request {
+        connection: u64,
+        method: String,
+        params: Value,
+    }
+
+    /// Hands out one scripted connection per `connect`; each request on a connection consumes
+    /// the next batch of lines, and a connection with no batches left reads as closed.
+    struct FakeConnector {
+        connections: Mutex<VecDeque<VecDeque<Vec<Line>>>>,
+        connects: AtomicU64,
+        requests: Arc<Mutex<Vec<Request>>>,
+    }
+
+    struct FakeStream {
+        connection: u64,
+        script: VecDeque<Vec<Line>>,
+        written: Vec<u8>,
+        pending: VecDeque<u8>,
+        requests: Arc<Mutex<Vec<Request>>>,
+    }
+
+    impl ElectrumConnector for FakeConnector {
+        fn describe(&self) -> String {
+            "fake:50001".to_string()
+        }
+
+        fn connect(&self) -> Result<Box<dyn ElectrumStream>, String> {
+            let connection = self.connects.fetch_add(1, Ordering::Relaxed);
+            let script = self
+                .connections
+                .lock()
+                .unwrap()
+                .pop_front()
+                .ok_or("connection refused")?;
+            Ok(Box::new(FakeStream {
+                connection,
+                script,
+                written: Vec::new(),
+                pending: VecDeque::new(),
+                requests: Arc::clone(&self.requests),
+            }))
+        }
+    }
+
+    impl Write for FakeStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            while let Some(end) = self.written.iter().position(|byte| *byte == b'\n') {
+                let line: Vec<u8> = self.written.drain(..=end).collect();
+                let request: Value = serde_json::from_slice(&line).unwrap();
+                let id = request["id"].as_u64().unwrap();
+                self.requests.lock().unwrap().push(Request {
+                    connection: self.connection,
+                    method: request["method"].as_str().unwrap().to_string(),
+                    params: request["params"].clone(),
+                });
+                for reply in self.script.pop_front().unwrap_or_default() {
+                    self.pending.extend(reply.message(id).to_string().bytes());
+                    self.pending.push_back(b'\n');
+                }
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Read for FakeStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let count = buf.len().min(self.pending.len());
+            for (slot, byte) in buf.iter_mut().zip(self.pending.drain(..count)) {
+                *slot = byte;
+            }
+            Ok(count)
+        }
+    }
+
+    fn version() -> Vec<Line> {
+        vec![Line::Reply(json!(["ElectrumX 1.16.0", "1.4"]))]
+    }
+
+    /// A negotiated connection answering `replies` in order.
+    fn session(replies: Vec<Vec<Line>>) -> VecDeque<Vec<Line>> {
+        std::iter::once(version()).chain(replies).collect()
+    }
+
+    fn fake_backend(
+        connections: Vec<VecDeque<Vec<Line>>>,
+    ) -> (ElectrumBackend, Arc<Mutex<Vec<Request>>>) {
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let connector = FakeConnector {
+            connections: Mutex::new(connections.into()),
+            connects: AtomicU64::new(0),
+            requests: Arc::clone(&requests),
+        };
+        let backend = ElectrumBackend::new(Box::new(connector)).with_retry(RetryPolicy {
+            max_retries: 2,
+            base_delay: Duration::from_millis(1),
+            max_jitter: Duration::ZERO,
+        });
+        (backend, requests)
+    }
+
+    fn methods(requests: &Mutex<Vec<Request>>) -> Vec<(u64, String)> {
+        requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|request| (request.connection, request.method.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn negotiates_the_version_once_per_connection() {
+        let (backend, requests) = fake_backend(vec![session(vec![
+            vec![Line::Reply(Value::Null)],
+            vec![Line::Reply(json!(0.00001))],
+        ])]);
+        backend.ping().unwrap();
+        assert_eq!(backend.relay_fee_per_kb(), Ok(1_000));
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(requests[0].method, "server.version");
+        assert_eq!(requests[0].params, json!(["redd-wallet", "1.4"]));
+        let methods: Vec<_> = requests
+            .iter()
+            .map(|r| (r.connection, &*r.method))
+            .collect();
+        assert_eq!(
+            methods,
+            [
+                (0, "server.version"),
+                (0, "server.ping"),
+                (0, "blockchain.relayfee")
+            ]
+        );
+
+        let (backend, _) = fake_backend(vec![VecDeque::from([vec![Line::Error(
+            "unsupported protocol version",
+        )]])]);
+        let backend = backend.with_retry(RetryPolicy::NONE);
+        assert!(backend
+            .ping()
+            .unwrap_err()
+            .contains("protocol negotiation failed: fake:50001 rejected server.version"));
+    }
+
+    #[test]
+    fn notifications_interleave_with_replies() {
+        let scripthash = "ab".repeat(32);
+        let (backend, _) = fake_backend(vec![session(vec![
+            vec![
+                Line::Notify(
+                    "blockchain.headers.subscribe",
+                    json!([{ "height": 7, "hex": "00" }]),
+                ),
+                Line::Reply(json!("status")),
+            ],
+            vec![
+                Line::Notify(
+                    "blockchain.scripthash.subscribe",
+                    json!([scripthash, "changed"]),
+                ),
+                Line::Reply(Value::Null),
+            ],
+        ])]);
+        assert_eq!(
+            backend.subscribe_scripthash(&scripthash),
+            Ok(Some("status".to_string()))
+        );
+        backend.ping().unwrap();
+
+        let notifications = backend.take_notifications();
+        let methods: Vec<_> = notifications.iter().map(|n| n.method.as_str()).collect();
+        assert_eq!(
+            methods,
+            [
+                "blockchain.headers.subscribe",
+                "blockchain.scripthash.subscribe"
+            ]
+        );
+        assert_eq!(notifications[1].params, json!([scripthash, "changed"]));
+        assert!(backend.take_notifications().is_empty());
+    }
+
+    #[test]
+    fn stale_reply_ids_are_skipped() {
+        let (backend, _) = fake_backend(vec![session(vec![vec![
+            Line::Stale(json!("late")),
+            Line::Reply(json!("current")),
+        ]])]);
+        assert_eq!(backend.block_header(5), Ok("current".to_string()));
+    }
+
+    #[test]
+    fn reconnects_after_a_transient_error() {
+        // The first connection closes instead of answering.
+        let (backend, requests) = fake_backend(vec![
+            session(vec![]),
+            session(vec![vec![Line::Reply(json!("header"))]]),
+        ]);
+        assert_eq!(backend.block_header(5), Ok("header".to_string()));
+        assert_eq!(
+            methods(&requests),
+            [
+                (0, "server.version".to_string()),
+                (0, "blockchain.block.header".to_string()),
+                (1, "server.version".to_string()),
+                (1, "blockchain.block.header".to_string()),
+            ]
+        );
+
+        // Server-side errors are permanent and keep the connection.
+        let (backend, requests) = fake_backend(vec![session(vec![
+            vec![Line::Error("height out of range")],
+            vec![Line::Reply(Value::Null)],
+        ])]);
+        assert!(backend
+            .block_header(u32::MAX)
+            .unwrap_err()
+            .ends_with("rejected blockchain.block.header: height out of range"));
+        backend.ping().unwrap();
+        assert!(methods(&requests)
+            .iter()
+            .all(|(connection, _)| *connection == 0));
+    }
+
+    #[test]
+    fn utxos_classify_immature_outputs() {
+        let script = ScriptBuf::new_p2pkh(&PubkeyHash::from_raw_hash(hash160::Hash::hash(b"k")));
+        let address = ReddcoinAddress::from_script(&script).unwrap().to_string();
+        let young = "aa".repeat(32);
+        let coinstake = serialize_hex(&Transaction {
+            version: Version(1),
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new("22".repeat(32).parse().unwrap(), 1),
+                ..TxIn::default()
+            }],
+            output: vec![
+                TxOut {
+                    value: bitcoin::Amount::ZERO,
+                    script_pubkey: ScriptBuf::new(),
+                },
+                TxOut {
+                    value: bitcoin::Amount::from_sat(1_000_000_000),
+                    script_pubkey: script.clone(),
+                },
+            ],
+        });
+        let (backend, requests) = fake_backend(vec![session(vec![
+            vec![Line::Reply(json!([
+                { "tx_hash": "bb".repeat(32), "tx_pos": 0, "height": 100, "value": 1_250_000_001u64 },
+                { "tx_hash": young, "tx_pos": 1, "height": 990, "value": 1_000_000_000u64 },
+                { "tx_hash": "cc".repeat(32), "tx_pos": 2, "height": 0, "value": 5 },
+            ]))],
+            vec![Line::Reply(json!({ "height": 1000, "hex": "00" }))],
+            vec![Line::Reply(json!(coinstake))],
+        ])]);
+        let utxos = backend.utxos(&address).unwrap();
+
+        let requests = requests.lock().unwrap();
+        let methods: Vec<_> = requests.iter().map(|r| r.method.as_str()).collect();
+        assert_eq!(
+            methods,
+            [
+                "server.version",
+                "blockchain.scripthash.listunspent",
+                "blockchain.headers.subscribe",
+                "blockchain.transaction.get"
+            ]
+        );
+        assert_eq!(requests[1].params, json!([script_hash(&script)]));
+        assert_eq!(requests[3].params, json!([young]));
+
+        assert_eq!(utxos[0].amount, Amount::from_base(1_250_000_001));
+        assert_eq!(utxos[0].confirmations, 901);
+        assert!(!utxos[0].is_coinstake);
+        assert_eq!(utxos[1].confirmations, 11);
+        assert!(utxos[1].is_coinstake && !utxos[1].is_coinbase);
+        assert_eq!((utxos[2].height, utxos[2].confirmations), (None, 0));
+        assert!(utxos.iter().all(|utxo| {
+            utxo.address.as_deref() == Some(address.as_str())
+                && utxo.script_pubkey == Some(script.to_hex_string())
+        }));
+    }
+
+    /// Answers requests on one server-side connection until the client hangs up. The version
+    /// handshake is answered here; everything else goes to `respond`.
+    fn serve_connection(
+        stream: impl Read + Write,
+        respond: &dyn Fn(&str, &Value) -> Vec<Line>,
+        requests: &mut Vec<(String, Value)>,
+    ) {
+        let mut reader = BufReader::new(stream);
+        loop {
+            let mut line = String::new();
+            if !matches!(reader.read_line(&mut line), Ok(read) if read > 0) {
+                return;
+            }
+            let request: Value = serde_json::from_str(&line).unwrap();
+            let id = request["id"].as_u64().unwrap();
+            let method = request["method"].as_str().unwrap().to_string();
+            let replies = match method.as_str() {
+                "server.version" => version(),
+                method => respond(method, &request["params"]),
+            };
+            requests.push((method, request["params"].clone()));
+
+            let mut out = String::new();
+            for reply in replies {
+                out.push_str(&reply.message(id).to_string());
+                out.push('\n');
+            }
+            let stream = reader.get_mut();
+            if stream
+                .write_all(out.as_bytes())
+                .and_then(|()| stream.flush())
+                .is_err()
+            {
+                return;
+            }
+        }
+    }
+
+    /// An in-process Electrum server on a loopback port, serving `connections` consecutive
+    /// connections, each wrapped by `wrap`. Joining the handle once the clients are gone yields
+    /// every request in order.
+    fn loopback_server<S: Read + Write>(
+        connections: usize,
+        wrap: impl Fn(TcpStream) -> S + Send + 'static,
+        respond: impl Fn(&str, &Value) -> Vec<Line> + Send + 'static,
+    ) -> (u16, std::thread::JoinHandle<Vec<(String, Value)>>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let handle = std::thread::spawn(move || {
+            let mut requests = Vec::new();
+            for _ in 0..connections {
+                let (stream, _) = listener.accept().unwrap();
+                serve_connection(wrap(stream), &respond, &mut requests);
+            }
+            requests
+        });
+        (port, handle)
+    }
+
+    fn test_header(nonce: u32) -> Header {
+        Header {
+            version: bitcoin::block::Version::ONE,
+            prev_blockhash: bitcoin::BlockHash::all_zeros(),
+            merkle_root: bitcoin::TxMerkleNode::all_zeros(),
+            time: 1_700_000_000,
+            bits: bitcoin::CompactTarget::from_consensus(0x1e0f_ffff),
+            nonce,
+        }
+    }
+
+    #[test]
+    fn loopback_server_answers_the_wallet_calls() {
+        let script = ScriptBuf::new_p2pkh(&PubkeyHash::from_raw_hash(hash160::Hash::hash(b"k")));
+        let address = ReddcoinAddress::from_script(&script).unwrap().to_string();
+        let scripthash = script_hash(&script);
+        let headers = [test_header(1), test_header(2)];
+        let headers_hex: String = headers.iter().map(serialize_hex).collect();
+        let tip_hex = serialize_hex(&headers[1]);
+        let (confirmed, pending, broadcast) = ("aa".repeat(32), "bb".repeat(32), "cc".repeat(32));
+
+        let notified = scripthash.clone();
+        let history = json!([
+            { "tx_hash": pending, "height": 0 },
+            { "tx_hash": confirmed, "height": 120 },
+        ]);
+        let txid = broadcast.clone();
+        let (port, server) = loopback_server(
+            1,
+            |stream| stream,
+            move |method, _params| match method {
+                // The status change arrives after the reply and is read with the next one.
+                "blockchain.scripthash.subscribe" => vec![
+                    Line::Reply(json!("status")),
+                    Line::Notify(
+                        "blockchain.scripthash.subscribe",
+                        json!([notified, "changed"]),
+                    ),
+                ],
+                "blockchain.scripthash.get_history" => vec![Line::Reply(history.clone())],
+                "blockchain.headers.subscribe" => {
+                    vec![Line::Reply(json!({ "height": 501, "hex": tip_hex }))]
+                }
+                "blockchain.block.headers" => vec![Line::Reply(
+                    json!({ "count": 2, "hex": headers_hex, "max": 2016 }),
+                )],
+                "blockchain.transaction.get_merkle" => vec![Line::Reply(json!({
+                    "block_height": 120,
+                    "merkle": ["dd".repeat(32), "ee".repeat(32)],
+                    "pos": 3,
+                }))],
+                "blockchain.transaction.broadcast" => vec![Line::Reply(json!(txid))],
+                _ => vec![Line::Error("unknown method")],
+            },
+        );
+        let backend = ElectrumBackend::new(Box::new(TcpElectrumConnector::new("127.0.0.1", port)));
+
+        assert_eq!(
+            backend.subscribe_scripthash(&scripthash),
+            Ok(Some("status".to_string()))
+        );
+        let history = backend.history(&address).unwrap();
+        let txids: Vec<_> = history.iter().map(|entry| entry.txid.as_str()).collect();
+        assert_eq!(txids, [confirmed.as_str(), pending.as_str()]);
+        assert_eq!((history[0].height, history[1].height), (Some(120), None));
+        let notifications = backend.take_notifications();
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].params, json!([scripthash, "changed"]));
+
+        assert_eq!(backend.header_tip_height(), Ok(501));
+        assert_eq!(
+            backend.chain_status().unwrap().best_block_hash,
+            headers[1].block_hash().to_string()
+        );
+        assert_eq!(backend.headers(500, 2).unwrap(), headers);
+
+        let proof = backend.inclusion_proof(&confirmed, 120).unwrap();
+        assert_eq!(
+            proof,
+            InclusionProof::Branch(MerkleBranch {
+                block_height: 120,
+                merkle: vec!["dd".repeat(32), "ee".repeat(32)],
+                pos: 3,
+            })
+        );
+        assert_eq!(backend.broadcast(" 0100 \n"), Ok(broadcast));
+
+        drop(backend);
+        let requests = server.join().unwrap();
+        let methods: Vec<_> = requests.iter().map(|(method, _)| method.as_str()).collect();
+        assert_eq!(
+            methods,
+            [
+                "server.version",
+                "blockchain.scripthash.subscribe",
+                "blockchain.scripthash.get_history",
+                "blockchain.headers.subscribe",
+                "blockchain.headers.subscribe",
+                "blockchain.block.headers",
+                "blockchain.transaction.get_merkle",
+                "blockchain.transaction.broadcast",
+            ]
+        );
+        assert_eq!(requests[1].1, json!([scripthash]));
+        assert_eq!(requests[2].1, json!([scripthash]));
+        assert_eq!(requests[5].1, json!([500, 2]));
+        assert_eq!(requests[6].1, json!([confirmed, 120]));
+        assert_eq!(requests[7].1, json!(["0100"]));
+    }
+
+    #[cfg(feature = "tls")]
+    #[test]
+    fn tls_connector_checks_the_server_certificate() {
+        // A self-signed certificate for localhost and 127.0.0.1, valid 2026-2126.
+        const CERTIFICATE: &[u8] = include_bytes!("testdata/electrum_localhost_cert.der");
+        const KEY: &[u8] = include_bytes!("testdata/electrum_localhost_key.der");
+
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let config = rustls::ServerConfig::builder_with_provider(provider)
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_no_client_auth()
+            .with_single_cert(
+                vec![CERTIFICATE.to_vec().into()],
+                rustls::pki_types::PrivatePkcs8KeyDer::from(KEY.to_vec()).into(),
+            )
+            .unwrap();
+        let config = Arc::new(config);
+        let (port, server) = loopback_server(
+            2,
+            move |stream| {
+                let connection = rustls::ServerConnection::new(Arc::clone(&config)).unwrap();
+                rustls::StreamOwned::new(connection, stream)
+            },
+            |method, _params| match method {
+                "blockchain.relayfee" => vec![Line::Reply(json!(0.00001))],
+                _ => vec![Line::Reply(Value::Null)],
+            },
+        );
+
+        // The public root set does not vouch for a self-signed certificate.
+        let connector = TlsElectrumConnector::new("localhost", port).unwrap();
+        let err = connector.connect().err().unwrap();
+        assert!(
+            err.starts_with(&format!("TLS handshake with ssl://localhost:{port} failed")),
+            "{err}"
+        );
+
+        let pinned = TlsElectrumConnector::with_pinned_certificates(
+            "localhost",
+            port,
+            &[CERTIFICATE.to_vec()],
+        )
+        .unwrap();
+        let backend = ElectrumBackend::new(Box::new(pinned));
+        backend.ping().unwrap();
+        assert_eq!(backend.relay_fee_per_kb(), Ok(1_000));
+        drop(backend);
+
+        let requests = server.join().unwrap();
+        let methods: Vec<_> = requests.iter().map(|(method, _)| method.as_str()).collect();
+        assert_eq!(
+            methods,
+            ["server.version", "server.ping", "blockchain.relayfee"]
+        );
+    }
+}
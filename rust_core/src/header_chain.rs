@@ -0,0 +1,865 @@
+//! SPV header chain.
+//!
+//! Keeps the chain of Reddcoin block headers from a trusted anchor to the tip so transactions can
+//! be anchored to headers the wallet validated itself instead of to a server's word. Headers are
+//! the standard 80-byte format; the block hash is their double SHA-256.
+//!
+//! What a header-only client can check, it checks: every header links to its parent, its
+//! timestamp is above the median of the previous eleven and not too far in the future, its
+//! `bits` are exactly what the PoSV retarget ([`next_target`]) demands after its parents,
+//! proof-of-work era headers hash below their target, and configured checkpoints are matched.
+//! Among competing branches the one with the most work wins, which also handles reorgs.
+//!
+//! What it cannot check is the PoSV kernel: whether a block's stake was valid depends on the
+//! staked output, which only a full node has. The chain therefore starts from a trusted anchor
+//! (a recent checkpoint shipped with the app) and is best synced against cross-checked backends;
+//! from there a forger would need to outpace the honest chain's work to mislead the wallet.
+
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+use bitcoin::block::Header;
+use bitcoin::hashes::{hmac, sha256, Hash, HashEngine};
+use bitcoin::pow::{CompactTarget, Target, Work};
+use bitcoin::BlockHash;
+use serde::{Deserialize, Serialize};
+
+use crate::chain_params::ChainParams;
+
+/// Headers timestamped further than this ahead of local time are rejected.
+pub const MAX_FUTURE_BLOCK_TIME_SECS: u32 = 2 * 60 * 60;
+/// Headers whose timestamps form the median a new header must exceed.
+const MEDIAN_TIME_SPAN: usize = 11;
+/// Encoded size of one header.
+pub const HEADER_SIZE: usize = 80;
+/// Headers requested per round trip while syncing.
+const SYNC_BATCH: u32 = 2016;
+/// Identifies the compact store format.
+const STORE_MAGIC: &[u8; 4] = b"RDHC";
+const STORE_VERSION: u8 = 1;
+
+/// The easiest target any Reddcoin block may claim (`~0 >> 20`, the scrypt-era limit). PoSV
+/// targets are always harder, so this bound never rejects a valid header.
+pub fn target_limit() -> Target {
+    let mut bytes = [0xff; 32];
+    bytes[0] = 0;
+    bytes[1] = 0;
+    bytes[2] = 0x0f;
+    Target::from_be_bytes(bytes)
+}
+
+/// The `bits` Reddcoin's `GetNextTargetRequired` demands of the block at `height`, given its
+/// parent and grandparent.
+///
+/// PoSV retargets every block, moving the parent's target toward the target spacing by an
+/// exponential moving average over `target_timespan_secs`. The first two proof-of-stake blocks
+/// have no proof-of-stake predecessors to average and use the limit. Proof-of-work blocks
+/// followed different rules and are not covered; see [`ChainParams::last_pow_height`].
+pub fn next_target(
+    height: u32,
+    parent: &Header,
+    grandparent: &Header,
+    params: &ChainParams,
+) -> CompactTarget {
+    let limit = target_limit();
+    if height <= params.last_pow_height.saturating_add(2) {
+        return limit.to_compact_lossy();
+    }
+    let spacing = i64::from(params.target_spacing_secs);
+    let mut actual = i64::from(parent.time) - i64::from(grandparent.time);
+    if actual < 0 {
+        actual = spacing;
+    }
+    let interval = i64::from(params.target_timespan_secs) / spacing;
+    // Both factors are positive and far below 2^63.
+    let numerator = ((interval - 1) * spacing + 2 * actual) as u64;
+    let denominator = ((interval + 1) * spacing) as u64;
+
+    // Core computes this in 256-bit integers that wrap on overflow; so does this.
+    let mut limbs = target_limbs(Target::from_compact(parent.bits));
+    let mut carry = 0u128;
+    for limb in limbs.iter_mut().rev() {
+        let product = u128::from(*limb) * u128::from(numerator) + carry;
+        *limb = product as u64;
+        carry = product >> 64;
+    }
+    let mut remainder = 0u128;
+    for limb in limbs.iter_mut() {
+        let dividend = (remainder << 64) | u128::from(*limb);
+        *limb = (dividend / u128::from(denominator)) as u64;
+        remainder = dividend % u128::from(denominator);
+    }
+    let target = limbs_target(limbs);
+    if target == Target::ZERO || target > limit {
+        limit.to_compact_lossy()
+    } else {
+        target.to_compact_lossy()
+    }
+}
+
+/// Whether the scrypt proof-of-work hash of `header` meets its own target, as every block up
+/// to [`ChainParams::last_pow_height`] must.
+pub fn meets_pow_target(header: &Header) -> bool {
+    let serialized = bitcoin::consensus::serialize(header);
+    let mut pow_hash = [0; 32];
+    scrypt(&serialized, &serialized, 1024, &mut pow_hash);
+    header
+        .target()
+        .is_met_by(BlockHash::from_byte_array(pow_hash))
+}
+
+/// A block the chain must contain at `height`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub height: u32,
+    /// Block hash in the usual display (RPC) byte order.
+    pub hash: String,
+}
+
+/// What [`HeaderChain::connect`] did with a batch of headers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "outcome", rename_all = "snake_case")]
+pub enum ConnectOutcome {
+    /// Every header was already in the chain.
+    Unchanged,
+    /// The headers extended the tip.
+    Extended { tip_height: u32 },
+    /// The headers formed a branch with more work; the chain switched to it.
+    Reorganized {
+        /// Height of the last block both branches share.
+        fork_height: u32,
+        /// Hashes of the headers that were replaced, lowest first.
+        disconnected: Vec<String>,
+        tip_height: u32,
+    },
+    /// The headers formed a valid branch with no more work than the current one and were
+    /// ignored.
+    Stale { fork_height: u32 },
+}
+
+/// Result of [`HeaderChain::sync`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SyncReport {
+    pub start_height: u32,
+    pub tip_height: u32,
+    /// Every reorganization applied during the sync, in order.
+    pub reorgs: Vec<ConnectOutcome>,
+}
+
+/// A source of headers by height, such as an Electrum server or a node.
+pub trait HeaderSource {
+    /// Height of the source's best chain.
+    fn header_tip_height(&self) -> Result<u32, String>;
+
+    /// Up to `count` consecutive headers starting at `start_height`. Fewer, or none, past the
+    /// source's tip.
+    fn headers(&self, start_height: u32, count: u32) -> Result<Vec<Header>, String>;
+}
+
+/// Validated headers from an anchor to the best tip.
+#[derive(Debug, Clone)]
+pub struct HeaderChain {
+    anchor_height: u32,
+    /// `headers[i]` is at height `anchor_height + i`.
+    headers: Vec<Header>,
+    heights: HashMap<BlockHash, u32>,
+    checkpoints: BTreeMap<u32, BlockHash>,
+}
+
+impl HeaderChain {
+    /// Starts a chain at `anchor`, which is trusted as-is, at `anchor_height`.
+    ///
+    /// The anchor must match any checkpoint at its height.
+    pub fn new(
+        anchor: Header,
+        anchor_height: u32,
+        checkpoints: &[Checkpoint],
+    ) -> Result<Self, String> {
+        let checkpoints = checkpoints
+            .iter()
+            .map(|checkpoint| {
+                BlockHash::from_str(&checkpoint.hash)
+                    .map(|hash| (checkpoint.height, hash))
+                    .map_err(|e| format!("invalid checkpoint hash at {}: {e}", checkpoint.height))
+            })
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
+        if let Some(expected) = checkpoints.get(&anchor_height) {
+            if *expected != anchor.block_hash() {
+                return Err(format!(
+                    "anchor {} does not match the checkpoint at height {anchor_height}",
+                    anchor.block_hash()
+                ));
+            }
+        }
+        Ok(Self {
+            anchor_height,
+            heights: HashMap::from([(anchor.block_hash(), anchor_height)]),
+            headers: vec![anchor],
+            checkpoints,
+        })
+    }
+
+    pub fn anchor_height(&self) -> u32 {
+        self.anchor_height
+    }
+
+    pub fn tip_height(&self) -> u32 {
+        self.anchor_height + self.headers.len() as u32 - 1
+    }
+
+    pub fn tip(&self) -> &Header {
+        self.headers
+            .last()
+            .expect("the chain always holds its anchor")
+    }
+
+    /// The header at `height` on the best chain, if synced that far.
+    pub fn header_at(&self, height: u32) -> Option<&Header> {
+        let index = height.checked_sub(self.anchor_height)?;
+        self.headers.get(index as usize)
+    }
+
+    /// Height of `hash` if it is on the best chain.
+    pub fn height_of(&self, hash: &BlockHash) -> Option<u32> {
+        self.heights.get(hash).copied()
+    }
+
+    /// Confirmations of a block at `height` on the best chain, 0 above the tip.
+    pub fn confirmations(&self, height: u32) -> u32 {
+        (self.tip_height() + 1).saturating_sub(height)
+    }
+
+    /// Block locator for `getheaders`: the tip, then hashes stepping back exponentially, ending
+    /// with the anchor. Lets a peer find the fork point in a handful of hashes.
+    pub fn locator(&self) -> Vec<BlockHash> {
+        let mut locator = Vec::new();
+        let mut index = self.headers.len() - 1;
+        let mut step = 1;
+        loop {
+            locator.push(self.headers[index].block_hash());
+            if index == 0 {
+                return locator;
+            }
+            if locator.len() >= 10 {
+                step *= 2;
+            }
+            index = index.saturating_sub(step);
+        }
+    }
+
+    /// Validates `headers` (consecutive, lowest first) and adds them to the chain.
+    ///
+    /// The first header's parent must already be on the best chain. Headers the chain already
+    /// holds are skipped; anything after them is a new branch, which replaces the current one
+    /// only if it has more work. `now` is the local Unix time for the future-timestamp rule.
+    pub fn connect(&mut self, headers: &[Header], now: u32) -> Result<ConnectOutcome, String> {
+        let Some(first) = headers.first() else {
+            return Ok(ConnectOutcome::Unchanged);
+        };
+        let parent_height = self.height_of(&first.prev_blockhash).ok_or_else(|| {
+            format!(
+                "header {} does not connect to the chain",
+                first.block_hash()
+            )
+        })?;
+
+        let known = headers
+            .iter()
+            .zip(parent_height + 1..)
+            .take_while(|(header, height)| {
+                self.header_at(*height)
+                    .is_some_and(|ours| ours.block_hash() == header.block_hash())
+            })
+            .count();
+        let branch = &headers[known..];
+        if branch.is_empty() {
+            return Ok(ConnectOutcome::Unchanged);
+        }
+        let fork_height = parent_height + known as u32;
+
+        // Timestamp context: the best chain up to the fork, then the branch itself.
+        let fork_index = (fork_height - self.anchor_height) as usize;
+        let context_start = (fork_index + 1).saturating_sub(MEDIAN_TIME_SPAN);
+        let mut recent: Vec<u32> = self.headers[context_start..=fork_index]
+            .iter()
+            .map(|header| header.time)
+            .collect();
+        let mut parent = self.headers[fork_index];
+        let mut grandparent = fork_index.checked_sub(1).map(|index| self.headers[index]);
+        for (header, height) in branch.iter().zip(fork_height + 1..) {
+            self.validate(header, height, &parent, grandparent.as_ref(), &recent, now)?;
+            grandparent = Some(parent);
+            parent = *header;
+            recent.push(header.time);
+            if recent.len() > MEDIAN_TIME_SPAN {
+                recent.remove(0);
+            }
+        }
+
+        let replaced = &self.headers[fork_index + 1..];
+        if !replaced.is_empty() && branch_work(branch) <= branch_work(replaced) {
+            // Ties go to the branch seen first, as nodes do.
+            return Ok(ConnectOutcome::Stale { fork_height });
+        }
+
+        let disconnected: Vec<Header> = self.headers.drain(fork_index + 1..).collect();
+        for header in &disconnected {
+            self.heights.remove(&header.block_hash());
+        }
+        for (header, height) in branch.iter().zip(fork_height + 1..) {
+            self.heights.insert(header.block_hash(), height);
+            self.headers.push(*header);
+        }
+
+        let tip_height = self.tip_height();
+        Ok(if disconnected.is_empty() {
+            ConnectOutcome::Extended { tip_height }
+        } else {
+            ConnectOutcome::Reorganized {
+                fork_height,
+                disconnected: disconnected
+                    .iter()
+                    .map(|header| header.block_hash().to_string())
+                    .collect(),
+                tip_height,
+            }
+        })
+    }
+
+    /// Header-only consensus checks for `header` at `height` on top of `parent`.
+    ///
+    /// The retarget rule needs the grandparent too, so it is skipped only for the header right
+    /// after the anchor.
+    fn validate(
+        &self,
+        header: &Header,
+        height: u32,
+        parent: &Header,
+        grandparent: Option<&Header>,
+        recent_times: &[u32],
+        now: u32,
+    ) -> Result<(), String> {
+        let hash = header.block_hash();
+        let previous = parent.block_hash();
+        if header.prev_blockhash != previous {
+            return Err(format!(
+                "header {hash} at height {height} does not link to {previous}"
+            ));
+        }
+        if let Some(expected) = self.checkpoints.get(&height) {
+            if *expected != hash {
+                return Err(format!(
+                    "header {hash} conflicts with the checkpoint {expected} at height {height}"
+                ));
+            }
+        }
+        let mut sorted = recent_times.to_vec();
+        sorted.sort_unstable();
+        let median_time_past = sorted[sorted.len() / 2];
+        if header.time <= median_time_past {
+            return Err(format!(
+                "header {hash} at height {height} has time {} at or before the median time \
+                 {median_time_past} of its predecessors",
+                header.time
+            ));
+        }
+        if header.time > now.saturating_add(MAX_FUTURE_BLOCK_TIME_SECS) {
+            return Err(format!(
+                "header {hash} at height {height} is timestamped {} seconds in the future",
+                header.time - now
+            ));
+        }
+        if header.target() > target_limit() || header.target() == Target::ZERO {
+            return Err(format!(
+                "header {hash} at height {height} claims invalid target bits {:#010x}",
+                header.bits.to_consensus()
+            ));
+        }
+        let params = ChainParams::MAINNET;
+        if height <= params.last_pow_height {
+            if !meets_pow_target(header) {
+                return Err(format!(
+                    "header {hash} at height {height} does not meet its proof-of-work target"
+                ));
+            }
+        } else if let Some(grandparent) = grandparent {
+            let expected = next_target(height, parent, grandparent, &params);
+            if header.bits != expected {
+                return Err(format!(
+                    "header {hash} at height {height} has bits {:#010x}, the retarget requires \
+                     {:#010x}",
+                    header.bits.to_consensus(),
+                    expected.to_consensus()
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Brings the chain up to `source`'s tip.
+    ///
+    /// When the source's next header does not link to the local tip, the chain has diverged from
+    /// the source's: earlier headers are re-requested with a doubling look-back until they link,
+    /// and the source's branch is adopted if it has more work.
+    pub fn sync(&mut self, source: &dyn HeaderSource, now: u32) -> Result<SyncReport, String> {
+        let start_height = self.tip_height();
+        let mut reorgs = Vec::new();
+        let mut lookback = 0u32;
+        loop {
+            let remote_height = source.header_tip_height()?;
+            let start = (self.tip_height() + 1)
+                .saturating_sub(lookback)
+                .max(self.anchor_height + 1);
+            if start > remote_height {
+                // The source is not ahead of us. It may still be on a shorter branch with more
+                // work, so compare its tip with ours at that height before stopping.
+                if remote_height <= self.anchor_height {
+                    break;
+                }
+                let remote_tip = source.headers(remote_height, 1)?;
+                let ours = self.header_at(remote_height).map(Header::block_hash);
+                if remote_tip.first().map(Header::block_hash) == ours {
+                    break;
+                }
+                lookback = self.tip_height() + 1 - remote_height;
+                continue;
+            }
+            let headers = source.headers(start, SYNC_BATCH.min(remote_height - start + 1))?;
+            let Some(first) = headers.first() else {
+                break;
+            };
+            if self.height_of(&first.prev_blockhash) != Some(start - 1) {
+                if start == self.anchor_height + 1 {
+                    return Err(format!(
+                        "{} is not on the source's chain",
+                        self.headers[0].block_hash()
+                    ));
+                }
+                lookback = lookback.saturating_mul(2).max(1);
+                continue;
+            }
+            match self.connect(&headers, now)? {
+                ConnectOutcome::Extended { .. } => lookback = 0,
+                reorg @ ConnectOutcome::Reorganized { .. } => {
+                    reorgs.push(reorg);
+                    lookback = 0;
+                }
+                // Everything re-requested is already ours: continue from the tip, or stop if we
+                // were already there.
+                ConnectOutcome::Unchanged if lookback > 0 => lookback = 0,
+                ConnectOutcome::Unchanged | ConnectOutcome::Stale { .. } => break,
+            }
+        }
+        Ok(SyncReport {
+            start_height,
+            tip_height: self.tip_height(),
+            reorgs,
+        })
+    }
+
+    /// Encodes the chain compactly: a small header, the anchor height, then the raw 80-byte
+    /// headers. Checkpoints are configuration and are not stored.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(13 + self.headers.len() * HEADER_SIZE);
+        bytes.extend_from_slice(STORE_MAGIC);
+        bytes.push(STORE_VERSION);
+        bytes.extend_from_slice(&self.anchor_height.to_le_bytes());
+        bytes.extend_from_slice(&(self.headers.len() as u32).to_le_bytes());
+        for header in &self.headers {
+            bytes.extend_from_slice(&bitcoin::consensus::serialize(header));
+        }
+        bytes
+    }
+
+    /// Decodes a chain written by [`Self::to_bytes`], re-validating every header against
+    /// `checkpoints` so a tampered store is rejected rather than trusted.
+    pub fn from_bytes(bytes: &[u8], checkpoints: &[Checkpoint]) -> Result<Self, String> {
+        let body = bytes
+            .strip_prefix(STORE_MAGIC.as_slice())
+            .ok_or_else(|| "not a header store".to_string())?;
+        let (&version, body) = body
+            .split_first()
+            .ok_or_else(|| "truncated header store".to_string())?;
+        if version != STORE_VERSION {
+            return Err(format!("unsupported header store version {version}"));
+        }
+        if body.len() < 8 {
+            return Err("truncated header store".to_string());
+        }
+        let anchor_height = u32::from_le_bytes(body[0..4].try_into().expect("4 bytes"));
+        let count = u32::from_le_bytes(body[4..8].try_into().expect("4 bytes")) as usize;
+        let raw = &body[8..];
+        if count == 0 || count.checked_mul(HEADER_SIZE) != Some(raw.len()) {
+            return Err("header store length does not match its header count".to_string());
+        }
+
+        let headers = raw
+            .chunks_exact(HEADER_SIZE)
+            .map(|chunk| {
+                bitcoin::consensus::deserialize::<Header>(chunk)
+                    .map_err(|e| format!("corrupt header in store: {e}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut chain = Self::new(headers[0], anchor_height, checkpoints)?;
+        // Stored headers were valid when they arrived; only the clock-dependent rule is skipped.
+        chain.connect(&headers[1..], u32::MAX)?;
+        Ok(chain)
+    }
+}
+
+/// Decodes concatenated 80-byte headers from hex, as Electrum and Core return them.
+pub fn parse_headers_hex(headers_hex: &str) -> Result<Vec<Header>, String> {
+    let raw = hex::decode(headers_hex.trim()).map_err(|e| format!("invalid header hex: {e}"))?;
+    if raw.len() % HEADER_SIZE != 0 {
+        return Err(format!(
+            "header data of {} bytes is not a multiple of 80",
+            raw.len()
+        ));
+    }
+    raw.chunks_exact(HEADER_SIZE)
+        .map(|chunk| {
+            bitcoin::consensus::deserialize(chunk).map_err(|e| format!("invalid header: {e}"))
+        })
+        .collect()
+}
+
+fn branch_work(headers: &[Header]) -> Work {
+    headers
+        .iter()
+        .map(|header| header.work())
+        .fold(Work::from_be_bytes([0; 32]), |total, work| total + work)
+}
+
+/// `target` as four big-endian 64-bit limbs.
+fn target_limbs(target: Target) -> [u64; 4] {
+    let bytes = target.to_be_bytes();
+    std::array::from_fn(|i| {
+        u64::from_be_bytes(bytes[i * 8..i * 8 + 8].try_into().expect("8 bytes"))
+    })
+}
+
+fn limbs_target(limbs: [u64; 4]) -> Target {
+    let mut bytes = [0; 32];
+    for (chunk, limb) in bytes.chunks_exact_mut(8).zip(limbs) {
+        chunk.copy_from_slice(&limb.to_be_bytes());
+    }
+    Target::from_be_bytes(bytes)
+}
+
+/// scrypt (RFC 7914) with `r = 1, p = 1`, the parameters of Reddcoin's proof of work.
+fn scrypt(password: &[u8], salt: &[u8], cost: usize, output: &mut [u8]) {
+    let mut block = [0u8; 128];
+    pbkdf2_sha256(password, salt, &mut block);
+    let mut x: [u32; 32] = std::array::from_fn(|i| {
+        u32::from_le_bytes(block[i * 4..i * 4 + 4].try_into().expect("4 bytes"))
+    });
+    let mut table = Vec::with_capacity(cost);
+    for _ in 0..cost {
+        table.push(x);
+        block_mix(&mut x);
+    }
+    for _ in 0..cost {
+        let j = x[16] as usize % cost;
+        for (word, mixed) in x.iter_mut().zip(table[j]) {
+            *word ^= mixed;
+        }
+        block_mix(&mut x);
+    }
+    for (chunk, word) in block.chunks_exact_mut(4).zip(x) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    pbkdf2_sha256(password, &block, output);
+}
+
+/// PBKDF2-HMAC-SHA256 with a single iteration, all scrypt needs.
+fn pbkdf2_sha256(password: &[u8], salt: &[u8], output: &mut [u8]) {
+    for (index, chunk) in (1u32..).zip(output.chunks_mut(32)) {
+        let mut engine = hmac::HmacEngine::<sha256::Hash>::new(password);
+        engine.input(salt);
+        engine.input(&index.to_be_bytes());
+        let block = hmac::Hmac::from_engine(engine).to_byte_array();
+        chunk.copy_from_slice(&block[..chunk.len()]);
+    }
+}
+
+/// scrypt's BlockMix for `r = 1`: two 64-byte halves through Salsa20/8.
+fn block_mix(block: &mut [u32; 32]) {
+    let mut x: [u32; 16] = block[16..].try_into().expect("16 words");
+    let mut mixed = [0u32; 32];
+    for half in 0..2 {
+        for (word, input) in x.iter_mut().zip(&block[half * 16..half * 16 + 16]) {
+            *word ^= input;
+        }
+        salsa20_8(&mut x);
+        mixed[half * 16..half * 16 + 16].copy_from_slice(&x);
+    }
+    *block = mixed;
+}
+
+fn salsa20_8(state: &mut [u32; 16]) {
+    fn quarter(x: &mut [u32; 16], a: usize, b: usize, c: usize, d: usize) {
+        x[b] ^= x[a].wrapping_add(x[d]).rotate_left(7);
+        x[c] ^= x[b].wrapping_add(x[a]).rotate_left(9);
+        x[d] ^= x[c].wrapping_add(x[b]).rotate_left(13);
+        x[a] ^= x[d].wrapping_add(x[c]).rotate_left(18);
+    }
+    let mut x = *state;
+    for _ in 0..4 {
+        quarter(&mut x, 0, 4, 8, 12);
+        quarter(&mut x, 5, 9, 13, 1);
+        quarter(&mut x, 10, 14, 2, 6);
+        quarter(&mut x, 15, 3, 7, 11);
+        quarter(&mut x, 0, 1, 2, 3);
+        quarter(&mut x, 5, 6, 7, 4);
+        quarter(&mut x, 10, 11, 8, 9);
+        quarter(&mut x, 15, 12, 13, 14);
+    }
+    for (word, mixed) in state.iter_mut().zip(x) {
+        *word = word.wrapping_add(mixed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::block::Version;
+    use bitcoin::hashes::Hash;
+    use bitcoin::TxMerkleNode;
+
+    use super::*;
+
+    const ANCHOR_HEIGHT: u32 = 3_000_000;
+    const START: u32 = 1_700_000_000;
+    const NOW: u32 = START + 100_000;
+
+    fn header(parent: BlockHash, time: u32, bits: CompactTarget, nonce: u32) -> Header {
+        Header {
+            version: Version::from_consensus(4),
+            prev_blockhash: parent,
+            merkle_root: TxMerkleNode::all_zeros(),
+            time,
+            bits,
+            nonce,
+        }
+    }
+
+    fn anchor() -> Header {
+        header(
+            BlockHash::all_zeros(),
+            START,
+            CompactTarget::from_consensus(0x1d00ffff),
+            0,
+        )
+    }
+
+    /// `count` valid headers `spacing` seconds apart on top of `tail`, whose last header is at
+    /// `height`. `nonce` tells otherwise identical branches apart.
+    fn extend(tail: &[Header], height: u32, count: u32, spacing: u32, nonce: u32) -> Vec<Header> {
+        let mut all = tail.to_vec();
+        for height in height + 1..=height + count {
+            let parent = *all.last().unwrap();
+            let bits = match all.len() {
+                1 => parent.bits,
+                len => next_target(height, &parent, &all[len - 2], &ChainParams::MAINNET),
+            };
+            all.push(header(
+                parent.block_hash(),
+                parent.time + spacing,
+                bits,
+                nonce,
+            ));
+        }
+        all.split_off(tail.len())
+    }
+
+    fn hashes(headers: &[Header]) -> Vec<String> {
+        headers
+            .iter()
+            .map(|header| header.block_hash().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn scrypt_matches_rfc_7914() {
+        let mut output = [0; 64];
+        scrypt(b"", b"", 16, &mut output);
+        assert_eq!(
+            hex::encode(output),
+            "77d6576238657b203b19ca42c18a0497f16b4844e3074ae8dfdffa3fede21442\
+             fcd0069ded0948f8326a753a0fc81f17e8d3e0fb2e0d3628cf35e20c38d18906"
+        );
+    }
+
+    #[test]
+    fn retarget_follows_block_spacing() {
+        let params = ChainParams::MAINNET;
+        let height = ANCHOR_HEIGHT + 2;
+        let grandparent = anchor();
+        let at = |spacing: u32| {
+            let parent = header(
+                grandparent.block_hash(),
+                START + spacing,
+                grandparent.bits,
+                0,
+            );
+            Target::from_compact(next_target(height, &parent, &grandparent, &params))
+        };
+        let steady = Target::from_compact(grandparent.bits);
+
+        assert_eq!(at(60), steady);
+        assert!(at(30) < steady);
+        assert!(at(600) > steady);
+        let limit = Target::from_compact(target_limit().to_compact_lossy());
+        assert_eq!(at(u32::MAX - START), limit);
+
+        // A parent timestamped before its own parent counts as on schedule.
+        let parent = header(grandparent.block_hash(), START - 1, grandparent.bits, 0);
+        assert_eq!(
+            Target::from_compact(next_target(height, &parent, &grandparent, &params)),
+            steady
+        );
+        // The first two proof-of-stake blocks start from the limit.
+        let first_pos = params.last_pow_height + 1;
+        assert_eq!(
+            next_target(first_pos + 1, &parent, &grandparent, &params),
+            target_limit().to_compact_lossy()
+        );
+    }
+
+    #[test]
+    fn connect_checks_linkage_bits_and_pow() {
+        let mut chain = HeaderChain::new(anchor(), ANCHOR_HEIGHT, &[]).unwrap();
+        let headers = extend(&[anchor()], ANCHOR_HEIGHT, 3, 60, 0);
+        assert_eq!(
+            chain.connect(&headers, NOW),
+            Ok(ConnectOutcome::Extended {
+                tip_height: ANCHOR_HEIGHT + 3
+            })
+        );
+        assert_eq!(
+            chain.height_of(&headers[2].block_hash()),
+            Some(ANCHOR_HEIGHT + 3)
+        );
+        assert_eq!(
+            chain.connect(&headers[1..], NOW),
+            Ok(ConnectOutcome::Unchanged)
+        );
+
+        let orphan = header(BlockHash::all_zeros(), NOW, anchor().bits, 1);
+        assert!(chain
+            .connect(&[orphan], NOW)
+            .unwrap_err()
+            .contains("does not connect"));
+
+        let mut broken = extend(&headers, ANCHOR_HEIGHT + 3, 2, 60, 0);
+        broken[1].prev_blockhash = BlockHash::all_zeros();
+        assert!(chain
+            .connect(&broken, NOW)
+            .unwrap_err()
+            .contains("does not link"));
+
+        let mut easier = extend(&headers, ANCHOR_HEIGHT + 3, 1, 60, 0);
+        easier[0].bits = CompactTarget::from_consensus(0x1d01ffff);
+        assert!(chain
+            .connect(&easier, NOW)
+            .unwrap_err()
+            .contains("the retarget requires"));
+        assert_eq!(chain.tip_height(), ANCHOR_HEIGHT + 3);
+
+        // Proof-of-work era headers must hash below their target.
+        let mut chain = HeaderChain::new(anchor(), 100, &[]).unwrap();
+        let unmined = extend(&[anchor()], 100, 1, 60, 0);
+        assert!(chain
+            .connect(&unmined, NOW)
+            .unwrap_err()
+            .contains("does not meet its proof-of-work target"));
+    }
+
+    #[test]
+    fn checkpoints_are_enforced() {
+        let headers = extend(&[anchor()], ANCHOR_HEIGHT, 2, 60, 0);
+        let checkpoints = [Checkpoint {
+            height: ANCHOR_HEIGHT + 2,
+            hash: headers[1].block_hash().to_string(),
+        }];
+        assert!(HeaderChain::new(headers[1], ANCHOR_HEIGHT + 2, &checkpoints).is_ok());
+        assert!(HeaderChain::new(anchor(), ANCHOR_HEIGHT + 2, &checkpoints)
+            .unwrap_err()
+            .contains("does not match the checkpoint"));
+
+        let mut chain = HeaderChain::new(anchor(), ANCHOR_HEIGHT, &checkpoints).unwrap();
+        let other = extend(&[anchor()], ANCHOR_HEIGHT, 2, 60, 1);
+        assert!(chain
+            .connect(&other, NOW)
+            .unwrap_err()
+            .contains("conflicts with the checkpoint"));
+        assert!(chain.connect(&headers, NOW).is_ok());
+    }
+
+    #[test]
+    fn reorgs_to_the_branch_with_more_work() {
+        let mut chain = HeaderChain::new(anchor(), ANCHOR_HEIGHT, &[]).unwrap();
+        let main = extend(&[anchor()], ANCHOR_HEIGHT, 2, 60, 0);
+        chain.connect(&main, NOW).unwrap();
+
+        let longer = extend(&[anchor()], ANCHOR_HEIGHT, 3, 60, 1);
+        assert_eq!(
+            chain.connect(&longer, NOW),
+            Ok(ConnectOutcome::Reorganized {
+                fork_height: ANCHOR_HEIGHT,
+                disconnected: hashes(&main),
+                tip_height: ANCHOR_HEIGHT + 3,
+            })
+        );
+        assert_eq!(chain.height_of(&main[0].block_hash()), None);
+        assert_eq!(chain.tip().block_hash(), longer[2].block_hash());
+
+        // Equal work: the branch seen first stays.
+        let rival = extend(&longer[..1], ANCHOR_HEIGHT + 1, 2, 60, 2);
+        assert_eq!(
+            chain.connect(&rival, NOW),
+            Ok(ConnectOutcome::Stale {
+                fork_height: ANCHOR_HEIGHT + 1
+            })
+        );
+        assert_eq!(chain.tip().block_hash(), longer[2].block_hash());
+    }
+
+    #[test]
+    fn store_round_trips_and_rejects_tampering() {
+        let mut chain = HeaderChain::new(anchor(), ANCHOR_HEIGHT, &[]).unwrap();
+        let headers = extend(&[anchor()], ANCHOR_HEIGHT, 3, 60, 0);
+        chain.connect(&headers, NOW).unwrap();
+        let bytes = chain.to_bytes();
+
+        let restored = HeaderChain::from_bytes(&bytes, &[]).unwrap();
+        assert_eq!(restored.anchor_height(), ANCHOR_HEIGHT);
+        assert_eq!(restored.tip_height(), ANCHOR_HEIGHT + 3);
+        assert_eq!(restored.tip(), chain.tip());
+
+        let mut huge_count = bytes.clone();
+        huge_count[9..13].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(HeaderChain::from_bytes(&huge_count, &[])
+            .unwrap_err()
+            .contains("does not match its header count"));
+
+        // The tip's `bits`, four bytes before its nonce.
+        let mut tampered = bytes.clone();
+        let bits = tampered.len() - 8;
+        tampered[bits] ^= 1;
+        assert!(HeaderChain::from_bytes(&tampered, &[])
+            .unwrap_err()
+            .contains("the retarget requires"));
+
+        let checkpoint = Checkpoint {
+            height: ANCHOR_HEIGHT + 1,
+            hash: "00".repeat(32),
+        };
+        assert!(HeaderChain::from_bytes(&bytes, &[checkpoint])
+            .unwrap_err()
+            .contains("conflicts with the checkpoint"));
+        assert_eq!(
+            HeaderChain::from_bytes(b"XXXX", &[]).unwrap_err(),
+            "not a header store"
+        );
+    }
+}
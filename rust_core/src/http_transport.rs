@@ -0,0 +1,451 @@
+//! Blocking HTTP/1.1 transport for chain backends.
+//!
+//! Backends talk HTTP through the [`HttpTransport`] trait rather than a specific client so the
+//! platform layer can supply its own (the mobile apps already ship a TLS stack) and tests can
+//! point at a local server. [`StdHttpTransport`] is the built-in implementation: plain `http://`
+//! over `std::net`, enough for a self-hosted node on the LAN, a local Tor proxy or a test
+//! fixture server. It deliberately refuses `https://` rather than silently downgrading.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+/// Default connect, read and write timeout, matching the Dart client.
+pub const DEFAULT_HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+/// Responses larger than this are refused rather than buffered.
+const MAX_RESPONSE_BYTES: usize = 32 * 1024 * 1024;
+
+/// An HTTP request as the backends build it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpRequest {
+    pub method: &'static str,
+    pub url: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl HttpRequest {
+    /// A `GET` request accepting JSON.
+    pub fn get(url: String) -> Self {
+        Self {
+            method: "GET",
+            url,
+            headers: vec![("Accept".to_string(), "application/json".to_string())],
+            body: Vec::new(),
+        }
+    }
+
+    /// A `POST` request with `body` of `content_type`, accepting JSON.
+    pub fn post(url: String, content_type: &str, body: Vec<u8>) -> Self {
+        let mut request = Self::get(url);
+        request.method = "POST";
+        request
+            .headers
+            .push(("Content-Type".to_string(), content_type.to_string()));
+        request.body = body;
+        request
+    }
+
+    /// Adds a header.
+    pub fn header(mut self, name: &str, value: String) -> Self {
+        self.headers.push((name.to_string(), value));
+        self
+    }
+}
+
+/// Status and body of an HTTP response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub body: Vec<u8>,
+}
+
+/// Sends HTTP requests on behalf of a chain backend.
+///
+/// An `Err` means no response was received (connection, TLS or timeout failure); any status
+/// code, including errors, is an `Ok` so the backend can decide whether to retry.
+pub trait HttpTransport: Send + Sync {
+    fn send(&self, request: &HttpRequest) -> Result<HttpResponse, String>;
+}
+
+/// Plain-HTTP transport over `std::net`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StdHttpTransport {
+    pub timeout: Duration,
+}
+
+impl Default for StdHttpTransport {
+    fn default() -> Self {
+        Self {
+            timeout: DEFAULT_HTTP_TIMEOUT,
+        }
+    }
+}
+
+impl HttpTransport for StdHttpTransport {
+    fn send(&self, request: &HttpRequest) -> Result<HttpResponse, String> {
+        let url = ParsedUrl::parse(&request.url)?;
+
+        let address = (url.host.as_str(), url.port)
+            .to_socket_addrs()
+            .map_err(|e| format!("cannot resolve {}: {e}", url.host))?
+            .next()
+            .ok_or_else(|| format!("{} resolved to no addresses", url.host))?;
+        let mut stream = TcpStream::connect_timeout(&address, self.timeout)
+            .map_err(|e| format!("cannot connect to {}:{}: {e}", url.host, url.port))?;
+        stream
+            .set_read_timeout(Some(self.timeout))
+            .and_then(|()| stream.set_write_timeout(Some(self.timeout)))
+            .map_err(|e| format!("cannot set socket timeout: {e}"))?;
+
+        let mut head = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\nContent-Length: {}\r\n",
+            request.method,
+            url.path,
+            url.host_header(),
+            request.body.len()
+        );
+        for (name, value) in &request.headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+        head.push_str("\r\n");
+        stream
+            .write_all(head.as_bytes())
+            .and_then(|()| stream.write_all(&request.body))
+            .map_err(|e| format!("failed to send request: {e}"))?;
+
+        read_response(BufReader::new(stream))
+    }
+}
+
+/// The parts of an `http://` URL the transport needs.
+struct ParsedUrl {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl ParsedUrl {
+    fn parse(url: &str) -> Result<Self, String> {
+        if url.starts_with("https://") {
+            return Err(format!(
+                "{url} needs a TLS transport; StdHttpTransport only speaks plain http://"
+            ));
+        }
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| format!("unsupported URL {url}: expected http://"))?;
+        let (authority, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index..]),
+            None => (rest, "/"),
+        };
+        let parse_port = |port: &str| {
+            port.parse::<u16>()
+                .map_err(|_| format!("invalid port in URL {url}"))
+        };
+        let (host, port) = if let Some(bracketed) = authority.strip_prefix('[') {
+            let (host, after) = bracketed
+                .split_once(']')
+                .ok_or_else(|| format!("unterminated IPv6 host in URL {url}"))?;
+            match after.strip_prefix(':') {
+                Some(port) => (host, parse_port(port)?),
+                None if after.is_empty() => (host, 80),
+                None => return Err(format!("invalid host in URL {url}")),
+            }
+        } else {
+            match authority.split_once(':') {
+                Some((host, port)) => (host, parse_port(port)?),
+                None => (authority, 80),
+            }
+        };
+        if host.is_empty() {
+            return Err(format!("URL {url} has no host"));
+        }
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    fn host_header(&self) -> String {
+        if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        }
+    }
+}
+
+/// Reads a complete response, honoring `Content-Length` and chunked encoding.
+fn read_response<R: BufRead>(mut reader: R) -> Result<HttpResponse, String> {
+    let mut status_line = String::new();
+    reader
+        .read_line(&mut status_line)
+        .map_err(|e| format!("failed to read response: {e}"))?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse::<u16>().ok())
+        .ok_or_else(|| format!("malformed HTTP status line {:?}", status_line.trim_end()))?;
+
+    let mut content_length = None;
+    let mut chunked = false;
+    loop {
+        let mut line = String::new();
+        reader
+            .read_line(&mut line)
+            .map_err(|e| format!("failed to read response headers: {e}"))?;
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            let value = value.trim();
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = Some(
+                    value
+                        .parse::<usize>()
+                        .map_err(|_| format!("invalid Content-Length {value:?}"))?,
+                );
+            } else if name.eq_ignore_ascii_case("transfer-encoding") {
+                chunked = value.eq_ignore_ascii_case("chunked");
+            }
+        }
+    }
+
+    let body = if chunked {
+        read_chunked(&mut reader)?
+    } else if let Some(length) = content_length {
+        if length > MAX_RESPONSE_BYTES {
+            return Err(format!("response of {length} bytes is too large"));
+        }
+        let mut body = vec![0; length];
+        reader
+            .read_exact(&mut body)
+            .map_err(|e| format!("failed to read response body: {e}"))?;
+        body
+    } else {
+        let mut body = Vec::new();
+        reader
+            .take(MAX_RESPONSE_BYTES as u64 + 1)
+            .read_to_end(&mut body)
+            .map_err(|e| format!("failed to read response body: {e}"))?;
+        if body.len() > MAX_RESPONSE_BYTES {
+            return Err("response is too large".to_string());
+        }
+        body
+    };
+
+    Ok(HttpResponse { status, body })
+}
+
+fn read_chunked<R: BufRead>(reader: &mut R) -> Result<Vec<u8>, String> {
+    let mut body = Vec::new();
+    loop {
+        let mut size_line = String::new();
+        reader
+            .read_line(&mut size_line)
+            .map_err(|e| format!("failed to read chunk size: {e}"))?;
+        let size_hex = size_line.trim().split(';').next().unwrap_or_default();
+        let size = usize::from_str_radix(size_hex, 16)
+            .map_err(|_| format!("invalid chunk size {size_hex:?}"))?;
+        if size == 0 {
+            return Ok(body);
+        }
+        if body
+            .len()
+            .checked_add(size)
+            .is_none_or(|total| total > MAX_RESPONSE_BYTES)
+        {
+            return Err("response is too large".to_string());
+        }
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader
+            .read_exact(&mut body[start..])
+            .map_err(|e| format!("failed to read chunk: {e}"))?;
+        let mut crlf = [0; 2];
+        reader
+            .read_exact(&mut crlf)
+            .map_err(|e| format!("failed to read chunk terminator: {e}"))?;
+    }
+}
+
+/// A one-shot HTTP server for backend tests: answers each connection with the next canned
+/// response and records what was asked.
+#[cfg(test)]
+pub(crate) mod test_server {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread::JoinHandle;
+
+    /// A request as the server received it.
+    #[derive(Debug, Clone)]
+    pub(crate) struct RecordedRequest {
+        pub method: String,
+        pub path: String,
+        pub headers: Vec<(String, String)>,
+        pub body: Vec<u8>,
+    }
+
+    impl RecordedRequest {
+        pub fn header(&self, name: &str) -> Option<&str> {
+            self.headers
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(name))
+                .map(|(_, value)| value.as_str())
+        }
+    }
+
+    pub(crate) struct TestServer {
+        pub url: String,
+        requests: Arc<Mutex<Vec<RecordedRequest>>>,
+        handle: Option<JoinHandle<()>>,
+    }
+
+    impl TestServer {
+        /// Waits until every canned response was served and returns the requests.
+        pub fn finish(mut self) -> Vec<RecordedRequest> {
+            self.handle.take().unwrap().join().unwrap();
+            self.requests.lock().unwrap().clone()
+        }
+    }
+
+    /// A complete response with `Content-Length`.
+    pub(crate) fn response(status: u16, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {status} Status\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    /// Serves `responses` (raw HTTP) to consecutive connections, one each.
+    pub(crate) fn serve(responses: Vec<String>) -> TestServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let recorded = Arc::clone(&requests);
+        let handle = std::thread::spawn(move || {
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut parts = request_line.split_whitespace();
+                let method = parts.next().unwrap_or_default().to_string();
+                let path = parts.next().unwrap_or_default().to_string();
+                let mut headers = Vec::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    let (name, value) = line.split_once(':').unwrap();
+                    headers.push((name.to_string(), value.trim().to_string()));
+                }
+                let length = headers
+                    .iter()
+                    .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+                    .map_or(0, |(_, value)| value.parse().unwrap());
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                recorded.lock().unwrap().push(RecordedRequest {
+                    method,
+                    path,
+                    headers,
+                    body,
+                });
+                let mut stream = reader.into_inner();
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        TestServer {
+            url,
+            requests,
+            handle: Some(handle),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_server::{response, serve};
+    use super::*;
+
+    fn send(request: HttpRequest) -> Result<HttpResponse, String> {
+        StdHttpTransport::default().send(&request)
+    }
+
+    #[test]
+    fn sends_request_and_reads_content_length_body() {
+        let server = serve(vec![response(201, r#"{"ok":true}"#)]);
+        let request = HttpRequest::post(
+            format!("{}/api/v2/sendtx/", server.url),
+            "text/plain",
+            b"0100".to_vec(),
+        )
+        .header("Authorization", "Basic abc".to_string());
+        let reply = send(request).unwrap();
+        assert_eq!(reply.status, 201);
+        assert_eq!(reply.body, br#"{"ok":true}"#);
+
+        let requests = server.finish();
+        assert_eq!(requests[0].method, "POST");
+        assert_eq!(requests[0].path, "/api/v2/sendtx/");
+        assert_eq!(requests[0].body, b"0100");
+        assert_eq!(requests[0].header("Content-Type"), Some("text/plain"));
+        assert_eq!(requests[0].header("Authorization"), Some("Basic abc"));
+        assert!(requests[0]
+            .header("Host")
+            .unwrap()
+            .starts_with("127.0.0.1:"));
+    }
+
+    #[test]
+    fn reads_chunked_and_close_delimited_bodies() {
+        let server = serve(vec![
+            "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4;ext=1\r\n{\"a\"\r\n3\r\n:1}\r\n0\r\n\r\n"
+                .to_string(),
+            "HTTP/1.1 200 OK\r\n\r\n[1,2]".to_string(),
+        ]);
+        let reply = send(HttpRequest::get(format!("{}/x", server.url))).unwrap();
+        assert_eq!(reply.body, br#"{"a":1}"#);
+        let reply = send(HttpRequest::get(format!("{}/y", server.url))).unwrap();
+        assert_eq!(reply.body, b"[1,2]");
+        server.finish();
+    }
+
+    #[test]
+    fn rejects_oversized_chunks() {
+        let huge = format!("{:x}", usize::MAX);
+        let reply = read_response(
+            format!("HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n1\r\nx\r\n{huge}\r\n")
+                .as_bytes(),
+        );
+        assert_eq!(reply.unwrap_err(), "response is too large");
+    }
+
+    #[test]
+    fn parses_urls_and_refuses_https() {
+        let url = ParsedUrl::parse("http://[::1]:8080/api?x=1").unwrap();
+        assert_eq!(
+            (url.host.as_str(), url.port, url.path.as_str()),
+            ("::1", 8080, "/api?x=1")
+        );
+        assert_eq!(url.host_header(), "[::1]:8080");
+        let url = ParsedUrl::parse("http://node.local").unwrap();
+        assert_eq!((url.port, url.path.as_str()), (80, "/"));
+
+        assert!(ParsedUrl::parse("https://blockbook.reddcoin.com")
+            .err()
+            .unwrap()
+            .contains("needs a TLS transport"));
+        assert!(ParsedUrl::parse("ftp://x").is_err());
+        assert!(ParsedUrl::parse("http://:80/").is_err());
+        assert!(ParsedUrl::parse("http://host:99999/").is_err());
+    }
+}
@@ -0,0 +1,480 @@
+//! Inheritance ("dead man's switch") recovery transactions.
+//!
+//! The owner pre-signs a transaction sweeping the wallet to an heir's address with an
+//! `nLockTime` far in the future. Nodes refuse it until the lock expires, so the heir can hold it
+//! without being able to move anything early. To prove liveness the owner spends one of its
+//! inputs back to themselves, which makes the old recovery transaction a double-spend, and
+//! pre-signs a replacement with a later lock.
+//!
+//! The bundle is plain JSON; the app encrypts it with `vault_crypto` before storing it for the
+//! heir.
+
+use std::str::FromStr;
+
+use bitcoin::absolute::LockTime;
+use bitcoin::secp256k1::{PublicKey, SecretKey};
+use bitcoin::{PublicKey as BitcoinPublicKey, Transaction, TxOut};
+use serde::{Deserialize, Serialize};
+
+use crate::address::ReddcoinAddress;
+use crate::chain_params::ChainParams;
+use crate::secp_context::secp;
+use crate::timelock::{absolute_lock_status, AbsoluteLock, ChainTip, LockStatus};
+use crate::transaction_signer::{
+    ensure_verified, estimate_legacy_tx_fee, order_and_sign, unsigned_inputs, validate_utxos,
+    BuildOptions, SignedTransaction, Utxo, LEGACY_P2PKH_DUST_LIMIT,
+};
+use crate::utxo::maturity_violation;
+
+/// A pre-signed recovery transaction and what the owner needs to invalidate it later.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InheritanceBundle {
+    pub heir_address: String,
+    /// Earliest height or time the heir can broadcast `recovery`.
+    pub lock_time: AbsoluteLock,
+    /// Outpoints spent by `recovery`, as `txid:vout`. Spending any one of them invalidates it.
+    pub inputs: Vec<String>,
+    pub recovery: SignedTransaction,
+}
+
+/// Result of a liveness check-in.
+#[derive(Debug, Clone, Serialize)]
+pub struct LivenessRefresh {
+    /// Spends one input of the previous recovery transaction back to the owner. Must be broadcast:
+    /// the new bundle spends its output, and the old bundle stays valid until it confirms.
+    pub liveness: SignedTransaction,
+    pub bundle: InheritanceBundle,
+}
+
+/// Owner key material shared by both workflows.
+struct OwnerKey {
+    secret_key: SecretKey,
+    pubkey: BitcoinPublicKey,
+}
+
+impl OwnerKey {
+    fn from_hex(private_key_hex: &str) -> Result<Self, String> {
+        if private_key_hex.len() != 64 {
+            return Err("private_key_hex must be exactly 64 hex characters".to_string());
+        }
+        let private_key_raw = hex::decode(private_key_hex)
+            .map_err(|e| format!("private_key_hex decode failed: {e}"))?;
+        let secret_key = SecretKey::from_slice(&private_key_raw)
+            .map_err(|e| format!("invalid secp256k1 private key: {e}"))?;
+        let pubkey = BitcoinPublicKey::new(PublicKey::from_secret_key(secp(), &secret_key));
+        Ok(Self { secret_key, pubkey })
+    }
+}
+
+/// Pre-signs a transaction sweeping every UTXO to `heir_address`, unspendable before `lock`.
+///
+/// A lock that is already satisfied at `tip` is rejected: the heir could broadcast immediately.
+/// `options` must leave `lock_time` unset, since the bundle's lock sets it.
+pub fn create_inheritance_bundle(
+    utxos_json: String,
+    private_key_hex: String,
+    heir_address: String,
+    lock_json: String,
+    fee_per_kb: u64,
+    tip: ChainTip,
+    options: BuildOptions,
+) -> Result<InheritanceBundle, String> {
+    let key = OwnerKey::from_hex(&private_key_hex)?;
+    let utxos: Vec<Utxo> = serde_json::from_str(&utxos_json)
+        .map_err(|e| format!("utxos_json must be a valid JSON array of UTXOs: {e}"))?;
+    let lock: AbsoluteLock =
+        serde_json::from_str(&lock_json).map_err(|e| format!("invalid lock_time: {e}"))?;
+
+    ensure_verified(&utxos, &options)?;
+
+    sign_recovery(&utxos, &key, heir_address, lock, fee_per_kb, &tip, &options)
+}
+
+/// Proves liveness: spends one input of `bundle_json`'s recovery transaction back to the owner's
+/// own address and pre-signs a replacement recovery transaction locked until `new_lock_json`.
+///
+/// `utxos_json` is the wallet's current UTXO set. The smallest still-unspent, mature recovery
+/// input that can pay its own fee is used, keeping the check-in cheap.
+///
+/// `options` applies to both transactions. The liveness output returns to the wallet, so policy
+/// treats it as change rather than a payment.
+#[allow(clippy::too_many_arguments)]
+pub fn refresh_inheritance_bundle(
+    bundle_json: String,
+    utxos_json: String,
+    private_key_hex: String,
+    new_lock_json: String,
+    fee_per_kb: u64,
+    tip: ChainTip,
+    options: BuildOptions,
+) -> Result<LivenessRefresh, String> {
+    let key = OwnerKey::from_hex(&private_key_hex)?;
+    let bundle: InheritanceBundle = serde_json::from_str(&bundle_json)
+        .map_err(|e| format!("invalid inheritance bundle: {e}"))?;
+    let mut utxos: Vec<Utxo> = serde_json::from_str(&utxos_json)
+        .map_err(|e| format!("utxos_json must be a valid JSON array of UTXOs: {e}"))?;
+    let new_lock: AbsoluteLock =
+        serde_json::from_str(&new_lock_json).map_err(|e| format!("invalid lock_time: {e}"))?;
+
+    ensure_verified(&utxos, &options)?;
+
+    let later = match (bundle.lock_time, new_lock) {
+        (AbsoluteLock::Height(old), AbsoluteLock::Height(new)) => new > old,
+        (AbsoluteLock::Time(old), AbsoluteLock::Time(new)) => new > old,
+        _ => false,
+    };
+    if !later {
+        return Err(format!(
+            "new lock {new_lock:?} must be later than {:?} and use the same unit",
+            bundle.lock_time
+        ));
+    }
+
+    let liveness_fee = estimate_legacy_tx_fee(1, 1, fee_per_kb)?;
+    let minimum_value = liveness_fee
+        .checked_add(LEGACY_P2PKH_DUST_LIMIT)
+        .ok_or_else(|| "liveness fee overflowed u64".to_string())?;
+
+    let spent_index = utxos
+        .iter()
+        .enumerate()
+        .filter(|(_, utxo)| bundle.inputs.contains(&outpoint_key(utxo)))
+        .filter(|(_, utxo)| utxo.amount >= minimum_value)
+        // An immature reward would make the liveness transaction consensus-invalid.
+        .filter(|(_, utxo)| {
+            maturity_violation(utxo, &ChainParams::MAINNET, Some(tip.height)).is_none()
+        })
+        .min_by_key(|(_, utxo)| utxo.amount)
+        .map(|(index, _)| index)
+        .ok_or_else(|| {
+            "no mature, unspent input of the current recovery transaction can pay a liveness fee; \
+             if its inputs are already spent it is invalid, so create a new bundle instead"
+                .to_string()
+        })?;
+    let spent = utxos.remove(spent_index);
+
+    let owner_address = ReddcoinAddress::p2pkh(&key.pubkey);
+    let liveness_value = spent
+        .amount
+        .checked_sub(liveness_fee)
+        .expect("input was checked against the liveness fee");
+
+    let tx = Transaction {
+        version: bitcoin::transaction::Version(2),
+        lock_time: LockTime::ZERO,
+        input: unsigned_inputs(std::slice::from_ref(&spent))?,
+        output: vec![TxOut {
+            value: liveness_value.into(),
+            script_pubkey: owner_address.script_pubkey(),
+        }],
+    };
+    let liveness = order_and_sign(
+        tx,
+        Some(0),
+        &options,
+        &key.secret_key,
+        &key.pubkey,
+        spent.amount,
+    )?;
+
+    // The replacement spends the liveness output, so the two can never both confirm.
+    utxos.push(Utxo {
+        txid: liveness.txid.clone(),
+        vout: 0,
+        amount: liveness_value,
+        height: None,
+        confirmations: 0,
+        is_coinbase: false,
+        is_coinstake: false,
+        script_pubkey: Some(owner_address.script_pubkey().to_hex_string()),
+        address: Some(owner_address.to_string()),
+        sequence: None,
+        verified: false,
+    });
+
+    let bundle = sign_recovery(
+        &utxos,
+        &key,
+        bundle.heir_address,
+        new_lock,
+        fee_per_kb,
+        &tip,
+        &options,
+    )?;
+
+    Ok(LivenessRefresh { liveness, bundle })
+}
+
+fn sign_recovery(
+    utxos: &[Utxo],
+    key: &OwnerKey,
+    heir_address: String,
+    lock: AbsoluteLock,
+    fee_per_kb: u64,
+    tip: &ChainTip,
+    options: &BuildOptions,
+) -> Result<InheritanceBundle, String> {
+    if options.lock_time.is_some() {
+        return Err("lock_time cannot be set: the bundle's lock sets it".to_string());
+    }
+    if absolute_lock_status(lock, tip) == LockStatus::Satisfied {
+        return Err(format!(
+            "lock {lock:?} is already satisfied at height {}; the heir could spend immediately",
+            tip.height
+        ));
+    }
+
+    let total_input_amount = validate_utxos(utxos)?;
+    let heir_script = ReddcoinAddress::from_str(&heir_address)
+        .map_err(|e| format!("invalid heir_address: {e}"))?
+        .script_pubkey();
+
+    let fee = estimate_legacy_tx_fee(utxos.len(), 1, fee_per_kb)?;
+    let recovery_value = total_input_amount
+        .checked_sub(fee)
+        .filter(|value| *value >= LEGACY_P2PKH_DUST_LIMIT)
+        .ok_or_else(|| {
+            format!("insufficient funds for recovery: inputs={total_input_amount}, fee={fee}")
+        })?;
+
+    let tx = Transaction {
+        version: bitcoin::transaction::Version(2),
+        lock_time: LockTime::ZERO,
+        input: unsigned_inputs(utxos)?,
+        output: vec![TxOut {
+            value: recovery_value.into(),
+            script_pubkey: heir_script,
+        }],
+    };
+    let options = BuildOptions {
+        lock_time: Some(lock),
+        ..options.clone()
+    };
+    let recovery = order_and_sign(
+        tx,
+        None,
+        &options,
+        &key.secret_key,
+        &key.pubkey,
+        total_input_amount,
+    )?;
+
+    Ok(InheritanceBundle {
+        heir_address,
+        lock_time: lock,
+        inputs: utxos.iter().map(outpoint_key).collect(),
+        recovery,
+    })
+}
+
+fn outpoint_key(utxo: &Utxo) -> String {
+    format!("{}:{}", utxo.txid.to_ascii_lowercase(), utxo.vout)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::consensus::deserialize;
+
+    use super::*;
+
+    const KEY_HEX: &str = "0505050505050505050505050505050505050505050505050505050505050505";
+    const FEE_PER_KB: u64 = 1_000_000;
+    const TIP: ChainTip = ChainTip {
+        height: 3_000_000,
+        median_time_past: 1_700_000_000,
+    };
+
+    fn heir() -> String {
+        let secret_key = SecretKey::from_slice(&[8u8; 32]).unwrap();
+        ReddcoinAddress::p2pkh(&BitcoinPublicKey::new(secret_key.public_key(secp()))).to_string()
+    }
+
+    fn utxos_json(utxos: &[(u8, u64, &str)]) -> String {
+        let entries: Vec<String> = utxos
+            .iter()
+            .map(|(index, amount, extra)| {
+                format!(
+                    r#"{{"txid":"{}","vout":0,"amount":{amount}{extra}}}"#,
+                    format!("{index:02x}").repeat(32)
+                )
+            })
+            .collect();
+        format!("[{}]", entries.join(","))
+    }
+
+    fn create(utxos: &[(u8, u64, &str)], lock: AbsoluteLock) -> Result<InheritanceBundle, String> {
+        create_inheritance_bundle(
+            utxos_json(utxos),
+            KEY_HEX.to_string(),
+            heir(),
+            serde_json::to_string(&lock).unwrap(),
+            FEE_PER_KB,
+            TIP,
+            BuildOptions::default(),
+        )
+    }
+
+    fn refresh(
+        bundle: &InheritanceBundle,
+        utxos: &[(u8, u64, &str)],
+        lock: AbsoluteLock,
+    ) -> Result<LivenessRefresh, String> {
+        refresh_inheritance_bundle(
+            serde_json::to_string(bundle).unwrap(),
+            utxos_json(utxos),
+            KEY_HEX.to_string(),
+            serde_json::to_string(&lock).unwrap(),
+            FEE_PER_KB,
+            TIP,
+            BuildOptions::default(),
+        )
+    }
+
+    fn decode(signed: &SignedTransaction) -> Transaction {
+        deserialize(&hex::decode(&signed.hex).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn bundle_sweeps_everything_to_the_heir_behind_the_lock() {
+        let lock = AbsoluteLock::Height(TIP.height + 100_000);
+        let bundle = create(&[(1, 500_000_000, ""), (2, 300_000_000, "")], lock).unwrap();
+        assert_eq!(
+            bundle.inputs,
+            [
+                format!("{}:0", "01".repeat(32)),
+                format!("{}:0", "02".repeat(32))
+            ]
+        );
+
+        let tx = decode(&bundle.recovery);
+        assert_eq!(tx.lock_time, lock.to_lock_time().unwrap());
+        assert!(tx
+            .input
+            .iter()
+            .all(|input| input.sequence.enables_absolute_lock_time()));
+        assert_eq!(tx.output.len(), 1);
+        assert_eq!(
+            tx.output[0].script_pubkey,
+            ReddcoinAddress::from_str(&heir()).unwrap().script_pubkey()
+        );
+        assert_eq!(
+            tx.output[0].value.to_sat() + bundle.recovery.fee.to_base(),
+            800_000_000
+        );
+
+        let err = create(&[(1, 500_000_000, "")], AbsoluteLock::Height(TIP.height)).unwrap_err();
+        assert!(err.contains("already satisfied"), "{err}");
+    }
+
+    #[test]
+    fn refresh_spends_the_smallest_input_and_relocks_later() {
+        let utxos = [(1, 500_000_000, ""), (2, 300_000_000, "")];
+        let bundle = create(&utxos, AbsoluteLock::Height(TIP.height + 1_000)).unwrap();
+        let new_lock = AbsoluteLock::Height(TIP.height + 2_000);
+        let refreshed = refresh(&bundle, &utxos, new_lock).unwrap();
+
+        let liveness = decode(&refreshed.liveness);
+        assert_eq!(liveness.input.len(), 1);
+        assert_eq!(
+            liveness.input[0].previous_output.txid.to_string(),
+            "02".repeat(32)
+        );
+        assert_eq!(liveness.lock_time, LockTime::ZERO);
+
+        // The replacement spends the liveness output, so the old recovery can never confirm
+        // alongside it.
+        let recovery = decode(&refreshed.bundle.recovery);
+        assert_eq!(refreshed.bundle.lock_time, new_lock);
+        assert_eq!(recovery.lock_time, new_lock.to_lock_time().unwrap());
+        let liveness_outpoint = format!("{}:0", refreshed.liveness.txid);
+        assert!(refreshed.bundle.inputs.contains(&liveness_outpoint));
+        assert!(!refreshed
+            .bundle
+            .inputs
+            .contains(&format!("{}:0", "02".repeat(32))));
+        assert_eq!(recovery.input.len(), 2);
+    }
+
+    #[test]
+    fn bundles_are_checked_against_the_policy() {
+        let utxos = [(1, 500_000_000, ""), (2, 300_000_000, "")];
+        let lock = AbsoluteLock::Height(TIP.height + 1_000);
+        let options = |json: String| BuildOptions::from_json(&json).unwrap();
+
+        let blocked = options(format!(
+            r#"{{"policy":{{"rules":{{"blocklist":["{}"]}}}}}}"#,
+            heir()
+        ));
+        let err = create_inheritance_bundle(
+            utxos_json(&utxos),
+            KEY_HEX.to_string(),
+            heir(),
+            serde_json::to_string(&lock).unwrap(),
+            FEE_PER_KB,
+            TIP,
+            blocked,
+        )
+        .unwrap_err();
+        assert!(err.starts_with("denied by policy"), "{err}");
+
+        // The heir is a new recipient; the liveness output returning to the owner is not.
+        let bundle = create(&utxos, lock).unwrap();
+        let refreshed = refresh_inheritance_bundle(
+            serde_json::to_string(&bundle).unwrap(),
+            utxos_json(&utxos),
+            KEY_HEX.to_string(),
+            serde_json::to_string(&AbsoluteLock::Height(TIP.height + 2_000)).unwrap(),
+            FEE_PER_KB,
+            TIP,
+            options(r#"{"policy":{"rules":{"first_time_recipient":"warn"}}}"#.to_string()),
+        )
+        .unwrap();
+        assert!(refreshed.liveness.policy_warnings.is_empty());
+        assert_eq!(refreshed.liveness.change_index, Some(0));
+        assert_eq!(refreshed.bundle.recovery.policy_warnings.len(), 1);
+
+        let err = create_inheritance_bundle(
+            utxos_json(&utxos),
+            KEY_HEX.to_string(),
+            heir(),
+            serde_json::to_string(&lock).unwrap(),
+            FEE_PER_KB,
+            TIP,
+            options(r#"{"lock_time":{"height":1}}"#.to_string()),
+        )
+        .unwrap_err();
+        assert!(err.starts_with("lock_time cannot be set"), "{err}");
+    }
+
+    #[test]
+    fn refresh_rejects_earlier_or_mixed_locks() {
+        let utxos = [(1, 500_000_000, "")];
+        let bundle = create(&utxos, AbsoluteLock::Height(TIP.height + 1_000)).unwrap();
+        for lock in [
+            AbsoluteLock::Height(TIP.height + 1_000),
+            AbsoluteLock::Height(TIP.height + 999),
+            AbsoluteLock::Time(1_900_000_000),
+        ] {
+            let err = refresh(&bundle, &utxos, lock).unwrap_err();
+            assert!(err.contains("must be later than"), "{lock:?}: {err}");
+        }
+    }
+
+    #[test]
+    fn refresh_needs_a_mature_input_that_can_pay_the_fee() {
+        let utxos = [(1, 500_000_000, ""), (2, 20_000, "")];
+        let bundle = create(&utxos, AbsoluteLock::Height(TIP.height + 1_000)).unwrap();
+        let later = AbsoluteLock::Height(TIP.height + 2_000);
+
+        // The only recovery input left is too small to pay for a liveness transaction.
+        let err = refresh(&bundle, &[(2, 20_000, "")], later).unwrap_err();
+        assert!(err.contains("can pay a liveness fee"), "{err}");
+
+        // A staking reward still maturing cannot be spent by the liveness transaction.
+        let stake = (3, 100_000_000, r#","is_coinstake":true,"height":2999990"#);
+        let mut bundle = create(&utxos[..1], AbsoluteLock::Height(TIP.height + 1_000)).unwrap();
+        bundle.inputs = vec![format!("{}:0", "03".repeat(32))];
+        let err = refresh(&bundle, &[stake], later).unwrap_err();
+        assert!(err.contains("no mature, unspent input"), "{err}");
+    }
+}
@@ -1,32 +1,743 @@
+pub mod account_discovery;
+pub mod address;
+pub mod amount;
+pub mod batch_payment;
+pub mod block_filter;
+pub mod blockbook;
+pub mod chain_backend;
+pub mod chain_params;
+pub mod consolidation;
+pub mod core_rpc;
+pub mod cpfp;
+pub mod electrum;
+pub mod header_chain;
+pub mod http_transport;
+pub mod inheritance;
+pub mod merkle_proof;
+pub mod multi_backend;
+pub mod p2p;
+pub mod payment_uri;
+pub mod policy;
+pub mod script_interpreter;
+pub mod secp_context;
+pub mod staking;
+pub mod standardness;
+pub mod timelock;
+pub mod transaction_builder;
+pub mod transaction_signer;
+pub mod tx_ordering;
+pub mod utxo;
+pub mod vault_crypto;
+pub mod wallet_import;
+pub mod wif;
+
 use std::ffi::{CStr, CString};
 use std::os::raw::c_char;
 
+/// Reads a required C string argument.
+///
+/// # Safety
+/// `ptr` must be NULL or point to a NUL-terminated string that stays valid for the call.
+unsafe fn read_c_str(ptr: *const c_char, name: &str) -> Result<String, String> {
+    if ptr.is_null() {
+        return Err(format!("{name} must not be NULL"));
+    }
+    Ok(CStr::from_ptr(ptr).to_string_lossy().into_owned())
+}
+
+/// Encodes a result using the `OK:<value>` / `ERR:<message>` convention from `redd_crypto.h`.
+fn ffi_result(result: Result<String, String>) -> *mut c_char {
+    let encoded = match result {
+        Ok(value) => format!("OK:{value}"),
+        Err(message) => format!("ERR:{message}"),
+    };
+    // Interior NUL bytes cannot appear in a C string; strip them rather than panic across FFI.
+    CString::new(encoded.replace('\0', ""))
+        .expect("NUL bytes were stripped")
+        .into_raw()
+}
+
 // FFI: Derive Reddcoin Address
+///
+/// # Safety
+/// `mnemonic_ptr` must point to a valid NUL-terminated string.
 #[no_mangle]
-pub extern "C" fn derive_address_ffi(mnemonic_ptr: *const c_char) -> *mut c_char {
+pub unsafe extern "C" fn derive_address_ffi(mnemonic_ptr: *const c_char) -> *mut c_char {
     let _mnemonic = unsafe { CStr::from_ptr(mnemonic_ptr).to_string_lossy().into_owned() };
     // Placeholder logic - will be replaced with actual BIP39/BIP44 derivation
-    CString::new("R_RustGeneratedAddress123456789").unwrap().into_raw()
+    CString::new("R_RustGeneratedAddress123456789")
+        .unwrap()
+        .into_raw()
 }
 
 // FFI: Free memory allocated by Rust to prevent memory leaks in Flutter
+///
+/// # Safety
+/// `s` must be NULL or a pointer previously returned by this library, freed at most once.
 #[no_mangle]
-pub extern "C" fn rust_cstr_free(s: *mut c_char) {
+pub unsafe extern "C" fn rust_cstr_free(s: *mut c_char) {
     unsafe {
-        if s.is_null() { return; }
+        if s.is_null() {
+            return;
+        }
         let _ = CString::from_raw(s);
     }
 }
 
 // FFI: Build and Sign Transaction
+///
+/// `_amount` is an exact decimal RDD string (e.g. `"12.5"`); amounts never cross FFI as floats.
 #[no_mangle]
 pub extern "C" fn build_and_sign_tx_ffi(
     _utxos: *const c_char,
     _to_address: *const c_char,
-    _amount: f64,
+    _amount: *const c_char,
     _mnemonic: *const c_char,
-    _op_return: *const c_char
+    _op_return: *const c_char,
 ) -> *mut c_char {
     // Placeholder logic - will be replaced with actual secp256k1 ECDSA signing
-    CString::new("signed_hex_payload_from_rust_core").unwrap().into_raw()
+    CString::new("signed_hex_payload_from_rust_core")
+        .unwrap()
+        .into_raw()
+}
+
+// FFI: Import a WIF private key (paper wallet / `dumpprivkey` output)
+///
+/// Returns `OK:{"private_key_hex":..,"compressed":..,"address":..}` on success.
+///
+/// # Safety
+/// `wif` must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn import_wif_ffi(wif: *const c_char) -> *mut c_char {
+    ffi_result((|| {
+        let wif = read_c_str(wif, "wif")?;
+        let imported = wif::import_wif(wif)?;
+        serde_json::to_string(&imported).map_err(|e| format!("failed to encode import: {e}"))
+    })())
+}
+
+// FFI: Export a raw hex private key as WIF
+///
+/// # Safety
+/// `private_key_hex` must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn export_wif_ffi(
+    private_key_hex: *const c_char,
+    compressed: bool,
+) -> *mut c_char {
+    ffi_result((|| {
+        let private_key_hex = read_c_str(private_key_hex, "private_key_hex")?;
+        wif::encode_wif(private_key_hex, compressed)
+    })())
+}
+
+// FFI: Sweep a WIF key's UTXOs into a wallet address
+///
+/// Returns `OK:<SignedTransaction JSON>`.
+///
+/// # Safety
+/// Every pointer argument must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sweep_wif_ffi(
+    utxos_json: *const c_char,
+    wif: *const c_char,
+    destination_address: *const c_char,
+    fee_per_kb: u64,
+    options_json: *const c_char,
+) -> *mut c_char {
+    ffi_result((|| {
+        let options = transaction_signer::BuildOptions::from_json(&read_c_str(
+            options_json,
+            "options_json",
+        )?)?;
+        let signed = transaction_signer::sign_wif_sweep(
+            read_c_str(utxos_json, "utxos_json")?,
+            read_c_str(wif, "wif")?,
+            read_c_str(destination_address, "destination_address")?,
+            fee_per_kb,
+            options,
+        )?;
+        serde_json::to_string(&signed).map_err(|e| format!("failed to encode transaction: {e}"))
+    })())
+}
+
+// FFI: Parse a Reddcoin Core `dumpwallet` export
+///
+/// Returns `OK:<WalletImport JSON>` on success.
+///
+/// # Safety
+/// `dump` must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn parse_dumpwallet_ffi(dump: *const c_char) -> *mut c_char {
+    ffi_result((|| {
+        let import = wallet_import::parse_core_dumpwallet(read_c_str(dump, "dump")?)?;
+        serde_json::to_string(&import).map_err(|e| format!("failed to encode import: {e}"))
+    })())
+}
+
+// FFI: Recreate a ReddWallet (Electrum 1.x) wallet from its hex seed or mnemonic
+///
+/// # Safety
+/// `seed` must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn import_reddwallet_seed_ffi(
+    seed: *const c_char,
+    receive_count: u32,
+    change_count: u32,
+) -> *mut c_char {
+    ffi_result((|| {
+        let import = wallet_import::import_reddwallet_seed(
+            read_c_str(seed, "seed")?,
+            receive_count,
+            change_count,
+        )?;
+        serde_json::to_string(&import).map_err(|e| format!("failed to encode import: {e}"))
+    })())
+}
+
+// FFI: Track a ReddWallet (Electrum 1.x) wallet from its master public key
+///
+/// # Safety
+/// `mpk_hex` must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn import_reddwallet_mpk_ffi(
+    mpk_hex: *const c_char,
+    receive_count: u32,
+    change_count: u32,
+) -> *mut c_char {
+    ffi_result((|| {
+        let import = wallet_import::import_reddwallet_master_public_key(
+            read_c_str(mpk_hex, "mpk_hex")?,
+            receive_count,
+            change_count,
+        )?;
+        serde_json::to_string(&import).map_err(|e| format!("failed to encode import: {e}"))
+    })())
+}
+
+// FFI: Partition UTXOs into spendable and excluded sets
+///
+/// `tip_height` of `0` means the chain tip is unknown. Returns `OK:<SpendableSet JSON>`.
+///
+/// # Safety
+/// `utxos_json` must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn filter_spendable_utxos_ffi(
+    utxos_json: *const c_char,
+    min_confirmations: u32,
+    tip_height: u32,
+) -> *mut c_char {
+    ffi_result((|| {
+        let set = utxo::filter_spendable_utxos(
+            read_c_str(utxos_json, "utxos_json")?,
+            min_confirmations,
+            tip_height,
+        )?;
+        serde_json::to_string(&set).map_err(|e| format!("failed to encode spendable set: {e}"))
+    })())
+}
+
+// FFI: Parse a decimal amount into base units
+///
+/// `denomination` is `rdd`, `mrdd` or `base`. Returns `OK:<base_units>` as a decimal string so
+/// values above 2^53 survive Dart's JSON/number handling.
+///
+/// # Safety
+/// Every pointer argument must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn parse_amount_ffi(
+    amount: *const c_char,
+    denomination: *const c_char,
+) -> *mut c_char {
+    ffi_result((|| {
+        amount::parse_amount(
+            read_c_str(amount, "amount")?,
+            read_c_str(denomination, "denomination")?,
+        )
+    })())
+}
+
+// FFI: Format base units in a display denomination
+///
+/// # Safety
+/// `denomination` must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn format_amount_ffi(
+    base_units: u64,
+    denomination: *const c_char,
+) -> *mut c_char {
+    ffi_result((|| {
+        amount::format_amount(base_units, read_c_str(denomination, "denomination")?)
+    })())
+}
+
+// FFI: Parse a `reddcoin:` payment URI (or bare address / @handle) from a QR scan
+///
+/// Returns `OK:<PaymentRequest JSON>` on success.
+///
+/// # Safety
+/// `uri` must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn parse_payment_uri_ffi(uri: *const c_char) -> *mut c_char {
+    ffi_result((|| {
+        payment_uri::parse_payment_uri_json(read_c_str(uri, "uri")?)
+    })())
+}
+
+// FFI: Build a `reddcoin:` payment URI for a receive QR code
+///
+/// Empty `amount`, `label` or `message` strings leave that parameter out.
+///
+/// # Safety
+/// Every pointer argument must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn build_payment_uri_ffi(
+    target: *const c_char,
+    amount: *const c_char,
+    label: *const c_char,
+    message: *const c_char,
+) -> *mut c_char {
+    ffi_result((|| {
+        payment_uri::build_payment_uri(
+            read_c_str(target, "target")?,
+            read_c_str(amount, "amount")?,
+            read_c_str(label, "label")?,
+            read_c_str(message, "message")?,
+        )
+    })())
+}
+
+// FFI: Sign a standard transfer and report txid, fee and change position
+///
+/// `options_json` may be empty or `{"input_ordering": "bip69"|"random"|"preserve",
+/// "output_ordering": ..., "lock_time": {"height": n}|{"time": unix},
+/// "policy": {"rules": {...}, "context": {...}}}`.
+/// Returns `OK:<SignedTransaction JSON>`.
+///
+/// # Safety
+/// Every pointer argument must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sign_standard_transfer_ffi(
+    utxos_json: *const c_char,
+    private_key_hex: *const c_char,
+    recipient_address: *const c_char,
+    change_address: *const c_char,
+    amount_base_units: u64,
+    fee_per_kb: u64,
+    options_json: *const c_char,
+) -> *mut c_char {
+    ffi_result((|| {
+        let options = transaction_signer::BuildOptions::from_json(&read_c_str(
+            options_json,
+            "options_json",
+        )?)?;
+        let signed = transaction_signer::sign_standard_transfer_with_options(
+            read_c_str(utxos_json, "utxos_json")?,
+            read_c_str(private_key_hex, "private_key_hex")?,
+            read_c_str(recipient_address, "recipient_address")?,
+            read_c_str(change_address, "change_address")?,
+            amount::Amount::from_base(amount_base_units),
+            fee_per_kb,
+            options,
+        )?;
+        serde_json::to_string(&signed).map_err(|e| format!("failed to encode transaction: {e}"))
+    })())
+}
+
+// FFI: Build a CLTV/CSV timelocked P2SH output
+///
+/// `lock_json` is `{"absolute":{"height":n}}`, `{"absolute":{"time":unix}}`,
+/// `{"relative":{"blocks":n}}` or `{"relative":{"seconds":n}}`.
+/// Returns `OK:{"redeem_script":"<hex>","address":"<P2SH address>"}`.
+///
+/// # Safety
+/// Every pointer argument must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn create_timelock_output_ffi(
+    lock_json: *const c_char,
+    pubkey_hex: *const c_char,
+) -> *mut c_char {
+    ffi_result((|| {
+        let output = timelock::create_timelock_output(
+            read_c_str(lock_json, "lock_json")?,
+            read_c_str(pubkey_hex, "pubkey_hex")?,
+        )?;
+        serde_json::to_string(&output).map_err(|e| format!("failed to encode timelock output: {e}"))
+    })())
+}
+
+// FFI: Spend timelocked P2SH outputs once their lock has expired
+///
+/// Returns `OK:<SignedTransaction JSON>`, or `ERR:` if the lock is not yet satisfied at the given
+/// tip.
+///
+/// # Safety
+/// Every pointer argument must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sign_timelock_spend_ffi(
+    utxos_json: *const c_char,
+    redeem_script_hex: *const c_char,
+    private_key_hex: *const c_char,
+    destination_address: *const c_char,
+    fee_per_kb: u64,
+    tip_height: u32,
+    tip_median_time_past: u32,
+    options_json: *const c_char,
+) -> *mut c_char {
+    ffi_result((|| {
+        let options = transaction_signer::BuildOptions::from_json(&read_c_str(
+            options_json,
+            "options_json",
+        )?)?;
+        let signed = timelock::sign_timelock_spend(
+            read_c_str(utxos_json, "utxos_json")?,
+            read_c_str(redeem_script_hex, "redeem_script_hex")?,
+            read_c_str(private_key_hex, "private_key_hex")?,
+            read_c_str(destination_address, "destination_address")?,
+            fee_per_kb,
+            timelock::ChainTip {
+                height: tip_height,
+                median_time_past: tip_median_time_past,
+            },
+            options,
+        )?;
+        serde_json::to_string(&signed).map_err(|e| format!("failed to encode transaction: {e}"))
+    })())
+}
+
+// FFI: Pre-sign a timelocked inheritance recovery transaction
+///
+/// `lock_json` is `{"height":n}` or `{"time":unix}` and must not be satisfied at the given tip.
+/// Returns `OK:<InheritanceBundle JSON>`.
+///
+/// # Safety
+/// Every pointer argument must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn create_inheritance_bundle_ffi(
+    utxos_json: *const c_char,
+    private_key_hex: *const c_char,
+    heir_address: *const c_char,
+    lock_json: *const c_char,
+    fee_per_kb: u64,
+    tip_height: u32,
+    tip_median_time_past: u32,
+    options_json: *const c_char,
+) -> *mut c_char {
+    ffi_result((|| {
+        let options = transaction_signer::BuildOptions::from_json(&read_c_str(
+            options_json,
+            "options_json",
+        )?)?;
+        let bundle = inheritance::create_inheritance_bundle(
+            read_c_str(utxos_json, "utxos_json")?,
+            read_c_str(private_key_hex, "private_key_hex")?,
+            read_c_str(heir_address, "heir_address")?,
+            read_c_str(lock_json, "lock_json")?,
+            fee_per_kb,
+            timelock::ChainTip {
+                height: tip_height,
+                median_time_past: tip_median_time_past,
+            },
+            options,
+        )?;
+        serde_json::to_string(&bundle).map_err(|e| format!("failed to encode bundle: {e}"))
+    })())
+}
+
+// FFI: Check in as alive: invalidate the current recovery transaction and re-sign a later one
+///
+/// Returns `OK:{"liveness":<SignedTransaction>,"bundle":<InheritanceBundle>}`.
+///
+/// # Safety
+/// Every pointer argument must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn refresh_inheritance_bundle_ffi(
+    bundle_json: *const c_char,
+    utxos_json: *const c_char,
+    private_key_hex: *const c_char,
+    new_lock_json: *const c_char,
+    fee_per_kb: u64,
+    tip_height: u32,
+    tip_median_time_past: u32,
+    options_json: *const c_char,
+) -> *mut c_char {
+    ffi_result((|| {
+        let options = transaction_signer::BuildOptions::from_json(&read_c_str(
+            options_json,
+            "options_json",
+        )?)?;
+        let refresh = inheritance::refresh_inheritance_bundle(
+            read_c_str(bundle_json, "bundle_json")?,
+            read_c_str(utxos_json, "utxos_json")?,
+            read_c_str(private_key_hex, "private_key_hex")?,
+            read_c_str(new_lock_json, "new_lock_json")?,
+            fee_per_kb,
+            timelock::ChainTip {
+                height: tip_height,
+                median_time_past: tip_median_time_past,
+            },
+            options,
+        )?;
+        serde_json::to_string(&refresh).map_err(|e| format!("failed to encode refresh: {e}"))
+    })())
+}
+
+// FFI: Sign a child-pays-for-parent transaction for a stuck parent
+///
+/// `parent_fee_base_units` is the fee the parent pays. Returns `OK:<CpfpBump JSON>`.
+///
+/// # Safety
+/// Every pointer argument must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sign_cpfp_child_ffi(
+    parent_tx_hex: *const c_char,
+    parent_vout: u32,
+    parent_fee_base_units: u64,
+    private_key_hex: *const c_char,
+    destination_address: *const c_char,
+    target_fee_per_kb: u64,
+    options_json: *const c_char,
+) -> *mut c_char {
+    ffi_result((|| {
+        let options = transaction_signer::BuildOptions::from_json(&read_c_str(
+            options_json,
+            "options_json",
+        )?)?;
+        let bump = cpfp::sign_cpfp_child(
+            read_c_str(parent_tx_hex, "parent_tx_hex")?,
+            parent_vout,
+            amount::Amount::from_base(parent_fee_base_units),
+            read_c_str(private_key_hex, "private_key_hex")?,
+            read_c_str(destination_address, "destination_address")?,
+            target_fee_per_kb,
+            options,
+        )?;
+        serde_json::to_string(&bump).map_err(|e| format!("failed to encode CPFP bump: {e}"))
+    })())
+}
+
+// FFI: Validate a batch payment list row by row
+///
+/// `format` is `csv` or `json`; `handles_json` maps ReddID handles to resolved addresses and may
+/// be empty. Returns `OK:<BatchValidation JSON>`.
+///
+/// # Safety
+/// Every pointer argument must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn validate_batch_payment_ffi(
+    entries: *const c_char,
+    format: *const c_char,
+    handles_json: *const c_char,
+) -> *mut c_char {
+    ffi_result((|| {
+        let validation = batch_payment::validate_batch_payment(
+            read_c_str(entries, "entries")?,
+            read_c_str(format, "format")?,
+            read_c_str(handles_json, "handles_json")?,
+        )?;
+        serde_json::to_string(&validation).map_err(|e| format!("failed to encode validation: {e}"))
+    })())
+}
+
+// FFI: Sign a batch payment, splitting it across transactions when needed
+///
+/// Returns `OK:<BatchPayment JSON>` with every signed transaction and a per-recipient report.
+///
+/// # Safety
+/// Every pointer argument must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sign_batch_payment_ffi(
+    utxos_json: *const c_char,
+    private_key_hex: *const c_char,
+    entries: *const c_char,
+    format: *const c_char,
+    handles_json: *const c_char,
+    change_address: *const c_char,
+    fee_per_kb: u64,
+    options_json: *const c_char,
+) -> *mut c_char {
+    ffi_result((|| {
+        let options = transaction_signer::BuildOptions::from_json(&read_c_str(
+            options_json,
+            "options_json",
+        )?)?;
+        let batch = batch_payment::sign_batch_payment(
+            read_c_str(utxos_json, "utxos_json")?,
+            read_c_str(private_key_hex, "private_key_hex")?,
+            read_c_str(entries, "entries")?,
+            read_c_str(format, "format")?,
+            read_c_str(handles_json, "handles_json")?,
+            read_c_str(change_address, "change_address")?,
+            fee_per_kb,
+            options,
+        )?;
+        serde_json::to_string(&batch).map_err(|e| format!("failed to encode batch: {e}"))
+    })())
+}
+
+// FFI: Plan a UTXO consolidation without signing
+///
+/// `options_json` may be empty or e.g. `{"goal":{"mode":"split","target_output":n},
+/// "max_fee_per_kb":n,"min_confirmations":n,"tip_height":n}`. Returns `OK:<ConsolidationPlan>`.
+///
+/// # Safety
+/// Every pointer argument must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn plan_consolidation_ffi(
+    utxos_json: *const c_char,
+    fee_per_kb: u64,
+    options_json: *const c_char,
+) -> *mut c_char {
+    ffi_result((|| {
+        let plan = consolidation::plan_consolidation_json(
+            read_c_str(utxos_json, "utxos_json")?,
+            fee_per_kb,
+            read_c_str(options_json, "options_json")?,
+        )?;
+        serde_json::to_string(&plan).map_err(|e| format!("failed to encode plan: {e}"))
+    })())
+}
+
+// FFI: Plan and sign a UTXO consolidation
+///
+/// Returns `OK:{"plan":<ConsolidationPlan>,"transactions":[<SignedTransaction>...]}`.
+///
+/// # Safety
+/// Every pointer argument must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn sign_consolidation_ffi(
+    utxos_json: *const c_char,
+    private_key_hex: *const c_char,
+    destination_address: *const c_char,
+    fee_per_kb: u64,
+    options_json: *const c_char,
+) -> *mut c_char {
+    ffi_result((|| {
+        let signed = consolidation::sign_consolidation(
+            read_c_str(utxos_json, "utxos_json")?,
+            read_c_str(private_key_hex, "private_key_hex")?,
+            read_c_str(destination_address, "destination_address")?,
+            fee_per_kb,
+            read_c_str(options_json, "options_json")?,
+        )?;
+        serde_json::to_string(&signed).map_err(|e| format!("failed to encode consolidation: {e}"))
+    })())
+}
+
+// FFI: Estimate PoSV v2 stake weight, time to stake and annual reward
+///
+/// `network_json` is `{"network_weight": <coin-days>, "tip_height": n}`.
+/// Returns `OK:<StakingEstimate JSON>`.
+///
+/// # Safety
+/// Every pointer argument must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn estimate_staking_ffi(
+    utxos_json: *const c_char,
+    network_json: *const c_char,
+) -> *mut c_char {
+    ffi_result((|| {
+        let estimate = staking::estimate_staking_json(
+            read_c_str(utxos_json, "utxos_json")?,
+            read_c_str(network_json, "network_json")?,
+        )?;
+        serde_json::to_string(&estimate).map_err(|e| format!("failed to encode estimate: {e}"))
+    })())
+}
+
+// FFI: Compare staking rewards under current, consolidated and split output layouts
+///
+/// Returns `OK:[<StrategyProjection JSON>...]` in the order of `strategies_json`.
+///
+/// # Safety
+/// Every pointer argument must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn compare_staking_strategies_ffi(
+    utxos_json: *const c_char,
+    network_json: *const c_char,
+    strategies_json: *const c_char,
+) -> *mut c_char {
+    ffi_result((|| {
+        let projections = staking::compare_staking_strategies_json(
+            read_c_str(utxos_json, "utxos_json")?,
+            read_c_str(network_json, "network_json")?,
+            read_c_str(strategies_json, "strategies_json")?,
+        )?;
+        serde_json::to_string(&projections)
+            .map_err(|e| format!("failed to encode projections: {e}"))
+    })())
+}
+
+// FFI: Evaluate policy rules against an already-built transaction
+///
+/// `change_index` of `-1` means no change output. Returns `OK:<PolicyReport JSON>`.
+///
+/// # Safety
+/// Every pointer argument must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn evaluate_policy_ffi(
+    tx_hex: *const c_char,
+    fee_base_units: u64,
+    change_index: i64,
+    policy_json: *const c_char,
+) -> *mut c_char {
+    ffi_result((|| {
+        let raw = hex::decode(read_c_str(tx_hex, "tx_hex")?.trim())
+            .map_err(|e| format!("tx_hex decode failed: {e}"))?;
+        let tx: bitcoin::Transaction = bitcoin::consensus::encode::deserialize(&raw)
+            .map_err(|e| format!("tx_hex is not a transaction: {e}"))?;
+        let policy =
+            policy::TransactionPolicy::from_json(&read_c_str(policy_json, "policy_json")?)?;
+        let report = policy.evaluate(
+            &tx,
+            amount::Amount::from_base(fee_base_units),
+            usize::try_from(change_index).ok(),
+        );
+        serde_json::to_string(&report).map_err(|e| format!("failed to encode policy report: {e}"))
+    })())
+}
+
+// FFI: Check an already-built transaction against relay standardness rules
+///
+/// Returns `OK:<StandardnessReport JSON>`; the transaction is standard when `violations` is empty.
+///
+/// # Safety
+/// Every pointer argument must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn check_standardness_ffi(
+    tx_hex: *const c_char,
+    fee_base_units: u64,
+) -> *mut c_char {
+    ffi_result((|| {
+        let raw = hex::decode(read_c_str(tx_hex, "tx_hex")?.trim())
+            .map_err(|e| format!("tx_hex decode failed: {e}"))?;
+        let tx: bitcoin::Transaction = bitcoin::consensus::encode::deserialize(&raw)
+            .map_err(|e| format!("tx_hex is not a transaction: {e}"))?;
+        let report = standardness::check_standardness(
+            &tx,
+            amount::Amount::from_base(fee_base_units),
+            &chain_params::ChainParams::MAINNET,
+        );
+        serde_json::to_string(&report)
+            .map_err(|e| format!("failed to encode standardness report: {e}"))
+    })())
+}
+
+// FFI: Verify every input script of a signed transaction
+///
+/// Returns `OK:<txid>` when every input validates.
+///
+/// # Safety
+/// Every pointer argument must be NULL or point to a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn verify_transaction_ffi(
+    tx_hex: *const c_char,
+    prevouts_json: *const c_char,
+) -> *mut c_char {
+    ffi_result((|| {
+        script_interpreter::verify_transaction(
+            &read_c_str(tx_hex, "tx_hex")?,
+            &read_c_str(prevouts_json, "prevouts_json")?,
+        )
+    })())
 }
@@ -0,0 +1,501 @@
+//! Merkle inclusion proofs against the local header chain.
+//!
+//! A server can claim any transaction paid the wallet. With a [`HeaderChain`] the claim can be
+//! checked: the server supplies the Merkle branch from the transaction to the block's Merkle
+//! root (Electrum `blockchain.transaction.get_merkle`) or a `merkleblock` (Core
+//! `gettxoutproof`), and the root must equal the one in the header the wallet validated itself.
+//! A lying server would have to forge a header with more work than the honest chain.
+//!
+//! Verification marks history entries and UTXOs `verified`; the signer can then be told to refuse
+//! anything else via `BuildOptions::require_verified_inputs`. The flag is never deserialized, so
+//! callers that hand the signer UTXOs as JSON pass [`InputProofs`] and the signer checks them
+//! itself.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use bitcoin::hashes::{sha256d, Hash, HashEngine};
+use bitcoin::{MerkleBlock, TxMerkleNode, Txid};
+use serde::{Deserialize, Serialize};
+
+use crate::chain_backend::{HistoryEntry, Utxo};
+use crate::header_chain::{Checkpoint, HeaderChain};
+
+/// A Merkle branch as Electrum servers return it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MerkleBranch {
+    pub block_height: u32,
+    /// Sibling hashes from the leaf upwards, in RPC byte order.
+    pub merkle: Vec<String>,
+    /// The transaction's index in the block.
+    pub pos: u32,
+}
+
+/// Proof that a transaction is in a block.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", content = "proof", rename_all = "snake_case")]
+pub enum InclusionProof {
+    Branch(MerkleBranch),
+    /// Hex-encoded `merkleblock`: the block header plus a partial Merkle tree.
+    MerkleBlock(String),
+}
+
+/// Everything the signer needs to prove inputs itself: the wallet's synced header chain and an
+/// inclusion proof per funding transaction.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct InputProofs {
+    /// Hex of [`HeaderChain::to_bytes`]; re-validated on every use.
+    pub header_chain: String,
+    /// Checkpoints the chain must match.
+    #[serde(default)]
+    pub checkpoints: Vec<Checkpoint>,
+    /// Proof per funding txid.
+    pub proofs: HashMap<String, InclusionProof>,
+}
+
+impl InputProofs {
+    /// Succeeds only if every UTXO is already `verified` or its funding transaction is proven
+    /// in the block at its `height`.
+    pub fn check(&self, utxos: &[Utxo]) -> Result<(), String> {
+        let raw = hex::decode(self.header_chain.trim())
+            .map_err(|e| format!("invalid header_chain hex: {e}"))?;
+        let chain = HeaderChain::from_bytes(&raw, &self.checkpoints)?;
+        for (index, utxo) in utxos.iter().enumerate() {
+            if utxo.verified {
+                continue;
+            }
+            let unproven = |reason: String| {
+                format!(
+                    "utxos[{index}] ({}:{}) has not been proof-verified against the header chain: \
+                     {reason}",
+                    utxo.txid, utxo.vout
+                )
+            };
+            let proof = self
+                .proofs
+                .get(&utxo.txid)
+                .ok_or_else(|| unproven("no inclusion proof supplied".to_string()))?;
+            let height = utxo
+                .height
+                .ok_or_else(|| unproven("no confirmation height".to_string()))?;
+            verify_inclusion(&chain, &utxo.txid, height, proof).map_err(unproven)?;
+        }
+        Ok(())
+    }
+}
+
+/// A backend that can prove a transaction's inclusion in the block at a height.
+pub trait ProofSource {
+    fn inclusion_proof(&self, txid: &str, height: u32) -> Result<InclusionProof, String>;
+}
+
+/// Why a transaction could not be verified.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProofFailureKind {
+    /// Unconfirmed, or confirmed above the synced header tip; retry after syncing.
+    NotSynced,
+    /// The backend did not supply a proof.
+    Unavailable,
+    /// The proof does not match the header chain: the backend's claim is false.
+    Invalid,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ProofFailure {
+    pub txid: String,
+    pub kind: ProofFailureKind,
+    pub reason: String,
+}
+
+/// Outcome of verifying a batch of transactions.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ProofReport {
+    pub verified: usize,
+    pub failures: Vec<ProofFailure>,
+}
+
+/// The Merkle root implied by `txid` sitting at index `pos` under `branch`.
+///
+/// Rejects a `pos` with bits beyond the branch, which would otherwise let one branch prove
+/// several positions.
+pub fn branch_root(txid: Txid, branch: &[TxMerkleNode], pos: u32) -> Result<TxMerkleNode, String> {
+    if branch.len() < 32 && pos >> branch.len() != 0 {
+        return Err(format!(
+            "position {pos} does not fit a branch of {} hashes",
+            branch.len()
+        ));
+    }
+    let mut node = txid.to_raw_hash();
+    for (level, sibling) in branch.iter().enumerate() {
+        let mut engine = sha256d::Hash::engine();
+        let sibling = sibling.to_raw_hash();
+        let (left, right) = if (pos >> level) & 1 == 1 {
+            (sibling, node)
+        } else {
+            (node, sibling)
+        };
+        engine.input(left.as_byte_array());
+        engine.input(right.as_byte_array());
+        node = sha256d::Hash::from_engine(engine);
+    }
+    Ok(TxMerkleNode::from_raw_hash(node))
+}
+
+/// Checks `proof` shows `txid` in the best-chain block at `height`.
+pub fn verify_inclusion(
+    chain: &HeaderChain,
+    txid: &str,
+    height: u32,
+    proof: &InclusionProof,
+) -> Result<(), String> {
+    let txid = Txid::from_str(txid).map_err(|e| format!("invalid txid {txid:?}: {e}"))?;
+    let header = chain
+        .header_at(height)
+        .ok_or_else(|| format!("height {height} is not in the synced header chain"))?;
+
+    match proof {
+        InclusionProof::Branch(branch) => {
+            if branch.block_height != height {
+                return Err(format!(
+                    "proof is for height {}, not {height}",
+                    branch.block_height
+                ));
+            }
+            let hashes = branch
+                .merkle
+                .iter()
+                .map(|hash| {
+                    TxMerkleNode::from_str(hash)
+                        .map_err(|e| format!("invalid branch hash {hash:?}: {e}"))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            let root = branch_root(txid, &hashes, branch.pos)?;
+            if root != header.merkle_root {
+                return Err(format!(
+                    "branch leads to root {root}, but the header at {height} commits to {}",
+                    header.merkle_root
+                ));
+            }
+        }
+        InclusionProof::MerkleBlock(merkle_block_hex) => {
+            let raw = hex::decode(merkle_block_hex.trim())
+                .map_err(|e| format!("invalid merkleblock hex: {e}"))?;
+            let merkle_block: MerkleBlock = bitcoin::consensus::deserialize(&raw)
+                .map_err(|e| format!("invalid merkleblock: {e}"))?;
+            if merkle_block.header.block_hash() != header.block_hash() {
+                return Err(format!(
+                    "merkleblock is for block {}, not {} at height {height}",
+                    merkle_block.header.block_hash(),
+                    header.block_hash()
+                ));
+            }
+            let mut matches = Vec::new();
+            let mut indexes = Vec::new();
+            let root = merkle_block
+                .txn
+                .extract_matches(&mut matches, &mut indexes)
+                .map_err(|e| format!("invalid partial merkle tree: {e}"))?;
+            if root != header.merkle_root {
+                return Err(format!(
+                    "partial tree leads to root {root}, but the header at {height} commits to {}",
+                    header.merkle_root
+                ));
+            }
+            if !matches.contains(&txid) {
+                return Err(format!("merkleblock does not include {txid}"));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Proves each confirmed entry of `entries` and sets its `verified` flag accordingly.
+pub fn verify_history(
+    chain: &HeaderChain,
+    source: &dyn ProofSource,
+    entries: &mut [HistoryEntry],
+) -> ProofReport {
+    let mut verifier = Verifier::new(chain, source);
+    for entry in entries.iter_mut() {
+        entry.verified = verifier.verify(&entry.txid, entry.height);
+    }
+    verifier.report
+}
+
+/// Proves the funding transaction of each UTXO and sets its `verified` flag accordingly. Each
+/// transaction is proven once however many outputs it funds.
+pub fn verify_utxos(
+    chain: &HeaderChain,
+    source: &dyn ProofSource,
+    utxos: &mut [Utxo],
+) -> ProofReport {
+    let mut verifier = Verifier::new(chain, source);
+    for utxo in utxos.iter_mut() {
+        utxo.verified = verifier.verify(&utxo.txid, utxo.height);
+    }
+    verifier.report
+}
+
+struct Verifier<'a> {
+    chain: &'a HeaderChain,
+    source: &'a dyn ProofSource,
+    /// Result per `(txid, height)` already checked.
+    seen: HashMap<(String, u32), bool>,
+    report: ProofReport,
+}
+
+impl<'a> Verifier<'a> {
+    fn new(chain: &'a HeaderChain, source: &'a dyn ProofSource) -> Self {
+        Self {
+            chain,
+            source,
+            seen: HashMap::new(),
+            report: ProofReport::default(),
+        }
+    }
+
+    fn verify(&mut self, txid: &str, height: Option<u32>) -> bool {
+        let Some(height) = height.filter(|height| *height <= self.chain.tip_height()) else {
+            self.fail(
+                txid,
+                ProofFailureKind::NotSynced,
+                "not confirmed in the synced header chain",
+            );
+            return false;
+        };
+        if height < self.chain.anchor_height() {
+            self.fail(
+                txid,
+                ProofFailureKind::NotSynced,
+                "confirmed before the header chain's anchor",
+            );
+            return false;
+        }
+        let key = (txid.to_ascii_lowercase(), height);
+        if let Some(verified) = self.seen.get(&key) {
+            return *verified;
+        }
+
+        let verified = match self.source.inclusion_proof(txid, height) {
+            Err(reason) => {
+                self.fail(txid, ProofFailureKind::Unavailable, &reason);
+                false
+            }
+            Ok(proof) => match verify_inclusion(self.chain, txid, height, &proof) {
+                Ok(()) => {
+                    self.report.verified += 1;
+                    true
+                }
+                Err(reason) => {
+                    self.fail(txid, ProofFailureKind::Invalid, &reason);
+                    false
+                }
+            },
+        };
+        self.seen.insert(key, verified);
+        verified
+    }
+
+    fn fail(&mut self, txid: &str, kind: ProofFailureKind, reason: &str) {
+        self.report.failures.push(ProofFailure {
+            txid: txid.to_string(),
+            kind,
+            reason: reason.to_string(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::block::{Header, Version};
+    use bitcoin::pow::CompactTarget;
+    use bitcoin::BlockHash;
+    use serde_json::json;
+
+    use super::*;
+    use crate::transaction_signer::{ensure_verified, BuildOptions};
+
+    const HEIGHT: u32 = 3_000_000;
+
+    struct Block {
+        header: Header,
+        txids: Vec<Txid>,
+    }
+
+    fn block() -> Block {
+        let txids: Vec<Txid> = (1..=4u8)
+            .map(|byte| Txid::from_byte_array([byte; 32]))
+            .collect();
+        let root = bitcoin::merkle_tree::calculate_root(txids.iter().copied()).unwrap();
+        let header = Header {
+            version: Version::from_consensus(4),
+            prev_blockhash: BlockHash::all_zeros(),
+            merkle_root: TxMerkleNode::from_raw_hash(root.to_raw_hash()),
+            time: 1_700_000_000,
+            bits: CompactTarget::from_consensus(0x1d00ffff),
+            nonce: 0,
+        };
+        Block { header, txids }
+    }
+
+    /// The branch proving `txids[2]`: its sibling, then the hash of the left pair.
+    fn branch(block: &Block) -> MerkleBranch {
+        let left = bitcoin::merkle_tree::calculate_root(block.txids[..2].iter().copied()).unwrap();
+        MerkleBranch {
+            block_height: HEIGHT,
+            merkle: vec![block.txids[3].to_string(), left.to_string()],
+            pos: 2,
+        }
+    }
+
+    fn merkle_block(header: &Header, txids: &[Txid], matched: Txid) -> InclusionProof {
+        let merkle_block =
+            MerkleBlock::from_header_txids_with_predicate(header, txids, |txid| *txid == matched);
+        InclusionProof::MerkleBlock(bitcoin::consensus::encode::serialize_hex(&merkle_block))
+    }
+
+    #[test]
+    fn branch_proofs_match_the_header() {
+        let block = block();
+        let chain = HeaderChain::new(block.header, HEIGHT, &[]).unwrap();
+        let txid = block.txids[2].to_string();
+        let valid = branch(&block);
+        assert_eq!(
+            verify_inclusion(
+                &chain,
+                &txid,
+                HEIGHT,
+                &InclusionProof::Branch(valid.clone())
+            ),
+            Ok(())
+        );
+
+        let mut bad_sibling = valid.clone();
+        bad_sibling.merkle[0] = block.txids[0].to_string();
+        let err = verify_inclusion(&chain, &txid, HEIGHT, &InclusionProof::Branch(bad_sibling))
+            .unwrap_err();
+        assert!(err.starts_with("branch leads to root"), "{err}");
+
+        let mut bad_pos = valid.clone();
+        bad_pos.pos = 6;
+        let err =
+            verify_inclusion(&chain, &txid, HEIGHT, &InclusionProof::Branch(bad_pos)).unwrap_err();
+        assert!(err.contains("does not fit a branch"), "{err}");
+
+        let err = verify_inclusion(&chain, &txid, HEIGHT + 1, &InclusionProof::Branch(valid))
+            .unwrap_err();
+        assert!(err.contains("not in the synced header chain"), "{err}");
+    }
+
+    #[test]
+    fn merkle_block_proofs_match_the_header() {
+        let block = block();
+        let chain = HeaderChain::new(block.header, HEIGHT, &[]).unwrap();
+        let target = block.txids[2];
+        let valid = merkle_block(&block.header, &block.txids, target);
+        assert_eq!(
+            verify_inclusion(&chain, &target.to_string(), HEIGHT, &valid),
+            Ok(())
+        );
+
+        let other_tx = merkle_block(&block.header, &block.txids, block.txids[0]);
+        let err = verify_inclusion(&chain, &target.to_string(), HEIGHT, &other_tx).unwrap_err();
+        assert!(err.contains("does not include"), "{err}");
+
+        let mut other_header = block.header;
+        other_header.nonce = 1;
+        let other_block = merkle_block(&other_header, &block.txids, target);
+        let err = verify_inclusion(&chain, &target.to_string(), HEIGHT, &other_block).unwrap_err();
+        assert!(err.starts_with("merkleblock is for block"), "{err}");
+
+        let err = verify_inclusion(
+            &chain,
+            &target.to_string(),
+            HEIGHT,
+            &InclusionProof::MerkleBlock("00".to_string()),
+        )
+        .unwrap_err();
+        assert!(err.starts_with("invalid merkleblock"), "{err}");
+    }
+
+    #[test]
+    fn verify_utxos_marks_proven_outputs() {
+        struct Source(InclusionProof);
+        impl ProofSource for Source {
+            fn inclusion_proof(&self, _: &str, _: u32) -> Result<InclusionProof, String> {
+                Ok(self.0.clone())
+            }
+        }
+
+        let block = block();
+        let chain = HeaderChain::new(block.header, HEIGHT, &[]).unwrap();
+        let mut utxos: Vec<Utxo> = serde_json::from_value(json!([
+            { "txid": block.txids[2].to_string(), "vout": 0, "amount": 1000, "height": HEIGHT },
+            { "txid": block.txids[2].to_string(), "vout": 1, "amount": 1000, "height": HEIGHT },
+            { "txid": block.txids[2].to_string(), "vout": 2, "amount": 1000 },
+        ]))
+        .unwrap();
+        let report = verify_utxos(
+            &chain,
+            &Source(InclusionProof::Branch(branch(&block))),
+            &mut utxos,
+        );
+
+        // Both outputs of the proven transaction share one proof.
+        assert_eq!(report.verified, 1);
+        assert_eq!(report.failures[0].kind, ProofFailureKind::NotSynced);
+        let verified: Vec<_> = utxos.iter().map(|utxo| utxo.verified).collect();
+        assert_eq!(verified, [true, true, false]);
+    }
+
+    #[test]
+    fn signer_proves_json_utxos_itself() {
+        let block = block();
+        let chain = HeaderChain::new(block.header, HEIGHT, &[]).unwrap();
+        let txid = block.txids[2].to_string();
+        // A claimed flag is ignored.
+        let utxos: Vec<Utxo> = serde_json::from_value(json!([
+            { "txid": txid, "vout": 0, "amount": 1000, "height": HEIGHT, "verified": true }
+        ]))
+        .unwrap();
+        assert!(!utxos[0].verified);
+
+        let options = |proof: InclusionProof| {
+            BuildOptions::from_json(
+                &json!({
+                    "require_verified_inputs": true,
+                    "input_proofs": {
+                        "header_chain": hex::encode(chain.to_bytes()),
+                        "proofs": { txid.clone(): proof },
+                    },
+                })
+                .to_string(),
+            )
+            .unwrap()
+        };
+        let unproven = BuildOptions::from_json(r#"{"require_verified_inputs":true}"#).unwrap();
+        assert!(ensure_verified(&utxos, &unproven)
+            .unwrap_err()
+            .contains("has not been proof-verified"));
+
+        let valid = options(InclusionProof::Branch(branch(&block)));
+        assert_eq!(ensure_verified(&utxos, &valid), Ok(()));
+        let valid = options(merkle_block(&block.header, &block.txids, block.txids[2]));
+        assert_eq!(ensure_verified(&utxos, &valid), Ok(()));
+
+        let mut bad = branch(&block);
+        bad.merkle.reverse();
+        let err = ensure_verified(&utxos, &options(InclusionProof::Branch(bad))).unwrap_err();
+        assert!(err.contains("branch leads to root"), "{err}");
+
+        let bad = merkle_block(&block.header, &block.txids, block.txids[1]);
+        let err = ensure_verified(&utxos, &options(bad)).unwrap_err();
+        assert!(err.contains("does not include"), "{err}");
+
+        let mut other = utxos.clone();
+        other[0].txid = block.txids[1].to_string();
+        let err = ensure_verified(&other, &valid).unwrap_err();
+        assert!(err.ends_with("no inclusion proof supplied"), "{err}");
+    }
+}
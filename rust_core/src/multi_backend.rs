@@ -0,0 +1,921 @@
+//! Failover and cross-checking over several chain backends.
+//!
+//! One server is a single point of failure and a single point of trust: if it is down the wallet
+//! is blind, and if it lags or lies the wallet shows a wrong balance. [`MultiBackend`] wraps any
+//! number of [`ChainBackend`]s and is itself one, so nothing upstream changes.
+//!
+//! In [`CrossCheckMode::Failover`] it asks one backend at a time and moves on when a backend
+//! errors. In [`CrossCheckMode::Quorum`] ("paranoid mode") it asks all of them at once and only
+//! answers with a UTXO set, tip or broadcast result that enough of them agree on. Either way, what
+//! went wrong is recorded as a [`BackendWarning`] for the app's diagnostics screen instead of
+//! being silently swallowed.
+//!
+//! Raw transactions are checked locally in both modes: a response whose txid does not match the
+//! request is treated as an error, whichever backend sent it.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+use bitcoin::Transaction;
+use serde::{Deserialize, Serialize};
+
+use crate::chain_backend::{AddressBalance, ChainBackend, ChainStatus, HistoryEntry, Utxo};
+
+/// How a [`MultiBackend`] combines its backends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum CrossCheckMode {
+    /// Use the first backend that answers, starting with the last one that worked.
+    Failover,
+    /// Ask every backend and require `required` identical answers for UTXO sets, tips, history,
+    /// balances and broadcasts. Fee estimates use the median of the answers.
+    Quorum { required: usize },
+}
+
+/// A backend's error or disagreeing answer.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackendDetail {
+    pub backend: String,
+    pub detail: String,
+}
+
+/// Something the app should surface in diagnostics.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum BackendWarning {
+    /// A backend failed and the next one was used.
+    Failover {
+        operation: String,
+        failed: BackendDetail,
+    },
+    /// Backends disagreed or failed in quorum mode. `agreeing` is empty when no answer reached
+    /// quorum and the operation failed.
+    Divergence {
+        operation: String,
+        /// The address or txid queried, if any.
+        subject: Option<String>,
+        agreeing: Vec<String>,
+        diverging: Vec<BackendDetail>,
+        failed: Vec<BackendDetail>,
+    },
+}
+
+/// A [`ChainBackend`] over several others.
+pub struct MultiBackend {
+    backends: Vec<Box<dyn ChainBackend>>,
+    mode: CrossCheckMode,
+    /// Index of the backend that answered last in failover mode.
+    preferred: AtomicUsize,
+    warnings: Mutex<Vec<BackendWarning>>,
+}
+
+impl MultiBackend {
+    /// Combines `backends`, in order of preference.
+    ///
+    /// A quorum must be at least one and at most the number of backends; a majority
+    /// (`n / 2 + 1`) is the usual choice.
+    pub fn new(backends: Vec<Box<dyn ChainBackend>>, mode: CrossCheckMode) -> Result<Self, String> {
+        if backends.is_empty() {
+            return Err("at least one backend is required".to_string());
+        }
+        if let CrossCheckMode::Quorum { required } = mode {
+            if required == 0 || required > backends.len() {
+                return Err(format!(
+                    "quorum of {required} is impossible with {} backends",
+                    backends.len()
+                ));
+            }
+        }
+        Ok(Self {
+            backends,
+            mode,
+            preferred: AtomicUsize::new(0),
+            warnings: Mutex::new(Vec::new()),
+        })
+    }
+
+    pub fn mode(&self) -> CrossCheckMode {
+        self.mode
+    }
+
+    /// Removes and returns every warning recorded so far, oldest first.
+    pub fn take_warnings(&self) -> Vec<BackendWarning> {
+        std::mem::take(
+            &mut *self
+                .warnings
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()),
+        )
+    }
+
+    fn warn(&self, warning: BackendWarning) {
+        self.warnings
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(warning);
+    }
+
+    /// Tries each backend in turn, starting with the preferred one, and returns the first
+    /// answer that `accept` does not reject.
+    fn failover<T>(
+        &self,
+        operation: &str,
+        call: impl Fn(&dyn ChainBackend) -> Result<T, String>,
+        accept: impl Fn(&T) -> Result<(), String>,
+    ) -> Result<T, String> {
+        let count = self.backends.len();
+        let start = self.preferred.load(Ordering::Relaxed) % count;
+        let mut errors = Vec::new();
+        for offset in 0..count {
+            let index = (start + offset) % count;
+            let backend = self.backends[index].as_ref();
+            match call(backend).and_then(|value| accept(&value).map(|()| value)) {
+                Ok(value) => {
+                    self.preferred.store(index, Ordering::Relaxed);
+                    return Ok(value);
+                }
+                Err(error) => {
+                    let failed = BackendDetail {
+                        backend: backend.name(),
+                        detail: error,
+                    };
+                    errors.push(format!("{}: {}", failed.backend, failed.detail));
+                    self.warn(BackendWarning::Failover {
+                        operation: operation.to_string(),
+                        failed,
+                    });
+                }
+            }
+        }
+        Err(format!(
+            "every backend failed {operation}: {}",
+            errors.join("; ")
+        ))
+    }
+
+    /// Asks every backend concurrently.
+    fn ask_all<T: Send>(
+        &self,
+        call: impl Fn(&dyn ChainBackend) -> Result<T, String> + Sync,
+    ) -> Vec<(String, Result<T, String>)> {
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = self
+                .backends
+                .iter()
+                .map(|backend| {
+                    let call = &call;
+                    scope.spawn(move || (backend.name(), call(backend.as_ref())))
+                })
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("backend call panicked"))
+                .collect()
+        })
+    }
+
+    /// Asks every backend and returns an answer at least `required` of them agree on, recording
+    /// a divergence warning whenever they were not unanimous.
+    ///
+    /// Answers agree when their `key`s are equal; the first agreeing backend's answer is returned.
+    fn quorum<T: Send, K: PartialEq>(
+        &self,
+        required: usize,
+        operation: &str,
+        subject: Option<&str>,
+        call: impl Fn(&dyn ChainBackend) -> Result<T, String> + Sync,
+        key: impl Fn(&T) -> K,
+        describe: impl Fn(&K) -> String,
+    ) -> Result<T, String> {
+        let mut groups: Vec<(K, T, Vec<String>)> = Vec::new();
+        let mut failed = Vec::new();
+        for (backend, answer) in self.ask_all(call) {
+            match answer {
+                Ok(value) => {
+                    let value_key = key(&value);
+                    match groups.iter_mut().find(|(known, _, _)| *known == value_key) {
+                        Some((_, _, names)) => names.push(backend),
+                        None => groups.push((value_key, value, vec![backend])),
+                    }
+                }
+                Err(detail) => failed.push(BackendDetail { backend, detail }),
+            }
+        }
+
+        // Stable: on a tie the answer from the earlier-configured backend wins.
+        let winner = groups
+            .iter()
+            .enumerate()
+            .max_by_key(|(index, (_, _, names))| (names.len(), std::cmp::Reverse(*index)))
+            .map(|(index, _)| index);
+        let reached = winner.filter(|index| groups[*index].2.len() >= required);
+
+        let unanimous = failed.is_empty() && groups.len() == 1;
+        if !unanimous {
+            let diverging = groups
+                .iter()
+                .enumerate()
+                .filter(|(index, _)| Some(*index) != reached)
+                .flat_map(|(_, (value_key, _, names))| {
+                    let detail = describe(value_key);
+                    names.iter().map(move |backend| BackendDetail {
+                        backend: backend.clone(),
+                        detail: detail.clone(),
+                    })
+                })
+                .collect::<Vec<_>>();
+            self.warn(BackendWarning::Divergence {
+                operation: operation.to_string(),
+                subject: subject.map(str::to_string),
+                agreeing: reached
+                    .map(|index| groups[index].2.clone())
+                    .unwrap_or_default(),
+                diverging,
+                failed,
+            });
+        }
+
+        match reached {
+            Some(index) => Ok(groups.swap_remove(index).1),
+            None => Err(format!(
+                "backends did not reach a quorum of {required} on {operation}{}",
+                subject
+                    .map(|subject| format!(" for {subject}"))
+                    .unwrap_or_default()
+            )),
+        }
+    }
+}
+
+impl ChainBackend for MultiBackend {
+    fn name(&self) -> String {
+        let names: Vec<String> = self.backends.iter().map(|backend| backend.name()).collect();
+        match self.mode {
+            CrossCheckMode::Failover => format!("failover [{}]", names.join(", ")),
+            CrossCheckMode::Quorum { required } => {
+                format!("quorum {required} of [{}]", names.join(", "))
+            }
+        }
+    }
+
+    fn chain_status(&self) -> Result<ChainStatus, String> {
+        match self.mode {
+            CrossCheckMode::Failover => {
+                self.failover("chain_status", |b| b.chain_status(), accept_any)
+            }
+            CrossCheckMode::Quorum { required } => self.quorum(
+                required,
+                "chain_status",
+                None,
+                |b| b.chain_status(),
+                ChainStatus::clone,
+                |status| format!("height {} ({})", status.height, status.best_block_hash),
+            ),
+        }
+    }
+
+    fn utxos(&self, address: &str) -> Result<Vec<Utxo>, String> {
+        match self.mode {
+            CrossCheckMode::Failover => self.failover("utxos", |b| b.utxos(address), accept_any),
+            CrossCheckMode::Quorum { required } => {
+                // Backends differ in metadata (confirmation counts move with each one's tip), so
+                // agreement is on the outpoints and values that determine what can be spent.
+                self.quorum(
+                    required,
+                    "utxos",
+                    Some(address),
+                    |b| b.utxos(address),
+                    |utxos| UtxoSet::new(utxos),
+                    UtxoSet::describe,
+                )
+            }
+        }
+    }
+
+    fn history(&self, address: &str) -> Result<Vec<HistoryEntry>, String> {
+        match self.mode {
+            CrossCheckMode::Failover => {
+                self.failover("history", |b| b.history(address), accept_any)
+            }
+            CrossCheckMode::Quorum { required } => self.quorum(
+                required,
+                "history",
+                Some(address),
+                |b| b.history(address),
+                Vec::clone,
+                |history| format!("{} transactions", history.len()),
+            ),
+        }
+    }
+
+    fn balance(&self, address: &str) -> Result<AddressBalance, String> {
+        match self.mode {
+            CrossCheckMode::Failover => {
+                self.failover("balance", |b| b.balance(address), accept_any)
+            }
+            CrossCheckMode::Quorum { required } => self.quorum(
+                required,
+                "balance",
+                Some(address),
+                |b| b.balance(address),
+                |balance| *balance,
+                |balance| {
+                    format!(
+                        "confirmed {} RDD, unconfirmed {} base units",
+                        balance.confirmed, balance.unconfirmed
+                    )
+                },
+            ),
+        }
+    }
+
+    /// Estimates legitimately differ between nodes, so quorum mode takes the median of the
+    /// answers rather than demanding agreement. A single outlier cannot move it.
+    fn estimate_fee_per_kb(&self, target_blocks: u16) -> Result<u64, String> {
+        match self.mode {
+            CrossCheckMode::Failover => self.failover(
+                "estimate_fee_per_kb",
+                |b| b.estimate_fee_per_kb(target_blocks),
+                accept_any,
+            ),
+            CrossCheckMode::Quorum { required } => {
+                let mut estimates = Vec::new();
+                let mut errors = Vec::new();
+                for (backend, answer) in self.ask_all(|b| b.estimate_fee_per_kb(target_blocks)) {
+                    match answer {
+                        Ok(fee) => estimates.push(fee),
+                        Err(error) => errors.push(format!("{backend}: {error}")),
+                    }
+                }
+                if estimates.len() < required {
+                    return Err(format!(
+                        "only {} of {required} required backends estimated a fee: {}",
+                        estimates.len(),
+                        errors.join("; ")
+                    ));
+                }
+                estimates.sort_unstable();
+                Ok(estimates[estimates.len() / 2])
+            }
+        }
+    }
+
+    /// The first backend whose transaction hashes to `txid` wins, in either mode; there is
+    /// nothing to vote on once the content is self-authenticating.
+    fn raw_transaction(&self, txid: &str) -> Result<String, String> {
+        self.failover(
+            "raw_transaction",
+            |b| b.raw_transaction(txid),
+            |hex| {
+                let computed = txid_of(hex)?;
+                if computed.eq_ignore_ascii_case(txid) {
+                    Ok(())
+                } else {
+                    Err(format!("returned transaction {computed} instead of {txid}"))
+                }
+            },
+        )
+    }
+
+    /// Quorum mode broadcasts through every backend, which also spreads the transaction faster,
+    /// and requires `required` of them to accept it.
+    fn broadcast(&self, tx_hex: &str) -> Result<String, String> {
+        let expected = txid_of(tx_hex)?;
+        let check = |txid: &String| {
+            if txid.eq_ignore_ascii_case(&expected) {
+                Ok(())
+            } else {
+                Err(format!("reported txid {txid} for transaction {expected}"))
+            }
+        };
+        match self.mode {
+            CrossCheckMode::Failover => self
+                .failover("broadcast", |b| b.broadcast(tx_hex), check)
+                .map(|_| expected.clone()),
+            CrossCheckMode::Quorum { required } => self.quorum(
+                required,
+                "broadcast",
+                Some(&expected),
+                |b| {
+                    b.broadcast(tx_hex)
+                        .and_then(|txid| check(&txid).map(|()| expected.clone()))
+                },
+                String::clone,
+                |txid| format!("accepted as {txid}"),
+            ),
+        }
+    }
+}
+
+/// The parts of a UTXO set backends must agree on, in canonical order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct UtxoSet(Vec<(String, u32, u64)>);
+
+impl UtxoSet {
+    fn new(utxos: &[Utxo]) -> Self {
+        let mut outputs: Vec<_> = utxos
+            .iter()
+            .map(|utxo| {
+                (
+                    utxo.txid.to_ascii_lowercase(),
+                    utxo.vout,
+                    utxo.amount.to_base(),
+                )
+            })
+            .collect();
+        outputs.sort_unstable();
+        Self(outputs)
+    }
+
+    fn describe(&self) -> String {
+        let total: u64 = self
+            .0
+            .iter()
+            .map(|(_, _, amount)| amount)
+            .fold(0, |a, b| a.saturating_add(*b));
+        format!(
+            "{} outputs totalling {} RDD",
+            self.0.len(),
+            crate::amount::Amount::from_base(total)
+        )
+    }
+}
+
+fn accept_any<T>(_: &T) -> Result<(), String> {
+    Ok(())
+}
+
+fn txid_of(tx_hex: &str) -> Result<String, String> {
+    let raw = hex::decode(tx_hex.trim()).map_err(|e| format!("invalid transaction hex: {e}"))?;
+    let tx: Transaction =
+        bitcoin::consensus::deserialize(&raw).map_err(|e| format!("invalid transaction: {e}"))?;
+    Ok(tx.compute_txid().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bitcoin::absolute::LockTime;
+    use bitcoin::consensus::encode::serialize_hex;
+    use bitcoin::transaction::Version;
+    use bitcoin::{OutPoint, ScriptBuf, TxIn, TxOut};
+
+    use super::*;
+    use crate::amount::Amount;
+
+    const ADDRESS: &str = "RsubjectAddress";
+
+    /// Answers from fixed results and logs each call as `name.operation`.
+    struct MockBackend {
+        name: &'static str,
+        tip: Result<u32, String>,
+        utxos: Result<Vec<(&'static str, u32, u64)>, String>,
+        fee: Result<u64, String>,
+        raw_transaction: Result<String, String>,
+        broadcast: Result<String, String>,
+        calls: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl MockBackend {
+        fn new(name: &'static str, calls: &Arc<Mutex<Vec<String>>>) -> Self {
+            Self {
+                name,
+                tip: Ok(100),
+                utxos: Ok(Vec::new()),
+                fee: Err("down".to_string()),
+                raw_transaction: Err("down".to_string()),
+                broadcast: Err("down".to_string()),
+                calls: Arc::clone(calls),
+            }
+        }
+
+        fn log(&self, operation: &str) {
+            self.calls
+                .lock()
+                .unwrap()
+                .push(format!("{}.{operation}", self.name));
+        }
+    }
+
+    impl ChainBackend for MockBackend {
+        fn name(&self) -> String {
+            self.name.to_string()
+        }
+
+        fn chain_status(&self) -> Result<ChainStatus, String> {
+            self.log("chain_status");
+            self.tip.clone().map(|height| ChainStatus {
+                height,
+                best_block_hash: format!("{height:064x}"),
+            })
+        }
+
+        fn utxos(&self, address: &str) -> Result<Vec<Utxo>, String> {
+            self.log("utxos");
+            let outputs = self.utxos.clone()?;
+            Ok(outputs
+                .into_iter()
+                .map(|(txid, vout, amount)| {
+                    serde_json::from_value(serde_json::json!({
+                        "txid": txid.repeat(32),
+                        "vout": vout,
+                        "amount": amount,
+                        "address": address,
+                        // Moves with each backend's tip, so it legitimately differs.
+                        "confirmations": self.tip.clone().unwrap_or(0),
+                    }))
+                    .unwrap()
+                })
+                .collect())
+        }
+
+        fn history(&self, _: &str) -> Result<Vec<HistoryEntry>, String> {
+            Err("unused".to_string())
+        }
+
+        fn balance(&self, _: &str) -> Result<AddressBalance, String> {
+            Err("unused".to_string())
+        }
+
+        fn estimate_fee_per_kb(&self, _: u16) -> Result<u64, String> {
+            self.log("estimate_fee_per_kb");
+            self.fee.clone()
+        }
+
+        fn raw_transaction(&self, _: &str) -> Result<String, String> {
+            self.log("raw_transaction");
+            self.raw_transaction.clone()
+        }
+
+        fn broadcast(&self, _: &str) -> Result<String, String> {
+            self.log("broadcast");
+            self.broadcast.clone()
+        }
+    }
+
+    fn multi(backends: Vec<MockBackend>, mode: CrossCheckMode) -> MultiBackend {
+        let backends = backends
+            .into_iter()
+            .map(|backend| Box::new(backend) as Box<dyn ChainBackend>)
+            .collect();
+        MultiBackend::new(backends, mode).unwrap()
+    }
+
+    fn take_calls(calls: &Mutex<Vec<String>>) -> Vec<String> {
+        std::mem::take(&mut *calls.lock().unwrap())
+    }
+
+    fn detail(backend: &str, detail: &str) -> BackendDetail {
+        BackendDetail {
+            backend: backend.to_string(),
+            detail: detail.to_string(),
+        }
+    }
+
+    /// A distinct transaction per `nonce`, as hex, with its txid.
+    fn transaction(nonce: u32) -> (String, String) {
+        let tx = Transaction {
+            version: Version(1),
+            lock_time: LockTime::from_consensus(nonce),
+            input: vec![TxIn {
+                previous_output: OutPoint::new("11".repeat(32).parse().unwrap(), 0),
+                ..TxIn::default()
+            }],
+            output: vec![TxOut {
+                value: bitcoin::Amount::from_sat(1_000),
+                script_pubkey: ScriptBuf::new(),
+            }],
+        };
+        (serialize_hex(&tx), tx.compute_txid().to_string())
+    }
+
+    fn outputs_detail(count: usize, total: u64) -> String {
+        format!("{count} outputs totalling {} RDD", Amount::from_base(total))
+    }
+
+    #[test]
+    fn new_rejects_impossible_quorums() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let backends = |count: usize| {
+            (0..count)
+                .map(|_| Box::new(MockBackend::new("a", &calls)) as Box<dyn ChainBackend>)
+                .collect::<Vec<_>>()
+        };
+        assert!(MultiBackend::new(Vec::new(), CrossCheckMode::Failover).is_err());
+        for required in [0, 3] {
+            let err = MultiBackend::new(backends(2), CrossCheckMode::Quorum { required });
+            assert_eq!(
+                err.err().unwrap(),
+                format!("quorum of {required} is impossible with 2 backends")
+            );
+        }
+    }
+
+    #[test]
+    fn failover_tries_backends_in_order_from_the_last_that_worked() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let backend = multi(
+            vec![
+                MockBackend {
+                    tip: Err("down".to_string()),
+                    fee: Ok(1_000),
+                    ..MockBackend::new("a", &calls)
+                },
+                MockBackend::new("b", &calls),
+                MockBackend::new("c", &calls),
+            ],
+            CrossCheckMode::Failover,
+        );
+
+        assert_eq!(backend.chain_status().unwrap().height, 100);
+        assert_eq!(take_calls(&calls), ["a.chain_status", "b.chain_status"]);
+        assert_eq!(
+            backend.take_warnings(),
+            [BackendWarning::Failover {
+                operation: "chain_status".to_string(),
+                failed: detail("a", "down"),
+            }]
+        );
+
+        // b answered last, so it is asked first, and the search wraps around to a.
+        backend.chain_status().unwrap();
+        assert_eq!(take_calls(&calls), ["b.chain_status"]);
+        assert!(backend.take_warnings().is_empty());
+        assert_eq!(backend.estimate_fee_per_kb(6), Ok(1_000));
+        assert_eq!(
+            take_calls(&calls),
+            [
+                "b.estimate_fee_per_kb",
+                "c.estimate_fee_per_kb",
+                "a.estimate_fee_per_kb"
+            ]
+        );
+        assert_eq!(backend.take_warnings().len(), 2);
+        backend.estimate_fee_per_kb(6).unwrap();
+        assert_eq!(take_calls(&calls), ["a.estimate_fee_per_kb"]);
+
+        let err = backend.raw_transaction(&"ab".repeat(32)).unwrap_err();
+        assert_eq!(
+            err,
+            "every backend failed raw_transaction: a: down; b: down; c: down"
+        );
+    }
+
+    #[test]
+    fn quorum_agrees_on_outpoints_and_reports_divergence() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let set = vec![("aa", 0, 500), ("bb", 1, 700)];
+        let reversed = vec![("bb", 1, 700), ("aa", 0, 500)];
+        let other = vec![("aa", 0, 500)];
+
+        // Unanimous answers, in any order and with any metadata, raise no warning.
+        let backend = multi(
+            vec![
+                MockBackend {
+                    utxos: Ok(set.clone()),
+                    ..MockBackend::new("a", &calls)
+                },
+                MockBackend {
+                    tip: Ok(101),
+                    utxos: Ok(reversed.clone()),
+                    ..MockBackend::new("b", &calls)
+                },
+            ],
+            CrossCheckMode::Quorum { required: 2 },
+        );
+        assert_eq!(backend.utxos(ADDRESS).unwrap().len(), 2);
+        assert!(backend.take_warnings().is_empty());
+
+        // Two of three agree.
+        let backend = multi(
+            vec![
+                MockBackend {
+                    utxos: Ok(other.clone()),
+                    ..MockBackend::new("a", &calls)
+                },
+                MockBackend {
+                    utxos: Ok(set.clone()),
+                    ..MockBackend::new("b", &calls)
+                },
+                MockBackend {
+                    utxos: Ok(reversed.clone()),
+                    ..MockBackend::new("c", &calls)
+                },
+            ],
+            CrossCheckMode::Quorum { required: 2 },
+        );
+        let utxos = backend.utxos(ADDRESS).unwrap();
+        assert_eq!(utxos.len(), 2);
+        // The first agreeing backend's answer is returned as it sent it.
+        assert_eq!(utxos[0].txid, "aa".repeat(32));
+        assert_eq!(
+            backend.take_warnings(),
+            [BackendWarning::Divergence {
+                operation: "utxos".to_string(),
+                subject: Some(ADDRESS.to_string()),
+                agreeing: vec!["b".to_string(), "c".to_string()],
+                diverging: vec![detail("a", &outputs_detail(1, 500))],
+                failed: Vec::new(),
+            }]
+        );
+    }
+
+    #[test]
+    fn quorum_ties_go_to_the_earlier_backend_and_misses_fail() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let backends = || {
+            vec![
+                MockBackend {
+                    utxos: Ok(vec![("aa", 0, 500)]),
+                    ..MockBackend::new("a", &calls)
+                },
+                MockBackend {
+                    utxos: Ok(vec![("bb", 0, 900)]),
+                    ..MockBackend::new("b", &calls)
+                },
+                MockBackend {
+                    utxos: Err("timed out".to_string()),
+                    ..MockBackend::new("c", &calls)
+                },
+            ]
+        };
+
+        let backend = multi(backends(), CrossCheckMode::Quorum { required: 1 });
+        assert_eq!(backend.utxos(ADDRESS).unwrap()[0].txid, "aa".repeat(32));
+        assert_eq!(
+            backend.take_warnings(),
+            [BackendWarning::Divergence {
+                operation: "utxos".to_string(),
+                subject: Some(ADDRESS.to_string()),
+                agreeing: vec!["a".to_string()],
+                diverging: vec![detail("b", &outputs_detail(1, 900))],
+                failed: vec![detail("c", "timed out")],
+            }]
+        );
+
+        let backend = multi(backends(), CrossCheckMode::Quorum { required: 2 });
+        assert_eq!(
+            backend.utxos(ADDRESS).unwrap_err(),
+            format!("backends did not reach a quorum of 2 on utxos for {ADDRESS}")
+        );
+        assert_eq!(
+            backend.take_warnings(),
+            [BackendWarning::Divergence {
+                operation: "utxos".to_string(),
+                subject: Some(ADDRESS.to_string()),
+                agreeing: Vec::new(),
+                diverging: vec![
+                    detail("a", &outputs_detail(1, 500)),
+                    detail("b", &outputs_detail(1, 900)),
+                ],
+                failed: vec![detail("c", "timed out")],
+            }]
+        );
+
+        // Tips are compared whole.
+        let backend = multi(
+            vec![
+                MockBackend {
+                    tip: Ok(101),
+                    ..MockBackend::new("a", &calls)
+                },
+                MockBackend::new("b", &calls),
+            ],
+            CrossCheckMode::Quorum { required: 2 },
+        );
+        assert!(backend
+            .chain_status()
+            .unwrap_err()
+            .ends_with("quorum of 2 on chain_status"));
+    }
+
+    #[test]
+    fn quorum_fee_is_the_median() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let fees = |fees: [Result<u64, String>; 3]| {
+            let [a, b, c] = fees;
+            multi(
+                vec![
+                    MockBackend {
+                        fee: a,
+                        ..MockBackend::new("a", &calls)
+                    },
+                    MockBackend {
+                        fee: b,
+                        ..MockBackend::new("b", &calls)
+                    },
+                    MockBackend {
+                        fee: c,
+                        ..MockBackend::new("c", &calls)
+                    },
+                ],
+                CrossCheckMode::Quorum { required: 2 },
+            )
+        };
+
+        // One outlier cannot move it.
+        let backend = fees([Ok(1_000_000_000), Ok(1_000), Ok(5_000)]);
+        assert_eq!(backend.estimate_fee_per_kb(6), Ok(5_000));
+        // With an even count the upper middle is taken.
+        let backend = fees([Ok(9_000), Err("down".to_string()), Ok(2_000)]);
+        assert_eq!(backend.estimate_fee_per_kb(6), Ok(9_000));
+
+        let backend = fees([Ok(1_000), Err("down".to_string()), Err("busy".to_string())]);
+        assert_eq!(
+            backend.estimate_fee_per_kb(6).unwrap_err(),
+            "only 1 of 2 required backends estimated a fee: b: down; c: busy"
+        );
+    }
+
+    #[test]
+    fn raw_transaction_must_hash_to_the_requested_txid() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let (wanted_hex, wanted) = transaction(1);
+        let (other_hex, other) = transaction(2);
+
+        // Checked in quorum mode too, where the first matching answer wins.
+        for mode in [
+            CrossCheckMode::Failover,
+            CrossCheckMode::Quorum { required: 2 },
+        ] {
+            let backend = multi(
+                vec![
+                    MockBackend {
+                        raw_transaction: Ok(other_hex.clone()),
+                        ..MockBackend::new("a", &calls)
+                    },
+                    MockBackend {
+                        raw_transaction: Ok(wanted_hex.clone()),
+                        ..MockBackend::new("b", &calls)
+                    },
+                ],
+                mode,
+            );
+            assert_eq!(backend.raw_transaction(&wanted), Ok(wanted_hex.clone()));
+            assert_eq!(
+                backend.take_warnings(),
+                [BackendWarning::Failover {
+                    operation: "raw_transaction".to_string(),
+                    failed: detail(
+                        "a",
+                        &format!("returned transaction {other} instead of {wanted}")
+                    ),
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn broadcast_rejects_a_mismatched_txid() {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let (tx_hex, txid) = transaction(1);
+        let wrong = "ff".repeat(32);
+        let mismatch = format!("reported txid {wrong} for transaction {txid}");
+        let backends = || {
+            vec![
+                MockBackend {
+                    broadcast: Ok(wrong.clone()),
+                    ..MockBackend::new("a", &calls)
+                },
+                MockBackend {
+                    broadcast: Ok(txid.clone()),
+                    ..MockBackend::new("b", &calls)
+                },
+                MockBackend {
+                    broadcast: Ok(txid.to_uppercase()),
+                    ..MockBackend::new("c", &calls)
+                },
+            ]
+        };
+
+        let backend = multi(backends(), CrossCheckMode::Failover);
+        assert_eq!(backend.broadcast(&tx_hex), Ok(txid.clone()));
+        assert_eq!(
+            backend.take_warnings(),
+            [BackendWarning::Failover {
+                operation: "broadcast".to_string(),
+                failed: detail("a", &mismatch),
+            }]
+        );
+
+        let backend = multi(backends(), CrossCheckMode::Quorum { required: 2 });
+        assert_eq!(backend.broadcast(&tx_hex), Ok(txid.clone()));
+        assert_eq!(
+            backend.take_warnings(),
+            [BackendWarning::Divergence {
+                operation: "broadcast".to_string(),
+                subject: Some(txid.clone()),
+                agreeing: vec!["b".to_string(), "c".to_string()],
+                diverging: Vec::new(),
+                failed: vec![detail("a", &mismatch)],
+            }]
+        );
+
+        let backend = multi(backends(), CrossCheckMode::Quorum { required: 3 });
+        assert_eq!(
+            backend.broadcast(&tx_hex).unwrap_err(),
+            format!("backends did not reach a quorum of 3 on broadcast for {txid}")
+        );
+    }
+}
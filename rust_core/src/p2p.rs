@@ -0,0 +1,1303 @@
+//! Reddcoin P2P wire protocol.
+//!
+//! Speaks the node-to-node protocol directly, so the wallet can broadcast a transaction and fetch
+//! headers from any Reddcoin node instead of relying on an indexer. Only the messages a light
+//! client needs are decoded: the `version`/`verack` handshake, `ping`/`pong`, `inv`/`getdata`/
+//! `notfound`, `tx`, `block`, `getheaders`/`headers`, `sendheaders`, `reject` and the BIP157
+//! `getcfilters`/`cfilter` pair. Everything else arrives as [`Message::Unknown`] and is ignored by
+//! [`Peer`].
+//!
+//! Every message is framed by the network magic from [`ChainParams`], a NUL-padded command, the
+//! payload length and the first four bytes of the payload's double SHA-256.
+//!
+//! A peer is not a trusted party: headers it returns are validated by [`HeaderChain`], and a
+//! broadcast only proves the peer did not reject the transaction. Nodes built on Bitcoin Core 0.20
+//! or later no longer send `reject` at all, so confirm the transaction through a backend
+//! afterwards.
+
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use bitcoin::bip158::BlockFilter;
+use bitcoin::block::Header;
+use bitcoin::hashes::{sha256d, Hash};
+use bitcoin::{Block, BlockHash, Transaction, Txid};
+
+use crate::block_filter::{decode_block, FilterSource, BASIC_FILTER_TYPE, FILTER_BATCH};
+use crate::chain_params::ChainParams;
+use crate::header_chain::{ConnectOutcome, HeaderChain, SyncReport, HEADER_SIZE};
+
+/// Protocol version announced in `version`. 70015 is understood by every Reddcoin Core release
+/// still on the network and enables `sendheaders` and nonce-carrying pings.
+pub const PROTOCOL_VERSION: i32 = 70015;
+/// User agent announced in `version`.
+pub const USER_AGENT: &str = "/redd-wallet:1.0/";
+/// Default connect, read and write timeout, also bounding each request's wait for its answer.
+pub const DEFAULT_PEER_TIMEOUT: Duration = Duration::from_secs(20);
+/// Payloads longer than this are refused rather than buffered (the node's own limit).
+pub const MAX_PAYLOAD_SIZE: usize = 4 * 1024 * 1024;
+/// Most headers a node returns for one `getheaders`; a full batch means more may follow.
+pub const MAX_HEADERS_RESULTS: usize = 2000;
+/// Most inventory entries one `inv`, `getdata` or `notfound` may carry.
+const MAX_INV_SIZE: usize = 50_000;
+/// Size of the frame before each payload.
+const MESSAGE_HEADER_SIZE: usize = 24;
+/// Length of the NUL-padded command field.
+const COMMAND_SIZE: usize = 12;
+
+/// Service bit of nodes serving BIP157 compact block filters.
+pub const NODE_COMPACT_FILTERS: u64 = 1 << 6;
+
+/// `reject` code for a transaction the node already has.
+pub const REJECT_DUPLICATE: u8 = 0x12;
+
+const MSG_TX: u32 = 1;
+const MSG_BLOCK: u32 = 2;
+const MSG_WITNESS_FLAG: u32 = 1 << 30;
+
+/// An `inv`, `getdata` or `notfound` entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Inventory {
+    Tx(Txid),
+    WitnessTx(Txid),
+    Block(BlockHash),
+    /// A type this client does not use, kept so the entry can be re-encoded unchanged.
+    Other {
+        kind: u32,
+        hash: [u8; 32],
+    },
+}
+
+impl Inventory {
+    /// The transaction this entry refers to, whatever its witness flag.
+    pub fn txid(&self) -> Option<Txid> {
+        match self {
+            Self::Tx(txid) | Self::WitnessTx(txid) => Some(*txid),
+            _ => None,
+        }
+    }
+
+    fn kind_and_hash(&self) -> (u32, [u8; 32]) {
+        match self {
+            Self::Tx(txid) => (MSG_TX, txid.to_byte_array()),
+            Self::WitnessTx(txid) => (MSG_TX | MSG_WITNESS_FLAG, txid.to_byte_array()),
+            Self::Block(hash) => (MSG_BLOCK, hash.to_byte_array()),
+            Self::Other { kind, hash } => (*kind, *hash),
+        }
+    }
+
+    fn from_kind_and_hash(kind: u32, hash: [u8; 32]) -> Self {
+        match kind {
+            MSG_TX => Self::Tx(Txid::from_byte_array(hash)),
+            k if k == MSG_TX | MSG_WITNESS_FLAG => Self::WitnessTx(Txid::from_byte_array(hash)),
+            MSG_BLOCK => Self::Block(BlockHash::from_byte_array(hash)),
+            kind => Self::Other { kind, hash },
+        }
+    }
+}
+
+/// A network address as carried in `version`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NetAddress {
+    pub services: u64,
+    /// IPv4 addresses are carried IPv4-mapped.
+    pub ip: Ipv6Addr,
+    pub port: u16,
+}
+
+impl NetAddress {
+    /// The all-zero address, which nodes accept for fields they do not use.
+    pub const UNSPECIFIED: NetAddress = NetAddress {
+        services: 0,
+        ip: Ipv6Addr::UNSPECIFIED,
+        port: 0,
+    };
+
+    pub fn from_socket_addr(address: SocketAddr, services: u64) -> Self {
+        let ip = match address.ip() {
+            IpAddr::V4(ip) => ip.to_ipv6_mapped(),
+            IpAddr::V6(ip) => ip,
+        };
+        Self {
+            services,
+            ip,
+            port: address.port(),
+        }
+    }
+}
+
+/// The `version` message opening the handshake.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionMessage {
+    pub version: i32,
+    pub services: u64,
+    pub timestamp: i64,
+    pub receiver: NetAddress,
+    pub sender: NetAddress,
+    /// Random value nodes use to detect connecting to themselves.
+    pub nonce: u64,
+    pub user_agent: String,
+    pub start_height: i32,
+    /// Whether the sender wants unsolicited transaction announcements.
+    pub relay: bool,
+}
+
+/// A `getheaders` request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetHeadersMessage {
+    pub version: u32,
+    /// Hashes the sender has, best first; the node answers from the first one on its chain.
+    pub locator: Vec<BlockHash>,
+    /// Last header wanted, all-zero for "as many as allowed".
+    pub stop: BlockHash,
+}
+
+/// A BIP157 `getcfilters` request for the filters of blocks `start_height` to `stop`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GetCFiltersMessage {
+    pub filter_type: u8,
+    pub start_height: u32,
+    pub stop: BlockHash,
+}
+
+/// A BIP157 `cfilter`: one block's filter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CFilterMessage {
+    pub filter_type: u8,
+    pub block_hash: BlockHash,
+    pub filter: BlockFilter,
+}
+
+/// A `reject` message: the node refused an earlier message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RejectMessage {
+    /// Command of the rejected message, e.g. `tx`.
+    pub message: String,
+    pub code: u8,
+    pub reason: String,
+    /// Hash of the rejected transaction or block, when there is one.
+    pub hash: Option<[u8; 32]>,
+}
+
+/// A decoded P2P message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    Version(VersionMessage),
+    Verack,
+    Ping(u64),
+    Pong(u64),
+    Inv(Vec<Inventory>),
+    GetData(Vec<Inventory>),
+    NotFound(Vec<Inventory>),
+    Tx(Transaction),
+    Block(Block),
+    GetHeaders(GetHeadersMessage),
+    Headers(Vec<Header>),
+    SendHeaders,
+    Reject(RejectMessage),
+    GetCFilters(GetCFiltersMessage),
+    CFilter(CFilterMessage),
+    /// Any command this client does not decode.
+    Unknown {
+        command: String,
+        payload: Vec<u8>,
+    },
+}
+
+impl Message {
+    pub fn command(&self) -> &str {
+        match self {
+            Self::Version(_) => "version",
+            Self::Verack => "verack",
+            Self::Ping(_) => "ping",
+            Self::Pong(_) => "pong",
+            Self::Inv(_) => "inv",
+            Self::GetData(_) => "getdata",
+            Self::NotFound(_) => "notfound",
+            Self::Tx(_) => "tx",
+            Self::Block(_) => "block",
+            Self::GetHeaders(_) => "getheaders",
+            Self::Headers(_) => "headers",
+            Self::SendHeaders => "sendheaders",
+            Self::Reject(_) => "reject",
+            Self::GetCFilters(_) => "getcfilters",
+            Self::CFilter(_) => "cfilter",
+            Self::Unknown { command, .. } => command,
+        }
+    }
+
+    fn encode_payload(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            Self::Version(version) => {
+                out.extend_from_slice(&version.version.to_le_bytes());
+                out.extend_from_slice(&version.services.to_le_bytes());
+                out.extend_from_slice(&version.timestamp.to_le_bytes());
+                write_net_address(&mut out, &version.receiver);
+                write_net_address(&mut out, &version.sender);
+                out.extend_from_slice(&version.nonce.to_le_bytes());
+                write_var_str(&mut out, &version.user_agent);
+                out.extend_from_slice(&version.start_height.to_le_bytes());
+                out.push(version.relay as u8);
+            }
+            Self::Verack | Self::SendHeaders => {}
+            Self::Ping(nonce) | Self::Pong(nonce) => out.extend_from_slice(&nonce.to_le_bytes()),
+            Self::Inv(items) | Self::GetData(items) | Self::NotFound(items) => {
+                write_compact_size(&mut out, items.len() as u64);
+                for item in items {
+                    let (kind, hash) = item.kind_and_hash();
+                    out.extend_from_slice(&kind.to_le_bytes());
+                    out.extend_from_slice(&hash);
+                }
+            }
+            Self::Tx(tx) => out = bitcoin::consensus::serialize(tx),
+            Self::Block(block) => out = bitcoin::consensus::serialize(block),
+            Self::GetHeaders(request) => {
+                out.extend_from_slice(&request.version.to_le_bytes());
+                write_compact_size(&mut out, request.locator.len() as u64);
+                for hash in &request.locator {
+                    out.extend_from_slice(hash.as_byte_array());
+                }
+                out.extend_from_slice(request.stop.as_byte_array());
+            }
+            Self::Headers(headers) => {
+                write_compact_size(&mut out, headers.len() as u64);
+                for header in headers {
+                    out.extend_from_slice(&bitcoin::consensus::serialize(header));
+                    // Transaction count, always zero in `headers`.
+                    out.push(0);
+                }
+            }
+            Self::Reject(reject) => {
+                write_var_str(&mut out, &reject.message);
+                out.push(reject.code);
+                write_var_str(&mut out, &reject.reason);
+                if let Some(hash) = reject.hash {
+                    out.extend_from_slice(&hash);
+                }
+            }
+            Self::GetCFilters(request) => {
+                out.push(request.filter_type);
+                out.extend_from_slice(&request.start_height.to_le_bytes());
+                out.extend_from_slice(request.stop.as_byte_array());
+            }
+            Self::CFilter(cfilter) => {
+                out.push(cfilter.filter_type);
+                out.extend_from_slice(cfilter.block_hash.as_byte_array());
+                write_compact_size(&mut out, cfilter.filter.content.len() as u64);
+                out.extend_from_slice(&cfilter.filter.content);
+            }
+            Self::Unknown { payload, .. } => out.clone_from(payload),
+        }
+        out
+    }
+
+    fn decode_payload(command: &str, payload: &[u8]) -> Result<Self, String> {
+        let mut reader = PayloadReader::new(payload);
+        let message = match command {
+            "version" => {
+                let version = reader.i32()?;
+                let services = reader.u64()?;
+                let timestamp = reader.i64()?;
+                let receiver = reader.net_address()?;
+                let sender = reader.net_address()?;
+                let nonce = reader.u64()?;
+                let user_agent = reader.var_str()?;
+                let start_height = reader.i32()?;
+                // Absent before protocol 70001, which meant "relay everything".
+                let relay = if reader.is_empty() {
+                    true
+                } else {
+                    reader.u8()? != 0
+                };
+                // Newer versions may append fields; they are not needed here.
+                return Ok(Self::Version(VersionMessage {
+                    version,
+                    services,
+                    timestamp,
+                    receiver,
+                    sender,
+                    nonce,
+                    user_agent,
+                    start_height,
+                    relay,
+                }));
+            }
+            "verack" => Self::Verack,
+            "sendheaders" => Self::SendHeaders,
+            "ping" => Self::Ping(reader.u64()?),
+            "pong" => Self::Pong(reader.u64()?),
+            "inv" => Self::Inv(reader.inventory()?),
+            "getdata" => Self::GetData(reader.inventory()?),
+            "notfound" => Self::NotFound(reader.inventory()?),
+            "tx" => {
+                let tx = bitcoin::consensus::deserialize(payload)
+                    .map_err(|e| format!("invalid tx message: {e}"))?;
+                return Ok(Self::Tx(tx));
+            }
+            "block" => return decode_block(payload).map(Self::Block),
+            "getcfilters" => Self::GetCFilters(GetCFiltersMessage {
+                filter_type: reader.u8()?,
+                start_height: reader.u32()?,
+                stop: BlockHash::from_byte_array(reader.hash()?),
+            }),
+            "cfilter" => {
+                let filter_type = reader.u8()?;
+                let block_hash = BlockHash::from_byte_array(reader.hash()?);
+                let len = reader.count(1)?;
+                Self::CFilter(CFilterMessage {
+                    filter_type,
+                    block_hash,
+                    filter: BlockFilter::new(reader.bytes(len)?),
+                })
+            }
+            "getheaders" => {
+                let version = reader.u32()?;
+                let count = reader.compact_size()?;
+                if count > 101 {
+                    return Err(format!("getheaders locator of {count} hashes is too long"));
+                }
+                let locator = (0..count)
+                    .map(|_| reader.hash().map(BlockHash::from_byte_array))
+                    .collect::<Result<_, _>>()?;
+                let stop = BlockHash::from_byte_array(reader.hash()?);
+                Self::GetHeaders(GetHeadersMessage {
+                    version,
+                    locator,
+                    stop,
+                })
+            }
+            "headers" => Self::Headers(reader.headers()?),
+            "reject" => {
+                let message = reader.var_str()?;
+                let code = reader.u8()?;
+                let reason = reader.var_str()?;
+                let hash = if reader.remaining() >= 32 {
+                    Some(reader.hash()?)
+                } else {
+                    None
+                };
+                // Some nodes append extra data; the hash is all this client needs.
+                return Ok(Self::Reject(RejectMessage {
+                    message,
+                    code,
+                    reason,
+                    hash,
+                }));
+            }
+            other => {
+                return Ok(Self::Unknown {
+                    command: other.to_string(),
+                    payload: payload.to_vec(),
+                })
+            }
+        };
+        if !reader.is_empty() {
+            return Err(format!(
+                "{command} message has {} unexpected trailing bytes",
+                reader.remaining()
+            ));
+        }
+        Ok(message)
+    }
+}
+
+/// Frames `message` for the network identified by `magic`.
+pub fn encode_message(magic: [u8; 4], message: &Message) -> Vec<u8> {
+    let payload = message.encode_payload();
+    let mut command = [0u8; COMMAND_SIZE];
+    let name = message.command().as_bytes();
+    let len = name.len().min(COMMAND_SIZE);
+    command[..len].copy_from_slice(&name[..len]);
+
+    let mut out = Vec::with_capacity(MESSAGE_HEADER_SIZE + payload.len());
+    out.extend_from_slice(&magic);
+    out.extend_from_slice(&command);
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(&checksum(&payload));
+    out.extend_from_slice(&payload);
+    out
+}
+
+/// Reads and decodes one framed message from `reader`.
+///
+/// Fails on a foreign magic, an oversized payload or a bad checksum; the stream is out of sync
+/// after any of these and should be closed.
+pub fn read_message<R: Read>(reader: &mut R, magic: [u8; 4]) -> Result<Message, String> {
+    let mut header = [0u8; MESSAGE_HEADER_SIZE];
+    reader
+        .read_exact(&mut header)
+        .map_err(|e| format!("failed to read message header: {e}"))?;
+    if header[..4] != magic {
+        return Err(format!(
+            "message magic {} does not match network magic {}",
+            hex::encode(&header[..4]),
+            hex::encode(magic)
+        ));
+    }
+    let command = parse_command(&header[4..16])?;
+    let length = u32::from_le_bytes(header[16..20].try_into().expect("4 bytes")) as usize;
+    if length > MAX_PAYLOAD_SIZE {
+        return Err(format!(
+            "{command} payload of {length} bytes exceeds the limit"
+        ));
+    }
+    let mut payload = vec![0u8; length];
+    reader
+        .read_exact(&mut payload)
+        .map_err(|e| format!("failed to read {command} payload: {e}"))?;
+    if header[20..24] != checksum(&payload) {
+        return Err(format!("{command} payload checksum mismatch"));
+    }
+    Message::decode_payload(&command, &payload)
+}
+
+/// Settings for a [`Peer`] connection.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PeerConfig {
+    pub params: ChainParams,
+    pub protocol_version: i32,
+    pub user_agent: String,
+    /// Best height the wallet knows, announced in `version`.
+    pub start_height: i32,
+    /// Bound on each request's wait for its answer, and on TCP connects, reads and writes.
+    pub timeout: Duration,
+}
+
+impl Default for PeerConfig {
+    fn default() -> Self {
+        Self {
+            params: ChainParams::MAINNET,
+            protocol_version: PROTOCOL_VERSION,
+            user_agent: USER_AGENT.to_string(),
+            start_height: 0,
+            timeout: DEFAULT_PEER_TIMEOUT,
+        }
+    }
+}
+
+/// A connection to one node over any byte stream.
+///
+/// Pings from the node are answered and unrelated messages skipped while waiting for an answer,
+/// so one `Peer` can be driven request by request.
+pub struct Peer<S: Read + Write> {
+    stream: S,
+    config: PeerConfig,
+    remote: Option<VersionMessage>,
+}
+
+impl Peer<TcpStream> {
+    /// Connects to `host:port` over TCP and completes the handshake.
+    pub fn connect_tcp(host: &str, port: u16, config: PeerConfig) -> Result<Self, String> {
+        let addresses = (host, port)
+            .to_socket_addrs()
+            .map_err(|e| format!("failed to resolve {host}:{port}: {e}"))?;
+        let mut last_error = format!("{host}:{port} did not resolve to any address");
+        for address in addresses {
+            match TcpStream::connect_timeout(&address, config.timeout) {
+                Ok(stream) => {
+                    stream
+                        .set_read_timeout(Some(config.timeout))
+                        .and_then(|_| stream.set_write_timeout(Some(config.timeout)))
+                        .map_err(|e| format!("failed to configure connection to {address}: {e}"))?;
+                    let mut peer = Peer::new(stream, config);
+                    peer.handshake(Some(address))?;
+                    return Ok(peer);
+                }
+                Err(e) => last_error = format!("failed to connect to {address}: {e}"),
+            }
+        }
+        Err(last_error)
+    }
+}
+
+impl<S: Read + Write> Peer<S> {
+    /// Wraps an open stream. Call [`Peer::handshake`] before anything else.
+    ///
+    /// Reads block as long as `stream` lets them; give it a read timeout so a silent node cannot
+    /// stall the wallet.
+    pub fn new(stream: S, config: PeerConfig) -> Self {
+        Self {
+            stream,
+            config,
+            remote: None,
+        }
+    }
+
+    /// The node's `version`, once the handshake completed.
+    pub fn remote_version(&self) -> Option<&VersionMessage> {
+        self.remote.as_ref()
+    }
+
+    /// Exchanges `version` and `verack` with the node. `remote` is its address, if known, for the
+    /// `version` message's receiver field.
+    pub fn handshake(&mut self, remote: Option<SocketAddr>) -> Result<(), String> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() as i64)
+            .unwrap_or(0);
+        self.send(&Message::Version(VersionMessage {
+            version: self.config.protocol_version,
+            services: 0,
+            timestamp,
+            receiver: remote
+                .map(|address| NetAddress::from_socket_addr(address, 0))
+                .unwrap_or(NetAddress::UNSPECIFIED),
+            sender: NetAddress::UNSPECIFIED,
+            nonce: rand::random(),
+            user_agent: self.config.user_agent.clone(),
+            start_height: self.config.start_height,
+            // A wallet does not want every mempool transaction announced to it.
+            relay: false,
+        }))?;
+
+        let deadline = self.deadline();
+        let mut remote_version = None;
+        let mut verack = false;
+        while remote_version.is_none() || !verack {
+            match self.receive(deadline, "the handshake")? {
+                Message::Version(version) => {
+                    if remote_version.is_some() {
+                        return Err("node sent version twice".to_string());
+                    }
+                    if version.version < 70001 {
+                        return Err(format!(
+                            "node protocol version {} is too old",
+                            version.version
+                        ));
+                    }
+                    self.send(&Message::Verack)?;
+                    remote_version = Some(version);
+                }
+                Message::Verack => verack = true,
+                Message::Reject(reject) => {
+                    return Err(format!("node rejected the handshake: {}", reject.reason))
+                }
+                _ => {}
+            }
+        }
+        self.remote = remote_version;
+        Ok(())
+    }
+
+    /// Sends `tx` to the node and waits until it has processed it.
+    ///
+    /// The transaction is announced with `inv` and also sent straight away, which nodes accept,
+    /// so broadcasting does not hinge on when the node chooses to request it; later `getdata`s
+    /// are still answered. A `ping` sent behind it marks the point by which the node has
+    /// processed the transaction: a `reject` for it before the `pong` is an error, anything else
+    /// counts as accepted. Returns the txid.
+    pub fn broadcast(&mut self, tx: &Transaction) -> Result<Txid, String> {
+        self.ensure_handshake()?;
+        let txid = tx.compute_txid();
+        self.send(&Message::Inv(vec![Inventory::Tx(txid)]))?;
+        self.send(&Message::Tx(tx.clone()))?;
+        let nonce = rand::random();
+        self.send(&Message::Ping(nonce))?;
+
+        let deadline = self.deadline();
+        loop {
+            match self.receive(deadline, "the broadcast")? {
+                Message::Pong(echo) if echo == nonce => return Ok(txid),
+                Message::GetData(items) if items.iter().any(|item| item.txid() == Some(txid)) => {
+                    self.send(&Message::Tx(tx.clone()))?;
+                }
+                Message::Reject(reject)
+                    if reject.message == "tx" && reject.hash == Some(txid.to_byte_array()) =>
+                {
+                    if reject.code == REJECT_DUPLICATE {
+                        return Ok(txid);
+                    }
+                    return Err(format!(
+                        "node rejected {txid} (code {:#04x}): {}",
+                        reject.code, reject.reason
+                    ));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Asks for the headers following the first `locator` hash on the node's chain, up to
+    /// [`MAX_HEADERS_RESULTS`]. The headers are not validated here.
+    pub fn get_headers(&mut self, locator: Vec<BlockHash>) -> Result<Vec<Header>, String> {
+        self.ensure_handshake()?;
+        self.send(&Message::GetHeaders(GetHeadersMessage {
+            version: self.config.protocol_version as u32,
+            locator,
+            stop: BlockHash::all_zeros(),
+        }))?;
+        let deadline = self.deadline();
+        loop {
+            if let Message::Headers(headers) = self.receive(deadline, "headers")? {
+                return Ok(headers);
+            }
+        }
+    }
+
+    /// Brings `chain` up to the node's best chain with repeated `getheaders`, validating every
+    /// header. `now` is the local Unix time for the future-timestamp rule.
+    ///
+    /// The locator always starts from `chain`'s own tip, so the node answers from the fork point
+    /// and reorgs are handled by [`HeaderChain::connect`]. A branch with no more work than ours
+    /// ends the sync.
+    pub fn sync_headers(
+        &mut self,
+        chain: &mut HeaderChain,
+        now: u32,
+    ) -> Result<SyncReport, String> {
+        let start_height = chain.tip_height();
+        let mut reorgs = Vec::new();
+        loop {
+            let headers = self.get_headers(chain.locator())?;
+            let full_batch = headers.len() >= MAX_HEADERS_RESULTS;
+            match chain.connect(&headers, now)? {
+                ConnectOutcome::Extended { .. } => {}
+                reorg @ ConnectOutcome::Reorganized { .. } => reorgs.push(reorg),
+                ConnectOutcome::Unchanged | ConnectOutcome::Stale { .. } => break,
+            }
+            if !full_batch {
+                break;
+            }
+        }
+        Ok(SyncReport {
+            start_height,
+            tip_height: chain.tip_height(),
+            reorgs,
+        })
+    }
+
+    /// The basic filters of `blocks` (height and hash, consecutive and lowest first, at most
+    /// [`FILTER_BATCH`]), in the same order.
+    pub fn get_block_filters(
+        &mut self,
+        blocks: &[(u32, BlockHash)],
+    ) -> Result<Vec<BlockFilter>, String> {
+        self.ensure_handshake()?;
+        let services = self.remote.as_ref().map_or(0, |version| version.services);
+        if services & NODE_COMPACT_FILTERS == 0 {
+            return Err("node does not serve compact block filters".to_string());
+        }
+        let (Some((start_height, _)), Some((_, stop))) = (blocks.first(), blocks.last()) else {
+            return Ok(Vec::new());
+        };
+        if blocks.len() > FILTER_BATCH as usize {
+            return Err(format!(
+                "at most {FILTER_BATCH} filters can be requested at once"
+            ));
+        }
+        self.send(&Message::GetCFilters(GetCFiltersMessage {
+            filter_type: BASIC_FILTER_TYPE,
+            start_height: *start_height,
+            stop: *stop,
+        }))?;
+
+        let deadline = self.deadline();
+        let mut filters = Vec::with_capacity(blocks.len());
+        while filters.len() < blocks.len() {
+            if let Message::CFilter(cfilter) = self.receive(deadline, "block filters")? {
+                let (height, expected) = blocks[filters.len()];
+                if cfilter.filter_type != BASIC_FILTER_TYPE || cfilter.block_hash != expected {
+                    return Err(format!(
+                        "expected the filter of block {expected} at height {height}, got type {} \
+                         for {}",
+                        cfilter.filter_type, cfilter.block_hash
+                    ));
+                }
+                filters.push(cfilter.filter);
+            }
+        }
+        Ok(filters)
+    }
+
+    /// Downloads block `hash`.
+    pub fn get_block(&mut self, hash: &BlockHash) -> Result<Block, String> {
+        self.ensure_handshake()?;
+        self.send(&Message::GetData(vec![Inventory::Block(*hash)]))?;
+        let deadline = self.deadline();
+        loop {
+            match self.receive(deadline, "a block")? {
+                Message::Block(block) if block.block_hash() == *hash => return Ok(block),
+                Message::NotFound(items) if items.contains(&Inventory::Block(*hash)) => {
+                    return Err(format!("node does not have block {hash}"))
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Frames and writes one message.
+    pub fn send(&mut self, message: &Message) -> Result<(), String> {
+        let frame = encode_message(self.config.params.network_magic, message);
+        self.stream
+            .write_all(&frame)
+            .and_then(|_| self.stream.flush())
+            .map_err(|e| format!("failed to send {}: {e}", message.command()))
+    }
+
+    /// Reads the next message other than a `ping`, which is answered on the way.
+    fn receive(&mut self, deadline: Instant, waiting_for: &str) -> Result<Message, String> {
+        loop {
+            if Instant::now() > deadline {
+                return Err(format!("timed out waiting for {waiting_for}"));
+            }
+            match read_message(&mut self.stream, self.config.params.network_magic)? {
+                Message::Ping(nonce) => self.send(&Message::Pong(nonce))?,
+                message => return Ok(message),
+            }
+        }
+    }
+
+    fn ensure_handshake(&self) -> Result<(), String> {
+        match self.remote {
+            Some(_) => Ok(()),
+            None => Err("the handshake with the node has not completed".to_string()),
+        }
+    }
+
+    fn deadline(&self) -> Instant {
+        Instant::now() + self.config.timeout
+    }
+}
+
+impl<S: Read + Write> FilterSource for Peer<S> {
+    fn block_filters(&mut self, blocks: &[(u32, BlockHash)]) -> Result<Vec<BlockFilter>, String> {
+        self.get_block_filters(blocks)
+    }
+
+    fn block(&mut self, hash: &BlockHash) -> Result<Block, String> {
+        self.get_block(hash)
+    }
+}
+
+/// Broadcasts the hex-encoded `tx_hex` straight to the node at `host:port` and returns the txid.
+pub fn broadcast_to_peer(
+    host: &str,
+    port: u16,
+    tx_hex: &str,
+    config: PeerConfig,
+) -> Result<String, String> {
+    let raw = hex::decode(tx_hex.trim()).map_err(|e| format!("invalid transaction hex: {e}"))?;
+    let tx: Transaction =
+        bitcoin::consensus::deserialize(&raw).map_err(|e| format!("invalid transaction: {e}"))?;
+    let mut peer = Peer::connect_tcp(host, port, config)?;
+    peer.broadcast(&tx).map(|txid| txid.to_string())
+}
+
+fn checksum(payload: &[u8]) -> [u8; 4] {
+    let hash = sha256d::Hash::hash(payload);
+    hash.as_byte_array()[..4].try_into().expect("4 bytes")
+}
+
+fn parse_command(raw: &[u8]) -> Result<String, String> {
+    let end = raw.iter().position(|b| *b == 0).unwrap_or(raw.len());
+    if raw[end..].iter().any(|b| *b != 0) || !raw[..end].iter().all(u8::is_ascii_graphic) {
+        return Err(format!(
+            "malformed command {:?}",
+            String::from_utf8_lossy(raw)
+        ));
+    }
+    Ok(String::from_utf8_lossy(&raw[..end]).into_owned())
+}
+
+fn write_compact_size(out: &mut Vec<u8>, n: u64) {
+    match n {
+        0..=0xfc => out.push(n as u8),
+        0xfd..=0xffff => {
+            out.push(0xfd);
+            out.extend_from_slice(&(n as u16).to_le_bytes());
+        }
+        0x1_0000..=0xffff_ffff => {
+            out.push(0xfe);
+            out.extend_from_slice(&(n as u32).to_le_bytes());
+        }
+        _ => {
+            out.push(0xff);
+            out.extend_from_slice(&n.to_le_bytes());
+        }
+    }
+}
+
+fn write_var_str(out: &mut Vec<u8>, s: &str) {
+    write_compact_size(out, s.len() as u64);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn write_net_address(out: &mut Vec<u8>, address: &NetAddress) {
+    out.extend_from_slice(&address.services.to_le_bytes());
+    out.extend_from_slice(&address.ip.octets());
+    // The port alone is big-endian.
+    out.extend_from_slice(&address.port.to_be_bytes());
+}
+
+/// Bounds-checked cursor over a payload.
+struct PayloadReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> PayloadReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn is_empty(&self) -> bool {
+        self.remaining() == 0
+    }
+
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], String> {
+        if n > self.remaining() {
+            return Err(format!(
+                "payload truncated: needed {n} more bytes, {} left",
+                self.remaining()
+            ));
+        }
+        let slice = &self.data[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(slice)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.bytes(N)?.try_into().expect("length checked"))
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        self.array().map(u32::from_le_bytes)
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        self.array().map(i32::from_le_bytes)
+    }
+
+    fn u64(&mut self) -> Result<u64, String> {
+        self.array().map(u64::from_le_bytes)
+    }
+
+    fn i64(&mut self) -> Result<i64, String> {
+        self.array().map(i64::from_le_bytes)
+    }
+
+    fn hash(&mut self) -> Result<[u8; 32], String> {
+        self.array()
+    }
+
+    fn compact_size(&mut self) -> Result<u64, String> {
+        let n = match self.u8()? {
+            0xfd => u16::from_le_bytes(self.array()?) as u64,
+            0xfe => u32::from_le_bytes(self.array()?) as u64,
+            0xff => u64::from_le_bytes(self.array()?),
+            n => n as u64,
+        };
+        Ok(n)
+    }
+
+    /// A compact size that must fit in what is left of the payload at `unit` bytes per item.
+    fn count(&mut self, unit: usize) -> Result<usize, String> {
+        let n = self.compact_size()?;
+        if n > (self.remaining() / unit.max(1)) as u64 {
+            return Err(format!("count {n} exceeds the payload"));
+        }
+        Ok(n as usize)
+    }
+
+    fn var_str(&mut self) -> Result<String, String> {
+        let len = self.count(1)?;
+        let bytes = self.bytes(len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| "string is not valid UTF-8".to_string())
+    }
+
+    fn net_address(&mut self) -> Result<NetAddress, String> {
+        let services = self.u64()?;
+        let ip = Ipv6Addr::from(self.array::<16>()?);
+        let port = u16::from_be_bytes(self.array()?);
+        Ok(NetAddress { services, ip, port })
+    }
+
+    fn inventory(&mut self) -> Result<Vec<Inventory>, String> {
+        let count = self.count(36)?;
+        if count > MAX_INV_SIZE {
+            return Err(format!("inventory of {count} entries is too large"));
+        }
+        (0..count)
+            .map(|_| {
+                let kind = self.u32()?;
+                Ok(Inventory::from_kind_and_hash(kind, self.hash()?))
+            })
+            .collect()
+    }
+
+    /// Headers, each followed by a zero transaction count. Some PoSV nodes also append each
+    /// block's signature; that layout is recognised by the payload not fitting the plain one.
+    fn headers(&mut self) -> Result<Vec<Header>, String> {
+        let count = self.count(HEADER_SIZE + 1)?;
+        if count > MAX_HEADERS_RESULTS {
+            return Err(format!("headers message of {count} headers is too large"));
+        }
+        let signed = self.remaining() != count * (HEADER_SIZE + 1);
+        (0..count)
+            .map(|_| {
+                let header = bitcoin::consensus::deserialize(self.bytes(HEADER_SIZE)?)
+                    .map_err(|e| format!("invalid header: {e}"))?;
+                if self.compact_size()? != 0 {
+                    return Err("headers message carries transactions".to_string());
+                }
+                if signed {
+                    let len = self.count(1)?;
+                    self.bytes(len)?;
+                }
+                Ok(header)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::VecDeque;
+    use std::io;
+    use std::rc::Rc;
+
+    use bitcoin::absolute::LockTime;
+    use bitcoin::block::Version;
+    use bitcoin::pow::CompactTarget;
+    use bitcoin::transaction::Version as TxVersion;
+    use bitcoin::{OutPoint, ScriptBuf, TxIn, TxMerkleNode, TxOut};
+
+    use super::*;
+    use crate::header_chain::next_target;
+
+    const MAGIC: [u8; 4] = ChainParams::MAINNET.network_magic;
+
+    type Script = Box<dyn FnMut(&Message) -> Vec<Vec<u8>>>;
+
+    /// The node's end of an in-memory connection: every complete frame the peer writes is
+    /// recorded and answered by `script` with raw frames.
+    struct FakeNode {
+        written: Vec<u8>,
+        replies: VecDeque<u8>,
+        script: Script,
+        received: Rc<RefCell<Vec<Message>>>,
+    }
+
+    impl Write for FakeNode {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            while self.written.len() >= MESSAGE_HEADER_SIZE {
+                let length = u32::from_le_bytes(self.written[16..20].try_into().unwrap()) as usize;
+                if self.written.len() < MESSAGE_HEADER_SIZE + length {
+                    break;
+                }
+                let frame: Vec<u8> = self.written.drain(..MESSAGE_HEADER_SIZE + length).collect();
+                let message = read_message(&mut frame.as_slice(), MAGIC).unwrap();
+                self.replies.extend((self.script)(&message).concat());
+                self.received.borrow_mut().push(message);
+            }
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Read for FakeNode {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let count = buf.len().min(self.replies.len());
+            for (slot, byte) in buf.iter_mut().zip(self.replies.drain(..count)) {
+                *slot = byte;
+            }
+            Ok(count)
+        }
+    }
+
+    fn frame(message: &Message) -> Vec<u8> {
+        encode_message(MAGIC, message)
+    }
+
+    fn node_version(version: i32) -> VersionMessage {
+        VersionMessage {
+            version,
+            services: 1 | NODE_COMPACT_FILTERS,
+            timestamp: 1_700_000_000,
+            receiver: NetAddress::UNSPECIFIED,
+            sender: NetAddress::UNSPECIFIED,
+            nonce: 42,
+            user_agent: "/Reddcoin Core:4.22.9/".to_string(),
+            start_height: 5_000_000,
+            relay: true,
+        }
+    }
+
+    /// A node answering every message with `script`.
+    fn scripted(script: impl FnMut(&Message) -> Vec<Vec<u8>> + 'static) -> FakeNode {
+        FakeNode {
+            written: Vec::new(),
+            replies: VecDeque::new(),
+            script: Box::new(script),
+            received: Rc::default(),
+        }
+    }
+
+    /// A node that completes the handshake and hands everything else to `script`.
+    fn node(mut script: impl FnMut(&Message) -> Vec<Vec<u8>> + 'static) -> FakeNode {
+        scripted(move |message| match message {
+            Message::Version(_) => vec![
+                frame(&Message::Version(node_version(PROTOCOL_VERSION))),
+                frame(&Message::Verack),
+            ],
+            Message::Verack => Vec::new(),
+            other => script(other),
+        })
+    }
+
+    fn connected(node: FakeNode) -> (Peer<FakeNode>, Rc<RefCell<Vec<Message>>>) {
+        let received = Rc::clone(&node.received);
+        let mut peer = Peer::new(node, PeerConfig::default());
+        peer.handshake(None).unwrap();
+        (peer, received)
+    }
+
+    fn commands(received: &RefCell<Vec<Message>>) -> Vec<String> {
+        received
+            .borrow()
+            .iter()
+            .map(|message| message.command().to_string())
+            .collect()
+    }
+
+    fn transaction() -> Transaction {
+        Transaction {
+            version: TxVersion(1),
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new("11".repeat(32).parse().unwrap(), 0),
+                ..TxIn::default()
+            }],
+            output: vec![TxOut {
+                value: bitcoin::Amount::from_sat(1_000),
+                script_pubkey: ScriptBuf::new(),
+            }],
+        }
+    }
+
+    /// Answers the broadcast's `ping` with `before`, then the `pong`.
+    fn broadcast_node(before: Vec<Message>) -> FakeNode {
+        node(move |message| match message {
+            Message::Ping(nonce) => before
+                .iter()
+                .chain([&Message::Pong(*nonce)])
+                .map(frame)
+                .collect(),
+            _ => Vec::new(),
+        })
+    }
+
+    fn reject(hash: Txid, code: u8) -> Message {
+        Message::Reject(RejectMessage {
+            message: "tx".to_string(),
+            code,
+            reason: "bad-txns-inputs-missingorspent".to_string(),
+            hash: Some(hash.to_byte_array()),
+        })
+    }
+
+    #[test]
+    fn handshake_exchanges_version_and_verack() {
+        let (peer, received) = connected(node(|_| Vec::new()));
+        assert_eq!(peer.remote_version(), Some(&node_version(PROTOCOL_VERSION)));
+        let received = received.borrow();
+        let Message::Version(ours) = &received[0] else {
+            panic!("expected version, got {:?}", received[0]);
+        };
+        assert_eq!(ours.user_agent, USER_AGENT);
+        assert!(!ours.relay);
+        assert_eq!(received[1], Message::Verack);
+
+        let old = scripted(|_| {
+            vec![
+                frame(&Message::Version(node_version(60_000))),
+                frame(&Message::Verack),
+            ]
+        });
+        let mut peer = Peer::new(old, PeerConfig::default());
+        assert!(peer
+            .get_headers(Vec::new())
+            .unwrap_err()
+            .contains("has not completed"));
+        assert!(peer.handshake(None).unwrap_err().contains("too old"));
+    }
+
+    #[test]
+    fn broadcast_is_acknowledged_by_the_pong() {
+        let tx = transaction();
+        let (mut peer, received) = connected(broadcast_node(vec![Message::Ping(7)]));
+        assert_eq!(peer.broadcast(&tx), Ok(tx.compute_txid()));
+
+        let received = received.borrow();
+        assert_eq!(
+            received[2],
+            Message::Inv(vec![Inventory::Tx(tx.compute_txid())])
+        );
+        assert_eq!(received[3], Message::Tx(tx.clone()));
+        assert!(matches!(received[4], Message::Ping(_)));
+        // The node's own ping is answered while waiting.
+        assert_eq!(received[5], Message::Pong(7));
+    }
+
+    #[test]
+    fn broadcast_fails_on_a_reject_for_its_hash() {
+        let tx = transaction();
+        let other = Txid::from_byte_array([9; 32]);
+        let (mut peer, _) = connected(broadcast_node(vec![
+            reject(other, 0x10),
+            reject(tx.compute_txid(), 0x10),
+        ]));
+        let err = peer.broadcast(&tx).unwrap_err();
+        assert!(
+            err.ends_with("(code 0x10): bad-txns-inputs-missingorspent"),
+            "{err}"
+        );
+
+        // A reject for another transaction is not ours; a duplicate is already accepted.
+        let (mut peer, _) = connected(broadcast_node(vec![reject(other, 0x10)]));
+        assert!(peer.broadcast(&tx).is_ok());
+        let (mut peer, _) = connected(broadcast_node(vec![reject(
+            tx.compute_txid(),
+            REJECT_DUPLICATE,
+        )]));
+        assert!(peer.broadcast(&tx).is_ok());
+    }
+
+    #[test]
+    fn broadcast_answers_getdata_again() {
+        let tx = transaction();
+        let (mut peer, received) =
+            connected(broadcast_node(vec![Message::GetData(vec![Inventory::Tx(
+                tx.compute_txid(),
+            )])]));
+        peer.broadcast(&tx).unwrap();
+        assert_eq!(
+            commands(&received),
+            ["version", "verack", "inv", "tx", "ping", "tx"]
+        );
+    }
+
+    /// `count` headers that pass [`HeaderChain`] validation on top of `anchor` at `height`.
+    fn headers_after(anchor: Header, height: u32, count: u32) -> Vec<Header> {
+        let mut all = vec![anchor];
+        for height in height + 1..=height + count {
+            let parent = *all.last().unwrap();
+            let bits = match all.len() {
+                1 => parent.bits,
+                len => next_target(height, &parent, &all[len - 2], &ChainParams::MAINNET),
+            };
+            all.push(Header {
+                version: Version::from_consensus(4),
+                prev_blockhash: parent.block_hash(),
+                merkle_root: TxMerkleNode::all_zeros(),
+                time: parent.time + 60,
+                bits,
+                nonce: 0,
+            });
+        }
+        all.split_off(1)
+    }
+
+    /// A `headers` payload in the PoSV layout, each header followed by a block signature.
+    fn signed_headers(headers: &[Header]) -> Message {
+        let mut payload = Vec::new();
+        write_compact_size(&mut payload, headers.len() as u64);
+        for header in headers {
+            payload.extend_from_slice(&bitcoin::consensus::serialize(header));
+            payload.push(0);
+            write_compact_size(&mut payload, 71);
+            payload.extend_from_slice(&[0x30; 71]);
+        }
+        Message::Unknown {
+            command: "headers".to_string(),
+            payload,
+        }
+    }
+
+    #[test]
+    fn headers_sync_accepts_both_layouts() {
+        let anchor = Header {
+            version: Version::from_consensus(4),
+            prev_blockhash: BlockHash::all_zeros(),
+            merkle_root: TxMerkleNode::all_zeros(),
+            time: 1_700_000_000,
+            bits: CompactTarget::from_consensus(0x1d00ffff),
+            nonce: 0,
+        };
+        let height = 3_000_000;
+        let headers = headers_after(anchor, height, 4);
+        let batches = RefCell::new(VecDeque::from([
+            signed_headers(&headers),
+            Message::Headers(headers[..2].to_vec()),
+        ]));
+        let (mut peer, received) = connected(node(move |message| match message {
+            Message::GetHeaders(_) => vec![frame(&batches.borrow_mut().pop_front().unwrap())],
+            _ => Vec::new(),
+        }));
+
+        let mut chain = HeaderChain::new(anchor, height, &[]).unwrap();
+        let report = peer.sync_headers(&mut chain, 1_800_000_000).unwrap();
+        // A short batch ends the sync.
+        assert_eq!(report.tip_height, height + 4);
+        assert_eq!(chain.tip().block_hash(), headers[3].block_hash());
+        let first_request = received.borrow()[2].clone();
+        let Message::GetHeaders(request) = first_request else {
+            panic!("expected getheaders, got {first_request:?}");
+        };
+        assert_eq!(request.locator, [anchor.block_hash()]);
+
+        assert_eq!(peer.get_headers(Vec::new()).unwrap(), headers[..2]);
+    }
+
+    #[test]
+    fn frames_with_bad_magic_or_checksum_are_rejected() {
+        let mut bytes = frame(&Message::Ping(1));
+        assert_eq!(
+            read_message(&mut bytes.as_slice(), MAGIC),
+            Ok(Message::Ping(1))
+        );
+
+        let mut foreign = bytes.clone();
+        foreign[0] ^= 0xff;
+        assert!(read_message(&mut foreign.as_slice(), MAGIC)
+            .unwrap_err()
+            .contains("does not match network magic"));
+
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert_eq!(
+            read_message(&mut bytes.as_slice(), MAGIC),
+            Err("ping payload checksum mismatch".to_string())
+        );
+
+        let mut oversized = frame(&Message::Verack);
+        oversized[16..20].copy_from_slice(&(MAX_PAYLOAD_SIZE as u32 + 1).to_le_bytes());
+        assert!(read_message(&mut oversized.as_slice(), MAGIC)
+            .unwrap_err()
+            .contains("exceeds the limit"));
+
+        // A node speaking another network fails the handshake.
+        let testnet =
+            scripted(|_| vec![encode_message([0xfc, 0xc1, 0xb7, 0xdc], &Message::Verack)]);
+        let mut peer = Peer::new(testnet, PeerConfig::default());
+        assert!(peer
+            .handshake(None)
+            .unwrap_err()
+            .contains("does not match network magic"));
+    }
+}
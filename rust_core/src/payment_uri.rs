@@ -0,0 +1,380 @@
+//! `reddcoin:` payment URIs (BIP21).
+//!
+//! Shape: `reddcoin:<target>[?amount=<rdd>&label=<text>&message=<text>&...]`, where the target
+//! is a Reddcoin address or a ReddID `@handle`. Parsing lives here so the scan and receive
+//! screens share one implementation instead of each splitting query strings in Dart.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use serde::Serialize;
+
+use crate::address::ReddcoinAddress;
+use crate::amount::Amount;
+
+/// URI scheme for Reddcoin payment requests. Matching is case-insensitive per RFC 3986.
+pub const REDDCOIN_URI_SCHEME: &str = "reddcoin";
+
+/// Prefix BIP21 reserves for parameters a wallet must understand to process the URI.
+const REQUIRED_PARAM_PREFIX: &str = "req-";
+
+/// Who a payment request pays.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PaymentTarget {
+    /// A validated Reddcoin address.
+    Address(String),
+    /// A ReddID handle (without the leading `@`) that must be resolved before paying.
+    Handle(String),
+}
+
+impl fmt::Display for PaymentTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Address(address) => f.write_str(address),
+            Self::Handle(handle) => write!(f, "@{handle}"),
+        }
+    }
+}
+
+/// A parsed (or to-be-generated) payment request.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PaymentRequest {
+    pub target: PaymentTarget,
+    /// Requested amount, serialized as integer base units.
+    pub amount: Option<Amount>,
+    pub label: Option<String>,
+    pub message: Option<String>,
+    /// Unrecognized optional parameters, kept so callers can display or forward them.
+    pub extras: BTreeMap<String, String>,
+}
+
+/// How `req-` parameters are handled.
+///
+/// BIP21 requires rejecting any URI carrying a `req-` parameter the wallet does not understand,
+/// because the payer may be relying on it (for example a required expiry). Callers list the
+/// `req-` names they do implement; every other one fails the parse.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequiredParamPolicy {
+    /// Parameter names including the `req-` prefix, e.g. `req-expires`.
+    pub understood: Vec<String>,
+}
+
+impl PaymentRequest {
+    /// Creates a request paying `target` with no optional fields.
+    pub fn new(target: PaymentTarget) -> Self {
+        Self {
+            target,
+            amount: None,
+            label: None,
+            message: None,
+            extras: BTreeMap::new(),
+        }
+    }
+
+    /// Renders the request as a `reddcoin:` URI.
+    ///
+    /// Amounts are written as whole RDD without trailing zeros, which is what other Reddcoin
+    /// wallets expect when they scan the QR code.
+    pub fn to_uri(&self) -> String {
+        let mut uri = format!("{REDDCOIN_URI_SCHEME}:{}", self.target);
+        let mut params = Vec::new();
+
+        if let Some(amount) = self.amount {
+            params.push(format!("amount={}", trim_amount(amount)));
+        }
+        if let Some(label) = &self.label {
+            params.push(format!("label={}", percent_encode(label)));
+        }
+        if let Some(message) = &self.message {
+            params.push(format!("message={}", percent_encode(message)));
+        }
+        for (key, value) in &self.extras {
+            params.push(format!("{}={}", percent_encode(key), percent_encode(value)));
+        }
+
+        if !params.is_empty() {
+            uri.push('?');
+            uri.push_str(&params.join("&"));
+        }
+        uri
+    }
+}
+
+/// Parses a `reddcoin:` URI, or a bare address/`@handle` as printed in most QR codes.
+pub fn parse_payment_uri(
+    uri: &str,
+    policy: &RequiredParamPolicy,
+) -> Result<PaymentRequest, String> {
+    let uri = uri.trim();
+    let body = match uri.split_once(':') {
+        Some((scheme, rest)) if scheme.eq_ignore_ascii_case(REDDCOIN_URI_SCHEME) => rest,
+        Some((scheme, _)) => {
+            return Err(format!(
+                "unsupported URI scheme `{scheme}` (expected `{REDDCOIN_URI_SCHEME}:`)"
+            ))
+        }
+        None => uri,
+    };
+    // Some wallets emit `reddcoin://R...`; the authority marker carries no meaning here.
+    let body = body.strip_prefix("//").unwrap_or(body);
+
+    let (raw_target, query) = match body.split_once('?') {
+        Some((target, query)) => (target, Some(query)),
+        None => (body, None),
+    };
+
+    let mut request = PaymentRequest::new(parse_target(&percent_decode(raw_target)?)?);
+
+    let Some(query) = query else {
+        return Ok(request);
+    };
+
+    let mut seen = Vec::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (raw_key, raw_value) = pair.split_once('=').unwrap_or((pair, ""));
+        let key = percent_decode(raw_key)?;
+        let value = percent_decode(raw_value)?;
+
+        if seen.contains(&key) {
+            return Err(format!("parameter `{key}` appears more than once"));
+        }
+        seen.push(key.clone());
+
+        match key.as_str() {
+            "amount" => {
+                request.amount = Some(
+                    Amount::from_str(&value)
+                        .map_err(|e| format!("invalid amount parameter: {e}"))?,
+                );
+            }
+            "label" => request.label = Some(value),
+            "message" => request.message = Some(value),
+            _ if key.starts_with(REQUIRED_PARAM_PREFIX) => {
+                if !policy.understood.iter().any(|known| known == &key) {
+                    return Err(format!("unsupported required parameter `{key}`"));
+                }
+                request.extras.insert(key, value);
+            }
+            _ => {
+                request.extras.insert(key, value);
+            }
+        }
+    }
+
+    Ok(request)
+}
+
+/// JSON entry point: parses `uri` with the default `req-` policy (reject all).
+pub fn parse_payment_uri_json(uri: String) -> Result<String, String> {
+    let request = parse_payment_uri(&uri, &RequiredParamPolicy::default())?;
+    serde_json::to_string(&request).map_err(|e| format!("failed to encode payment request: {e}"))
+}
+
+/// Builds a receive-request URI. Empty strings mean "not set" so the function maps cleanly onto
+/// FFI string arguments.
+pub fn build_payment_uri(
+    target: String,
+    amount: String,
+    label: String,
+    message: String,
+) -> Result<String, String> {
+    let mut request = PaymentRequest::new(parse_target(target.trim())?);
+
+    if !amount.trim().is_empty() {
+        request.amount =
+            Some(Amount::from_str(&amount).map_err(|e| format!("invalid amount: {e}"))?);
+    }
+    if !label.is_empty() {
+        request.label = Some(label);
+    }
+    if !message.is_empty() {
+        request.message = Some(message);
+    }
+
+    Ok(request.to_uri())
+}
+
+fn parse_target(target: &str) -> Result<PaymentTarget, String> {
+    if target.is_empty() {
+        return Err("payment URI has no address or handle".to_string());
+    }
+
+    if let Some(handle) = target.strip_prefix('@') {
+        let valid = !handle.is_empty()
+            && handle
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'.' | b'-' | b'_'));
+        if !valid {
+            return Err(format!("invalid ReddID handle `{target}`"));
+        }
+        return Ok(PaymentTarget::Handle(handle.to_string()));
+    }
+
+    let address =
+        ReddcoinAddress::from_str(target).map_err(|e| format!("invalid payment address: {e}"))?;
+    Ok(PaymentTarget::Address(address.to_string()))
+}
+
+/// Formats an amount as whole RDD with trailing fractional zeros removed (`12.5`, `3`).
+fn trim_amount(amount: Amount) -> String {
+    let formatted = amount.to_string();
+    formatted
+        .trim_end_matches('0')
+        .trim_end_matches('.')
+        .to_string()
+}
+
+/// Percent-encodes everything except RFC 3986 unreserved characters.
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+/// Decodes `%XX` escapes. `+` is left alone: BIP21 follows RFC 3986, not form encoding.
+fn percent_decode(value: &str) -> Result<String, String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            // `from_str_radix` alone would accept a sign, so `%+1` would decode to 0x01.
+            let escaped = bytes
+                .get(index + 1..index + 3)
+                .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                .ok_or_else(|| format!("invalid percent-encoding in `{value}`"))?;
+            decoded.push(escaped);
+            index += 3;
+        } else {
+            decoded.push(bytes[index]);
+            index += 1;
+        }
+    }
+    String::from_utf8(decoded).map_err(|e| format!("percent-decoded value is not UTF-8: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::secp256k1::SecretKey;
+    use bitcoin::PublicKey;
+
+    use super::*;
+    use crate::secp_context::secp;
+
+    fn address() -> String {
+        let secret_key = SecretKey::from_slice(&[7u8; 32]).unwrap();
+        ReddcoinAddress::p2pkh(&PublicKey::new(secret_key.public_key(secp()))).to_string()
+    }
+
+    fn parse(uri: &str) -> Result<PaymentRequest, String> {
+        parse_payment_uri(uri, &RequiredParamPolicy::default())
+    }
+
+    #[test]
+    fn parses_full_uri() {
+        let address = address();
+        let request = parse(&format!(
+            "ReddCoin:{address}?amount=12.5&label=Tip%20jar&message=thanks%21&foo=bar+baz"
+        ))
+        .unwrap();
+        assert_eq!(request.target, PaymentTarget::Address(address));
+        assert_eq!(request.amount, Some(Amount::from_base(1_250_000_000)));
+        assert_eq!(request.label.as_deref(), Some("Tip jar"));
+        assert_eq!(request.message.as_deref(), Some("thanks!"));
+        // `+` is not a space in RFC 3986.
+        assert_eq!(request.extras["foo"], "bar+baz");
+    }
+
+    #[test]
+    fn accepts_bare_targets_and_authority_marker() {
+        let address = address();
+        assert_eq!(
+            parse(&address).unwrap().target,
+            PaymentTarget::Address(address.clone())
+        );
+        assert_eq!(
+            parse(&format!("reddcoin://{address}")).unwrap().target,
+            PaymentTarget::Address(address)
+        );
+        assert_eq!(
+            parse("reddcoin:@alice.rdd").unwrap().target,
+            PaymentTarget::Handle("alice.rdd".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_uris() {
+        let address = address();
+        for uri in [
+            "bitcoin:1BoatSLRHtKNngkdXEeobR76b6LFQ4eT2",
+            "reddcoin:",
+            "reddcoin:@",
+            "reddcoin:@bad handle",
+            "reddcoin:Rnotanaddress",
+        ] {
+            assert!(parse(uri).is_err(), "{uri}");
+        }
+        for query in [
+            "amount=1e3",
+            "amount=-1",
+            "amount=0.123456789",
+            "label=a&label=b",
+            "label=%G1",
+            "label=%+1",
+            "label=%-1",
+            "label=%4",
+            "label=%C3%28",
+        ] {
+            assert!(
+                parse(&format!("reddcoin:{address}?{query}")).is_err(),
+                "{query}"
+            );
+        }
+    }
+
+    #[test]
+    fn enforces_required_parameters() {
+        let uri = format!("reddcoin:{}?req-expires=1700000000", address());
+        assert!(parse(&uri)
+            .unwrap_err()
+            .contains("unsupported required parameter"));
+        let policy = RequiredParamPolicy {
+            understood: vec!["req-expires".to_string()],
+        };
+        let request = parse_payment_uri(&uri, &policy).unwrap();
+        assert_eq!(request.extras["req-expires"], "1700000000");
+    }
+
+    #[test]
+    fn build_round_trips_through_parse() {
+        let address = address();
+        let uri = build_payment_uri(
+            address.clone(),
+            "3.10".to_string(),
+            "Café & bar".to_string(),
+            String::new(),
+        )
+        .unwrap();
+        assert_eq!(
+            uri,
+            format!("reddcoin:{address}?amount=3.1&label=Caf%C3%A9%20%26%20bar")
+        );
+        let request = parse(&uri).unwrap();
+        assert_eq!(request.amount, Some(Amount::from_base(310_000_000)));
+        assert_eq!(request.label.as_deref(), Some("Café & bar"));
+        assert_eq!(request.message, None);
+
+        let whole = build_payment_uri(address, "3".into(), String::new(), String::new());
+        assert!(whole.unwrap().ends_with("?amount=3"));
+    }
+}
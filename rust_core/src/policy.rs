@@ -0,0 +1,441 @@
+//! Transaction policy guardrails.
+//!
+//! Consensus accepts almost any transaction the wallet can build, including one that pays a
+//! 10,000 RDD fee because of a typo. Policy rules run on the fully built transaction before any
+//! signature exists. A `deny` verdict stops signing; a `warn` verdict is returned alongside the
+//! signed transaction so the app can ask for confirmation before broadcasting.
+//!
+//! Rust keeps no state between calls, so history-dependent rules (daily limits, first-time
+//! recipients) read it from the caller-supplied [`PolicyContext`].
+
+use bitcoin::{Script, Transaction};
+use serde::{Deserialize, Serialize};
+
+use crate::address::ReddcoinAddress;
+use crate::amount::Amount;
+
+/// Outcome of a rule, ordered by severity.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    #[default]
+    Allow,
+    Warn,
+    Deny,
+}
+
+/// A limit and what happens when it is exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct Threshold<T> {
+    pub limit: T,
+    /// Defaults to `deny`.
+    #[serde(default = "deny")]
+    pub action: Verdict,
+}
+
+fn deny() -> Verdict {
+    Verdict::Deny
+}
+
+/// Configurable rules. Every rule is off unless set.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct PolicyRules {
+    /// Largest acceptable absolute fee.
+    pub max_fee: Option<Threshold<Amount>>,
+    /// Largest acceptable fee relative to the amount paid to recipients, in basis points.
+    pub max_fee_ratio_bps: Option<Threshold<u64>>,
+    /// Largest amount (payments plus fee) that may leave the wallet per day, including
+    /// [`PolicyContext::spent_today`].
+    pub daily_spend_limit: Option<Threshold<Amount>>,
+    /// When set, every recipient must be on this list.
+    pub allowlist: Option<Vec<String>>,
+    /// Recipients that are always denied.
+    pub blocklist: Vec<String>,
+    /// Verdict for recipients not in [`PolicyContext::known_recipients`].
+    pub first_time_recipient: Option<Verdict>,
+    /// Largest acceptable OP_RETURN payload, in bytes.
+    pub max_op_return_bytes: Option<Threshold<usize>>,
+}
+
+/// Wallet history the rules are evaluated against.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct PolicyContext {
+    /// Amount already sent today, including fees.
+    pub spent_today: Amount,
+    /// Addresses the wallet has paid before.
+    pub known_recipients: Vec<String>,
+}
+
+/// Rules plus the context to evaluate them in, as carried by `BuildOptions::policy`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct TransactionPolicy {
+    pub rules: PolicyRules,
+    pub context: PolicyContext,
+}
+
+/// Which rule produced a finding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PolicyRule {
+    MaxFee,
+    MaxFeeRatio,
+    DailySpendLimit,
+    Allowlist,
+    Blocklist,
+    FirstTimeRecipient,
+    MaxOpReturnBytes,
+}
+
+/// One rule that did not simply allow the transaction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PolicyFinding {
+    pub rule: PolicyRule,
+    pub verdict: Verdict,
+    pub reason: String,
+}
+
+/// Combined result: the most severe verdict and every non-allow finding.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct PolicyReport {
+    pub verdict: Verdict,
+    pub findings: Vec<PolicyFinding>,
+}
+
+impl PolicyReport {
+    fn push(&mut self, rule: PolicyRule, verdict: Verdict, reason: String) {
+        if verdict == Verdict::Allow {
+            return;
+        }
+        self.verdict = self.verdict.max(verdict);
+        self.findings.push(PolicyFinding {
+            rule,
+            verdict,
+            reason,
+        });
+    }
+}
+
+impl TransactionPolicy {
+    /// Parses a policy from JSON; an empty string means no rules.
+    pub fn from_json(policy_json: &str) -> Result<Self, String> {
+        if policy_json.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_json::from_str(policy_json).map_err(|e| format!("invalid policy: {e}"))
+    }
+
+    /// Evaluates an unsigned transaction.
+    ///
+    /// `change_index` marks the output returning to the wallet; every other non-OP_RETURN
+    /// output counts as a payment to a recipient.
+    pub fn evaluate(
+        &self,
+        tx: &Transaction,
+        fee: Amount,
+        change_index: Option<usize>,
+    ) -> PolicyReport {
+        let rules = &self.rules;
+        let mut report = PolicyReport::default();
+
+        let mut sent = Amount::ZERO;
+        let mut recipients = Vec::new();
+        for (index, output) in tx.output.iter().enumerate() {
+            if output.script_pubkey.is_op_return() {
+                if let Some(threshold) = rules.max_op_return_bytes {
+                    let size = op_return_payload_len(&output.script_pubkey);
+                    if size > threshold.limit {
+                        report.push(
+                            PolicyRule::MaxOpReturnBytes,
+                            threshold.action,
+                            format!(
+                                "OP_RETURN output {index} carries {size} bytes (limit {})",
+                                threshold.limit
+                            ),
+                        );
+                    }
+                }
+                continue;
+            }
+            if Some(index) == change_index {
+                continue;
+            }
+            sent = sent.saturating_add(output.value.into());
+            recipients.push(
+                ReddcoinAddress::from_script(&output.script_pubkey)
+                    .map(|address| address.to_string())
+                    .unwrap_or_else(|| output.script_pubkey.to_hex_string()),
+            );
+        }
+
+        if let Some(threshold) = rules.max_fee {
+            if fee > threshold.limit {
+                report.push(
+                    PolicyRule::MaxFee,
+                    threshold.action,
+                    format!("fee {fee} RDD exceeds the {} RDD limit", threshold.limit),
+                );
+            }
+        }
+
+        if let Some(threshold) = rules.max_fee_ratio_bps {
+            if let Some(ratio) = fee
+                .to_base()
+                .saturating_mul(10_000)
+                .checked_div(sent.to_base())
+            {
+                if ratio > threshold.limit {
+                    report.push(
+                        PolicyRule::MaxFeeRatio,
+                        threshold.action,
+                        format!(
+                            "fee is {}.{:02}% of the amount sent (limit {}.{:02}%)",
+                            ratio / 100,
+                            ratio % 100,
+                            threshold.limit / 100,
+                            threshold.limit % 100
+                        ),
+                    );
+                }
+            }
+        }
+
+        if let Some(threshold) = rules.daily_spend_limit {
+            let total = self
+                .context
+                .spent_today
+                .saturating_add(sent)
+                .saturating_add(fee);
+            if total > threshold.limit {
+                report.push(
+                    PolicyRule::DailySpendLimit,
+                    threshold.action,
+                    format!(
+                        "today's spending would reach {total} RDD (limit {})",
+                        threshold.limit
+                    ),
+                );
+            }
+        }
+
+        for recipient in &recipients {
+            if rules.blocklist.iter().any(|blocked| blocked == recipient) {
+                report.push(
+                    PolicyRule::Blocklist,
+                    Verdict::Deny,
+                    format!("{recipient} is blocklisted"),
+                );
+            }
+            if let Some(allowlist) = &rules.allowlist {
+                if !allowlist.iter().any(|allowed| allowed == recipient) {
+                    report.push(
+                        PolicyRule::Allowlist,
+                        Verdict::Deny,
+                        format!("{recipient} is not on the allowlist"),
+                    );
+                }
+            }
+            if let Some(verdict) = rules.first_time_recipient {
+                if !self.context.known_recipients.contains(recipient) {
+                    report.push(
+                        PolicyRule::FirstTimeRecipient,
+                        verdict,
+                        format!("{recipient} has never been paid from this wallet"),
+                    );
+                }
+            }
+        }
+
+        report
+    }
+}
+
+/// Bytes pushed after `OP_RETURN`.
+fn op_return_payload_len(script: &Script) -> usize {
+    script
+        .instructions()
+        .skip(1)
+        .filter_map(Result::ok)
+        .filter_map(|instruction| instruction.push_bytes().map(|bytes| bytes.len()))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::absolute::LockTime;
+    use bitcoin::script::PushBytesBuf;
+    use bitcoin::secp256k1::SecretKey;
+    use bitcoin::{PublicKey, ScriptBuf, TxOut};
+
+    use super::*;
+    use crate::secp_context::secp;
+
+    const RDD: u64 = 100_000_000;
+
+    fn address(seed: u8) -> ReddcoinAddress {
+        let secret_key = SecretKey::from_slice(&[seed; 32]).unwrap();
+        ReddcoinAddress::p2pkh(&PublicKey::new(secret_key.public_key(secp())))
+    }
+
+    fn output(seed: u8, rdd: u64) -> TxOut {
+        TxOut {
+            value: bitcoin::Amount::from_sat(rdd * RDD),
+            script_pubkey: address(seed).script_pubkey(),
+        }
+    }
+
+    /// Pays 10 RDD to address 1 and returns 90 RDD of change to address 9.
+    fn payment() -> Transaction {
+        Transaction {
+            version: bitcoin::transaction::Version(2),
+            lock_time: LockTime::ZERO,
+            input: Vec::new(),
+            output: vec![output(1, 10), output(9, 90)],
+        }
+    }
+
+    fn policy(json: &str) -> TransactionPolicy {
+        TransactionPolicy::from_json(json).unwrap()
+    }
+
+    fn verdict(policy_json: &str, fee_rdd: u64) -> (Verdict, Vec<PolicyRule>) {
+        let report =
+            policy(policy_json).evaluate(&payment(), Amount::from_base(fee_rdd * RDD), Some(1));
+        let rules = report.findings.iter().map(|finding| finding.rule).collect();
+        (report.verdict, rules)
+    }
+
+    #[test]
+    fn no_rules_allow_everything() {
+        assert_eq!(verdict("", 1_000), (Verdict::Allow, Vec::new()));
+        assert!(TransactionPolicy::from_json("{\"rules\":1}")
+            .unwrap_err()
+            .starts_with("invalid policy"));
+    }
+
+    #[test]
+    fn fee_limits_allow_warn_or_deny() {
+        let max_fee = |action: &str| {
+            format!(
+                r#"{{"rules":{{"max_fee":{{"limit":{},"action":"{action}"}}}}}}"#,
+                RDD
+            )
+        };
+        assert_eq!(verdict(&max_fee("deny"), 1), (Verdict::Allow, Vec::new()));
+        assert_eq!(
+            verdict(&max_fee("warn"), 2),
+            (Verdict::Warn, vec![PolicyRule::MaxFee])
+        );
+        assert_eq!(
+            verdict(&max_fee("deny"), 2),
+            (Verdict::Deny, vec![PolicyRule::MaxFee])
+        );
+        // The action defaults to deny.
+        let implicit = format!(r#"{{"rules":{{"max_fee":{{"limit":{RDD}}}}}}}"#);
+        assert_eq!(verdict(&implicit, 2).0, Verdict::Deny);
+
+        // 1 RDD on a 10 RDD payment is 10%; change does not count as sent.
+        let ratio = |limit: u64| {
+            format!(r#"{{"rules":{{"max_fee_ratio_bps":{{"limit":{limit},"action":"warn"}}}}}}"#)
+        };
+        assert_eq!(verdict(&ratio(1_000), 1).0, Verdict::Allow);
+        assert_eq!(
+            verdict(&ratio(999), 1),
+            (Verdict::Warn, vec![PolicyRule::MaxFeeRatio])
+        );
+        let report = policy(&ratio(999)).evaluate(&payment(), Amount::from_base(RDD), Some(1));
+        assert_eq!(
+            report.findings[0].reason,
+            "fee is 10.00% of the amount sent (limit 9.99%)"
+        );
+    }
+
+    #[test]
+    fn daily_limit_includes_earlier_spending_and_the_fee() {
+        let limit = |spent: u64| {
+            format!(
+                r#"{{"rules":{{"daily_spend_limit":{{"limit":{}}}}},"context":{{"spent_today":{}}}}}"#,
+                20 * RDD,
+                spent * RDD
+            )
+        };
+        // 10 RDD sent plus a 1 RDD fee.
+        assert_eq!(verdict(&limit(9), 1).0, Verdict::Allow);
+        assert_eq!(
+            verdict(&limit(10), 1),
+            (Verdict::Deny, vec![PolicyRule::DailySpendLimit])
+        );
+    }
+
+    #[test]
+    fn recipient_lists_check_every_payment_but_not_change() {
+        let payee = address(1).to_string();
+        let change = address(9).to_string();
+        let allow = format!(r#"{{"rules":{{"allowlist":["{payee}"]}}}}"#);
+        assert_eq!(verdict(&allow, 1).0, Verdict::Allow);
+        let allow_change_only = format!(r#"{{"rules":{{"allowlist":["{change}"]}}}}"#);
+        assert_eq!(
+            verdict(&allow_change_only, 1),
+            (Verdict::Deny, vec![PolicyRule::Allowlist])
+        );
+
+        let block = format!(r#"{{"rules":{{"blocklist":["{payee}"]}}}}"#);
+        assert_eq!(
+            verdict(&block, 1),
+            (Verdict::Deny, vec![PolicyRule::Blocklist])
+        );
+        let block_change = format!(r#"{{"rules":{{"blocklist":["{change}"]}}}}"#);
+        assert_eq!(verdict(&block_change, 1).0, Verdict::Allow);
+
+        let first_time = |known: &str| {
+            format!(
+                r#"{{"rules":{{"first_time_recipient":"warn"}},"context":{{"known_recipients":[{known}]}}}}"#
+            )
+        };
+        assert_eq!(
+            verdict(&first_time(""), 1),
+            (Verdict::Warn, vec![PolicyRule::FirstTimeRecipient])
+        );
+        assert_eq!(
+            verdict(&first_time(&format!("\"{payee}\"")), 1).0,
+            Verdict::Allow
+        );
+    }
+
+    #[test]
+    fn op_return_size_and_combined_verdicts() {
+        let mut tx = payment();
+        let payload = PushBytesBuf::try_from(vec![0u8; 40]).unwrap();
+        tx.output.insert(
+            0,
+            TxOut {
+                value: bitcoin::Amount::ZERO,
+                script_pubkey: ScriptBuf::new_op_return(payload),
+            },
+        );
+        let limit = |bytes: usize| {
+            policy(&format!(
+                r#"{{"rules":{{"max_op_return_bytes":{{"limit":{bytes},"action":"warn"}},"max_fee":{{"limit":{RDD}}}}}}}"#
+            ))
+        };
+        let fee = Amount::from_base(RDD);
+        assert_eq!(
+            limit(40).evaluate(&tx, fee, Some(2)).verdict,
+            Verdict::Allow
+        );
+        let report = limit(39).evaluate(&tx, fee, Some(2));
+        assert_eq!(report.verdict, Verdict::Warn);
+        assert_eq!(
+            report.findings[0].reason,
+            "OP_RETURN output 0 carries 40 bytes (limit 39)"
+        );
+
+        // The report keeps every finding and takes the most severe verdict.
+        let report = limit(39).evaluate(&tx, Amount::from_base(2 * RDD), Some(2));
+        assert_eq!(report.verdict, Verdict::Deny);
+        let rules: Vec<_> = report.findings.iter().map(|finding| finding.rule).collect();
+        assert_eq!(rules, [PolicyRule::MaxOpReturnBytes, PolicyRule::MaxFee]);
+    }
+}
@@ -0,0 +1,872 @@
+//! Script verification for signed transactions.
+//!
+//! Signing only proves we produced *a* signature; a wrong sighash, scriptCode or key encoding
+//! still yields a transaction that every node rejects. Running each input's scriptSig against the
+//! previous output's scriptPubKey before returning a signed transaction turns that broadcast-time
+//! rejection into a local error that names the failing input.
+//!
+//! The interpreter covers the legacy scripts the wallet produces or is asked to spend: P2PKH,
+//! P2PK, P2SH, bare and P2SH multisig, and the CLTV/CSV timelock scripts from
+//! [`crate::timelock`]. It enforces the standard verification flags that matter for those
+//! scripts (strict DER, low-S, NULLDUMMY, NULLFAIL, push-only scriptSig, clean stack) and rejects
+//! any opcode outside that set rather than guessing at its semantics. Segwit is out of scope;
+//! the wallet never builds witness spends.
+
+use std::str::FromStr;
+
+use bitcoin::consensus::encode::deserialize;
+use bitcoin::hashes::{hash160, ripemd160, sha256, sha256d, Hash};
+use bitcoin::opcodes::all::*;
+use bitcoin::opcodes::Opcode;
+use bitcoin::script::Instruction;
+use bitcoin::secp256k1::Message;
+use bitcoin::sighash::SighashCache;
+use bitcoin::{Script, ScriptBuf, Sequence, Transaction};
+
+use crate::address::ReddcoinAddress;
+use crate::secp_context::secp;
+use crate::transaction_signer::{parallel_map, Utxo};
+
+/// Largest element that may be pushed onto the stack (`MAX_SCRIPT_ELEMENT_SIZE`).
+const MAX_SCRIPT_ELEMENT_SIZE: usize = 520;
+/// Largest script that may be executed (`MAX_SCRIPT_SIZE`).
+const MAX_SCRIPT_SIZE: usize = 10_000;
+/// Largest combined stack depth (`MAX_STACK_SIZE`).
+const MAX_STACK_SIZE: usize = 1000;
+/// Most public keys an `OP_CHECKMULTISIG` may check (`MAX_PUBKEYS_PER_MULTISIG`).
+const MAX_PUBKEYS_PER_MULTISIG: usize = 20;
+/// Locktimes and sequences are compared as 5-byte script numbers so values up to 2^39 fit.
+const LOCKTIME_NUM_SIZE: usize = 5;
+/// Script numbers used in arithmetic and counts are limited to 4 bytes.
+const DEFAULT_NUM_SIZE: usize = 4;
+/// BIP68 sequence bit that turns off relative locktime.
+const SEQUENCE_DISABLE_FLAG: u32 = 1 << 31;
+/// BIP68 sequence bit selecting time (512-second units) rather than blocks.
+const SEQUENCE_TYPE_FLAG: u32 = 1 << 22;
+
+/// The transaction and input whose signatures are being checked.
+struct SignatureChecker<'a> {
+    tx: &'a Transaction,
+    index: usize,
+}
+
+/// Verifies every input of `tx` against the scriptPubKey of the output it spends.
+///
+/// `prevout_scripts[i]` is the scriptPubKey spent by input `i`. Inputs are independent, so they
+/// are checked on `threads` threads exactly as signing is.
+pub fn verify_inputs(
+    tx: &Transaction,
+    prevout_scripts: &[ScriptBuf],
+    threads: Option<usize>,
+) -> Result<(), String> {
+    if prevout_scripts.len() != tx.input.len() {
+        return Err(format!(
+            "{} prevout scripts supplied for {} inputs",
+            prevout_scripts.len(),
+            tx.input.len()
+        ));
+    }
+    parallel_map(prevout_scripts.len(), threads, |index| {
+        verify_input(tx, index, &prevout_scripts[index])
+            .map_err(|e| format!("input {index} failed script verification: {e}"))
+    })?;
+    Ok(())
+}
+
+/// Verifies input `index` of `tx` against `script_pubkey`.
+pub fn verify_input(tx: &Transaction, index: usize, script_pubkey: &Script) -> Result<(), String> {
+    let input = tx
+        .input
+        .get(index)
+        .ok_or_else(|| format!("transaction has no input {index}"))?;
+    let checker = SignatureChecker { tx, index };
+
+    let script_sig = &input.script_sig;
+    if !script_sig.is_push_only() {
+        return Err("scriptSig is not push-only".to_string());
+    }
+
+    let mut stack = Vec::new();
+    execute(script_sig, &mut stack, &checker)?;
+    let p2sh_stack = stack.clone();
+
+    execute(script_pubkey, &mut stack, &checker)?;
+    if !stack.last().is_some_and(|top| cast_to_bool(top)) {
+        return Err("scriptPubKey evaluated to false".to_string());
+    }
+
+    if script_pubkey.is_p2sh() {
+        let mut stack = p2sh_stack;
+        let redeem_script = ScriptBuf::from(
+            stack
+                .pop()
+                .ok_or_else(|| "P2SH spend is missing its redeem script".to_string())?,
+        );
+        execute(&redeem_script, &mut stack, &checker)?;
+        if !stack.last().is_some_and(|top| cast_to_bool(top)) {
+            return Err("redeem script evaluated to false".to_string());
+        }
+        if stack.len() != 1 {
+            return Err("redeem script left extra items on the stack".to_string());
+        }
+    } else if stack.len() != 1 {
+        return Err("scriptPubKey left extra items on the stack".to_string());
+    }
+
+    Ok(())
+}
+
+/// Verifies a hex-encoded signed transaction against the UTXOs it spends.
+///
+/// `prevouts_json` uses the same UTXO array shape the signing functions take; each input is
+/// matched by outpoint, and each UTXO needs `script_pubkey` or `address`. Returns the txid.
+pub fn verify_transaction(tx_hex: &str, prevouts_json: &str) -> Result<String, String> {
+    let raw = hex::decode(tx_hex.trim()).map_err(|e| format!("tx_hex decode failed: {e}"))?;
+    let tx: Transaction =
+        deserialize(&raw).map_err(|e| format!("tx_hex is not a transaction: {e}"))?;
+    let prevouts: Vec<Utxo> = serde_json::from_str(prevouts_json)
+        .map_err(|e| format!("prevouts_json must be a valid JSON array of UTXOs: {e}"))?;
+
+    let mut prevout_scripts = Vec::with_capacity(tx.input.len());
+    for (index, input) in tx.input.iter().enumerate() {
+        let outpoint = input.previous_output;
+        let utxo = prevouts
+            .iter()
+            .find(|utxo| {
+                utxo.vout == outpoint.vout
+                    && utxo.txid.eq_ignore_ascii_case(&outpoint.txid.to_string())
+            })
+            .ok_or_else(|| format!("no prevout supplied for input {index} ({outpoint})"))?;
+        prevout_scripts.push(prevout_script(utxo, index)?);
+    }
+
+    verify_inputs(&tx, &prevout_scripts, None)?;
+    Ok(tx.compute_txid().to_string())
+}
+
+/// The scriptPubKey a UTXO is locked to, from its explicit script or its address.
+pub(crate) fn prevout_script(utxo: &Utxo, index: usize) -> Result<ScriptBuf, String> {
+    if let Some(script_hex) = &utxo.script_pubkey {
+        return ScriptBuf::from_hex(script_hex)
+            .map_err(|e| format!("invalid script_pubkey for input {index}: {e}"));
+    }
+    if let Some(address) = &utxo.address {
+        return ReddcoinAddress::from_str(address)
+            .map(|address| address.script_pubkey())
+            .map_err(|e| format!("invalid address for input {index}: {e}"));
+    }
+    Err(format!(
+        "prevout for input {index} needs a script_pubkey or address"
+    ))
+}
+
+/// Executes `script` on `stack`. Signatures commit to the whole script, since
+/// `OP_CODESEPARATOR` is not supported.
+fn execute(
+    script: &Script,
+    stack: &mut Vec<Vec<u8>>,
+    checker: &SignatureChecker<'_>,
+) -> Result<(), String> {
+    if script.len() > MAX_SCRIPT_SIZE {
+        return Err(format!(
+            "script is {} bytes (max {MAX_SCRIPT_SIZE})",
+            script.len()
+        ));
+    }
+
+    // One entry per open OP_IF; an opcode runs only when every entry is true.
+    let mut conditions: Vec<bool> = Vec::new();
+
+    for instruction in script.instructions_minimal() {
+        let instruction = instruction.map_err(|e| format!("malformed script: {e}"))?;
+        let executing = conditions.iter().all(|condition| *condition);
+
+        let op = match instruction {
+            Instruction::PushBytes(bytes) => {
+                if bytes.len() > MAX_SCRIPT_ELEMENT_SIZE {
+                    return Err(format!(
+                        "push of {} bytes exceeds {MAX_SCRIPT_ELEMENT_SIZE}",
+                        bytes.len()
+                    ));
+                }
+                if executing {
+                    stack.push(bytes.as_bytes().to_vec());
+                }
+                check_stack_size(stack)?;
+                continue;
+            }
+            Instruction::Op(op) => op,
+        };
+
+        match op {
+            OP_IF | OP_NOTIF => {
+                let mut value = false;
+                if executing {
+                    // MINIMALIF is a witness-only rule in Core, so legacy and P2SH scripts
+                    // accept any truthy or falsy argument here.
+                    let top = pop(stack)?;
+                    value = cast_to_bool(&top) == (op == OP_IF);
+                }
+                conditions.push(value);
+                continue;
+            }
+            OP_ELSE => {
+                let last = conditions
+                    .last_mut()
+                    .ok_or_else(|| "OP_ELSE without OP_IF".to_string())?;
+                *last = !*last;
+                continue;
+            }
+            OP_ENDIF => {
+                conditions
+                    .pop()
+                    .ok_or_else(|| "OP_ENDIF without OP_IF".to_string())?;
+                continue;
+            }
+            _ if !executing => continue,
+            _ => {}
+        }
+
+        if let Some(number) = small_integer(op) {
+            stack.push(encode_num(number));
+            check_stack_size(stack)?;
+            continue;
+        }
+
+        match op {
+            OP_NOP => {}
+            OP_VERIFY => verify_top(stack, "OP_VERIFY")?,
+            OP_RETURN => return Err("OP_RETURN executed".to_string()),
+            OP_DROP => {
+                pop(stack)?;
+            }
+            OP_2DROP => {
+                pop(stack)?;
+                pop(stack)?;
+            }
+            OP_DUP => {
+                let top = stack
+                    .last()
+                    .cloned()
+                    .ok_or_else(|| "stack underflow".to_string())?;
+                stack.push(top);
+            }
+            OP_SWAP => {
+                let len = stack.len();
+                if len < 2 {
+                    return Err("stack underflow".to_string());
+                }
+                stack.swap(len - 1, len - 2);
+            }
+            OP_SIZE => {
+                let size = stack
+                    .last()
+                    .map(Vec::len)
+                    .ok_or_else(|| "stack underflow".to_string())?;
+                stack.push(encode_num(size as i64));
+            }
+            OP_EQUAL | OP_EQUALVERIFY => {
+                let b = pop(stack)?;
+                let a = pop(stack)?;
+                stack.push(encode_bool(a == b));
+                if op == OP_EQUALVERIFY {
+                    verify_top(stack, "OP_EQUALVERIFY")?;
+                }
+            }
+            OP_RIPEMD160 => {
+                let top = pop(stack)?;
+                stack.push(ripemd160::Hash::hash(&top).to_byte_array().to_vec());
+            }
+            OP_SHA256 => {
+                let top = pop(stack)?;
+                stack.push(sha256::Hash::hash(&top).to_byte_array().to_vec());
+            }
+            OP_HASH160 => {
+                let top = pop(stack)?;
+                stack.push(hash160::Hash::hash(&top).to_byte_array().to_vec());
+            }
+            OP_HASH256 => {
+                let top = pop(stack)?;
+                stack.push(sha256d::Hash::hash(&top).to_byte_array().to_vec());
+            }
+            OP_CHECKSIG | OP_CHECKSIGVERIFY => {
+                let pubkey = pop(stack)?;
+                let signature = pop(stack)?;
+                let valid = checker.check_signature(&signature, &pubkey, script)?;
+                if !valid && !signature.is_empty() {
+                    return Err("signature does not match the public key and sighash".to_string());
+                }
+                stack.push(encode_bool(valid));
+                if op == OP_CHECKSIGVERIFY {
+                    verify_top(stack, "OP_CHECKSIGVERIFY")?;
+                }
+            }
+            OP_CHECKMULTISIG | OP_CHECKMULTISIGVERIFY => {
+                let valid = check_multisig(stack, checker, script)?;
+                stack.push(encode_bool(valid));
+                if op == OP_CHECKMULTISIGVERIFY {
+                    verify_top(stack, "OP_CHECKMULTISIGVERIFY")?;
+                }
+            }
+            OP_CLTV => {
+                let lock = peek_num(stack, LOCKTIME_NUM_SIZE)?;
+                checker.check_lock_time(lock)?;
+            }
+            OP_CSV => {
+                let sequence = peek_num(stack, LOCKTIME_NUM_SIZE)?;
+                checker.check_sequence(sequence)?;
+            }
+            other => return Err(format!("unsupported opcode {other}")),
+        }
+        check_stack_size(stack)?;
+    }
+
+    if !conditions.is_empty() {
+        return Err("unbalanced OP_IF".to_string());
+    }
+    Ok(())
+}
+
+/// Runs `OP_CHECKMULTISIG`: `<dummy> <sig>... <m> <pubkey>... <n>`.
+fn check_multisig(
+    stack: &mut Vec<Vec<u8>>,
+    checker: &SignatureChecker<'_>,
+    script_code: &Script,
+) -> Result<bool, String> {
+    let key_count = pop_num(stack, DEFAULT_NUM_SIZE)?;
+    let key_count = usize::try_from(key_count)
+        .ok()
+        .filter(|count| *count <= MAX_PUBKEYS_PER_MULTISIG)
+        .ok_or_else(|| format!("invalid multisig key count {key_count}"))?;
+    let mut pubkeys = (0..key_count)
+        .map(|_| pop(stack))
+        .collect::<Result<Vec<_>, _>>()?;
+    pubkeys.reverse();
+
+    let sig_count = pop_num(stack, DEFAULT_NUM_SIZE)?;
+    let sig_count = usize::try_from(sig_count)
+        .ok()
+        .filter(|count| *count <= key_count)
+        .ok_or_else(|| format!("invalid multisig signature count {sig_count}"))?;
+    let mut signatures = (0..sig_count)
+        .map(|_| pop(stack))
+        .collect::<Result<Vec<_>, _>>()?;
+    signatures.reverse();
+
+    // The extra item consumed by the historical off-by-one bug must be empty (NULLDUMMY).
+    if !pop(stack)?.is_empty() {
+        return Err("OP_CHECKMULTISIG dummy element must be empty".to_string());
+    }
+
+    // Signatures must appear in the same order as their keys, so each key is tried at most once.
+    let mut keys = pubkeys.iter();
+    let mut valid = true;
+    for signature in &signatures {
+        let mut matched = false;
+        for pubkey in keys.by_ref() {
+            if checker.check_signature(signature, pubkey, script_code)? {
+                matched = true;
+                break;
+            }
+        }
+        if !matched {
+            valid = false;
+            break;
+        }
+    }
+
+    if !valid && signatures.iter().any(|signature| !signature.is_empty()) {
+        return Err("multisig signatures do not match the public keys and sighash".to_string());
+    }
+    Ok(valid)
+}
+
+impl SignatureChecker<'_> {
+    /// Checks one signature. An empty signature is simply false; a malformed one is an error.
+    fn check_signature(
+        &self,
+        signature: &[u8],
+        pubkey: &[u8],
+        script_code: &Script,
+    ) -> Result<bool, String> {
+        if signature.is_empty() {
+            return Ok(false);
+        }
+        let signature = bitcoin::ecdsa::Signature::from_slice(signature)
+            .map_err(|e| format!("invalid signature encoding: {e}"))?;
+        let pubkey = bitcoin::PublicKey::from_slice(pubkey)
+            .map_err(|e| format!("invalid public key encoding: {e}"))?;
+
+        let sighash = SighashCache::new(self.tx)
+            .legacy_signature_hash(self.index, script_code, signature.sighash_type.to_u32())
+            .map_err(|e| format!("failed to construct sighash: {e}"))?;
+        let message = Message::from_digest(*sighash.as_byte_array());
+
+        // libsecp256k1 rejects high-S signatures, which enforces LOW_S.
+        Ok(secp()
+            .verify_ecdsa(&message, &signature.signature, &pubkey.inner)
+            .is_ok())
+    }
+
+    /// BIP65: the transaction's locktime must have reached `lock`, in the same unit.
+    fn check_lock_time(&self, lock: i64) -> Result<(), String> {
+        if lock < 0 {
+            return Err("negative OP_CHECKLOCKTIMEVERIFY locktime".to_string());
+        }
+        let tx_lock = i64::from(self.tx.lock_time.to_consensus_u32());
+        let threshold = i64::from(crate::timelock::LOCKTIME_THRESHOLD);
+        if (lock < threshold) != (tx_lock < threshold) {
+            return Err("OP_CHECKLOCKTIMEVERIFY mixes height and time locks".to_string());
+        }
+        if lock > tx_lock {
+            return Err(format!(
+                "transaction locktime {tx_lock} is before the script lock {lock}"
+            ));
+        }
+        if self.tx.input[self.index].sequence == Sequence::MAX {
+            return Err("input sequence is final, so the locktime is not enforced".to_string());
+        }
+        Ok(())
+    }
+
+    /// BIP112: the input's relative lock must have reached `sequence`, in the same unit.
+    fn check_sequence(&self, sequence: i64) -> Result<(), String> {
+        if sequence < 0 {
+            return Err("negative OP_CHECKSEQUENCEVERIFY sequence".to_string());
+        }
+        let sequence = sequence as u32;
+        if sequence & SEQUENCE_DISABLE_FLAG != 0 {
+            return Ok(());
+        }
+        if self.tx.version.0 < 2 {
+            return Err("OP_CHECKSEQUENCEVERIFY requires transaction version 2".to_string());
+        }
+        let tx_sequence = self.tx.input[self.index].sequence.to_consensus_u32();
+        if tx_sequence & SEQUENCE_DISABLE_FLAG != 0 {
+            return Err("input sequence disables relative locktime".to_string());
+        }
+        let mask = SEQUENCE_TYPE_FLAG | 0xffff;
+        let (lock, tx_lock) = (sequence & mask, tx_sequence & mask);
+        if (lock & SEQUENCE_TYPE_FLAG) != (tx_lock & SEQUENCE_TYPE_FLAG) {
+            return Err("OP_CHECKSEQUENCEVERIFY mixes block and time locks".to_string());
+        }
+        if lock > tx_lock {
+            return Err(format!(
+                "input sequence {tx_lock:#x} is below the script lock {lock:#x}"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Value pushed by `OP_1NEGATE` and `OP_1`..`OP_16`.
+fn small_integer(op: Opcode) -> Option<i64> {
+    let code = op.to_u8();
+    if op == OP_PUSHNUM_NEG1 {
+        Some(-1)
+    } else if (OP_PUSHNUM_1.to_u8()..=OP_PUSHNUM_16.to_u8()).contains(&code) {
+        Some(i64::from(code - OP_PUSHNUM_1.to_u8() + 1))
+    } else {
+        None
+    }
+}
+
+fn pop(stack: &mut Vec<Vec<u8>>) -> Result<Vec<u8>, String> {
+    stack.pop().ok_or_else(|| "stack underflow".to_string())
+}
+
+fn pop_num(stack: &mut Vec<Vec<u8>>, max_len: usize) -> Result<i64, String> {
+    decode_num(&pop(stack)?, max_len)
+}
+
+fn peek_num(stack: &[Vec<u8>], max_len: usize) -> Result<i64, String> {
+    decode_num(
+        stack.last().ok_or_else(|| "stack underflow".to_string())?,
+        max_len,
+    )
+}
+
+fn verify_top(stack: &mut Vec<Vec<u8>>, op: &str) -> Result<(), String> {
+    if cast_to_bool(&pop(stack)?) {
+        Ok(())
+    } else {
+        Err(format!("{op} failed"))
+    }
+}
+
+fn check_stack_size(stack: &[Vec<u8>]) -> Result<(), String> {
+    if stack.len() > MAX_STACK_SIZE {
+        return Err(format!("stack exceeds {MAX_STACK_SIZE} items"));
+    }
+    Ok(())
+}
+
+/// Script truthiness: any non-zero byte, except that a lone sign bit is negative zero.
+fn cast_to_bool(bytes: &[u8]) -> bool {
+    bytes
+        .iter()
+        .enumerate()
+        .any(|(index, byte)| *byte != 0 && !(index == bytes.len() - 1 && *byte == 0x80))
+}
+
+fn encode_bool(value: bool) -> Vec<u8> {
+    if value {
+        vec![1]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Decodes a minimally-encoded little-endian sign-magnitude script number.
+fn decode_num(bytes: &[u8], max_len: usize) -> Result<i64, String> {
+    let Some(&last) = bytes.last() else {
+        return Ok(0);
+    };
+    if bytes.len() > max_len {
+        return Err(format!("script number longer than {max_len} bytes"));
+    }
+    if last & 0x7f == 0 && (bytes.len() == 1 || bytes[bytes.len() - 2] & 0x80 == 0) {
+        return Err("script number is not minimally encoded".to_string());
+    }
+    let mut value = 0i64;
+    for (index, byte) in bytes.iter().enumerate() {
+        value |= i64::from(*byte) << (8 * index);
+    }
+    let sign_bit = 0x80i64 << (8 * (bytes.len() - 1));
+    Ok(if value & sign_bit != 0 {
+        -(value & !sign_bit)
+    } else {
+        value
+    })
+}
+
+/// Encodes a script number minimally.
+fn encode_num(value: i64) -> Vec<u8> {
+    let mut magnitude = value.unsigned_abs();
+    let mut bytes = Vec::new();
+    while magnitude > 0 {
+        bytes.push((magnitude & 0xff) as u8);
+        magnitude >>= 8;
+    }
+    if let Some(last) = bytes.last_mut() {
+        if *last & 0x80 != 0 {
+            bytes.push(if value < 0 { 0x80 } else { 0 });
+        } else if value < 0 {
+            *last |= 0x80;
+        }
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::absolute::LockTime;
+    use bitcoin::script::{Builder, PushBytesBuf};
+    use bitcoin::secp256k1::SecretKey;
+    use bitcoin::sighash::EcdsaSighashType;
+    use bitcoin::transaction::Version;
+    use bitcoin::{Amount, OutPoint, PublicKey, TxIn, TxOut, Witness};
+
+    use super::*;
+    use crate::timelock::{build_timelock_script, AbsoluteLock, RelativeLock, Timelock};
+
+    fn key(seed: u8) -> (SecretKey, PublicKey) {
+        let secret_key = SecretKey::from_slice(&[seed; 32]).unwrap();
+        (secret_key, PublicKey::new(secret_key.public_key(secp())))
+    }
+
+    fn spending_tx(version: i32, lock_time: u32, sequence: Sequence) -> Transaction {
+        Transaction {
+            version: Version(version),
+            lock_time: LockTime::from_consensus(lock_time),
+            input: vec![TxIn {
+                previous_output: OutPoint::new("11".repeat(32).parse().unwrap(), 0),
+                script_sig: ScriptBuf::new(),
+                sequence,
+                witness: Witness::new(),
+            }],
+            output: vec![TxOut {
+                value: Amount::from_sat(50_000),
+                script_pubkey: ScriptBuf::new_p2pkh(&key(99).1.pubkey_hash()),
+            }],
+        }
+    }
+
+    /// DER signature plus sighash byte over input 0 with `script_code`.
+    fn sign(tx: &Transaction, script_code: &Script, secret_key: &SecretKey) -> Vec<u8> {
+        let sighash = SighashCache::new(tx)
+            .legacy_signature_hash(0, script_code, EcdsaSighashType::All.to_u32())
+            .unwrap();
+        let message = Message::from_digest(*sighash.as_byte_array());
+        bitcoin::ecdsa::Signature {
+            signature: secp().sign_ecdsa(&message, secret_key),
+            sighash_type: EcdsaSighashType::All,
+        }
+        .to_vec()
+    }
+
+    fn push(data: &[u8]) -> PushBytesBuf {
+        PushBytesBuf::try_from(data.to_vec()).unwrap()
+    }
+
+    fn verify(
+        mut tx: Transaction,
+        script_sig: ScriptBuf,
+        script_pubkey: &Script,
+    ) -> Result<(), String> {
+        tx.input[0].script_sig = script_sig;
+        verify_input(&tx, 0, script_pubkey)
+    }
+
+    fn multisig_script(keys: &[PublicKey]) -> ScriptBuf {
+        let mut builder = Builder::new().push_int(2);
+        for key in keys {
+            builder = builder.push_key(key);
+        }
+        builder
+            .push_int(keys.len() as i64)
+            .push_opcode(OP_CHECKMULTISIG)
+            .into_script()
+    }
+
+    #[test]
+    fn p2pkh() {
+        let (secret_key, pubkey) = key(1);
+        let script_pubkey = ScriptBuf::new_p2pkh(&pubkey.pubkey_hash());
+        let tx = spending_tx(1, 0, Sequence::MAX);
+        let signature = sign(&tx, &script_pubkey, &secret_key);
+        let script_sig = |signature: &[u8], pubkey: &PublicKey| {
+            Builder::new()
+                .push_slice(push(signature))
+                .push_key(pubkey)
+                .into_script()
+        };
+
+        assert!(verify(tx.clone(), script_sig(&signature, &pubkey), &script_pubkey).is_ok());
+
+        // Signed by another key, or presented with another key.
+        let other = sign(&tx, &script_pubkey, &key(2).0);
+        assert!(
+            verify(tx.clone(), script_sig(&other, &pubkey), &script_pubkey)
+                .unwrap_err()
+                .contains("does not match")
+        );
+        assert!(verify(
+            tx.clone(),
+            script_sig(&signature, &key(2).1),
+            &script_pubkey
+        )
+        .unwrap_err()
+        .contains("OP_EQUALVERIFY failed"));
+
+        // Clean stack and push-only scriptSig.
+        let extra = Builder::new()
+            .push_int(1)
+            .push_slice(push(&signature))
+            .push_key(&pubkey)
+            .into_script();
+        assert!(verify(tx.clone(), extra, &script_pubkey)
+            .unwrap_err()
+            .contains("extra items"));
+        let not_push_only = Builder::new()
+            .push_slice(push(&signature))
+            .push_key(&pubkey)
+            .push_opcode(OP_NOP)
+            .into_script();
+        assert!(verify(tx, not_push_only, &script_pubkey)
+            .unwrap_err()
+            .contains("push-only"));
+    }
+
+    #[test]
+    fn non_minimal_pushes_are_rejected() {
+        let (secret_key, pubkey) = key(1);
+        let script_pubkey = ScriptBuf::new_p2pkh(&pubkey.pubkey_hash());
+        let tx = spending_tx(1, 0, Sequence::MAX);
+        let signature = sign(&tx, &script_pubkey, &secret_key);
+
+        // OP_PUSHDATA1 for a 33-byte key that a direct push would carry.
+        let mut raw = vec![signature.len() as u8];
+        raw.extend_from_slice(&signature);
+        raw.extend_from_slice(&[OP_PUSHDATA1.to_u8(), 33]);
+        raw.extend_from_slice(&pubkey.to_bytes());
+        let err = verify(tx, ScriptBuf::from(raw), &script_pubkey).unwrap_err();
+        assert!(err.contains("malformed script"), "{err}");
+
+        assert!(decode_num(&[0x01, 0x00], 4)
+            .unwrap_err()
+            .contains("minimally encoded"));
+        assert_eq!(decode_num(&[0xff, 0x00], 4), Ok(255));
+        assert_eq!(decode_num(&[0x81], 4), Ok(-1));
+        assert_eq!(encode_num(-255), vec![0xff, 0x80]);
+    }
+
+    #[test]
+    fn bare_multisig() {
+        let keys: Vec<_> = (1..=3).map(key).collect();
+        let pubkeys: Vec<_> = keys.iter().map(|(_, pubkey)| *pubkey).collect();
+        let script_pubkey = multisig_script(&pubkeys);
+        let tx = spending_tx(1, 0, Sequence::MAX);
+        let sig1 = sign(&tx, &script_pubkey, &keys[0].0);
+        let sig3 = sign(&tx, &script_pubkey, &keys[2].0);
+        let script_sig = |dummy: &[u8], sigs: [&[u8]; 2]| {
+            Builder::new()
+                .push_slice(push(dummy))
+                .push_slice(push(sigs[0]))
+                .push_slice(push(sigs[1]))
+                .into_script()
+        };
+
+        assert!(verify(tx.clone(), script_sig(&[], [&sig1, &sig3]), &script_pubkey).is_ok());
+        // NULLDUMMY.
+        assert!(verify(
+            tx.clone(),
+            script_sig(&[0xab, 0xcd], [&sig1, &sig3]),
+            &script_pubkey
+        )
+        .unwrap_err()
+        .contains("dummy element must be empty"));
+        // Signatures out of key order fail, and a failing non-empty signature is NULLFAIL.
+        assert!(
+            verify(tx.clone(), script_sig(&[], [&sig3, &sig1]), &script_pubkey)
+                .unwrap_err()
+                .contains("do not match")
+        );
+        // Empty signatures make the check false without an error.
+        assert!(verify(tx, script_sig(&[], [&[], &[]]), &script_pubkey)
+            .unwrap_err()
+            .contains("evaluated to false"));
+    }
+
+    #[test]
+    fn p2sh_multisig() {
+        let keys: Vec<_> = (1..=3).map(key).collect();
+        let pubkeys: Vec<_> = keys.iter().map(|(_, pubkey)| *pubkey).collect();
+        let redeem_script = multisig_script(&pubkeys);
+        let script_pubkey = ScriptBuf::new_p2sh(&redeem_script.script_hash());
+        let tx = spending_tx(1, 0, Sequence::MAX);
+        let sig1 = sign(&tx, &redeem_script, &keys[0].0);
+        let sig2 = sign(&tx, &redeem_script, &keys[1].0);
+        let script_sig = |dummy: &[u8], redeem_script: &Script| {
+            Builder::new()
+                .push_slice(push(dummy))
+                .push_slice(push(&sig1))
+                .push_slice(push(&sig2))
+                .push_slice(push(redeem_script.as_bytes()))
+                .into_script()
+        };
+
+        assert!(verify(tx.clone(), script_sig(&[], &redeem_script), &script_pubkey).is_ok());
+        assert!(verify(
+            tx.clone(),
+            script_sig(&[0xab, 0xcd], &redeem_script),
+            &script_pubkey
+        )
+        .unwrap_err()
+        .contains("dummy element must be empty"));
+        let other_redeem = multisig_script(&pubkeys[..2]);
+        assert!(verify(tx, script_sig(&[], &other_redeem), &script_pubkey)
+            .unwrap_err()
+            .contains("scriptPubKey evaluated to false"));
+    }
+
+    #[test]
+    fn op_if_accepts_non_minimal_arguments() {
+        let (secret_key, pubkey) = key(3);
+        let redeem_script = Builder::new()
+            .push_opcode(OP_IF)
+            .push_key(&pubkey)
+            .push_opcode(OP_CHECKSIG)
+            .push_opcode(OP_ELSE)
+            .push_int(0)
+            .push_opcode(OP_ENDIF)
+            .into_script();
+        let script_pubkey = ScriptBuf::new_p2sh(&redeem_script.script_hash());
+        let tx = spending_tx(1, 0, Sequence::MAX);
+        let signature = sign(&tx, &redeem_script, &secret_key);
+        let script_sig = |condition: Builder| {
+            let mut raw = Builder::new().push_slice(push(&signature)).into_bytes();
+            raw.extend(condition.into_bytes());
+            raw.extend(
+                Builder::new()
+                    .push_slice(push(redeem_script.as_bytes()))
+                    .into_bytes(),
+            );
+            ScriptBuf::from(raw)
+        };
+        let data = |bytes: &[u8]| Builder::new().push_slice(push(bytes));
+
+        assert!(verify(
+            tx.clone(),
+            script_sig(Builder::new().push_int(1)),
+            &script_pubkey
+        )
+        .is_ok());
+        // OP_2 and a two-byte push would both fail MINIMALIF but are fine outside witness code.
+        assert!(verify(
+            tx.clone(),
+            script_sig(Builder::new().push_int(2)),
+            &script_pubkey
+        )
+        .is_ok());
+        assert!(verify(tx.clone(), script_sig(data(&[0x00, 0x01])), &script_pubkey).is_ok());
+        // Negative zero is false and takes the failing branch.
+        assert!(verify(tx, script_sig(data(&[0x80])), &script_pubkey)
+            .unwrap_err()
+            .contains("evaluated to false"));
+    }
+
+    fn timelock_spend(
+        lock: Timelock,
+        version: i32,
+        lock_time: u32,
+        sequence: Sequence,
+    ) -> Result<(), String> {
+        let (secret_key, pubkey) = key(4);
+        let redeem_script = build_timelock_script(lock, &pubkey).unwrap();
+        let script_pubkey = ScriptBuf::new_p2sh(&redeem_script.script_hash());
+        let tx = spending_tx(version, lock_time, sequence);
+        let script_sig = Builder::new()
+            .push_slice(push(&sign(&tx, &redeem_script, &secret_key)))
+            .push_slice(push(redeem_script.as_bytes()))
+            .into_script();
+        verify(tx, script_sig, &script_pubkey)
+    }
+
+    #[test]
+    fn check_lock_time_verify() {
+        let lock = Timelock::Absolute(AbsoluteLock::Height(500_000));
+        let non_final = Sequence::ENABLE_LOCKTIME_NO_RBF;
+        assert!(timelock_spend(lock, 2, 500_000, non_final).is_ok());
+        assert!(timelock_spend(lock, 2, 600_000, non_final).is_ok());
+        assert!(timelock_spend(lock, 2, 499_999, non_final)
+            .unwrap_err()
+            .contains("before the script lock"));
+        assert!(timelock_spend(lock, 2, 500_000, Sequence::MAX)
+            .unwrap_err()
+            .contains("sequence is final"));
+        assert!(timelock_spend(lock, 2, 1_700_000_000, non_final)
+            .unwrap_err()
+            .contains("mixes height and time"));
+    }
+
+    #[test]
+    fn check_sequence_verify() {
+        let lock = Timelock::Relative(RelativeLock::Blocks(10));
+        assert!(timelock_spend(lock, 2, 0, Sequence::from_height(10)).is_ok());
+        assert!(timelock_spend(lock, 2, 0, Sequence::from_height(9))
+            .unwrap_err()
+            .contains("below the script lock"));
+        assert!(timelock_spend(lock, 1, 0, Sequence::from_height(10))
+            .unwrap_err()
+            .contains("requires transaction version 2"));
+        assert!(timelock_spend(lock, 2, 0, Sequence::MAX)
+            .unwrap_err()
+            .contains("disables relative locktime"));
+        assert!(
+            timelock_spend(lock, 2, 0, Sequence::from_512_second_intervals(10))
+                .unwrap_err()
+                .contains("mixes block and time")
+        );
+    }
+}
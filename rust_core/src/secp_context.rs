@@ -0,0 +1,21 @@
+//! Process-wide secp256k1 context.
+//!
+//! Building a `Secp256k1` context allocates and precomputes tables, which costs more than signing
+//! a single input. Every signing and verification path shares this one instead of creating its
+//! own per call. It is randomized once at start-up so its blinding protects signing against
+//! side-channel attacks for the lifetime of the process.
+
+use std::sync::OnceLock;
+
+use bitcoin::secp256k1::{All, Secp256k1};
+
+static CONTEXT: OnceLock<Secp256k1<All>> = OnceLock::new();
+
+/// The shared signing and verification context.
+pub fn secp() -> &'static Secp256k1<All> {
+    CONTEXT.get_or_init(|| {
+        let mut context = Secp256k1::new();
+        context.randomize(&mut rand::thread_rng());
+        context
+    })
+}
@@ -0,0 +1,549 @@
+//! PoSV v2 stake weight and reward estimates.
+//!
+//! Proof of Stake Velocity weights each output by a coin-age function that grows roughly
+//! linearly for the first week and logarithmically after that. Coins that sit idle earn less per
+//! day than coins that stake regularly; that is the "velocity" part. How a wallet's balance is
+//! split across outputs therefore changes both how often it stakes and what it earns, which is
+//! what the what-if comparison shows.
+//!
+//! These are estimates for display. Ages are derived from confirmations and the target block
+//! spacing, and the network weight is whatever the caller's node reports, so the results drift as
+//! the network does.
+
+use serde::{Deserialize, Serialize};
+
+use crate::amount::{Amount, BASE_UNITS_PER_RDD};
+use crate::chain_params::ChainParams;
+use crate::utxo::{filter_spendable, ExcludedUtxo, SpendabilityRules, Utxo};
+
+const SECS_PER_DAY: f64 = 24.0 * 60.0 * 60.0;
+const SECS_PER_YEAR: f64 = 365.0 * SECS_PER_DAY;
+
+/// Integration steps for stake probability: fine while the weight curve is steep during the
+/// first week, coarse once it flattens into its logarithmic tail.
+const FINE_STEP_SECS: f64 = 60.0 * 60.0;
+const COARSE_STEP_SECS: f64 = SECS_PER_DAY;
+
+/// Ages past this are reported as "will not stake in any useful time frame".
+const MAX_PROJECTION_SECS: f64 = 10.0 * SECS_PER_YEAR;
+
+/// Network state the estimate is made against.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct StakingNetwork {
+    /// Total network stake weight in coin-days, as reported by `getstakinginfo`'s
+    /// `netstakeweight`.
+    pub network_weight: f64,
+    /// Current tip, used to derive ages from heights when confirmations are missing.
+    #[serde(default)]
+    pub tip_height: Option<u32>,
+}
+
+/// Stake figures for one output.
+#[derive(Debug, Clone, Serialize)]
+pub struct UtxoStake {
+    pub txid: String,
+    pub vout: u32,
+    pub amount: Amount,
+    pub age_secs: u64,
+    /// Current stake weight in coin-days.
+    pub weight: f64,
+    /// Seconds from now until this output is expected to stake, or `None` if beyond ten years.
+    pub expected_secs_to_stake: Option<u64>,
+    /// Steady-state yearly reward if this output keeps re-staking at its own pace.
+    pub projected_annual_reward: Amount,
+}
+
+/// Stake figures for a wallet.
+#[derive(Debug, Clone, Serialize)]
+pub struct StakingEstimate {
+    pub utxos: Vec<UtxoStake>,
+    /// Outputs that cannot stake yet (immature or unconfirmed).
+    pub excluded: Vec<ExcludedUtxo>,
+    pub staking_amount: Amount,
+    /// Current wallet stake weight in coin-days.
+    pub total_weight: f64,
+    /// Expected seconds until any output in the wallet stakes at current weights.
+    pub expected_secs_to_stake: Option<u64>,
+    pub projected_annual_reward: Amount,
+}
+
+/// A way of arranging the wallet's balance for the what-if comparison.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum StakingStrategy {
+    /// Keep the outputs as they are.
+    Current,
+    /// Merge everything into one output.
+    Consolidate,
+    /// Re-split into outputs of `target_output` each.
+    Split { target_output: Amount },
+}
+
+/// Steady-state projection for one strategy.
+#[derive(Debug, Clone, Serialize)]
+pub struct StrategyProjection {
+    #[serde(flatten)]
+    pub strategy: StakingStrategy,
+    pub output_count: usize,
+    /// Mean time between stakes for a typical output under this strategy.
+    pub mean_secs_between_stakes: Option<u64>,
+    pub projected_annual_reward: Amount,
+    /// Projected reward as a share of the staked balance, in basis points.
+    pub annual_yield_bps: u64,
+}
+
+/// PoSV v2 coin-age weight, in days, for an output of the given age.
+///
+/// Zero until the minimum stake age, then `-0.00408163 d³ + 0.05714286 d² + d` for the first
+/// seven days and `8.4 ln(d) - 7.94564525` afterwards (the two meet at 8.4 days of weight).
+/// Like Reddcoin Core's `GetCoinAgeWeightV2`, the cap applies to the resulting weight, not the
+/// age: weight keeps growing until it reaches the maximum stake age, about 546 days in on
+/// mainnet.
+pub fn coin_age_weight_days(age_secs: f64, params: &ChainParams) -> f64 {
+    let days = (age_secs - f64::from(params.stake_min_age_secs)).max(0.0) / SECS_PER_DAY;
+    let weight = if days <= 7.0 {
+        -0.00408163 * days.powi(3) + 0.05714286 * days.powi(2) + days
+    } else {
+        8.4 * days.ln() - 7.94564525
+    };
+    weight.min(f64::from(params.stake_max_age_secs) / SECS_PER_DAY)
+}
+
+/// Stake weight of `amount` at `age_secs`, in coin-days.
+pub fn stake_weight(amount: Amount, age_secs: f64, params: &ChainParams) -> f64 {
+    rdd(amount) * coin_age_weight_days(age_secs, params)
+}
+
+/// Reward for staking `amount` after it has aged `age_secs`.
+pub fn stake_reward(amount: Amount, age_secs: f64, params: &ChainParams) -> Amount {
+    let rate = f64::from(params.stake_annual_reward_bps) / 10_000.0;
+    let reward_rdd = stake_weight(amount, age_secs, params) * rate / 365.0;
+    Amount::from_base((reward_rdd * BASE_UNITS_PER_RDD as f64) as u64)
+}
+
+/// Estimates stake weight, time to stake and annual reward for the wallet's outputs.
+pub fn estimate_staking(
+    utxos: Vec<Utxo>,
+    network: &StakingNetwork,
+    params: &ChainParams,
+) -> Result<StakingEstimate, String> {
+    if network.network_weight <= 0.0 || !network.network_weight.is_finite() {
+        return Err("network_weight must be a positive number of coin-days".to_string());
+    }
+
+    let rules = SpendabilityRules {
+        min_confirmations: 1,
+        tip_height: network.tip_height,
+    };
+    let set = filter_spendable(utxos, params, &rules);
+
+    let mut stakes = Vec::with_capacity(set.spendable.len());
+    for utxo in set.spendable {
+        let age_secs = f64::from(utxo.confirmations_at(network.tip_height))
+            * f64::from(params.target_spacing_secs);
+        let (expected, annual) = project_output(utxo.amount, age_secs, network, params);
+        stakes.push(UtxoStake {
+            weight: stake_weight(utxo.amount, age_secs, params),
+            expected_secs_to_stake: expected.map(|secs| secs as u64),
+            projected_annual_reward: annual,
+            age_secs: age_secs as u64,
+            txid: utxo.txid,
+            vout: utxo.vout,
+            amount: utxo.amount,
+        });
+    }
+
+    let total_weight: f64 = stakes.iter().map(|stake| stake.weight).sum();
+    // A wallet with weight W wins a given block with probability W / network_weight.
+    let expected_secs_to_stake = (total_weight > 0.0).then(|| {
+        (network.network_weight / total_weight * f64::from(params.target_spacing_secs)) as u64
+    });
+
+    Ok(StakingEstimate {
+        projected_annual_reward: stakes
+            .iter()
+            .map(|stake| stake.projected_annual_reward)
+            .sum(),
+        staking_amount: set.spendable_amount,
+        excluded: set.excluded,
+        total_weight,
+        expected_secs_to_stake,
+        utxos: stakes,
+    })
+}
+
+/// Compares steady-state rewards for each strategy over the wallet's mature balance.
+///
+/// Consolidating or splitting resets every output's age, but in steady state each output cycles
+/// from age zero to its next stake, so ages are ignored and only output sizes matter.
+pub fn compare_staking_strategies(
+    utxos: Vec<Utxo>,
+    network: &StakingNetwork,
+    strategies: &[StakingStrategy],
+    params: &ChainParams,
+) -> Result<Vec<StrategyProjection>, String> {
+    let estimate = estimate_staking(utxos, network, params)?;
+    let current: Vec<Amount> = estimate.utxos.iter().map(|stake| stake.amount).collect();
+    let balance = estimate.staking_amount;
+
+    strategies
+        .iter()
+        .map(|strategy| {
+            let outputs = match *strategy {
+                StakingStrategy::Current => current.clone(),
+                StakingStrategy::Consolidate if balance > Amount::ZERO => vec![balance],
+                StakingStrategy::Consolidate => Vec::new(),
+                StakingStrategy::Split { target_output } => {
+                    if target_output == Amount::ZERO {
+                        return Err("split target_output must be positive".to_string());
+                    }
+                    let count = balance.to_base() / target_output.to_base();
+                    let mut outputs = vec![target_output; count.saturating_sub(1) as usize];
+                    // The last output absorbs the remainder, as the consolidation planner does.
+                    let rest = balance
+                        .checked_sub(
+                            target_output
+                                .checked_mul(outputs.len() as u64)
+                                .unwrap_or(Amount::MAX),
+                        )
+                        .unwrap_or(Amount::ZERO);
+                    if rest > Amount::ZERO {
+                        outputs.push(rest);
+                    }
+                    outputs
+                }
+            };
+
+            let cycles: Vec<(Option<f64>, Amount)> = outputs
+                .iter()
+                .map(|amount| project_output(*amount, 0.0, network, params))
+                .collect();
+            let projected_annual_reward: Amount = cycles.iter().map(|(_, reward)| *reward).sum();
+            let timed: Vec<f64> = cycles.iter().filter_map(|(secs, _)| *secs).collect();
+            let mean_secs_between_stakes = (!timed.is_empty())
+                .then(|| (timed.iter().sum::<f64>() / timed.len() as f64) as u64);
+
+            Ok(StrategyProjection {
+                strategy: *strategy,
+                output_count: outputs.len(),
+                mean_secs_between_stakes,
+                annual_yield_bps: projected_annual_reward
+                    .to_base()
+                    .saturating_mul(10_000)
+                    .checked_div(balance.to_base())
+                    .unwrap_or(0),
+                projected_annual_reward,
+            })
+        })
+        .collect()
+}
+
+/// JSON entry point for [`estimate_staking`] on mainnet parameters.
+pub fn estimate_staking_json(
+    utxos_json: String,
+    network_json: String,
+) -> Result<StakingEstimate, String> {
+    let (utxos, network) = parse_inputs(&utxos_json, &network_json)?;
+    estimate_staking(utxos, &network, &ChainParams::MAINNET)
+}
+
+/// JSON entry point for [`compare_staking_strategies`] on mainnet parameters.
+///
+/// `strategies_json` is an array such as
+/// `[{"strategy":"current"},{"strategy":"consolidate"},{"strategy":"split","target_output":n}]`.
+pub fn compare_staking_strategies_json(
+    utxos_json: String,
+    network_json: String,
+    strategies_json: String,
+) -> Result<Vec<StrategyProjection>, String> {
+    let (utxos, network) = parse_inputs(&utxos_json, &network_json)?;
+    let strategies: Vec<StakingStrategy> =
+        serde_json::from_str(&strategies_json).map_err(|e| format!("invalid strategies: {e}"))?;
+    compare_staking_strategies(utxos, &network, &strategies, &ChainParams::MAINNET)
+}
+
+fn parse_inputs(
+    utxos_json: &str,
+    network_json: &str,
+) -> Result<(Vec<Utxo>, StakingNetwork), String> {
+    let utxos: Vec<Utxo> = serde_json::from_str(utxos_json)
+        .map_err(|e| format!("utxos_json must be a valid JSON array of UTXOs: {e}"))?;
+    let network: StakingNetwork = serde_json::from_str(network_json)
+        .map_err(|e| format!("invalid staking network parameters: {e}"))?;
+    Ok((utxos, network))
+}
+
+/// Projects one output starting at `age_secs`: expected seconds until it stakes, and the yearly
+/// reward if it keeps re-staking with the same cycle.
+///
+/// The per-block chance of staking is `weight / network_weight`, and weight grows with age, so
+/// the expected stake time is where the integrated chance reaches one.
+fn project_output(
+    amount: Amount,
+    age_secs: f64,
+    network: &StakingNetwork,
+    params: &ChainParams,
+) -> (Option<f64>, Amount) {
+    let per_second = 1.0 / (network.network_weight * f64::from(params.target_spacing_secs));
+
+    let time_to_stake = |start_age: f64| -> Option<f64> {
+        let mut expected_stakes = 0.0;
+        let mut elapsed = 0.0;
+        while elapsed < MAX_PROJECTION_SECS {
+            let age = start_age + elapsed;
+            let step = if age < f64::from(params.stake_min_age_secs) + 7.0 * SECS_PER_DAY {
+                FINE_STEP_SECS
+            } else {
+                COARSE_STEP_SECS
+            };
+            let midpoint = age + step / 2.0;
+            expected_stakes += stake_weight(amount, midpoint, params) * per_second * step;
+            elapsed += step;
+            if expected_stakes >= 1.0 {
+                return Some(elapsed);
+            }
+        }
+        None
+    };
+
+    let expected = time_to_stake(age_secs);
+    // Each stake resets the output's age, so steady state is a cycle starting from age zero.
+    let annual = match time_to_stake(0.0) {
+        Some(cycle) => {
+            let per_stake = stake_reward(amount, cycle, params);
+            Amount::from_base((per_stake.to_base() as f64 * SECS_PER_YEAR / cycle) as u64)
+        }
+        None => Amount::ZERO,
+    };
+    (expected, annual)
+}
+
+fn rdd(amount: Amount) -> f64 {
+    amount.to_base() as f64 / BASE_UNITS_PER_RDD as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PARAMS: ChainParams = ChainParams::MAINNET;
+
+    fn age_days(days: f64) -> f64 {
+        f64::from(PARAMS.stake_min_age_secs) + days * SECS_PER_DAY
+    }
+
+    #[test]
+    fn weight_is_zero_until_min_age() {
+        assert_eq!(coin_age_weight_days(0.0, &PARAMS), 0.0);
+        assert_eq!(
+            coin_age_weight_days(f64::from(PARAMS.stake_min_age_secs), &PARAMS),
+            0.0
+        );
+    }
+
+    #[test]
+    fn curve_pieces_meet_at_seven_days() {
+        let cubic = coin_age_weight_days(age_days(7.0), &PARAMS);
+        let log = coin_age_weight_days(age_days(7.0 + 1e-9), &PARAMS);
+        assert!((cubic - 8.4).abs() < 1e-5, "{cubic}");
+        assert!((log - cubic).abs() < 1e-5, "{log}");
+        assert!(coin_age_weight_days(age_days(1.0), &PARAMS) < cubic);
+    }
+
+    #[test]
+    fn weight_matches_core_values() {
+        // `GetCoinAgeWeightV2` results in seconds, by days past the minimum stake age.
+        for (days, core_secs) in [
+            (1.0, 90_984),
+            (7.0, 725_760),
+            (30.0, 1_781_949),
+            (45.0, 2_076_219),
+            (400.0, 3_661_861),
+            (546.0, 3_887_684),
+            (547.0, 3_888_000),
+            (1000.0, 3_888_000),
+        ] {
+            let secs = (coin_age_weight_days(age_days(days), &PARAMS) * SECS_PER_DAY) as i64;
+            assert_eq!(secs, core_secs, "{days} days");
+        }
+    }
+
+    #[test]
+    fn weight_is_capped_after_the_curve() {
+        let max_weight = f64::from(PARAMS.stake_max_age_secs) / SECS_PER_DAY;
+        // Reaching the maximum stake age does not freeze the weight; only the weight is capped.
+        let at_max_age = coin_age_weight_days(f64::from(PARAMS.stake_max_age_secs), &PARAMS);
+        assert!(at_max_age < 25.0, "{at_max_age}");
+        assert!(coin_age_weight_days(age_days(100.0), &PARAMS) > at_max_age);
+        assert_eq!(coin_age_weight_days(age_days(600.0), &PARAMS), max_weight);
+        assert_eq!(
+            stake_weight(whole(10), age_days(600.0), &PARAMS),
+            10.0 * max_weight
+        );
+    }
+
+    fn whole(rdd: u64) -> Amount {
+        Amount::from_base(rdd * BASE_UNITS_PER_RDD)
+    }
+
+    fn utxos(entries: &[(u8, u64, &str)]) -> Vec<Utxo> {
+        entries
+            .iter()
+            .map(|(index, rdd, extra)| {
+                serde_json::from_str(&format!(
+                    r#"{{"txid":"{}","vout":0,"amount":{}{extra}}}"#,
+                    format!("{index:02x}").repeat(32),
+                    rdd * BASE_UNITS_PER_RDD
+                ))
+                .unwrap()
+            })
+            .collect()
+    }
+
+    const NETWORK: StakingNetwork = StakingNetwork {
+        network_weight: 50_000_000.0,
+        tip_height: Some(1_000_000),
+    };
+
+    /// Confirmations for an output `days` past the minimum stake age.
+    fn confirmations(days: f64) -> String {
+        let secs = age_days(days) / f64::from(PARAMS.target_spacing_secs);
+        format!(r#","confirmations":{}"#, secs as u32)
+    }
+
+    #[test]
+    fn estimate_weights_mature_outputs_by_age() {
+        let (young, old) = (confirmations(2.0), confirmations(60.0));
+        let wallet = utxos(&[
+            (1, 1_000, &young),
+            (2, 1_000, &old),
+            (3, 500, r#","confirmations":10,"coinstake":true"#),
+            (4, 700, ""),
+        ]);
+        let estimate = estimate_staking(wallet, &NETWORK, &PARAMS).unwrap();
+
+        let excluded: Vec<_> = estimate.excluded.iter().map(|e| e.amount).collect();
+        assert_eq!(excluded, [whole(500), whole(700)]);
+        assert_eq!(estimate.staking_amount, whole(2_000));
+        assert_eq!(estimate.utxos.len(), 2);
+
+        let (young, old) = (&estimate.utxos[0], &estimate.utxos[1]);
+        assert_eq!(old.age_secs, age_days(60.0) as u64);
+        assert_eq!(
+            old.weight,
+            stake_weight(whole(1_000), old.age_secs as f64, &PARAMS)
+        );
+        assert!(old.weight > young.weight);
+        assert!(old.expected_secs_to_stake < young.expected_secs_to_stake);
+        // Outputs of the same size re-stake on the same cycle once they have staked.
+        assert_eq!(old.projected_annual_reward, young.projected_annual_reward);
+        assert!(old.projected_annual_reward > Amount::ZERO);
+
+        assert_eq!(estimate.total_weight, young.weight + old.weight);
+        let expected = NETWORK.network_weight / estimate.total_weight * 60.0;
+        assert_eq!(estimate.expected_secs_to_stake, Some(expected as u64));
+        assert_eq!(
+            estimate.projected_annual_reward,
+            young
+                .projected_annual_reward
+                .saturating_add(old.projected_annual_reward)
+        );
+    }
+
+    #[test]
+    fn estimate_needs_a_network_weight_and_staking_outputs() {
+        for network_weight in [0.0, -1.0, f64::NAN] {
+            let network = StakingNetwork {
+                network_weight,
+                tip_height: None,
+            };
+            assert!(estimate_staking(Vec::new(), &network, &PARAMS).is_err());
+        }
+        // Below the minimum stake age an output has no weight yet.
+        let fresh = estimate_staking(
+            utxos(&[(1, 1_000, r#","confirmations":5"#)]),
+            &NETWORK,
+            &PARAMS,
+        )
+        .unwrap();
+        assert_eq!(fresh.total_weight, 0.0);
+        assert_eq!(fresh.expected_secs_to_stake, None);
+        assert!(fresh.utxos[0].expected_secs_to_stake.is_some());
+    }
+
+    #[test]
+    fn strategies_rearrange_the_mature_balance() {
+        let age = confirmations(10.0);
+        let wallet = utxos(&[
+            (1, 400, &age),
+            (2, 600, &age),
+            (3, 50, r#","confirmations":0"#),
+        ]);
+        let strategies = [
+            StakingStrategy::Current,
+            StakingStrategy::Consolidate,
+            StakingStrategy::Split {
+                target_output: whole(300),
+            },
+        ];
+        let projections =
+            compare_staking_strategies(wallet, &NETWORK, &strategies, &PARAMS).unwrap();
+
+        let counts: Vec<_> = projections.iter().map(|p| p.output_count).collect();
+        // 1000 RDD in 300 RDD outputs: two full ones and a last one holding the remaining 400.
+        assert_eq!(counts, [2, 1, 3]);
+        for projection in &projections {
+            assert_eq!(
+                projection.annual_yield_bps,
+                projection.projected_annual_reward.to_base() * 10_000
+                    / (1_000 * BASE_UNITS_PER_RDD)
+            );
+            assert!(projection.mean_secs_between_stakes.is_some());
+        }
+        // A single larger output wins blocks more often than the smaller ones it replaces.
+        assert!(projections[1].mean_secs_between_stakes < projections[0].mean_secs_between_stakes);
+
+        let err = compare_staking_strategies(
+            utxos(&[(1, 400, &age)]),
+            &NETWORK,
+            &[StakingStrategy::Split {
+                target_output: Amount::ZERO,
+            }],
+            &PARAMS,
+        )
+        .unwrap_err();
+        assert_eq!(err, "split target_output must be positive");
+    }
+
+    #[test]
+    fn strategies_parse_from_json() {
+        let age = confirmations(10.0);
+        let projections = compare_staking_strategies_json(
+            serde_json::to_string(&utxos(&[(1, 400, &age)])).unwrap(),
+            r#"{"network_weight":50000000}"#.to_string(),
+            r#"[{"strategy":"current"},{"strategy":"consolidate"}]"#.to_string(),
+        )
+        .unwrap();
+        // One output consolidates to itself.
+        assert_eq!(
+            projections[0].projected_annual_reward,
+            projections[1].projected_annual_reward
+        );
+        assert!(compare_staking_strategies_json(
+            "[]".to_string(),
+            "{}".to_string(),
+            "[]".to_string()
+        )
+        .unwrap_err()
+        .starts_with("invalid staking network parameters"));
+
+        let empty = compare_staking_strategies_json(
+            "[]".to_string(),
+            r#"{"network_weight":1}"#.to_string(),
+            r#"[{"strategy":"consolidate"}]"#.to_string(),
+        )
+        .unwrap();
+        assert_eq!(empty[0].output_count, 0);
+        assert_eq!(empty[0].annual_yield_bps, 0);
+    }
+}
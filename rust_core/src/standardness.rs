@@ -0,0 +1,385 @@
+//! Reddcoin relay standardness checks.
+//!
+//! A transaction can be consensus-valid and still be refused by every node's mempool because it
+//! breaks relay policy: a dust output, an oversized OP_RETURN, too many sigops, a fee below the
+//! minimum relay rate. Blockbook only reports such failures as a generic broadcast error, so every
+//! signing path runs these checks on the finished transaction and fails locally with the exact
+//! rule instead.
+//!
+//! The rules mirror Reddcoin Core's `IsStandardTx` plus the minimum relay fee check done on
+//! mempool acceptance.
+
+use bitcoin::consensus::encode::serialize;
+use bitcoin::{FeeRate, Script, Transaction};
+use serde::Serialize;
+
+use crate::amount::Amount;
+use crate::chain_params::ChainParams;
+use crate::transaction_builder::STANDARD_OP_RETURN_MAX_BYTES;
+use crate::transaction_signer::MAX_STANDARD_TX_SIZE;
+
+/// Largest standard OP_RETURN script: the data limit plus `OP_RETURN` and a `PUSHDATA1` header.
+const MAX_OP_RETURN_RELAY: usize = STANDARD_OP_RETURN_MAX_BYTES + 3;
+/// Standard transactions may carry at most one OP_RETURN output.
+const MAX_OP_RETURN_OUTPUTS: usize = 1;
+/// Largest standard scriptSig (`MAX_STANDARD_SCRIPTSIG_SIZE`), enough for a 15-of-15 P2SH multisig.
+const MAX_STANDARD_SCRIPTSIG_SIZE: usize = 1650;
+/// Legacy sigop budget per transaction (`MAX_STANDARD_TX_SIGOPS_COST` / 4).
+const MAX_STANDARD_TX_SIGOPS: usize = 4000;
+/// Bare multisig outputs are only standard up to 3 keys.
+const MAX_BARE_MULTISIG_KEYS: usize = 3;
+
+/// Result of a standardness check. The transaction is standard when `violations` is empty.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct StandardnessReport {
+    /// Serialized size in bytes.
+    pub size: u64,
+    /// Legacy sigops counted in every scriptSig and scriptPubKey.
+    pub sigops: usize,
+    /// Fee the transaction must pay to be relayed.
+    pub min_relay_fee: Amount,
+    pub violations: Vec<String>,
+}
+
+/// Smallest value an output with `script_pubkey` may carry without being dust.
+///
+/// The threshold is the cost of creating and later spending the output at the dust relay fee, so
+/// it depends on the output type: 546 for P2PKH, 540 for P2SH, zero for OP_RETURN.
+pub fn dust_threshold(script_pubkey: &Script, params: &ChainParams) -> Amount {
+    let rate = FeeRate::from_sat_per_kwu(params.dust_relay_fee_per_kb / 4);
+    script_pubkey.minimal_non_dust_custom(rate).into()
+}
+
+/// Checks `tx` against relay policy, given the fee it pays.
+///
+/// Sigops inside P2SH redeem scripts are not counted because the prevouts are not known here;
+/// the wallet only builds P2SH spends with a single `OP_CHECKSIG`, far below the budget.
+pub fn check_standardness(
+    tx: &Transaction,
+    fee: Amount,
+    params: &ChainParams,
+) -> StandardnessReport {
+    let mut violations = Vec::new();
+
+    if !(1..=2).contains(&tx.version.0) {
+        violations.push(format!("version {} is not standard", tx.version.0));
+    }
+
+    let size = serialize(tx).len() as u64;
+    if size > MAX_STANDARD_TX_SIZE {
+        violations.push(format!(
+            "size {size} bytes exceeds the {MAX_STANDARD_TX_SIZE}-byte standard limit"
+        ));
+    }
+
+    for (index, input) in tx.input.iter().enumerate() {
+        let script_sig = &input.script_sig;
+        if script_sig.len() > MAX_STANDARD_SCRIPTSIG_SIZE {
+            violations.push(format!(
+                "input {index} scriptSig is {} bytes (max {MAX_STANDARD_SCRIPTSIG_SIZE})",
+                script_sig.len()
+            ));
+        }
+        if !script_sig.is_push_only() {
+            violations.push(format!("input {index} scriptSig is not push-only"));
+        }
+    }
+
+    let mut op_returns = 0;
+    for (index, output) in tx.output.iter().enumerate() {
+        let script = &output.script_pubkey;
+        if script.is_op_return() {
+            op_returns += 1;
+            if script.len() > MAX_OP_RETURN_RELAY {
+                violations.push(format!(
+                    "OP_RETURN output {index} script is {} bytes (max {MAX_OP_RETURN_RELAY})",
+                    script.len()
+                ));
+            }
+            continue;
+        }
+        if !is_standard_output_script(script) {
+            violations.push(format!("output {index} has a non-standard script"));
+            continue;
+        }
+        let threshold = dust_threshold(script, params);
+        let value = Amount::from(output.value);
+        if value < threshold {
+            violations.push(format!(
+                "output {index} value {value} is below the dust limit ({threshold})"
+            ));
+        }
+    }
+    if op_returns > MAX_OP_RETURN_OUTPUTS {
+        violations.push(format!(
+            "{op_returns} OP_RETURN outputs (max {MAX_OP_RETURN_OUTPUTS})"
+        ));
+    }
+
+    let sigops = tx
+        .input
+        .iter()
+        .map(|input| input.script_sig.count_sigops_legacy())
+        .chain(
+            tx.output
+                .iter()
+                .map(|output| output.script_pubkey.count_sigops_legacy()),
+        )
+        .sum();
+    if sigops > MAX_STANDARD_TX_SIGOPS {
+        violations.push(format!(
+            "{sigops} sigops exceed the standard limit of {MAX_STANDARD_TX_SIGOPS}"
+        ));
+    }
+
+    let min_relay_fee = Amount::from_base(size.saturating_mul(params.min_relay_fee_per_kb) / 1000);
+    if fee < min_relay_fee {
+        violations.push(format!(
+            "fee {fee} is below the minimum relay fee ({min_relay_fee} for {size} bytes)"
+        ));
+    }
+
+    StandardnessReport {
+        size,
+        sigops,
+        min_relay_fee,
+        violations,
+    }
+}
+
+/// Runs [`check_standardness`] and turns any violation into an error.
+pub(crate) fn ensure_standard(tx: &Transaction, fee: Amount) -> Result<(), String> {
+    let report = check_standardness(tx, fee, &ChainParams::MAINNET);
+    if report.violations.is_empty() {
+        Ok(())
+    } else {
+        Err(format!(
+            "transaction is not standard: {}",
+            report.violations.join("; ")
+        ))
+    }
+}
+
+/// Output types nodes relay: P2PKH, P2SH, P2PK, small bare multisig and witness programs.
+fn is_standard_output_script(script: &Script) -> bool {
+    if script.is_p2pkh() || script.is_p2sh() || script.is_p2pk() || script.is_witness_program() {
+        return true;
+    }
+    if script.is_multisig() {
+        let keys = script
+            .instructions()
+            .filter(|instruction| {
+                matches!(instruction, Ok(bitcoin::script::Instruction::PushBytes(_)))
+            })
+            .count();
+        return (1..=MAX_BARE_MULTISIG_KEYS).contains(&keys);
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::absolute::LockTime;
+    use bitcoin::hashes::Hash;
+    use bitcoin::opcodes::all::{OP_CHECKMULTISIG, OP_CHECKSIG, OP_PUSHNUM_1};
+    use bitcoin::script::{Builder, PushBytesBuf};
+    use bitcoin::{OutPoint, PubkeyHash, ScriptBuf, ScriptHash, Sequence, TxIn, TxOut, Witness};
+
+    use super::*;
+
+    const PARAMS: ChainParams = ChainParams::MAINNET;
+    const FEE: Amount = Amount::from_base(100_000);
+
+    fn p2pkh() -> ScriptBuf {
+        ScriptBuf::new_p2pkh(&PubkeyHash::from_byte_array([7; 20]))
+    }
+
+    fn p2sh() -> ScriptBuf {
+        ScriptBuf::new_p2sh(&ScriptHash::from_byte_array([8; 20]))
+    }
+
+    fn op_return(bytes: usize) -> ScriptBuf {
+        ScriptBuf::new_op_return(PushBytesBuf::try_from(vec![0xab; bytes]).unwrap())
+    }
+
+    fn push(bytes: usize) -> Builder {
+        Builder::new().push_slice(PushBytesBuf::try_from(vec![0x30; bytes]).unwrap())
+    }
+
+    /// One signature-sized and one key-sized push, like a P2PKH spend.
+    fn signed_input() -> TxIn {
+        TxIn {
+            previous_output: OutPoint::new(bitcoin::Txid::from_byte_array([1; 32]), 0),
+            script_sig: push(72)
+                .push_slice(PushBytesBuf::try_from(vec![0x02; 33]).unwrap())
+                .into_script(),
+            sequence: Sequence::MAX,
+            witness: Witness::new(),
+        }
+    }
+
+    fn output(script_pubkey: ScriptBuf, value: u64) -> TxOut {
+        TxOut {
+            value: bitcoin::Amount::from_sat(value),
+            script_pubkey,
+        }
+    }
+
+    fn transfer() -> Transaction {
+        Transaction {
+            version: bitcoin::transaction::Version(2),
+            lock_time: LockTime::ZERO,
+            input: vec![signed_input()],
+            output: vec![output(p2pkh(), 100_000_000), output(p2sh(), 50_000_000)],
+        }
+    }
+
+    fn violations(tx: &Transaction, fee: Amount) -> Vec<String> {
+        check_standardness(tx, fee, &PARAMS).violations
+    }
+
+    #[test]
+    fn accepts_a_normal_transfer() {
+        let tx = transfer();
+        let report = check_standardness(&tx, FEE, &PARAMS);
+        assert!(report.violations.is_empty(), "{:?}", report.violations);
+        assert_eq!(report.size, serialize(&tx).len() as u64);
+        // Only the P2PKH output's OP_CHECKSIG counts; P2SH sigops live in the redeem script.
+        assert_eq!(report.sigops, 1);
+        assert_eq!(report.min_relay_fee, Amount::from_base(report.size));
+        assert!(ensure_standard(&tx, FEE).is_ok());
+    }
+
+    #[test]
+    fn rejects_unknown_versions_and_oversized_transactions() {
+        for version in [0, 3] {
+            let mut tx = transfer();
+            tx.version = bitcoin::transaction::Version(version);
+            assert_eq!(
+                violations(&tx, FEE),
+                [format!("version {version} is not standard")]
+            );
+        }
+
+        let mut tx = transfer();
+        tx.output = vec![output(p2pkh(), 1_000_000); 3_000];
+        let found = violations(&tx, Amount::from_base(1_000_000));
+        assert_eq!(found.len(), 1);
+        assert!(
+            found[0].ends_with("exceeds the 100000-byte standard limit"),
+            "{found:?}"
+        );
+    }
+
+    #[test]
+    fn script_sigs_must_be_small_and_push_only() {
+        let mut tx = transfer();
+        tx.input[0].script_sig = push(MAX_STANDARD_SCRIPTSIG_SIZE).into_script();
+        assert_eq!(
+            violations(&tx, FEE),
+            [format!(
+                "input 0 scriptSig is 1653 bytes (max {MAX_STANDARD_SCRIPTSIG_SIZE})"
+            )]
+        );
+
+        tx.input[0].script_sig = push(72).push_opcode(OP_CHECKSIG).into_script();
+        assert_eq!(violations(&tx, FEE), ["input 0 scriptSig is not push-only"]);
+    }
+
+    #[test]
+    fn limits_op_return_outputs() {
+        let mut tx = transfer();
+        tx.output
+            .push(output(op_return(STANDARD_OP_RETURN_MAX_BYTES), 0));
+        assert!(violations(&tx, FEE).is_empty());
+
+        tx.output.push(output(op_return(4), 0));
+        assert_eq!(violations(&tx, FEE), ["2 OP_RETURN outputs (max 1)"]);
+
+        tx.output.pop();
+        tx.output[2] = output(op_return(STANDARD_OP_RETURN_MAX_BYTES + 1), 0);
+        assert_eq!(
+            violations(&tx, FEE),
+            [format!(
+                "OP_RETURN output 2 script is {} bytes (max {MAX_OP_RETURN_RELAY})",
+                MAX_OP_RETURN_RELAY + 1
+            )]
+        );
+    }
+
+    #[test]
+    fn rejects_non_standard_output_scripts() {
+        let mut tx = transfer();
+        tx.output[1] = output(
+            Builder::new().push_opcode(OP_PUSHNUM_1).into_script(),
+            1_000_000,
+        );
+        assert_eq!(violations(&tx, FEE), ["output 1 has a non-standard script"]);
+    }
+
+    #[test]
+    fn dust_depends_on_the_output_type() {
+        assert_eq!(dust_threshold(&p2pkh(), &PARAMS), Amount::from_base(546));
+        assert_eq!(dust_threshold(&p2sh(), &PARAMS), Amount::from_base(540));
+        assert_eq!(dust_threshold(&op_return(10), &PARAMS), Amount::ZERO);
+
+        let mut tx = transfer();
+        tx.output = vec![
+            output(p2pkh(), 546),
+            output(p2sh(), 540),
+            output(op_return(10), 0),
+        ];
+        assert!(violations(&tx, FEE).is_empty());
+
+        tx.output = vec![output(p2pkh(), 545), output(p2sh(), 539)];
+        assert_eq!(
+            violations(&tx, FEE),
+            [
+                format!(
+                    "output 0 value {} is below the dust limit ({})",
+                    Amount::from_base(545),
+                    Amount::from_base(546)
+                ),
+                format!(
+                    "output 1 value {} is below the dust limit ({})",
+                    Amount::from_base(539),
+                    Amount::from_base(540)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn counts_sigops_against_the_budget() {
+        // Legacy counting charges 20 sigops for every OP_CHECKMULTISIG.
+        let heavy = Builder::new()
+            .push_opcode(OP_CHECKMULTISIG)
+            .into_script()
+            .to_bytes()
+            .repeat(MAX_STANDARD_TX_SIGOPS / 20 + 1);
+        let mut tx = transfer();
+        tx.output[1] = output(ScriptBuf::from_bytes(heavy), 1_000_000);
+        let report = check_standardness(&tx, FEE, &PARAMS);
+        assert_eq!(report.sigops, 1 + MAX_STANDARD_TX_SIGOPS + 20);
+        assert!(report
+            .violations
+            .contains(&"4021 sigops exceed the standard limit of 4000".to_string()));
+    }
+
+    #[test]
+    fn fee_must_meet_the_minimum_relay_fee() {
+        let tx = transfer();
+        let size = serialize(&tx).len() as u64;
+        assert!(violations(&tx, Amount::from_base(size)).is_empty());
+        let low = Amount::from_base(size - 1);
+        let expected = format!(
+            "fee {low} is below the minimum relay fee ({} for {size} bytes)",
+            Amount::from_base(size)
+        );
+        assert_eq!(violations(&tx, low), std::slice::from_ref(&expected));
+        assert_eq!(
+            ensure_standard(&tx, low).unwrap_err(),
+            format!("transaction is not standard: {expected}")
+        );
+    }
+}
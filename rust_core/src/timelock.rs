@@ -0,0 +1,595 @@
+//! Absolute and relative timelocks.
+//!
+//! Covers two needs:
+//! * Post-dating ordinary transactions with `nLockTime` (see `BuildOptions::lock_time`).
+//! * Timelocked savings: P2SH outputs guarded by `OP_CHECKLOCKTIMEVERIFY` (absolute, BIP65) or
+//!   `OP_CHECKSEQUENCEVERIFY` (relative, BIP112) in front of a single-key `OP_CHECKSIG`.
+//!
+//! Spending a lock before it is satisfied is consensus-invalid, so every spend is checked
+//! against the caller-supplied chain tip before anything is signed.
+
+use std::str::FromStr;
+
+use bitcoin::absolute::LockTime;
+use bitcoin::consensus::encode::serialize_hex;
+use bitcoin::hashes::Hash;
+use bitcoin::opcodes::all::{OP_CHECKSIG, OP_CLTV, OP_CSV, OP_DROP};
+use bitcoin::script::{Builder, Instruction, PushBytesBuf};
+use bitcoin::secp256k1::{Message, SecretKey};
+use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+use bitcoin::{PublicKey, Script, ScriptBuf, Sequence, Transaction, TxOut};
+use serde::{Deserialize, Serialize};
+
+use crate::address::ReddcoinAddress;
+use crate::amount::Amount;
+use crate::chain_params::ChainParams;
+use crate::script_interpreter::{prevout_script, verify_inputs};
+use crate::secp_context::secp;
+use crate::standardness::ensure_standard;
+use crate::transaction_signer::{
+    check_policy, ensure_verified, estimate_legacy_tx_fee_for_input_size, parallel_map,
+    parse_utxos, unsigned_inputs, BuildOptions, SignedTransaction,
+};
+use crate::tx_ordering::order_inputs;
+use crate::utxo::Utxo;
+
+/// `nLockTime` values below this are block heights; at or above it they are Unix timestamps.
+pub const LOCKTIME_THRESHOLD: u32 = 500_000_000;
+
+/// BIP68 time-based relative locks count in units of 512 seconds.
+const RELATIVE_LOCK_GRANULARITY_SECS: u32 = 512;
+
+/// Serialized size of an input minus its scriptSig: outpoint (36) + sequence (4) + length (1).
+const INPUT_BASE_SIZE: u64 = 41;
+
+/// Upper bound of a DER signature plus sighash byte, including its push opcode.
+const SIGNATURE_PUSH_SIZE: u64 = 74;
+
+/// An absolute lock: the transaction or output is unspendable until a height or time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AbsoluteLock {
+    /// Block height the lock expires at.
+    Height(u32),
+    /// Unix timestamp, compared against median time past (BIP113).
+    Time(u32),
+}
+
+impl AbsoluteLock {
+    /// Converts to a consensus `LockTime`, rejecting values on the wrong side of the threshold.
+    pub fn to_lock_time(self) -> Result<LockTime, String> {
+        match self {
+            Self::Height(height) => LockTime::from_height(height)
+                .map_err(|e| format!("invalid lock height {height}: {e}")),
+            Self::Time(time) => {
+                LockTime::from_time(time).map_err(|e| format!("invalid lock time {time}: {e}"))
+            }
+        }
+    }
+
+    fn from_lock_time(lock_time: LockTime) -> Self {
+        match lock_time {
+            LockTime::Blocks(height) => Self::Height(height.to_consensus_u32()),
+            LockTime::Seconds(time) => Self::Time(time.to_consensus_u32()),
+        }
+    }
+}
+
+/// A relative lock: an output is unspendable until it has aged by blocks or time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelativeLock {
+    /// Confirmations the spent output needs.
+    Blocks(u16),
+    /// Seconds the spent output needs to have aged, rounded up to BIP68's 512-second units.
+    Seconds(u32),
+}
+
+impl RelativeLock {
+    /// Encodes the lock as the input `nSequence` value BIP68 expects.
+    pub fn to_sequence(self) -> Result<Sequence, String> {
+        match self {
+            Self::Blocks(blocks) => Ok(Sequence::from_height(blocks)),
+            Self::Seconds(seconds) => {
+                let intervals = seconds.div_ceil(RELATIVE_LOCK_GRANULARITY_SECS);
+                let intervals = u16::try_from(intervals)
+                    .map_err(|_| format!("relative lock of {seconds}s exceeds BIP68 range"))?;
+                Ok(Sequence::from_512_second_intervals(intervals))
+            }
+        }
+    }
+}
+
+/// A timelock guarding a P2SH output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Timelock {
+    Absolute(AbsoluteLock),
+    Relative(RelativeLock),
+}
+
+/// The chain state a lock is evaluated against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub struct ChainTip {
+    pub height: u32,
+    /// Median time past of the tip block, which BIP113 uses for time-based locks.
+    pub median_time_past: u32,
+}
+
+/// Whether a lock can be satisfied by a transaction mined in the next block.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum LockStatus {
+    Satisfied,
+    /// Blocks that still have to be mined.
+    WaitBlocks {
+        remaining: u32,
+    },
+    /// Seconds that still have to pass.
+    WaitSeconds {
+        remaining: u32,
+    },
+}
+
+/// Checks an absolute lock against the tip.
+///
+/// Consensus requires `nLockTime < height` of the including block (the tip plus one) for height
+/// locks and `nLockTime < median time past` for time locks.
+pub fn absolute_lock_status(lock: AbsoluteLock, tip: &ChainTip) -> LockStatus {
+    match lock {
+        AbsoluteLock::Height(height) if height <= tip.height => LockStatus::Satisfied,
+        AbsoluteLock::Height(height) => LockStatus::WaitBlocks {
+            remaining: height - tip.height,
+        },
+        AbsoluteLock::Time(time) if time < tip.median_time_past => LockStatus::Satisfied,
+        AbsoluteLock::Time(time) => LockStatus::WaitSeconds {
+            remaining: time - tip.median_time_past + 1,
+        },
+    }
+}
+
+/// Checks a relative lock against the age of the output being spent.
+///
+/// Time-based locks are measured from the median time past of the output's block, which UTXO
+/// sources do not report. They are approximated from confirmations and the target block spacing,
+/// so a "satisfied" time lock may still be a few blocks early; the node will refuse it rather
+/// than lose funds.
+pub fn relative_lock_status(
+    lock: RelativeLock,
+    confirmations: u32,
+    params: &ChainParams,
+) -> LockStatus {
+    match lock {
+        RelativeLock::Blocks(blocks) if confirmations >= u32::from(blocks) => LockStatus::Satisfied,
+        RelativeLock::Blocks(blocks) => LockStatus::WaitBlocks {
+            remaining: u32::from(blocks) - confirmations,
+        },
+        RelativeLock::Seconds(seconds) => {
+            let required =
+                seconds.div_ceil(RELATIVE_LOCK_GRANULARITY_SECS) * RELATIVE_LOCK_GRANULARITY_SECS;
+            let elapsed = confirmations.saturating_mul(params.target_spacing_secs);
+            if elapsed >= required {
+                LockStatus::Satisfied
+            } else {
+                LockStatus::WaitSeconds {
+                    remaining: required - elapsed,
+                }
+            }
+        }
+    }
+}
+
+/// A timelocked output script and the address that pays to it.
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelockOutput {
+    pub redeem_script: String,
+    pub address: String,
+}
+
+/// Builds `<lock> OP_CLTV|OP_CSV OP_DROP <pubkey> OP_CHECKSIG` and its P2SH address.
+pub fn build_timelock_script(lock: Timelock, pubkey: &PublicKey) -> Result<ScriptBuf, String> {
+    let builder = match lock {
+        Timelock::Absolute(lock) => Builder::new()
+            .push_lock_time(lock.to_lock_time()?)
+            .push_opcode(OP_CLTV),
+        Timelock::Relative(lock) => Builder::new()
+            .push_sequence(lock.to_sequence()?)
+            .push_opcode(OP_CSV),
+    };
+
+    Ok(builder
+        .push_opcode(OP_DROP)
+        .push_key(pubkey)
+        .push_opcode(OP_CHECKSIG)
+        .into_script())
+}
+
+/// Recovers the lock and key from a script produced by [`build_timelock_script`].
+pub fn parse_timelock_script(script: &Script) -> Result<(Timelock, PublicKey), String> {
+    let instructions = script
+        .instructions()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("redeem script does not parse: {e}"))?;
+
+    let [lock_push, Instruction::Op(lock_op), Instruction::Op(OP_DROP), Instruction::PushBytes(key), Instruction::Op(OP_CHECKSIG)] =
+        instructions.as_slice()
+    else {
+        return Err("redeem script is not a single-key CLTV/CSV timelock".to_string());
+    };
+
+    let value = lock_push
+        .script_num()
+        .filter(|value| *value >= 0 && *value <= i64::from(u32::MAX))
+        .ok_or_else(|| "timelock value is not a valid script number".to_string())?
+        as u32;
+
+    let lock = match *lock_op {
+        OP_CLTV => Timelock::Absolute(AbsoluteLock::from_lock_time(LockTime::from_consensus(
+            value,
+        ))),
+        OP_CSV => {
+            let relative = Sequence::from_consensus(value)
+                .to_relative_lock_time()
+                .ok_or_else(|| "CSV value does not encode a relative lock".to_string())?;
+            Timelock::Relative(match relative {
+                bitcoin::relative::LockTime::Blocks(height) => RelativeLock::Blocks(height.value()),
+                bitcoin::relative::LockTime::Time(time) => {
+                    RelativeLock::Seconds(u32::from(time.value()) * RELATIVE_LOCK_GRANULARITY_SECS)
+                }
+            })
+        }
+        _ => return Err("redeem script is not a single-key CLTV/CSV timelock".to_string()),
+    };
+
+    let pubkey = PublicKey::from_slice(key.as_bytes())
+        .map_err(|e| format!("redeem script public key is invalid: {e}"))?;
+
+    Ok((lock, pubkey))
+}
+
+/// JSON entry point: builds a timelocked P2SH output for `pubkey_hex` (compressed or
+/// uncompressed). `lock_json` is e.g. `{"absolute":{"height":2500000}}` or
+/// `{"relative":{"blocks":1440}}`.
+pub fn create_timelock_output(
+    lock_json: String,
+    pubkey_hex: String,
+) -> Result<TimelockOutput, String> {
+    let lock: Timelock =
+        serde_json::from_str(&lock_json).map_err(|e| format!("invalid timelock: {e}"))?;
+    let pubkey =
+        PublicKey::from_str(pubkey_hex.trim()).map_err(|e| format!("invalid pubkey_hex: {e}"))?;
+
+    let script = build_timelock_script(lock, &pubkey)?;
+    Ok(TimelockOutput {
+        address: ReddcoinAddress::p2sh(&script).to_string(),
+        redeem_script: script.to_hex_string(),
+    })
+}
+
+/// Spends timelocked P2SH outputs into `destination_address` once the lock is satisfied.
+///
+/// Every UTXO must pay to the P2SH address of `redeem_script_hex` and carry its `script_pubkey`
+/// or `address` so that can be checked; the signed inputs are verified against those scripts.
+/// Absolute locks set the transaction's `nLockTime` to the script's value (CLTV requires
+/// `nLockTime >= lock`); relative locks set each input's `nSequence` to the script's value (CSV
+/// requires `nSequence >= lock`).
+///
+/// `options` supplies the policy, input ordering, proof and threading settings. Its `lock_time`
+/// must be unset: the redeem script decides the locktime.
+#[allow(clippy::too_many_arguments)]
+pub fn sign_timelock_spend(
+    utxos_json: String,
+    redeem_script_hex: String,
+    private_key_hex: String,
+    destination_address: String,
+    fee_per_kb: u64,
+    tip: ChainTip,
+    options: BuildOptions,
+) -> Result<SignedTransaction, String> {
+    if options.lock_time.is_some() {
+        return Err("lock_time cannot be set when spending a timelock output".to_string());
+    }
+
+    let redeem_script = ScriptBuf::from_hex(redeem_script_hex.trim())
+        .map_err(|e| format!("redeem_script_hex decode failed: {e}"))?;
+    let (lock, pubkey) = parse_timelock_script(&redeem_script)?;
+
+    let private_key_raw =
+        hex::decode(private_key_hex).map_err(|e| format!("private_key_hex decode failed: {e}"))?;
+    let secret_key = SecretKey::from_slice(&private_key_raw)
+        .map_err(|e| format!("invalid secp256k1 private key: {e}"))?;
+
+    if secret_key.public_key(secp()) != pubkey.inner {
+        return Err("private key does not match the redeem script's public key".to_string());
+    }
+
+    let (utxos, total_input_amount) = parse_utxos(&utxos_json)?;
+    ensure_verified(&utxos, &options)?;
+    let lock_script = ScriptBuf::new_p2sh(&redeem_script.script_hash());
+    let spent_scripts = utxos
+        .iter()
+        .enumerate()
+        .map(|(index, utxo)| ensure_pays_to_script(utxo, index, &lock_script))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut inputs = unsigned_inputs(&utxos)?;
+
+    let lock_time = match lock {
+        Timelock::Absolute(lock) => {
+            if let LockStatus::WaitBlocks { remaining } | LockStatus::WaitSeconds { remaining } =
+                absolute_lock_status(lock, &tip)
+            {
+                return Err(format!(
+                    "timelock not yet satisfied ({remaining} remaining)"
+                ));
+            }
+            for input in &mut inputs {
+                input.sequence = Sequence::ENABLE_LOCKTIME_NO_RBF;
+            }
+            lock.to_lock_time()?
+        }
+        Timelock::Relative(lock) => {
+            let sequence = lock.to_sequence()?;
+            for (index, (input, utxo)) in inputs.iter_mut().zip(&utxos).enumerate() {
+                let confirmations = utxo.confirmations_at(Some(tip.height));
+                if let LockStatus::WaitBlocks { remaining }
+                | LockStatus::WaitSeconds { remaining } =
+                    relative_lock_status(lock, confirmations, &ChainParams::MAINNET)
+                {
+                    return Err(format!(
+                        "utxos[{index}] timelock not yet satisfied ({remaining} remaining)"
+                    ));
+                }
+                input.sequence = sequence;
+            }
+            LockTime::ZERO
+        }
+    };
+
+    let input_size = INPUT_BASE_SIZE + SIGNATURE_PUSH_SIZE + 2 + redeem_script.len() as u64;
+    let fee = estimate_legacy_tx_fee_for_input_size(inputs.len(), input_size, 1, fee_per_kb)?;
+    let send_value = total_input_amount
+        .checked_sub(fee)
+        .filter(|value| *value > Amount::ZERO)
+        .ok_or_else(|| format!("insufficient funds: inputs={total_input_amount}, fee={fee}"))?;
+
+    let destination_script = ReddcoinAddress::from_str(&destination_address)
+        .map_err(|e| format!("invalid destination_address: {e}"))?
+        .script_pubkey();
+
+    let mut tx = Transaction {
+        version: bitcoin::transaction::Version(2),
+        lock_time,
+        input: inputs,
+        output: vec![TxOut {
+            value: send_value.into(),
+            script_pubkey: destination_script,
+        }],
+    };
+
+    let policy_warnings = check_policy(&tx, fee, None, &options)?;
+    // Every input spends the same script, so reordering keeps `spent_scripts` aligned.
+    order_inputs(&mut tx.input, options.input_ordering);
+    sign_p2sh_single_key_inputs(
+        &mut tx,
+        &secret_key,
+        &redeem_script,
+        options.signing_threads,
+    )?;
+    verify_inputs(&tx, &spent_scripts, options.signing_threads)?;
+    ensure_standard(&tx, fee)?;
+
+    Ok(SignedTransaction {
+        hex: serialize_hex(&tx),
+        txid: tx.compute_txid().to_string(),
+        fee,
+        change_index: None,
+        policy_warnings,
+    })
+}
+
+/// Checks that `utxo` is locked to `expected` by its `script_pubkey` and `address`, whichever
+/// are given, and returns its script. Signing a UTXO locked to anything else would only produce
+/// an invalid spend, or sign away an output the redeem script does not guard.
+fn ensure_pays_to_script(
+    utxo: &Utxo,
+    index: usize,
+    expected: &Script,
+) -> Result<ScriptBuf, String> {
+    let script = prevout_script(utxo, index)?;
+    let address_script = match &utxo.address {
+        Some(address) => Some(
+            ReddcoinAddress::from_str(address)
+                .map_err(|e| format!("invalid address for utxos[{index}]: {e}"))?
+                .script_pubkey(),
+        ),
+        None => None,
+    };
+    if script != *expected || address_script.is_some_and(|script| script != *expected) {
+        return Err(format!(
+            "utxos[{index}] is not locked to the redeem script's P2SH address"
+        ));
+    }
+    Ok(script)
+}
+
+/// Signs every input as a spend of a P2SH output whose redeem script needs one signature.
+fn sign_p2sh_single_key_inputs(
+    tx: &mut Transaction,
+    secret_key: &SecretKey,
+    redeem_script: &Script,
+    threads: Option<usize>,
+) -> Result<(), String> {
+    let redeem_push = PushBytesBuf::try_from(redeem_script.to_bytes())
+        .map_err(|e| format!("redeem script exceeds push limits: {e}"))?;
+
+    let sighash_cache = SighashCache::new(&*tx);
+    let script_sigs = parallel_map(tx.input.len(), threads, |index| {
+        // For P2SH the scriptCode committed to by the sighash is the redeem script.
+        let sighash = sighash_cache
+            .legacy_signature_hash(index, redeem_script, EcdsaSighashType::All.to_u32())
+            .map_err(|e| format!("failed to construct sighash for input {index}: {e}"))?;
+
+        let message = Message::from_digest(*sighash.as_byte_array());
+        let signature = bitcoin::ecdsa::Signature {
+            signature: secp().sign_ecdsa(&message, secret_key),
+            sighash_type: EcdsaSighashType::All,
+        };
+        let sig_push = PushBytesBuf::try_from(signature.to_vec())
+            .map_err(|e| format!("signature encoding failed push-bytes checks: {e}"))?;
+
+        Ok(Builder::new()
+            .push_slice(sig_push)
+            .push_slice(&redeem_push)
+            .into_script())
+    })?;
+
+    for (input, script_sig) in tx.input.iter_mut().zip(script_sigs) {
+        input.script_sig = script_sig;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY_HEX: &str = "0303030303030303030303030303030303030303030303030303030303030303";
+    const TIP: ChainTip = ChainTip {
+        height: 3_000_000,
+        median_time_past: 1_700_000_000,
+    };
+
+    fn pubkey() -> PublicKey {
+        let secret_key = SecretKey::from_slice(&hex::decode(KEY_HEX).unwrap()).unwrap();
+        PublicKey::new(secret_key.public_key(secp()))
+    }
+
+    fn destination() -> String {
+        let secret_key = SecretKey::from_slice(&[9u8; 32]).unwrap();
+        ReddcoinAddress::p2pkh(&PublicKey::new(secret_key.public_key(secp()))).to_string()
+    }
+
+    fn utxos_json(locked_to: &str, confirmations: u32) -> String {
+        format!(
+            r#"[{{"txid":"{}","vout":1,"amount":500000000,"confirmations":{confirmations},"address":"{locked_to}"}}]"#,
+            "cd".repeat(32)
+        )
+    }
+
+    fn spend(lock: Timelock, utxos_json: String) -> Result<SignedTransaction, String> {
+        let script = build_timelock_script(lock, &pubkey()).unwrap();
+        sign_timelock_spend(
+            utxos_json,
+            script.to_hex_string(),
+            KEY_HEX.to_string(),
+            destination(),
+            1_000_000,
+            TIP,
+            BuildOptions::default(),
+        )
+    }
+
+    fn p2sh_address(lock: Timelock) -> String {
+        ReddcoinAddress::p2sh(&build_timelock_script(lock, &pubkey()).unwrap()).to_string()
+    }
+
+    #[test]
+    fn script_round_trips() {
+        for lock in [
+            Timelock::Absolute(AbsoluteLock::Height(2_500_000)),
+            Timelock::Absolute(AbsoluteLock::Time(1_700_000_000)),
+            Timelock::Relative(RelativeLock::Blocks(144)),
+            Timelock::Relative(RelativeLock::Seconds(1024)),
+        ] {
+            let script = build_timelock_script(lock, &pubkey()).unwrap();
+            assert_eq!(parse_timelock_script(&script).unwrap(), (lock, pubkey()));
+        }
+    }
+
+    #[test]
+    fn spends_satisfied_absolute_lock() {
+        let lock = Timelock::Absolute(AbsoluteLock::Height(TIP.height));
+        let signed = spend(lock, utxos_json(&p2sh_address(lock), 10)).unwrap();
+        let tx: Transaction =
+            bitcoin::consensus::deserialize(&hex::decode(signed.hex).unwrap()).unwrap();
+        assert_eq!(tx.lock_time, LockTime::from_height(TIP.height).unwrap());
+        assert_eq!(tx.input[0].sequence, Sequence::ENABLE_LOCKTIME_NO_RBF);
+    }
+
+    #[test]
+    fn spend_applies_build_options() {
+        let lock = Timelock::Absolute(AbsoluteLock::Height(TIP.height));
+        let script = build_timelock_script(lock, &pubkey()).unwrap();
+        let spend_with = |options_json: &str| {
+            sign_timelock_spend(
+                utxos_json(&p2sh_address(lock), 10),
+                script.to_hex_string(),
+                KEY_HEX.to_string(),
+                destination(),
+                1_000_000,
+                TIP,
+                BuildOptions::from_json(options_json).unwrap(),
+            )
+        };
+
+        let signed = spend_with(r#"{"policy":{"rules":{"first_time_recipient":"warn"}}}"#).unwrap();
+        assert_eq!(signed.policy_warnings.len(), 1);
+        let err = spend_with(&format!(
+            r#"{{"policy":{{"rules":{{"blocklist":["{}"]}}}}}}"#,
+            destination()
+        ))
+        .unwrap_err();
+        assert!(err.starts_with("denied by policy"), "{err}");
+        let err = spend_with(r#"{"require_verified_inputs":true}"#).unwrap_err();
+        assert!(err.contains("has not been proof-verified"), "{err}");
+        let err = spend_with(r#"{"lock_time":{"height":1}}"#).unwrap_err();
+        assert_eq!(
+            err,
+            "lock_time cannot be set when spending a timelock output"
+        );
+    }
+
+    #[test]
+    fn refuses_unsatisfied_locks() {
+        let lock = Timelock::Absolute(AbsoluteLock::Height(TIP.height + 5));
+        let err = spend(lock, utxos_json(&p2sh_address(lock), 10)).unwrap_err();
+        assert!(err.contains("5 remaining"), "{err}");
+
+        let lock = Timelock::Relative(RelativeLock::Blocks(20));
+        let err = spend(lock, utxos_json(&p2sh_address(lock), 19)).unwrap_err();
+        assert!(err.contains("1 remaining"), "{err}");
+        assert!(spend(lock, utxos_json(&p2sh_address(lock), 20)).is_ok());
+    }
+
+    #[test]
+    fn rejects_utxos_not_locked_to_the_redeem_script() {
+        let lock = Timelock::Absolute(AbsoluteLock::Height(TIP.height));
+        let other = Timelock::Absolute(AbsoluteLock::Height(TIP.height - 1));
+
+        let err = spend(lock, utxos_json(&p2sh_address(other), 10)).unwrap_err();
+        assert!(err.contains("not locked to the redeem script"), "{err}");
+        let err = spend(lock, utxos_json(&destination(), 10)).unwrap_err();
+        assert!(err.contains("not locked to the redeem script"), "{err}");
+
+        // A matching script_pubkey does not excuse a contradicting address.
+        let expected = ScriptBuf::new_p2sh(
+            &build_timelock_script(lock, &pubkey())
+                .unwrap()
+                .script_hash(),
+        );
+        let mismatched = format!(
+            r#"[{{"txid":"{}","vout":0,"amount":500000000,"script_pubkey":"{}","address":"{}"}}]"#,
+            "cd".repeat(32),
+            expected.to_hex_string(),
+            p2sh_address(other)
+        );
+        assert!(spend(lock, mismatched).is_err());
+
+        let bare = format!(
+            r#"[{{"txid":"{}","vout":0,"amount":500000000}}]"#,
+            "cd".repeat(32)
+        );
+        let err = spend(lock, bare).unwrap_err();
+        assert!(err.contains("needs a script_pubkey or address"), "{err}");
+    }
+}
@@ -1,7 +1,7 @@
-/// Builds protocol-specific payloads for ReddID transactions.
-///
-/// The API in this module is intentionally small and explicit because it will be called through
-/// FFI boundaries where debugging is harder and invalid inputs are more likely.
+//! Builds protocol-specific payloads for ReddID transactions.
+//!
+//! The API in this module is intentionally small and explicit because it will be called through
+//! FFI boundaries where debugging is harder and invalid inputs are more likely.
 
 /// Maximum standard OP_RETURN payload size in bytes.
 ///
@@ -9,7 +9,7 @@
 /// * Most nodes/mempools enforce an 80-byte standard relay limit for OP_RETURN data.
 /// * Failing fast in the Rust core gives a clearer error than letting transaction broadcast fail
 ///   later with a generic policy rejection.
-const STANDARD_OP_RETURN_MAX_BYTES: usize = 80;
+pub(crate) const STANDARD_OP_RETURN_MAX_BYTES: usize = 80;
 
 /// ReddID protocol marker bytes.
 ///
@@ -1,16 +1,31 @@
 use std::str::FromStr;
 
 use bitcoin::absolute::LockTime;
-use bitcoin::consensus::encode::serialize_hex;
-use bitcoin::hashes::{hash160, Hash};
+use bitcoin::consensus::encode::{serialize, serialize_hex};
+use bitcoin::hashes::Hash;
 use bitcoin::script::{Builder, PushBytesBuf};
 use bitcoin::sighash::{EcdsaSighashType, SighashCache};
 use bitcoin::{
-    secp256k1::{Message, PublicKey, Secp256k1, SecretKey},
-    Address, Amount, OutPoint, PublicKey as BitcoinPublicKey, ScriptBuf, Sequence, Transaction,
-    TxIn, TxOut, Txid, Witness,
+    secp256k1::{Message, PublicKey, SecretKey},
+    OutPoint, PublicKey as BitcoinPublicKey, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid,
+    Witness,
 };
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+
+use crate::address::ReddcoinAddress;
+use crate::amount::Amount;
+use crate::chain_params::ChainParams;
+use crate::merkle_proof::InputProofs;
+use crate::policy::{PolicyFinding, TransactionPolicy, Verdict};
+use crate::script_interpreter::verify_inputs;
+use crate::secp_context::secp;
+use crate::standardness::{dust_threshold, ensure_standard};
+use crate::timelock::AbsoluteLock;
+use crate::transaction_builder::STANDARD_OP_RETURN_MAX_BYTES;
+use crate::tx_ordering::{order_inputs, order_outputs, OrderingPolicy};
+use crate::utxo::maturity_violation;
+pub use crate::utxo::Utxo;
+use crate::wif::decode_wif;
 
 /// Reddcoin's Base58 P2PKH version byte (`R...` legacy addresses).
 ///
@@ -19,75 +34,205 @@ use serde::Deserialize;
 /// so we need to consciously document and handle this divergence.
 pub const REDDCOIN_VERSION_BYTE: u8 = 0x3D;
 const LEGACY_BASE_TX_SIZE: u64 = 10;
-const LEGACY_P2PKH_INPUT_SIZE: u64 = 148;
+pub(crate) const LEGACY_P2PKH_INPUT_SIZE: u64 = 148;
+/// Uncompressed public keys are 65 bytes instead of 33, so their spends are 32 bytes larger.
+const LEGACY_P2PKH_UNCOMPRESSED_INPUT_SIZE: u64 = 180;
 const LEGACY_OUTPUT_SIZE: u64 = 34;
-const LEGACY_P2PKH_DUST_LIMIT: u64 = 546;
+pub(crate) const LEGACY_P2PKH_DUST_LIMIT: Amount = Amount::from_base(546);
+/// Largest transaction relayed by default policy (`MAX_STANDARD_TX_SIZE`), in bytes.
+pub const MAX_STANDARD_TX_SIZE: u64 = 100_000;
 
-fn estimate_legacy_tx_fee(inputs: usize, outputs: usize, fee_per_kb: u64) -> Result<u64, String> {
+pub(crate) fn estimate_legacy_tx_fee(
+    inputs: usize,
+    outputs: usize,
+    fee_per_kb: u64,
+) -> Result<Amount, String> {
+    estimate_legacy_tx_fee_for_input_size(inputs, LEGACY_P2PKH_INPUT_SIZE, outputs, fee_per_kb)
+}
+
+/// Estimated serialized size in bytes of a legacy transaction with P2PKH-sized outputs.
+pub(crate) fn estimate_legacy_tx_size(
+    inputs: usize,
+    input_size: u64,
+    outputs: usize,
+) -> Result<u64, String> {
     // This estimator intentionally targets legacy P2PKH serialization sizes because ReddMobile's
     // current transaction path signs non-segwit scripts.
-    let tx_size = LEGACY_BASE_TX_SIZE
-        .checked_add((inputs as u64).saturating_mul(LEGACY_P2PKH_INPUT_SIZE))
+    LEGACY_BASE_TX_SIZE
+        .checked_add((inputs as u64).saturating_mul(input_size))
         .and_then(|v| v.checked_add((outputs as u64).saturating_mul(LEGACY_OUTPUT_SIZE)))
-        .ok_or_else(|| "fee calculation overflowed tx size".to_string())?;
+        .ok_or_else(|| "fee calculation overflowed tx size".to_string())
+}
+
+pub(crate) fn estimate_legacy_tx_fee_for_input_size(
+    inputs: usize,
+    input_size: u64,
+    outputs: usize,
+    fee_per_kb: u64,
+) -> Result<Amount, String> {
+    let tx_size = estimate_legacy_tx_size(inputs, input_size, outputs)?;
+    fee_for_size(tx_size, fee_per_kb)
+}
 
+/// Fee for `tx_size` bytes at `fee_per_kb`, rounded up to the next base unit.
+pub(crate) fn fee_for_size(tx_size: u64, fee_per_kb: u64) -> Result<Amount, String> {
     let fee_numerator = tx_size
         .checked_mul(fee_per_kb)
         .and_then(|v| v.checked_add(999))
         .ok_or_else(|| "fee calculation overflowed u64".to_string())?;
 
-    Ok(fee_numerator / 1000)
+    Ok(Amount::from_base(fee_numerator / 1000))
 }
 
-#[derive(Debug, Clone, Deserialize)]
-pub struct Utxo {
-    pub txid: String,
-    pub vout: u32,
-    pub amount: u64,
+/// Below this many inputs, starting threads costs more than signing serially saves.
+const PARALLEL_SIGNING_MIN_INPUTS: usize = 32;
+
+/// Signs every input of `tx` as a legacy P2PKH spend controlled by `secret_key`.
+///
+/// `pubkey` decides both the scriptCode committed to by the sighash and the key pushed into
+/// `script_sig`, so its compression flag must match the address the coins were sent to. Paper
+/// wallets and early Reddcoin Core keys use uncompressed keys; everything newer is compressed.
+///
+/// Every digest is computed from one `SighashCache` over the unsigned transaction before any
+/// `script_sig` is written; legacy sighashes blank the other inputs' scripts anyway, so the
+/// result is identical to signing one input at a time. `threads` is passed to [`parallel_map`].
+pub fn sign_p2pkh_inputs(
+    tx: &mut Transaction,
+    secret_key: &SecretKey,
+    pubkey: &BitcoinPublicKey,
+    threads: Option<usize>,
+) -> Result<(), String> {
+    let secp = secp();
+    // Legacy P2PKH signs each input against the previous output's script template.
+    let p2pkh_script = ScriptBuf::new_p2pkh(&pubkey.pubkey_hash());
+    let pubkey_push = PushBytesBuf::try_from(pubkey.to_bytes())
+        .map_err(|e| format!("public key encoding failed push-bytes checks: {e}"))?;
+
+    let sighash_cache = SighashCache::new(&*tx);
+    let script_sigs = parallel_map(tx.input.len(), threads, |index| {
+        let sighash = sighash_cache
+            .legacy_signature_hash(index, &p2pkh_script, EcdsaSighashType::All.to_u32())
+            .map_err(|e| format!("failed to construct sighash for input {index}: {e}"))?;
+
+        let message = Message::from_digest(*sighash.as_byte_array());
+        let bitcoin_signature = bitcoin::ecdsa::Signature {
+            signature: secp.sign_ecdsa(&message, secret_key),
+            sighash_type: EcdsaSighashType::All,
+        };
+        let sig_push = PushBytesBuf::try_from(bitcoin_signature.to_vec())
+            .map_err(|e| format!("signature encoding failed push-bytes checks: {e}"))?;
+
+        Ok(Builder::new()
+            .push_slice(sig_push)
+            .push_slice(&pubkey_push)
+            .into_script())
+    })?;
+
+    for (input, script_sig) in tx.input.iter_mut().zip(script_sigs) {
+        input.script_sig = script_sig;
+    }
+    Ok(())
 }
 
-/// Signs an OP_RETURN transaction for ReddID-style payload anchoring.
+/// Runs `f` for every index in `0..count`, returning results in index order.
 ///
-/// Input UTXOs are accepted as a JSON array string and each input is signed with the same
-/// private key under legacy P2PKH semantics.
-pub fn sign_opreturn_transaction(
-    utxos_json: String,
-    private_key_hex: String,
-    op_return_data: String,
-    change_address: String,
-    fee_per_kb: u64,
-) -> Result<String, String> {
-    if private_key_hex.len() != 64 {
-        return Err("private_key_hex must be exactly 64 hex characters".to_string());
+/// `threads` of `None` picks automatically: serial below [`PARALLEL_SIGNING_MIN_INPUTS`], one
+/// thread per available core above it. `Some(1)` forces serial work. Signatures are
+/// deterministic (RFC 6979), so the thread count never changes the output.
+pub(crate) fn parallel_map<T, F>(
+    count: usize,
+    threads: Option<usize>,
+    f: F,
+) -> Result<Vec<T>, String>
+where
+    T: Send,
+    F: Fn(usize) -> Result<T, String> + Sync,
+{
+    let threads = threads
+        .unwrap_or_else(|| {
+            if count < PARALLEL_SIGNING_MIN_INPUTS {
+                1
+            } else {
+                std::thread::available_parallelism().map_or(1, |cores| cores.get())
+            }
+        })
+        .clamp(1, count.max(1));
+    if threads == 1 {
+        return (0..count).map(f).collect();
     }
 
-    let utxos: Vec<Utxo> = serde_json::from_str(&utxos_json)
+    let chunk_size = count.div_ceil(threads);
+    std::thread::scope(|scope| {
+        let f = &f;
+        let workers: Vec<_> = (0..count)
+            .step_by(chunk_size)
+            .map(|start| {
+                scope.spawn(move || {
+                    (start..count.min(start + chunk_size))
+                        .map(f)
+                        .collect::<Result<Vec<T>, String>>()
+                })
+            })
+            .collect();
+
+        let mut results = Vec::with_capacity(count);
+        for worker in workers {
+            let chunk = worker
+                .join()
+                .map_err(|_| "signing worker thread panicked".to_string())??;
+            results.extend(chunk);
+        }
+        Ok(results)
+    })
+}
+
+/// Parses the `utxos_json` argument shared by every signing entry point and totals its value.
+pub(crate) fn parse_utxos(utxos_json: &str) -> Result<(Vec<Utxo>, Amount), String> {
+    let utxos: Vec<Utxo> = serde_json::from_str(utxos_json)
         .map_err(|e| format!("utxos_json must be a valid JSON array of UTXOs: {e}"))?;
 
+    let total_input_amount = validate_utxos(&utxos)?;
+    Ok((utxos, total_input_amount))
+}
+
+/// Checks a UTXO list is non-empty and mature, and totals its value.
+pub(crate) fn validate_utxos(utxos: &[Utxo]) -> Result<Amount, String> {
     if utxos.is_empty() {
         return Err("at least one UTXO input is required".to_string());
     }
 
-    let total_input_amount = utxos.iter().try_fold(0u64, |acc, utxo| {
-        acc.checked_add(utxo.amount)
-            .ok_or_else(|| "total input amount overflowed u64".to_string())
-    })?;
-
-    let payload_bytes = hex::decode(op_return_data)
-        .map_err(|e| format!("op_return_data must be valid hex: {e}"))?;
-
-    let op_return_push = PushBytesBuf::try_from(payload_bytes)
-        .map_err(|e| format!("OP_RETURN payload exceeds script push limits: {e}"))?;
+    // Immature coinbase/coinstake spends are consensus-invalid, so refuse them outright. Callers
+    // wanting a full report (including unconfirmed outputs) should run `filter_spendable` first.
+    for (index, utxo) in utxos.iter().enumerate() {
+        if let Some(reason) = maturity_violation(utxo, &ChainParams::MAINNET, None) {
+            return Err(format!("utxos[{index}] is not spendable yet: {reason}"));
+        }
+    }
 
-    let private_key_raw =
-        hex::decode(private_key_hex).map_err(|e| format!("private_key_hex decode failed: {e}"))?;
-    let secret_key = SecretKey::from_slice(&private_key_raw)
-        .map_err(|e| format!("invalid secp256k1 private key: {e}"))?;
+    Amount::checked_sum(utxos.iter().map(|utxo| utxo.amount))
+        .ok_or_else(|| "total input amount overflowed u64".to_string())
+}
 
-    let secp = Secp256k1::new();
-    let public_key = PublicKey::from_secret_key(&secp, &secret_key);
-    let bitcoin_pubkey = BitcoinPublicKey::new(public_key);
+/// Enforces [`BuildOptions::require_verified_inputs`], proving unverified inputs from
+/// [`BuildOptions::input_proofs`] when given.
+pub(crate) fn ensure_verified(utxos: &[Utxo], options: &BuildOptions) -> Result<(), String> {
+    if !options.require_verified_inputs {
+        return Ok(());
+    }
+    if let Some(proofs) = &options.input_proofs {
+        return proofs.check(utxos);
+    }
+    match utxos.iter().position(|utxo| !utxo.verified) {
+        Some(index) => Err(format!(
+            "utxos[{index}] ({}:{}) has not been proof-verified against the header chain",
+            utxos[index].txid, utxos[index].vout
+        )),
+        None => Ok(()),
+    }
+}
 
+/// Converts UTXOs into unsigned inputs, validating each txid along the way.
+pub(crate) fn unsigned_inputs(utxos: &[Utxo]) -> Result<Vec<TxIn>, String> {
     let mut inputs = Vec::with_capacity(utxos.len());
     for (index, utxo) in utxos.iter().enumerate() {
         if utxo.txid.len() != 64 {
@@ -105,21 +250,239 @@ pub fn sign_opreturn_transaction(
                 vout: utxo.vout,
             },
             script_sig: ScriptBuf::new(),
-            sequence: Sequence::MAX,
+            sequence: utxo
+                .sequence
+                .map(Sequence::from_consensus)
+                .unwrap_or(Sequence::MAX),
             witness: Witness::new(),
         });
     }
 
+    Ok(inputs)
+}
+
+/// Options controlling how a transaction is assembled before it is signed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct BuildOptions {
+    #[serde(default)]
+    pub input_ordering: OrderingPolicy,
+    #[serde(default)]
+    pub output_ordering: OrderingPolicy,
+    /// Earliest height or time the transaction may be mined, for post-dated payments.
+    #[serde(default)]
+    pub lock_time: Option<AbsoluteLock>,
+    /// Guardrails evaluated on the built transaction before it is signed.
+    #[serde(default)]
+    pub policy: Option<TransactionPolicy>,
+    /// Threads used to sign and verify inputs; `None` chooses by input count and core count.
+    #[serde(default)]
+    pub signing_threads: Option<usize>,
+    /// Refuse inputs whose funding transaction has not been proven against the local header
+    /// chain (`Utxo::verified`), so a lying server cannot get the wallet to sign against coins
+    /// that do not exist.
+    #[serde(default)]
+    pub require_verified_inputs: bool,
+    /// Proofs for inputs that arrive unverified, such as every UTXO parsed from JSON.
+    #[serde(default)]
+    pub input_proofs: Option<InputProofs>,
+}
+
+impl BuildOptions {
+    /// Parses options from JSON; an empty string selects the defaults.
+    pub fn from_json(options_json: &str) -> Result<Self, String> {
+        if options_json.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        serde_json::from_str(options_json).map_err(|e| format!("invalid build options: {e}"))
+    }
+
+    /// Defaults with construction order kept for inputs and outputs. The hex-only entry points
+    /// use this: their callers get no `change_index` and rely on change staying last.
+    pub fn preserve_order() -> Self {
+        Self {
+            input_ordering: OrderingPolicy::Preserve,
+            output_ordering: OrderingPolicy::Preserve,
+            ..Self::default()
+        }
+    }
+}
+
+/// A signed transaction plus the metadata the app needs to display and label it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SignedTransaction {
+    pub hex: String,
+    pub txid: String,
+    /// Absolute fee paid, including any sub-dust change folded into it.
+    pub fee: Amount,
+    /// Position of the change output after ordering, or `None` when there is no change output.
+    pub change_index: Option<u32>,
+    /// Policy findings that warned without denying; the app should confirm them before
+    /// broadcasting.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub policy_warnings: Vec<PolicyFinding>,
+}
+
+/// Evaluates [`BuildOptions::policy`] on an unsigned transaction, failing on a `deny` verdict and
+/// returning the findings that only warn.
+pub(crate) fn check_policy(
+    tx: &Transaction,
+    fee: Amount,
+    change_index: Option<usize>,
+    options: &BuildOptions,
+) -> Result<Vec<PolicyFinding>, String> {
+    let Some(policy) = &options.policy else {
+        return Ok(Vec::new());
+    };
+    let report = policy.evaluate(tx, fee, change_index);
+    if report.verdict == Verdict::Deny {
+        let reasons: Vec<&str> = report
+            .findings
+            .iter()
+            .filter(|finding| finding.verdict == Verdict::Deny)
+            .map(|finding| finding.reason.as_str())
+            .collect();
+        return Err(format!("denied by policy: {}", reasons.join("; ")));
+    }
+    Ok(report.findings)
+}
+
+/// Checks policy, applies locktime, orders, signs, verifies the scripts, checks standardness and
+/// serializes a fully-funded transaction.
+///
+/// A zero-value change output is dropped here rather than signed: it would be non-standard dust
+/// and its value has already been folded into the fee.
+///
+/// `nLockTime` is ignored when every input is final, so setting a locktime also moves inputs
+/// still at `0xFFFFFFFF` to `0xFFFFFFFE`. Inputs with an explicit sequence keep it.
+pub(crate) fn order_and_sign(
+    mut tx: Transaction,
+    mut change_index: Option<usize>,
+    options: &BuildOptions,
+    secret_key: &SecretKey,
+    pubkey: &BitcoinPublicKey,
+    total_input_amount: Amount,
+) -> Result<SignedTransaction, String> {
+    if let Some(index) = change_index {
+        if tx.output[index].value == bitcoin::Amount::ZERO {
+            tx.output.remove(index);
+            change_index = None;
+        }
+    }
+
+    let total_output_amount = Amount::checked_sum(tx.output.iter().map(|out| out.value.into()))
+        .ok_or_else(|| "total output amount overflowed u64".to_string())?;
+    let fee = total_input_amount
+        .checked_sub(total_output_amount)
+        .ok_or_else(|| "outputs exceed inputs".to_string())?;
+
+    let policy_warnings = check_policy(&tx, fee, change_index, options)?;
+
+    if let Some(lock) = options.lock_time {
+        tx.lock_time = lock.to_lock_time()?;
+        for input in &mut tx.input {
+            if input.sequence == Sequence::MAX {
+                input.sequence = Sequence::ENABLE_LOCKTIME_NO_RBF;
+            }
+        }
+    }
+
+    order_inputs(&mut tx.input, options.input_ordering);
+    let change_index = order_outputs(&mut tx.output, change_index, options.output_ordering);
+
+    sign_p2pkh_inputs(&mut tx, secret_key, pubkey, options.signing_threads)?;
+    let spent_script = ScriptBuf::new_p2pkh(&pubkey.pubkey_hash());
+    verify_inputs(
+        &tx,
+        &vec![spent_script; tx.input.len()],
+        options.signing_threads,
+    )?;
+    // Checked after signing so the size, and therefore the minimum relay fee, is exact.
+    ensure_standard(&tx, fee)?;
+
+    Ok(SignedTransaction {
+        hex: serialize_hex(&tx),
+        txid: tx.compute_txid().to_string(),
+        fee,
+        change_index: change_index.map(|index| index as u32),
+        policy_warnings,
+    })
+}
+
+/// Parses a Reddcoin address argument into its output script.
+fn address_script(address: &str, field: &str) -> Result<ScriptBuf, String> {
+    ReddcoinAddress::from_str(address)
+        .map(|address| address.script_pubkey())
+        .map_err(|e| format!("invalid {field}: {e}"))
+}
+
+/// Signs an OP_RETURN transaction for ReddID-style payload anchoring.
+///
+/// Input UTXOs are accepted as a JSON array string and each input is signed with the same
+/// private key under legacy P2PKH semantics. Outputs keep their fixed order (OP_RETURN, then
+/// change); use [`sign_opreturn_transaction_with_options`] for privacy ordering.
+pub fn sign_opreturn_transaction(
+    utxos_json: String,
+    private_key_hex: String,
+    op_return_data: String,
+    change_address: String,
+    fee_per_kb: u64,
+) -> Result<String, String> {
+    sign_opreturn_transaction_with_options(
+        utxos_json,
+        private_key_hex,
+        op_return_data,
+        change_address,
+        fee_per_kb,
+        BuildOptions::preserve_order(),
+    )
+    .map(|signed| signed.hex)
+}
+
+/// [`sign_opreturn_transaction`] with explicit ordering options and full result metadata.
+pub fn sign_opreturn_transaction_with_options(
+    utxos_json: String,
+    private_key_hex: String,
+    op_return_data: String,
+    change_address: String,
+    fee_per_kb: u64,
+    options: BuildOptions,
+) -> Result<SignedTransaction, String> {
+    if private_key_hex.len() != 64 {
+        return Err("private_key_hex must be exactly 64 hex characters".to_string());
+    }
+
+    let (utxos, total_input_amount) = parse_utxos(&utxos_json)?;
+    ensure_verified(&utxos, &options)?;
+
+    let payload_bytes = hex::decode(op_return_data)
+        .map_err(|e| format!("op_return_data must be valid hex: {e}"))?;
+    if payload_bytes.len() > STANDARD_OP_RETURN_MAX_BYTES {
+        return Err(format!(
+            "OP_RETURN payload too large: {} bytes (max {STANDARD_OP_RETURN_MAX_BYTES})",
+            payload_bytes.len()
+        ));
+    }
+
+    let op_return_push = PushBytesBuf::try_from(payload_bytes)
+        .map_err(|e| format!("OP_RETURN payload exceeds script push limits: {e}"))?;
+
+    let private_key_raw =
+        hex::decode(private_key_hex).map_err(|e| format!("private_key_hex decode failed: {e}"))?;
+    let secret_key = SecretKey::from_slice(&private_key_raw)
+        .map_err(|e| format!("invalid secp256k1 private key: {e}"))?;
+
+    let public_key = PublicKey::from_secret_key(secp(), &secret_key);
+    let bitcoin_pubkey = BitcoinPublicKey::new(public_key);
+
+    let inputs = unsigned_inputs(&utxos)?;
+
     let op_return_output = TxOut {
-        value: Amount::from_sat(0),
+        value: bitcoin::Amount::ZERO,
         script_pubkey: ScriptBuf::new_op_return(op_return_push),
     };
-    let op_return_cost = op_return_output.value.to_sat();
+    let op_return_cost = Amount::from(op_return_output.value);
 
-    let address = Address::from_str(&change_address)
-        .map(|addr| addr.assume_checked())
-        .map_err(|e| format!("invalid change_address: {e}"))?;
-    let change_script = address.script_pubkey();
+    let change_script = address_script(&change_address, "change_address")?;
 
     let mut tx = Transaction {
         version: bitcoin::transaction::Version(2),
@@ -128,15 +491,21 @@ pub fn sign_opreturn_transaction(
         output: vec![
             op_return_output,
             TxOut {
-                value: Amount::from_sat(0),
+                value: bitcoin::Amount::ZERO,
                 script_pubkey: change_script,
             },
         ],
     };
 
-    let mut absolute_fee = estimate_legacy_tx_fee(tx.input.len(), tx.output.len(), fee_per_kb)?;
+    // The OP_RETURN output is larger than the P2PKH-sized outputs the estimator assumes, so its
+    // exact serialized size is added instead; a full 80-byte payload would otherwise pay for
+    // 58 bytes too few and fall under the minimum relay fee.
+    let tx_size = estimate_legacy_tx_size(tx.input.len(), LEGACY_P2PKH_INPUT_SIZE, 1)?
+        .checked_add(serialize(&tx.output[0]).len() as u64)
+        .ok_or_else(|| "fee calculation overflowed tx size".to_string())?;
+    let absolute_fee = fee_for_size(tx_size, fee_per_kb)?;
 
-    let mut required_amount = op_return_cost
+    let required_amount = op_return_cost
         .checked_add(absolute_fee)
         .ok_or_else(|| "required amount overflowed u64".to_string())?;
 
@@ -146,61 +515,25 @@ pub fn sign_opreturn_transaction(
         ));
     }
 
-    let mut change_value = total_input_amount - required_amount;
+    let mut change_value = total_input_amount
+        .checked_sub(required_amount)
+        .expect("required amount was checked against inputs");
     // Dust outputs are not economically spendable and are commonly rejected by network policy.
     // We fold sub-dust change into the fee so we do not create toxic outputs that strand funds.
-    if change_value > 0 && change_value < LEGACY_P2PKH_DUST_LIMIT {
-        absolute_fee = absolute_fee
-            .checked_add(change_value)
-            .ok_or_else(|| "fee calculation overflowed dust adjustment".to_string())?;
-        required_amount = op_return_cost
-            .checked_add(absolute_fee)
-            .ok_or_else(|| "required amount overflowed dust adjustment".to_string())?;
-        change_value = 0;
+    if change_value > Amount::ZERO && change_value < LEGACY_P2PKH_DUST_LIMIT {
+        change_value = Amount::ZERO;
     }
 
-    tx.output[1].value = Amount::from_sat(change_value);
-
-    let pubkey_hash = hash160::Hash::hash(&bitcoin_pubkey.inner.serialize());
-    let p2pkh_script = Builder::new()
-        .push_opcode(bitcoin::opcodes::all::OP_DUP)
-        .push_opcode(bitcoin::opcodes::all::OP_HASH160)
-        .push_slice(pubkey_hash.as_byte_array())
-        .push_opcode(bitcoin::opcodes::all::OP_EQUALVERIFY)
-        .push_opcode(bitcoin::opcodes::all::OP_CHECKSIG)
-        .into_script();
-
-    for index in 0..tx.input.len() {
-        // Legacy P2PKH signs each input against the previous output's script template.
-        // `SighashCache` must borrow the mutable transaction while computing each digest.
-        let sighash = {
-            let sighash_cache = SighashCache::new(&mut tx);
-            sighash_cache
-                .legacy_signature_hash(index, &p2pkh_script, EcdsaSighashType::All.to_u32())
-                .map_err(|e| format!("failed to construct sighash for input {index}: {e}"))?
-        };
-
-        let message = Message::from_digest(*sighash.as_byte_array());
-        let ecdsa_signature = secp.sign_ecdsa(&message, &secret_key);
-
-        let bitcoin_signature = bitcoin::ecdsa::Signature {
-            signature: ecdsa_signature,
-            sighash_type: EcdsaSighashType::All,
-        };
-        let signature_with_hashtype = bitcoin_signature.to_vec();
-
-        let sig_push = PushBytesBuf::try_from(signature_with_hashtype)
-            .map_err(|e| format!("signature encoding failed push-bytes checks: {e}"))?;
-        let pubkey_push = PushBytesBuf::try_from(bitcoin_pubkey.to_bytes())
-            .map_err(|e| format!("public key encoding failed push-bytes checks: {e}"))?;
+    tx.output[1].value = change_value.into();
 
-        tx.input[index].script_sig = Builder::new()
-            .push_slice(sig_push)
-            .push_slice(pubkey_push)
-            .into_script();
-    }
-
-    Ok(serialize_hex(&tx))
+    order_and_sign(
+        tx,
+        Some(1),
+        &options,
+        &secret_key,
+        &bitcoin_pubkey,
+        total_input_amount,
+    )
 }
 
 /// Backward-compatible alias for multi-input signing callers.
@@ -221,81 +554,77 @@ pub fn sign_multi_input_transaction(
 }
 
 /// Signs a standard P2PKH transfer with recipient and change outputs.
+///
+/// Outputs keep their fixed order (recipient, then change); use
+/// [`sign_standard_transfer_with_options`] for privacy ordering and the change position.
 pub fn sign_standard_transfer(
     utxos_json: String,
     private_key_hex: String,
     recipient_address: String,
     change_address: String,
-    amount_to_send: u64,
+    amount_to_send: Amount,
     fee_per_kb: u64,
 ) -> Result<String, String> {
+    sign_standard_transfer_with_options(
+        utxos_json,
+        private_key_hex,
+        recipient_address,
+        change_address,
+        amount_to_send,
+        fee_per_kb,
+        BuildOptions::preserve_order(),
+    )
+    .map(|signed| signed.hex)
+}
+
+/// [`sign_standard_transfer`] with explicit ordering options and full result metadata.
+pub fn sign_standard_transfer_with_options(
+    utxos_json: String,
+    private_key_hex: String,
+    recipient_address: String,
+    change_address: String,
+    amount_to_send: Amount,
+    fee_per_kb: u64,
+    options: BuildOptions,
+) -> Result<SignedTransaction, String> {
     if private_key_hex.len() != 64 {
         return Err("private_key_hex must be exactly 64 hex characters".to_string());
     }
 
-    let utxos: Vec<Utxo> = serde_json::from_str(&utxos_json)
-        .map_err(|e| format!("utxos_json must be a valid JSON array of UTXOs: {e}"))?;
-
-    if utxos.is_empty() {
-        return Err("at least one UTXO input is required".to_string());
-    }
-
-    let total_input_amount = utxos.iter().try_fold(0u64, |acc, utxo| {
-        acc.checked_add(utxo.amount)
-            .ok_or_else(|| "total input amount overflowed u64".to_string())
-    })?;
+    let (utxos, total_input_amount) = parse_utxos(&utxos_json)?;
+    ensure_verified(&utxos, &options)?;
 
     let private_key_raw =
         hex::decode(private_key_hex).map_err(|e| format!("private_key_hex decode failed: {e}"))?;
     let secret_key = SecretKey::from_slice(&private_key_raw)
         .map_err(|e| format!("invalid secp256k1 private key: {e}"))?;
 
-    let secp = Secp256k1::new();
-    let public_key = PublicKey::from_secret_key(&secp, &secret_key);
+    let public_key = PublicKey::from_secret_key(secp(), &secret_key);
     let bitcoin_pubkey = BitcoinPublicKey::new(public_key);
 
-    let mut inputs = Vec::with_capacity(utxos.len());
-    for (index, utxo) in utxos.iter().enumerate() {
-        if utxo.txid.len() != 64 {
-            return Err(format!(
-                "utxos[{index}].txid must be exactly 64 hex characters"
-            ));
-        }
+    let inputs = unsigned_inputs(&utxos)?;
 
-        let txid =
-            Txid::from_str(&utxo.txid).map_err(|e| format!("invalid utxos[{index}].txid: {e}"))?;
+    let recipient_script = address_script(&recipient_address, "recipient_address")?;
+    let change_script = address_script(&change_address, "change_address")?;
 
-        inputs.push(TxIn {
-            previous_output: OutPoint {
-                txid,
-                vout: utxo.vout,
-            },
-            script_sig: ScriptBuf::new(),
-            sequence: Sequence::MAX,
-            witness: Witness::new(),
-        });
+    let recipient_dust = dust_threshold(&recipient_script, &ChainParams::MAINNET);
+    if amount_to_send < recipient_dust {
+        return Err(format!(
+            "amount_to_send {amount_to_send} is below the dust limit ({recipient_dust})"
+        ));
     }
 
-    let recipient_script = Address::from_str(&recipient_address)
-        .map(|addr| addr.assume_checked())
-        .map_err(|e| format!("invalid recipient_address: {e}"))?
-        .script_pubkey();
-    let change_script = Address::from_str(&change_address)
-        .map(|addr| addr.assume_checked())
-        .map_err(|e| format!("invalid change_address: {e}"))?
-        .script_pubkey();
-
     let mut tx = Transaction {
         version: bitcoin::transaction::Version(2),
         lock_time: LockTime::ZERO,
         input: inputs,
         output: vec![
             TxOut {
-                value: Amount::from_sat(amount_to_send),
+                value: amount_to_send.into(),
                 script_pubkey: recipient_script,
             },
             TxOut {
-                value: Amount::from_sat(0),
+                value: bitcoin::Amount::ZERO,
                 script_pubkey: change_script,
             },
         ],
@@ -313,9 +642,11 @@ pub fn sign_standard_transfer(
         ));
     }
 
-    let mut change_value = total_input_amount - required_amount;
+    let mut change_value = total_input_amount
+        .checked_sub(required_amount)
+        .expect("required amount was checked against inputs");
     // Keep change policy aligned with OP_RETURN signing path: avoid creating sub-dust outputs.
-    if change_value > 0 && change_value < LEGACY_P2PKH_DUST_LIMIT {
+    if change_value > Amount::ZERO && change_value < LEGACY_P2PKH_DUST_LIMIT {
         absolute_fee = absolute_fee
             .checked_add(change_value)
             .ok_or_else(|| "fee calculation overflowed dust adjustment".to_string())?;
@@ -327,47 +658,213 @@ pub fn sign_standard_transfer(
                 "insufficient funds after dust adjustment: inputs={total_input_amount}, required={required_amount}"
             ));
         }
-        change_value = 0;
+        change_value = Amount::ZERO;
     }
 
-    tx.output[1].value = Amount::from_sat(change_value);
-
-    let pubkey_hash = hash160::Hash::hash(&bitcoin_pubkey.inner.serialize());
-    let p2pkh_script = Builder::new()
-        .push_opcode(bitcoin::opcodes::all::OP_DUP)
-        .push_opcode(bitcoin::opcodes::all::OP_HASH160)
-        .push_slice(pubkey_hash.as_byte_array())
-        .push_opcode(bitcoin::opcodes::all::OP_EQUALVERIFY)
-        .push_opcode(bitcoin::opcodes::all::OP_CHECKSIG)
-        .into_script();
-
-    for index in 0..tx.input.len() {
-        let sighash = {
-            let sighash_cache = SighashCache::new(&mut tx);
-            sighash_cache
-                .legacy_signature_hash(index, &p2pkh_script, EcdsaSighashType::All.to_u32())
-                .map_err(|e| format!("failed to construct sighash for input {index}: {e}"))?
-        };
+    tx.output[1].value = change_value.into();
 
-        let message = Message::from_digest(*sighash.as_byte_array());
-        let ecdsa_signature = secp.sign_ecdsa(&message, &secret_key);
+    order_and_sign(
+        tx,
+        Some(1),
+        &options,
+        &secret_key,
+        &bitcoin_pubkey,
+        total_input_amount,
+    )
+}
 
-        let bitcoin_signature = bitcoin::ecdsa::Signature {
-            signature: ecdsa_signature,
-            sighash_type: EcdsaSighashType::All,
+/// Sweeps every UTXO controlled by a WIF key into a single output paying `destination_address`.
+///
+/// This is the import path for paper wallets and Reddcoin Core `dumpprivkey` output. Sweeping
+/// moves the funds under the mobile wallet's own keys, so the imported key never has to be stored.
+/// Both compressed and uncompressed WIF keys are supported; the WIF compression flag selects the
+/// public key encoding that matches the address the funds were originally sent to.
+pub fn sign_wif_sweep(
+    utxos_json: String,
+    wif: String,
+    destination_address: String,
+    fee_per_kb: u64,
+    options: BuildOptions,
+) -> Result<SignedTransaction, String> {
+    let key = decode_wif(&wif)?;
+    let (utxos, total_input_amount) = parse_utxos(&utxos_json)?;
+    ensure_verified(&utxos, &options)?;
+
+    let bitcoin_pubkey = key.public_key(secp());
+
+    let destination_script = address_script(&destination_address, "destination_address")?;
+
+    let input_size = if key.compressed {
+        LEGACY_P2PKH_INPUT_SIZE
+    } else {
+        LEGACY_P2PKH_UNCOMPRESSED_INPUT_SIZE
+    };
+    let absolute_fee =
+        estimate_legacy_tx_fee_for_input_size(utxos.len(), input_size, 1, fee_per_kb)?;
+
+    if absolute_fee >= total_input_amount {
+        return Err(format!(
+            "insufficient funds: inputs={total_input_amount}, fee={absolute_fee}"
+        ));
+    }
+
+    let sweep_value = total_input_amount
+        .checked_sub(absolute_fee)
+        .expect("fee was checked against inputs");
+    // A sweep has no change output to fold dust into, so a sub-dust result is simply unspendable.
+    if sweep_value < LEGACY_P2PKH_DUST_LIMIT {
+        return Err(format!(
+            "sweep output {sweep_value} is below the dust limit ({LEGACY_P2PKH_DUST_LIMIT})"
+        ));
+    }
+
+    let tx = Transaction {
+        version: bitcoin::transaction::Version(2),
+        lock_time: LockTime::ZERO,
+        input: unsigned_inputs(&utxos)?,
+        output: vec![TxOut {
+            value: sweep_value.into(),
+            script_pubkey: destination_script,
+        }],
+    };
+
+    order_and_sign(
+        tx,
+        None,
+        &options,
+        &key.secret_key,
+        &bitcoin_pubkey,
+        total_input_amount,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::secp256k1::SecretKey;
+    use bitcoin::PublicKey as BitcoinPublicKey;
+
+    use super::*;
+
+    const KEY_HEX: &str = "0101010101010101010101010101010101010101010101010101010101010101";
+
+    fn key_address(key_hex: &str) -> String {
+        let secret_key = SecretKey::from_slice(&hex::decode(key_hex).unwrap()).unwrap();
+        let pubkey = BitcoinPublicKey::new(secret_key.public_key(secp()));
+        ReddcoinAddress::p2pkh(&pubkey).to_string()
+    }
+
+    fn utxos_json() -> String {
+        format!(
+            r#"[{{"txid": "{}", "vout": 0, "amount": 1000000000}}]"#,
+            "ab".repeat(32)
+        )
+    }
+
+    #[test]
+    fn legacy_transfer_keeps_change_last() {
+        let change = key_address(KEY_HEX);
+        let recipient = key_address(&"02".repeat(32));
+        // Random ordering would move the change first about half the time.
+        for _ in 0..16 {
+            let hex = sign_standard_transfer(
+                utxos_json(),
+                KEY_HEX.to_string(),
+                recipient.clone(),
+                change.clone(),
+                Amount::from_base(100_000_000),
+                1_000_000,
+            )
+            .unwrap();
+            let tx: Transaction =
+                bitcoin::consensus::deserialize(&hex::decode(hex).unwrap()).unwrap();
+            assert_eq!(tx.output[0].value.to_sat(), 100_000_000);
+            assert_eq!(
+                tx.output[1].script_pubkey,
+                address_script(&change, "change").unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn sweep_runs_the_policy_before_signing() {
+        let wif = crate::wif::encode_wif(KEY_HEX.to_string(), false).unwrap();
+        let sweep = |options: BuildOptions| {
+            sign_wif_sweep(
+                utxos_json(),
+                wif.clone(),
+                key_address(&"02".repeat(32)),
+                1_000_000,
+                options,
+            )
         };
-        let signature_with_hashtype = bitcoin_signature.to_vec();
+        let signed = sweep(BuildOptions::default()).unwrap();
+        assert_eq!(signed.change_index, None);
 
-        let sig_push = PushBytesBuf::try_from(signature_with_hashtype)
-            .map_err(|e| format!("signature encoding failed push-bytes checks: {e}"))?;
-        let pubkey_push = PushBytesBuf::try_from(bitcoin_pubkey.to_bytes())
-            .map_err(|e| format!("public key encoding failed push-bytes checks: {e}"))?;
+        let deny = BuildOptions::from_json(r#"{"policy":{"rules":{"max_fee":{"limit":1}}}}"#);
+        let err = sweep(deny.unwrap()).unwrap_err();
+        assert!(err.starts_with("denied by policy: fee"), "{err}");
+    }
 
-        tx.input[index].script_sig = Builder::new()
-            .push_slice(sig_push)
-            .push_slice(pubkey_push)
-            .into_script();
+    #[test]
+    fn thread_count_does_not_change_signatures() {
+        let utxos: Vec<String> = (0..48u8)
+            .map(|index| {
+                format!(
+                    r#"{{"txid": "{}", "vout": {index}, "amount": 100000000}}"#,
+                    format!("{index:02x}").repeat(32)
+                )
+            })
+            .collect();
+        let utxos_json = format!("[{}]", utxos.join(","));
+        let wif = crate::wif::encode_wif(KEY_HEX.to_string(), true).unwrap();
+        let sweep = |threads: usize| {
+            let options = BuildOptions {
+                signing_threads: Some(threads),
+                ..BuildOptions::preserve_order()
+            };
+            sign_wif_sweep(
+                utxos_json.clone(),
+                wif.clone(),
+                key_address(&"02".repeat(32)),
+                1_000_000,
+                options,
+            )
+            .unwrap()
+            .hex
+        };
+
+        let serial = sweep(1);
+        assert_eq!(sweep(4), serial);
+
+        let mut tx: Transaction =
+            bitcoin::consensus::deserialize(&hex::decode(&serial).unwrap()).unwrap();
+        assert_eq!(tx.input.len(), 48);
+        let scripts = vec![address_script(&key_address(KEY_HEX), "input").unwrap(); 48];
+        crate::script_interpreter::verify_inputs(&tx, &scripts, Some(1)).unwrap();
+        crate::script_interpreter::verify_inputs(&tx, &scripts, Some(4)).unwrap();
+
+        // Each signature commits to its own input.
+        let first = tx.input[0].script_sig.clone();
+        tx.input[0].script_sig = tx.input[40].script_sig.clone();
+        tx.input[40].script_sig = first;
+        let err = crate::script_interpreter::verify_inputs(&tx, &scripts, Some(4)).unwrap_err();
+        assert!(err.contains("input 0"), "{err}");
     }
 
-    Ok(serialize_hex(&tx))
+    #[test]
+    fn legacy_opreturn_keeps_change_last() {
+        for _ in 0..16 {
+            let hex = sign_opreturn_transaction(
+                utxos_json(),
+                KEY_HEX.to_string(),
+                hex::encode("hello"),
+                key_address(KEY_HEX),
+                1_000_000,
+            )
+            .unwrap();
+            let tx: Transaction =
+                bitcoin::consensus::deserialize(&hex::decode(hex).unwrap()).unwrap();
+            assert!(tx.output[0].script_pubkey.is_op_return());
+        }
+    }
 }
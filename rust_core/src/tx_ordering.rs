@@ -0,0 +1,158 @@
+//! Input and output ordering policies.
+//!
+//! Always placing the payment first and change second lets chain analysis label the change
+//! output of every ReddMobile transaction. Ordering is applied after amounts are final and
+//! before signing, since every legacy signature commits to the full input and output order.
+
+use std::cmp::Ordering;
+
+use bitcoin::{TxIn, TxOut};
+use rand::seq::SliceRandom;
+use serde::Deserialize;
+
+/// How inputs or outputs are arranged in the final transaction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrderingPolicy {
+    /// Keep construction order (payment/OP_RETURN first, change last). Only useful for callers
+    /// that depend on fixed positions.
+    Preserve,
+    /// BIP69 lexicographic ordering. Deterministic, and shared with other BIP69 wallets so the
+    /// order itself reveals nothing.
+    Bip69,
+    /// Uniform shuffle from the thread-local CSPRNG.
+    #[default]
+    Random,
+}
+
+/// Orders inputs according to `policy`.
+///
+/// BIP69 sorts by previous txid in its displayed (big-endian hex) form, then by output index.
+/// `Txid`'s own `Ord` compares the internal little-endian bytes, so it cannot be used directly.
+pub fn order_inputs(inputs: &mut [TxIn], policy: OrderingPolicy) {
+    match policy {
+        OrderingPolicy::Preserve => {}
+        OrderingPolicy::Bip69 => inputs.sort_by(|a, b| {
+            let a_txid = a.previous_output.txid.to_string();
+            let b_txid = b.previous_output.txid.to_string();
+            a_txid
+                .cmp(&b_txid)
+                .then(a.previous_output.vout.cmp(&b.previous_output.vout))
+        }),
+        OrderingPolicy::Random => inputs.shuffle(&mut rand::thread_rng()),
+    }
+}
+
+/// Orders outputs according to `policy` and returns where `tracked_index` ended up.
+///
+/// The tracked index is normally the change output, which the app still needs to label after
+/// its position has been hidden from observers.
+pub fn order_outputs(
+    outputs: &mut Vec<TxOut>,
+    tracked_index: Option<usize>,
+    policy: OrderingPolicy,
+) -> Option<usize> {
+    let mut indexed: Vec<(usize, TxOut)> = outputs.drain(..).enumerate().collect();
+
+    match policy {
+        OrderingPolicy::Preserve => {}
+        OrderingPolicy::Bip69 => indexed.sort_by(|(_, a), (_, b)| bip69_output_cmp(a, b)),
+        OrderingPolicy::Random => indexed.shuffle(&mut rand::thread_rng()),
+    }
+
+    let new_index = tracked_index.and_then(|tracked| {
+        indexed
+            .iter()
+            .position(|(original, _)| *original == tracked)
+    });
+    outputs.extend(indexed.into_iter().map(|(_, output)| output));
+    new_index
+}
+
+/// BIP69 output order: ascending amount, then ascending scriptPubKey bytes.
+fn bip69_output_cmp(a: &TxOut, b: &TxOut) -> Ordering {
+    a.value
+        .cmp(&b.value)
+        .then_with(|| a.script_pubkey.as_bytes().cmp(b.script_pubkey.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use bitcoin::{Amount, OutPoint, ScriptBuf, Txid};
+
+    use super::*;
+
+    fn input(txid: &str, vout: u32) -> TxIn {
+        TxIn {
+            previous_output: OutPoint::new(Txid::from_str(txid).unwrap(), vout),
+            ..TxIn::default()
+        }
+    }
+
+    fn output(value: u64, script: &[u8]) -> TxOut {
+        TxOut {
+            value: Amount::from_sat(value),
+            script_pubkey: ScriptBuf::from_bytes(script.to_vec()),
+        }
+    }
+
+    #[test]
+    fn bip69_sorts_inputs_by_displayed_txid_then_vout() {
+        // Internal byte order would put `b` first: its last displayed byte is the smallest.
+        let a = "0100000000000000000000000000000000000000000000000000000000000002";
+        let b = "0200000000000000000000000000000000000000000000000000000000000001";
+        let mut inputs = vec![input(b, 0), input(a, 1), input(a, 0)];
+        order_inputs(&mut inputs, OrderingPolicy::Bip69);
+        let order: Vec<_> = inputs.iter().map(|i| i.previous_output).collect();
+        assert_eq!(
+            order,
+            vec![
+                input(a, 0).previous_output,
+                input(a, 1).previous_output,
+                input(b, 0).previous_output,
+            ]
+        );
+    }
+
+    #[test]
+    fn bip69_sorts_outputs_by_amount_then_script_and_tracks_change() {
+        let mut outputs = vec![
+            output(500, &[0x02]),
+            output(100, &[0x09]),
+            output(500, &[0x01]),
+        ];
+        let change = order_outputs(&mut outputs, Some(0), OrderingPolicy::Bip69);
+        assert_eq!(
+            outputs,
+            vec![
+                output(100, &[0x09]),
+                output(500, &[0x01]),
+                output(500, &[0x02])
+            ]
+        );
+        assert_eq!(change, Some(2));
+    }
+
+    #[test]
+    fn preserve_keeps_construction_order() {
+        let original = vec![output(9, &[1]), output(1, &[2])];
+        let mut outputs = original.clone();
+        assert_eq!(
+            order_outputs(&mut outputs, Some(1), OrderingPolicy::Preserve),
+            Some(1)
+        );
+        assert_eq!(outputs, original);
+    }
+
+    #[test]
+    fn random_tracks_the_change_output() {
+        for _ in 0..20 {
+            let mut outputs: Vec<_> = (0..5).map(|i| output(i, &[i as u8])).collect();
+            let change = order_outputs(&mut outputs, Some(3), OrderingPolicy::Random).unwrap();
+            assert_eq!(outputs[change], output(3, &[3]));
+            assert_eq!(outputs.len(), 5);
+        }
+    }
+}
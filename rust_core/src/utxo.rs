@@ -0,0 +1,402 @@
+//! Wallet UTXO model and spendability rules.
+//!
+//! Blockbook (and any other chain source) reports outputs that exist but cannot be spent yet:
+//! unconfirmed outputs and coinbase/coinstake rewards below maturity. Broadcasting a spend of an
+//! immature output is rejected by the network, so we filter here and tell the app exactly which
+//! outputs were held back and why.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::amount::Amount;
+use crate::chain_params::ChainParams;
+
+/// An unspent output owned by the wallet.
+///
+/// Only `txid`, `vout` and `amount` are required so existing callers that pass the minimal
+/// shape keep working; the remaining metadata defaults to "unknown".
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Utxo {
+    pub txid: String,
+    pub vout: u32,
+    /// Value, serialized as integer base units.
+    pub amount: Amount,
+    /// Height of the block that confirmed the funding transaction, if confirmed.
+    #[serde(default)]
+    pub height: Option<u32>,
+    /// Confirmation count as reported by the chain source.
+    #[serde(default)]
+    pub confirmations: u32,
+    /// Whether the funding transaction is a coinbase.
+    #[serde(default, alias = "coinbase")]
+    pub is_coinbase: bool,
+    /// Whether the funding transaction is a PoSV coinstake.
+    #[serde(default, alias = "coinstake")]
+    pub is_coinstake: bool,
+    /// Hex-encoded scriptPubKey of the output.
+    #[serde(default)]
+    pub script_pubkey: Option<String>,
+    /// Address the output pays to.
+    #[serde(default)]
+    pub address: Option<String>,
+    /// `nSequence` to spend this output with. Defaults to final (`0xFFFFFFFF`), or to
+    /// `0xFFFFFFFE` when the transaction carries a locktime.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sequence: Option<u32>,
+    /// Whether the funding transaction's inclusion was proven against the local header chain
+    /// (see [`crate::merkle_proof::verify_utxos`]). Backends always report `false`.
+    ///
+    /// Never read from JSON: a caller could simply claim it. UTXOs that cross the FFI are proven
+    /// by the signer instead, from [`crate::merkle_proof::InputProofs`].
+    #[serde(skip_deserializing, skip_serializing_if = "std::ops::Not::not")]
+    pub verified: bool,
+}
+
+impl Utxo {
+    /// Whether the output was created by block generation (mining or staking).
+    pub fn is_generated(&self) -> bool {
+        self.is_coinbase || self.is_coinstake
+    }
+
+    /// Returns the confirmation count, deriving it from `height` when a tip is known.
+    ///
+    /// A reported count is preferred; the derived value only fills the gap when the source
+    /// supplied a height but no count (ElectrumX-style history entries, for example).
+    pub fn confirmations_at(&self, tip_height: Option<u32>) -> u32 {
+        if self.confirmations > 0 {
+            return self.confirmations;
+        }
+        match (self.height, tip_height) {
+            (Some(height), Some(tip)) if height > 0 && tip >= height => tip - height + 1,
+            _ => 0,
+        }
+    }
+}
+
+/// Why an output was excluded from spending.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum ExclusionReason {
+    /// The output has fewer confirmations than the caller's policy requires.
+    Unconfirmed { confirmations: u32, required: u32 },
+    /// A coinbase output that has not reached coinbase maturity.
+    ImmatureCoinbase { confirmations: u32, required: u32 },
+    /// A PoSV staking reward that has not reached coinbase maturity.
+    ImmatureCoinstake { confirmations: u32, required: u32 },
+    /// Worth no more than the fee to spend it at the current rate.
+    Uneconomical { spend_fee: Amount },
+}
+
+impl fmt::Display for ExclusionReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unconfirmed {
+                confirmations,
+                required,
+            } => write!(f, "unconfirmed ({confirmations}/{required} confirmations)"),
+            Self::ImmatureCoinbase {
+                confirmations,
+                required,
+            } => write!(
+                f,
+                "immature coinbase ({confirmations}/{required} confirmations)"
+            ),
+            Self::ImmatureCoinstake {
+                confirmations,
+                required,
+            } => write!(
+                f,
+                "immature coinstake ({confirmations}/{required} confirmations)"
+            ),
+            Self::Uneconomical { spend_fee } => {
+                write!(f, "uneconomical (spending it costs {spend_fee})")
+            }
+        }
+    }
+}
+
+/// An output held back by [`filter_spendable`].
+#[derive(Debug, Clone, Serialize)]
+pub struct ExcludedUtxo {
+    pub txid: String,
+    pub vout: u32,
+    pub amount: Amount,
+    pub reason: ExclusionReason,
+}
+
+/// Caller policy layered on top of consensus maturity rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpendabilityRules {
+    /// Minimum confirmations for ordinary outputs. `0` allows spending unconfirmed outputs,
+    /// which is what fee bumping needs.
+    pub min_confirmations: u32,
+    /// Current chain tip, used to derive confirmations from heights.
+    pub tip_height: Option<u32>,
+}
+
+impl Default for SpendabilityRules {
+    fn default() -> Self {
+        Self {
+            min_confirmations: 1,
+            tip_height: None,
+        }
+    }
+}
+
+/// Outputs partitioned into spendable and excluded sets.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SpendableSet {
+    pub spendable: Vec<Utxo>,
+    pub excluded: Vec<ExcludedUtxo>,
+    pub spendable_amount: Amount,
+    pub excluded_amount: Amount,
+}
+
+/// Checks consensus maturity only, ignoring caller confirmation policy.
+///
+/// The signer uses this directly: spending an immature generated output can never succeed,
+/// whereas spending an unconfirmed ordinary output is legitimate (e.g. chained change).
+pub fn maturity_violation(
+    utxo: &Utxo,
+    params: &ChainParams,
+    tip_height: Option<u32>,
+) -> Option<ExclusionReason> {
+    if !utxo.is_generated() {
+        return None;
+    }
+
+    let confirmations = utxo.confirmations_at(tip_height);
+    if confirmations >= params.coinbase_maturity {
+        return None;
+    }
+
+    let required = params.coinbase_maturity;
+    Some(if utxo.is_coinstake {
+        ExclusionReason::ImmatureCoinstake {
+            confirmations,
+            required,
+        }
+    } else {
+        ExclusionReason::ImmatureCoinbase {
+            confirmations,
+            required,
+        }
+    })
+}
+
+/// Splits `utxos` into outputs that can be spent now and outputs that must wait, with reasons.
+pub fn filter_spendable(
+    utxos: Vec<Utxo>,
+    params: &ChainParams,
+    rules: &SpendabilityRules,
+) -> SpendableSet {
+    let mut set = SpendableSet::default();
+
+    for utxo in utxos {
+        let confirmations = utxo.confirmations_at(rules.tip_height);
+        let reason = maturity_violation(&utxo, params, rules.tip_height).or((confirmations
+            < rules.min_confirmations)
+            .then_some(ExclusionReason::Unconfirmed {
+                confirmations,
+                required: rules.min_confirmations,
+            }));
+
+        match reason {
+            Some(reason) => {
+                set.excluded_amount = set.excluded_amount.saturating_add(utxo.amount);
+                set.excluded.push(ExcludedUtxo {
+                    txid: utxo.txid,
+                    vout: utxo.vout,
+                    amount: utxo.amount,
+                    reason,
+                });
+            }
+            None => {
+                set.spendable_amount = set.spendable_amount.saturating_add(utxo.amount);
+                set.spendable.push(utxo);
+            }
+        }
+    }
+
+    set
+}
+
+/// JSON entry point for [`filter_spendable`] using mainnet parameters.
+///
+/// `tip_height` of `0` means "unknown" so the function can be called across FFI without an
+/// optional integer.
+pub fn filter_spendable_utxos(
+    utxos_json: String,
+    min_confirmations: u32,
+    tip_height: u32,
+) -> Result<SpendableSet, String> {
+    let utxos: Vec<Utxo> = serde_json::from_str(&utxos_json)
+        .map_err(|e| format!("utxos_json must be a valid JSON array of UTXOs: {e}"))?;
+
+    let rules = SpendabilityRules {
+        min_confirmations,
+        tip_height: (tip_height > 0).then_some(tip_height),
+    };
+
+    Ok(filter_spendable(utxos, &ChainParams::MAINNET, &rules))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MATURITY: u32 = ChainParams::MAINNET.coinbase_maturity;
+
+    fn utxo(json: &str) -> Utxo {
+        serde_json::from_str(json).unwrap()
+    }
+
+    fn generated(kind: &str, confirmations: u32) -> Utxo {
+        utxo(&format!(
+            r#"{{"txid":"{}","vout":0,"amount":100,"confirmations":{confirmations},"{kind}":true}}"#,
+            "ab".repeat(32)
+        ))
+    }
+
+    #[test]
+    fn confirmations_prefer_the_reported_count() {
+        let minimal = utxo(r#"{"txid":"aa","vout":1,"amount":5}"#);
+        assert_eq!(minimal.confirmations_at(Some(1_000)), 0);
+        assert!(!minimal.is_generated());
+
+        let with_height = utxo(r#"{"txid":"aa","vout":1,"amount":5,"height":100}"#);
+        assert_eq!(with_height.confirmations_at(None), 0);
+        assert_eq!(with_height.confirmations_at(Some(100)), 1);
+        assert_eq!(with_height.confirmations_at(Some(149)), 50);
+        // A tip below the output's height (a stale tip) is not a negative count.
+        assert_eq!(with_height.confirmations_at(Some(99)), 0);
+
+        let reported = utxo(r#"{"txid":"aa","vout":1,"amount":5,"height":100,"confirmations":7}"#);
+        assert_eq!(reported.confirmations_at(Some(1_000)), 7);
+    }
+
+    #[test]
+    fn generated_outputs_mature_at_the_boundary() {
+        let params = ChainParams::MAINNET;
+        for kind in ["coinbase", "coinstake"] {
+            assert!(maturity_violation(&generated(kind, MATURITY), &params, None).is_none());
+            let reason = maturity_violation(&generated(kind, MATURITY - 1), &params, None);
+            let expected = if kind == "coinbase" {
+                ExclusionReason::ImmatureCoinbase {
+                    confirmations: MATURITY - 1,
+                    required: MATURITY,
+                }
+            } else {
+                ExclusionReason::ImmatureCoinstake {
+                    confirmations: MATURITY - 1,
+                    required: MATURITY,
+                }
+            };
+            assert_eq!(reason, Some(expected), "{kind}");
+        }
+        // Ordinary outputs have no maturity rule, even unconfirmed.
+        let ordinary = utxo(r#"{"txid":"aa","vout":0,"amount":5}"#);
+        assert!(maturity_violation(&ordinary, &params, None).is_none());
+    }
+
+    #[test]
+    fn maturity_uses_the_tip_when_only_a_height_is_known() {
+        let params = ChainParams::MAINNET;
+        let stake = utxo(r#"{"txid":"aa","vout":0,"amount":5,"height":1000,"is_coinstake":true}"#);
+        // Without a tip the output cannot be shown to be mature.
+        assert_eq!(
+            maturity_violation(&stake, &params, None),
+            Some(ExclusionReason::ImmatureCoinstake {
+                confirmations: 0,
+                required: MATURITY,
+            })
+        );
+        assert!(maturity_violation(&stake, &params, Some(1000 + MATURITY - 2)).is_some());
+        assert!(maturity_violation(&stake, &params, Some(1000 + MATURITY - 1)).is_none());
+    }
+
+    #[test]
+    fn filter_reports_each_exclusion() {
+        let utxos = format!(
+            "[{}, {}, {}, {}, {}]",
+            r#"{"txid":"01","vout":0,"amount":100,"height":990}"#,
+            r#"{"txid":"02","vout":0,"amount":200}"#,
+            r#"{"txid":"03","vout":0,"amount":300,"height":990,"coinbase":true}"#,
+            r#"{"txid":"04","vout":0,"amount":400,"confirmations":3,"coinstake":true}"#,
+            r#"{"txid":"05","vout":0,"amount":500,"height":900,"coinstake":true}"#,
+        );
+        let set = filter_spendable_utxos(utxos.clone(), 2, 1000).unwrap();
+        let spendable: Vec<_> = set.spendable.iter().map(|u| u.txid.as_str()).collect();
+        assert_eq!(spendable, ["01", "05"]);
+        assert_eq!(set.spendable_amount, Amount::from_base(600));
+        assert_eq!(set.excluded_amount, Amount::from_base(900));
+        let reasons: Vec<_> = set
+            .excluded
+            .iter()
+            .map(|e| (e.txid.as_str(), e.reason))
+            .collect();
+        assert_eq!(
+            reasons,
+            [
+                (
+                    "02",
+                    ExclusionReason::Unconfirmed {
+                        confirmations: 0,
+                        required: 2
+                    }
+                ),
+                (
+                    "03",
+                    ExclusionReason::ImmatureCoinbase {
+                        confirmations: 11,
+                        required: MATURITY
+                    }
+                ),
+                (
+                    "04",
+                    ExclusionReason::ImmatureCoinstake {
+                        confirmations: 3,
+                        required: MATURITY
+                    }
+                ),
+            ]
+        );
+
+        // A tip of 0 means unknown: height-only outputs count as unconfirmed.
+        let set = filter_spendable_utxos(utxos.clone(), 1, 0).unwrap();
+        assert!(set.spendable.is_empty());
+        // With no confirmation policy only the immature rewards are held back.
+        let set = filter_spendable_utxos(utxos, 0, 1000).unwrap();
+        assert_eq!(set.excluded.len(), 2);
+    }
+
+    #[test]
+    fn exclusion_reasons_serialize_and_display() {
+        let reason = ExclusionReason::ImmatureCoinstake {
+            confirmations: 3,
+            required: 50,
+        };
+        assert_eq!(
+            serde_json::to_value(reason).unwrap(),
+            serde_json::json!({"kind": "immature_coinstake", "confirmations": 3, "required": 50})
+        );
+        assert_eq!(
+            reason.to_string(),
+            "immature coinstake (3/50 confirmations)"
+        );
+        let uneconomical = ExclusionReason::Uneconomical {
+            spend_fee: Amount::from_base(148_000),
+        };
+        assert_eq!(
+            serde_json::to_value(uneconomical).unwrap()["kind"],
+            "uneconomical"
+        );
+        assert!(uneconomical
+            .to_string()
+            .starts_with("uneconomical (spending it costs "));
+        assert!(filter_spendable_utxos("{}".to_string(), 1, 0)
+            .unwrap_err()
+            .contains("valid JSON array"));
+    }
+}
@@ -0,0 +1,523 @@
+//! Importers for wallets created outside ReddMobile.
+//!
+//! Two legacy sources are supported:
+//! * Reddcoin Core's `dumpwallet` text export, which lists every WIF key with its label and role.
+//! * ReddWallet (the Electrum 1.x fork), whose deterministic wallets are fully described by a
+//!   128-bit hex seed (or the 12-word mnemonic encoding it), or by the master public key for
+//!   watch-only tracking.
+//!
+//! Both produce the same [`WalletImport`] shape so the app can either sweep the keys with
+//! `sign_wif_sweep` or keep the addresses as tracked, labelled entries.
+
+use bitcoin::hashes::{sha256, sha256d, Hash, HashEngine};
+use bitcoin::secp256k1::{PublicKey, Scalar, SecretKey};
+use bitcoin::PublicKey as BitcoinPublicKey;
+use serde::Serialize;
+
+use crate::address::ReddcoinAddress;
+use crate::secp_context::secp;
+use crate::wif::{decode_wif, WifKey};
+
+/// Electrum 1.x stretches the seed with this many SHA-256 rounds before using it as a key.
+const REDDWALLET_STRETCH_ROUNDS: usize = 100_000;
+
+/// ReddWallet's hex seed is 128 bits, shown to users as 32 hex characters.
+const REDDWALLET_SEED_HEX_LEN: usize = 32;
+
+/// Electrum 1.x mnemonic word list; each group of three words encodes 32 bits of the seed.
+const ELECTRUM_V1_WORDS: &str = include_str!("electrum_v1_words.txt");
+
+/// Number of words in the Electrum 1.x word list.
+const ELECTRUM_V1_WORD_COUNT: usize = 1626;
+
+/// ReddWallet mnemonics encode the 128-bit seed as 12 words.
+const REDDWALLET_MNEMONIC_WORDS: usize = 12;
+
+/// ReddWallet's master public key is an uncompressed point without the `0x04` prefix.
+const REDDWALLET_MPK_HEX_LEN: usize = 128;
+
+/// Upper bound on derived addresses per chain, so a typo in a count cannot stall the app.
+const MAX_DERIVED_KEYS_PER_CHAIN: u32 = 10_000;
+
+/// Role a key played in the source wallet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyRole {
+    /// A receiving key the user labelled (possibly with an empty label).
+    Receive,
+    /// A keypool key that was generated but never handed out.
+    Reserve,
+    /// A change key.
+    Change,
+    /// The active HD seed / master key of a Reddcoin Core HD wallet.
+    HdSeed,
+    /// An HD seed that was rotated out but may still control funds.
+    InactiveHdSeed,
+}
+
+/// One key (or, for watch-only imports, one address) recovered from a legacy wallet.
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportedKey {
+    /// WIF private key, absent for watch-only imports.
+    pub wif: Option<String>,
+    /// P2PKH address derived from the key.
+    pub address: String,
+    pub label: Option<String>,
+    pub role: KeyRole,
+    /// Creation time as written by the source wallet (ISO 8601 for Reddcoin Core dumps).
+    pub created_at: Option<String>,
+    /// Derivation path for HD keys (`hdkeypath=` in Core dumps, `m/<change>/<n>` for ReddWallet).
+    pub key_path: Option<String>,
+}
+
+/// Result of importing a legacy wallet.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct WalletImport {
+    pub keys: Vec<ImportedKey>,
+    /// Hex redeem scripts listed by Core with `script=1`; they carry no private key.
+    pub scripts: Vec<String>,
+    /// The `extended private masterkey` header of HD Core dumps, if present.
+    pub extended_master_key: Option<String>,
+    /// Whether the import carries private keys or only addresses to track.
+    pub watch_only: bool,
+}
+
+/// Parses the text written by Reddcoin Core's `dumpwallet` RPC.
+///
+/// Key lines have the shape `<wif> <time> <flag> # addr=<address>[ hdkeypath=<path>]`, where the
+/// flag is `label=<percent-encoded>`, `reserve=1`, `change=1`, `hdseed=1` (`hdmaster=1` before
+/// 0.17), `inactivehdseed=1` or `script=1`. Comment lines are skipped apart from the extended
+/// master key header.
+pub fn parse_core_dumpwallet(dump: String) -> Result<WalletImport, String> {
+    let secp = secp();
+    let mut import = WalletImport::default();
+
+    for (index, raw_line) in dump.lines().enumerate() {
+        let line_number = index + 1;
+        let line = raw_line.trim();
+
+        if line.is_empty() {
+            continue;
+        }
+
+        if let Some(comment) = line.strip_prefix('#') {
+            if let Some(xprv) = comment.trim().strip_prefix("extended private masterkey:") {
+                import.extended_master_key = Some(xprv.trim().to_string());
+            }
+            continue;
+        }
+
+        let (fields, comment) = match line.split_once('#') {
+            Some((fields, comment)) => (fields, comment),
+            None => (line, ""),
+        };
+        let fields: Vec<&str> = fields.split_whitespace().collect();
+        if fields.len() < 3 {
+            return Err(format!(
+                "line {line_number}: expected `<key> <time> <flag>`, got {} fields",
+                fields.len()
+            ));
+        }
+
+        let (key, created_at, flag) = (fields[0], fields[1], fields[2]);
+
+        if flag == "script=1" {
+            hex::decode(key).map_err(|e| format!("line {line_number}: invalid script hex: {e}"))?;
+            import.scripts.push(key.to_string());
+            continue;
+        }
+
+        let (role, label) = match flag.split_once('=') {
+            Some(("label", encoded)) => (KeyRole::Receive, Some(decode_dump_string(encoded))),
+            Some(("reserve", _)) => (KeyRole::Reserve, None),
+            Some(("change", _)) => (KeyRole::Change, None),
+            Some(("hdseed" | "hdmaster", _)) => (KeyRole::HdSeed, None),
+            Some(("inactivehdseed" | "inactivehdmaster", _)) => (KeyRole::InactiveHdSeed, None),
+            _ => return Err(format!("line {line_number}: unknown key flag `{flag}`")),
+        };
+
+        let wif_key = decode_wif(key).map_err(|e| format!("line {line_number}: {e}"))?;
+        let key_path = comment
+            .split_whitespace()
+            .find_map(|field| field.strip_prefix("hdkeypath="))
+            .map(str::to_string);
+
+        import.keys.push(ImportedKey {
+            wif: Some(wif_key.to_wif()),
+            address: wif_key.address(secp).to_string(),
+            label,
+            role,
+            created_at: Some(created_at.to_string()),
+            key_path,
+        });
+    }
+
+    if import.keys.is_empty() && import.scripts.is_empty() {
+        return Err("dump contains no keys".to_string());
+    }
+
+    Ok(import)
+}
+
+/// Recreates a ReddWallet deterministic wallet from its 32-character hex seed or the 12-word
+/// mnemonic ReddWallet showed for it.
+///
+/// ReddWallet inherited Electrum 1.x's scheme: the seed is stretched into a master secret, and
+/// key `n` on chain `change` is `master + H("n:change:" || mpk)`. Keys are uncompressed, so the
+/// returned WIFs carry no compression flag and sweep with the uncompressed input size.
+pub fn import_reddwallet_seed(
+    seed: String,
+    receive_count: u32,
+    change_count: u32,
+) -> Result<WalletImport, String> {
+    let seed = seed.trim();
+    let seed = if seed.split_whitespace().nth(1).is_some() {
+        decode_reddwallet_mnemonic(seed)?
+    } else {
+        seed.to_string()
+    };
+    let seed = seed.as_str();
+    if seed.len() != REDDWALLET_SEED_HEX_LEN || hex::decode(seed).is_err() {
+        return Err(format!(
+            "ReddWallet seed must be {REDDWALLET_SEED_HEX_LEN} hex characters or \
+             {REDDWALLET_MNEMONIC_WORDS} mnemonic words"
+        ));
+    }
+    validate_derivation_counts(receive_count, change_count)?;
+
+    let secp = secp();
+    let master_secret = SecretKey::from_slice(stretch_reddwallet_seed(seed).as_byte_array())
+        .map_err(|e| format!("stretched seed is not a valid private key: {e}"))?;
+    let mpk = master_secret.public_key(secp).serialize_uncompressed();
+
+    let mut import = WalletImport::default();
+    for (change, count) in [(0u32, receive_count), (1u32, change_count)] {
+        for n in 0..count {
+            let secret_key = master_secret
+                .add_tweak(&reddwallet_sequence(&mpk[1..], n, change)?)
+                .map_err(|e| format!("failed to derive key {change}/{n}: {e}"))?;
+            let wif_key = WifKey {
+                secret_key,
+                compressed: false,
+            };
+            import.keys.push(reddwallet_entry(
+                Some(wif_key.to_wif()),
+                wif_key.public_key(secp),
+                change,
+                n,
+            ));
+        }
+    }
+
+    Ok(import)
+}
+
+/// Derives ReddWallet addresses from its 128-hex-character master public key for watch-only
+/// tracking, without access to any private key.
+pub fn import_reddwallet_master_public_key(
+    mpk_hex: String,
+    receive_count: u32,
+    change_count: u32,
+) -> Result<WalletImport, String> {
+    let mpk_hex = mpk_hex.trim();
+    if mpk_hex.len() != REDDWALLET_MPK_HEX_LEN {
+        return Err(format!(
+            "ReddWallet master public key must be {REDDWALLET_MPK_HEX_LEN} hex characters"
+        ));
+    }
+    validate_derivation_counts(receive_count, change_count)?;
+
+    let mpk = hex::decode(mpk_hex).map_err(|e| format!("master public key decode failed: {e}"))?;
+    let mut uncompressed = Vec::with_capacity(65);
+    uncompressed.push(0x04);
+    uncompressed.extend_from_slice(&mpk);
+    let master_public = PublicKey::from_slice(&uncompressed)
+        .map_err(|e| format!("invalid master public key: {e}"))?;
+
+    let secp = secp();
+    let mut import = WalletImport {
+        watch_only: true,
+        ..WalletImport::default()
+    };
+    for (change, count) in [(0u32, receive_count), (1u32, change_count)] {
+        for n in 0..count {
+            let public_key = master_public
+                .add_exp_tweak(secp, &reddwallet_sequence(&mpk, n, change)?)
+                .map_err(|e| format!("failed to derive key {change}/{n}: {e}"))?;
+            import.keys.push(reddwallet_entry(
+                None,
+                BitcoinPublicKey::new_uncompressed(public_key),
+                change,
+                n,
+            ));
+        }
+    }
+
+    Ok(import)
+}
+
+fn validate_derivation_counts(receive_count: u32, change_count: u32) -> Result<(), String> {
+    if receive_count == 0 && change_count == 0 {
+        return Err("at least one key must be derived".to_string());
+    }
+    if receive_count > MAX_DERIVED_KEYS_PER_CHAIN || change_count > MAX_DERIVED_KEYS_PER_CHAIN {
+        return Err(format!(
+            "at most {MAX_DERIVED_KEYS_PER_CHAIN} keys can be derived per chain"
+        ));
+    }
+    Ok(())
+}
+
+fn reddwallet_entry(
+    wif: Option<String>,
+    public_key: BitcoinPublicKey,
+    change: u32,
+    n: u32,
+) -> ImportedKey {
+    ImportedKey {
+        wif,
+        address: ReddcoinAddress::p2pkh(&public_key).to_string(),
+        label: None,
+        role: if change == 0 {
+            KeyRole::Receive
+        } else {
+            KeyRole::Change
+        },
+        created_at: None,
+        key_path: Some(format!("m/{change}/{n}")),
+    }
+}
+
+/// Electrum 1.x `mn_decode`: every three words `w1 w2 w3` (as word-list indices) encode
+/// `w1 + n((w2 - w1) mod n) + n²((w3 - w2) mod n)` with `n` = 1626, printed as 8 hex digits.
+/// The result is the hex seed itself, which is what gets stretched.
+fn decode_reddwallet_mnemonic(mnemonic: &str) -> Result<String, String> {
+    let words: Vec<&str> = ELECTRUM_V1_WORDS.lines().collect();
+    debug_assert_eq!(words.len(), ELECTRUM_V1_WORD_COUNT);
+    let indices = mnemonic
+        .split_whitespace()
+        .map(|word| {
+            let word = word.to_lowercase();
+            words
+                .iter()
+                .position(|candidate| *candidate == word)
+                .map(|index| index as i64)
+                .ok_or_else(|| format!("{word:?} is not a ReddWallet mnemonic word"))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    if indices.len() != REDDWALLET_MNEMONIC_WORDS {
+        return Err(format!(
+            "ReddWallet mnemonic must be {REDDWALLET_MNEMONIC_WORDS} words, got {}",
+            indices.len()
+        ));
+    }
+
+    let n = ELECTRUM_V1_WORD_COUNT as i64;
+    let mut seed = String::with_capacity(REDDWALLET_SEED_HEX_LEN);
+    for group in indices.chunks(3) {
+        let (w1, w2, w3) = (group[0], group[1], group[2]);
+        let x = w1 + n * (w2 - w1).rem_euclid(n) + n * n * (w3 - w2).rem_euclid(n);
+        let x = u32::try_from(x)
+            .map_err(|_| "mnemonic words do not encode a ReddWallet seed".to_string())?;
+        seed.push_str(&format!("{x:08x}"));
+    }
+    Ok(seed)
+}
+
+/// Electrum 1.x key stretching: `seed = sha256(seed || original)` repeated 100,000 times, where
+/// `original` is the ASCII hex seed itself (not its decoded bytes).
+fn stretch_reddwallet_seed(seed: &str) -> sha256::Hash {
+    let original = seed.as_bytes();
+    let hash_round = |previous: &[u8]| {
+        let mut engine = sha256::Hash::engine();
+        engine.input(previous);
+        engine.input(original);
+        sha256::Hash::from_engine(engine)
+    };
+
+    let mut stretched = hash_round(original);
+    for _ in 1..REDDWALLET_STRETCH_ROUNDS {
+        stretched = hash_round(stretched.as_byte_array());
+    }
+    stretched
+}
+
+/// Electrum 1.x per-key offset: `sha256d("<n>:<change>:" || mpk)` interpreted as a scalar.
+fn reddwallet_sequence(mpk: &[u8], n: u32, change: u32) -> Result<Scalar, String> {
+    let mut engine = sha256d::Hash::engine();
+    engine.input(format!("{n}:{change}:").as_bytes());
+    engine.input(mpk);
+    let digest = sha256d::Hash::from_engine(engine);
+    Scalar::from_be_bytes(digest.to_byte_array())
+        .map_err(|_| format!("derivation offset for key {change}/{n} is out of range"))
+}
+
+/// Reverses Reddcoin Core's `EncodeDumpString`, which percent-encodes whitespace, control bytes,
+/// non-ASCII bytes and `%` so labels never break the one-key-per-line format.
+fn decode_dump_string(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' {
+            // `from_str_radix` alone would accept a sign, so `%+1` would decode to 0x01.
+            let escaped = bytes
+                .get(index + 1..index + 3)
+                .filter(|hex| hex.iter().all(u8::is_ascii_hexdigit))
+                .and_then(|hex| std::str::from_utf8(hex).ok())
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            if let Some(byte) = escaped {
+                decoded.push(byte);
+                index += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[index]);
+        index += 1;
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MNEMONIC: &str =
+        "hardly point goal hallway patience key stone difference ready caught listen fact";
+    const SEED_HEX: &str = "8edad31a95e7d59f8837667510d75a4d";
+
+    fn wif(byte: u8) -> String {
+        crate::wif::encode_wif(format!("{byte:02x}").repeat(32), true).unwrap()
+    }
+
+    fn core_dump() -> String {
+        let (receive, reserve, change, seed) = (wif(1), wif(2), wif(3), wif(4));
+        format!(
+            "# Wallet dump created by Reddcoin version v4.22.9\n\
+             # * Created on 2024-01-10T12:00:00Z\n\
+             # extended private masterkey: xprv9s21ZrQH143K3GJpoapnV8SFfukcVBSfeCficPSG\n\
+             \n\
+             {receive} 2024-01-01T00:00:00Z label=Savings%20%2b%2525 # addr=Rx hdkeypath=m/0'/0'/0'\n\
+             {reserve} 2024-01-01T00:00:01Z reserve=1 # addr=Rx hdkeypath=m/0'/0'/1'\n\
+             {change} 2024-01-01T00:00:02Z change=1 # addr=Rx hdkeypath=m/0'/1'/0'\n\
+             {seed} 2024-01-01T00:00:03Z hdmaster=1 # addr=Rx hdkeypath=m\n\
+             5221deadbeef 0 script=1\n\
+             \n\
+             # End of dump\n"
+        )
+    }
+
+    #[test]
+    fn parses_core_dumpwallet_roles_and_labels() {
+        let import = parse_core_dumpwallet(core_dump()).unwrap();
+        assert!(!import.watch_only);
+        assert_eq!(
+            import.extended_master_key.as_deref(),
+            Some("xprv9s21ZrQH143K3GJpoapnV8SFfukcVBSfeCficPSG")
+        );
+        assert_eq!(import.scripts, ["5221deadbeef"]);
+
+        let roles: Vec<_> = import.keys.iter().map(|key| key.role).collect();
+        assert_eq!(
+            roles,
+            [
+                KeyRole::Receive,
+                KeyRole::Reserve,
+                KeyRole::Change,
+                KeyRole::HdSeed
+            ]
+        );
+        let receive = &import.keys[0];
+        assert_eq!(receive.wif, Some(wif(1)));
+        assert_eq!(receive.label.as_deref(), Some("Savings +%25"));
+        assert_eq!(receive.created_at.as_deref(), Some("2024-01-01T00:00:00Z"));
+        assert_eq!(receive.key_path.as_deref(), Some("m/0'/0'/0'"));
+        // The address is derived from the key, not copied from the comment.
+        let expected = decode_wif(&wif(1)).unwrap().address(secp()).to_string();
+        assert_eq!(receive.address, expected);
+        assert!(import.keys[1..].iter().all(|key| key.label.is_none()));
+        assert_eq!(import.keys[3].key_path.as_deref(), Some("m"));
+    }
+
+    #[test]
+    fn accepts_hdseed_and_inactive_seed_flags() {
+        let dump = format!(
+            "{} 0 hdseed=1 # addr=Rx\n{} 0 inactivehdseed=1 # addr=Rx\n",
+            wif(4),
+            wif(5)
+        );
+        let import = parse_core_dumpwallet(dump).unwrap();
+        assert_eq!(import.keys[0].role, KeyRole::HdSeed);
+        assert_eq!(import.keys[1].role, KeyRole::InactiveHdSeed);
+        assert!(import.extended_master_key.is_none());
+    }
+
+    #[test]
+    fn rejects_malformed_dump_lines() {
+        let bad_wif = format!("# header\n{} 0 reserve=1\nNotAWif 0 reserve=1\n", wif(1));
+        assert!(parse_core_dumpwallet(bad_wif)
+            .unwrap_err()
+            .starts_with("line 3:"));
+        let unknown_flag = format!("{} 0 watch=1\n", wif(1));
+        assert!(parse_core_dumpwallet(unknown_flag)
+            .unwrap_err()
+            .contains("unknown key flag `watch=1`"));
+        let short = format!("{} 0\n", wif(1));
+        assert!(parse_core_dumpwallet(short)
+            .unwrap_err()
+            .contains("got 2 fields"));
+        let comments_only = "# Wallet dump\n\n# End of dump\n".to_string();
+        assert_eq!(
+            parse_core_dumpwallet(comments_only).unwrap_err(),
+            "dump contains no keys"
+        );
+    }
+
+    #[test]
+    fn dump_strings_need_two_hex_digits() {
+        assert_eq!(decode_dump_string("a%20b%2Fc"), "a b/c");
+        assert_eq!(decode_dump_string("%41"), "A");
+        assert_eq!(decode_dump_string("%+1"), "%+1");
+        assert_eq!(decode_dump_string("%g0%4"), "%g0%4");
+    }
+
+    #[test]
+    fn decodes_electrum_v1_mnemonic() {
+        assert_eq!(ELECTRUM_V1_WORDS.lines().count(), ELECTRUM_V1_WORD_COUNT);
+        assert_eq!(decode_reddwallet_mnemonic(MNEMONIC).unwrap(), SEED_HEX);
+        assert_eq!(
+            decode_reddwallet_mnemonic(&MNEMONIC.to_uppercase()).unwrap(),
+            SEED_HEX
+        );
+    }
+
+    #[test]
+    fn mnemonic_import_matches_hex_import() {
+        let from_words = import_reddwallet_seed(format!("  {MNEMONIC}\n"), 2, 1).unwrap();
+        let from_hex = import_reddwallet_seed(SEED_HEX.to_string(), 2, 1).unwrap();
+        let addresses = |import: &WalletImport| {
+            import
+                .keys
+                .iter()
+                .map(|key| (key.address.clone(), key.wif.clone()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(from_words.keys.len(), 3);
+        assert_eq!(addresses(&from_words), addresses(&from_hex));
+    }
+
+    #[test]
+    fn rejects_bad_mnemonics() {
+        let unknown = MNEMONIC.replace("hardly", "reddcoin");
+        assert!(import_reddwallet_seed(unknown, 1, 0)
+            .unwrap_err()
+            .contains("not a ReddWallet mnemonic word"));
+        let short = MNEMONIC.rsplit_once(' ').unwrap().0.to_string();
+        assert!(import_reddwallet_seed(short, 1, 0)
+            .unwrap_err()
+            .contains("must be 12 words"));
+        assert!(import_reddwallet_seed("abcd".to_string(), 1, 0)
+            .unwrap_err()
+            .contains("32 hex characters"));
+    }
+}
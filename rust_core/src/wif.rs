@@ -0,0 +1,197 @@
+//! Wallet Import Format (WIF) encoding for Reddcoin private keys.
+//!
+//! WIF is what Reddcoin Core's `dumpprivkey` prints and what legacy paper wallets carry. Unlike
+//! the raw 64-char hex used elsewhere on the FFI boundary, a WIF string is checksummed, tagged
+//! with the network's secret-key prefix and records whether the key's public half is compressed.
+
+use bitcoin::base58;
+use bitcoin::secp256k1::{Secp256k1, SecretKey, Signing};
+use bitcoin::PublicKey;
+use serde::Serialize;
+
+use crate::address::ReddcoinAddress;
+use crate::secp_context::secp;
+
+/// Reddcoin's Base58 secret-key version byte (`SECRET_KEY` in Reddcoin Core chain params).
+///
+/// Uncompressed keys encode to strings starting with `7`, compressed ones with `U` or `V`.
+pub const REDDCOIN_WIF_PREFIX: u8 = 0xBD;
+
+/// Trailing marker byte that flags a WIF payload as belonging to a compressed public key.
+const COMPRESSED_FLAG: u8 = 0x01;
+
+/// A decoded WIF private key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WifKey {
+    pub secret_key: SecretKey,
+    /// Whether the matching public key (and therefore the address) uses the 33-byte encoding.
+    pub compressed: bool,
+}
+
+impl WifKey {
+    /// Returns the public key serialized the way this WIF key's address expects.
+    pub fn public_key<C: Signing>(&self, secp: &Secp256k1<C>) -> PublicKey {
+        let inner = self.secret_key.public_key(secp);
+        if self.compressed {
+            PublicKey::new(inner)
+        } else {
+            PublicKey::new_uncompressed(inner)
+        }
+    }
+
+    /// Returns the P2PKH address funds for this key were sent to.
+    pub fn address<C: Signing>(&self, secp: &Secp256k1<C>) -> ReddcoinAddress {
+        ReddcoinAddress::p2pkh(&self.public_key(secp))
+    }
+
+    /// Encodes the key back to its WIF string.
+    pub fn to_wif(&self) -> String {
+        let mut payload = Vec::with_capacity(34);
+        payload.push(REDDCOIN_WIF_PREFIX);
+        payload.extend_from_slice(&self.secret_key.secret_bytes());
+        if self.compressed {
+            payload.push(COMPRESSED_FLAG);
+        }
+        base58::encode_check(&payload)
+    }
+}
+
+/// JSON-friendly summary of an imported key, returned across FFI.
+#[derive(Debug, Clone, Serialize)]
+pub struct WifImport {
+    pub private_key_hex: String,
+    pub compressed: bool,
+    pub address: String,
+}
+
+/// Decodes a Reddcoin WIF string.
+///
+/// Keys carrying another network's prefix (for example a Bitcoin `5...`/`K...` key) are rejected
+/// rather than silently accepted, because sweeping them would look for funds on the wrong chain.
+pub fn decode_wif(wif: &str) -> Result<WifKey, String> {
+    let payload = base58::decode_check(wif.trim())
+        .map_err(|e| format!("WIF is not valid Base58Check: {e}"))?;
+
+    let compressed = match payload.len() {
+        33 => false,
+        34 if payload[33] == COMPRESSED_FLAG => true,
+        34 => {
+            return Err(format!(
+                "WIF compression flag must be 0x01, got 0x{:02x}",
+                payload[33]
+            ))
+        }
+        other => return Err(format!("WIF payload must be 33 or 34 bytes, got {other}")),
+    };
+
+    if payload[0] != REDDCOIN_WIF_PREFIX {
+        return Err(format!(
+            "WIF prefix 0x{:02x} is not a Reddcoin secret key (expected 0x{REDDCOIN_WIF_PREFIX:02x})",
+            payload[0]
+        ));
+    }
+
+    let secret_key = SecretKey::from_slice(&payload[1..33])
+        .map_err(|e| format!("invalid secp256k1 private key: {e}"))?;
+
+    Ok(WifKey {
+        secret_key,
+        compressed,
+    })
+}
+
+/// Encodes a 64-char hex private key as Reddcoin WIF.
+pub fn encode_wif(private_key_hex: String, compressed: bool) -> Result<String, String> {
+    if private_key_hex.len() != 64 {
+        return Err("private_key_hex must be exactly 64 hex characters".to_string());
+    }
+
+    let private_key_raw =
+        hex::decode(private_key_hex).map_err(|e| format!("private_key_hex decode failed: {e}"))?;
+    let secret_key = SecretKey::from_slice(&private_key_raw)
+        .map_err(|e| format!("invalid secp256k1 private key: {e}"))?;
+
+    Ok(WifKey {
+        secret_key,
+        compressed,
+    }
+    .to_wif())
+}
+
+/// Decodes a WIF string into the hex key, compression flag and address the app needs to display
+/// and track an imported key.
+pub fn import_wif(wif: String) -> Result<WifImport, String> {
+    let key = decode_wif(&wif)?;
+    let secp = secp();
+
+    Ok(WifImport {
+        private_key_hex: hex::encode(key.secret_key.secret_bytes()),
+        compressed: key.compressed,
+        address: key.address(secp).to_string(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY_HEX: &str = "0c28fca386c7a227600b2fe50b7cae11ec86d3bf1fbe471be89827e19d72aa1d";
+
+    #[test]
+    fn round_trips_both_encodings() {
+        for compressed in [false, true] {
+            let wif = encode_wif(KEY_HEX.to_string(), compressed).unwrap();
+            let prefixes: &[char] = if compressed { &['U', 'V'] } else { &['7'] };
+            assert!(prefixes.contains(&wif.chars().next().unwrap()), "{wif}");
+
+            let import = import_wif(wif.clone()).unwrap();
+            assert_eq!(import.private_key_hex, KEY_HEX);
+            assert_eq!(import.compressed, compressed);
+            assert!(import.address.starts_with('R'));
+            assert_eq!(decode_wif(&format!("  {wif}\n")).unwrap().to_wif(), wif);
+        }
+    }
+
+    #[test]
+    fn compression_changes_the_address() {
+        let uncompressed = import_wif(encode_wif(KEY_HEX.to_string(), false).unwrap()).unwrap();
+        let compressed = import_wif(encode_wif(KEY_HEX.to_string(), true).unwrap()).unwrap();
+        assert_ne!(uncompressed.address, compressed.address);
+    }
+
+    #[test]
+    fn rejects_bitcoin_keys() {
+        let err = decode_wif("5HueCGU8rMjxEXxiPuD5BDku4MkFqeZyd4dZ1jvhTVqvbTLvyTJ").unwrap_err();
+        assert!(err.contains("not a Reddcoin secret key"), "{err}");
+    }
+
+    #[test]
+    fn rejects_bad_checksum_flag_and_length() {
+        let wif = encode_wif(KEY_HEX.to_string(), true).unwrap();
+        let mut tampered = wif.clone().into_bytes();
+        let last = tampered.len() - 1;
+        tampered[last] = if tampered[last] == b'2' { b'3' } else { b'2' };
+        assert!(decode_wif(std::str::from_utf8(&tampered).unwrap())
+            .unwrap_err()
+            .contains("Base58Check"));
+
+        let mut payload = base58::decode_check(&wif).unwrap();
+        payload[33] = 0x02;
+        assert!(decode_wif(&base58::encode_check(&payload))
+            .unwrap_err()
+            .contains("compression flag"));
+        payload.push(0x01);
+        assert!(decode_wif(&base58::encode_check(&payload))
+            .unwrap_err()
+            .contains("33 or 34 bytes"));
+    }
+
+    #[test]
+    fn rejects_invalid_hex_keys() {
+        assert!(encode_wif("00".repeat(31), true).is_err());
+        assert!(encode_wif("zz".repeat(32), true).is_err());
+        assert!(encode_wif("00".repeat(32), true)
+            .unwrap_err()
+            .contains("invalid secp256k1"));
+    }
+}